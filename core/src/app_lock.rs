@@ -0,0 +1,211 @@
+// Optional PIN in front of the saved session, with a duress PIN.
+//
+// The real PIN opens the saved session as usual. The duress PIN opens a
+// decoy account instead: a key of its own with an empty local cache, so
+// someone forcing the user to unlock finds nothing. After `wipe_after`
+// wrong PINs in a row the caller wipes the saved session.
+//
+// This stops whoever is in front of the screen, not forensics: the PIN
+// hashes sit in the app data directory like everything else.
+use crate::documents;
+use crate::error::MessengerError;
+use crate::local_store::LocalStore;
+use anyhow::{anyhow, Result};
+use argon2::Argon2;
+use pkarr::{Keypair, PublicKey};
+use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use subtle::ConstantTimeEq;
+
+const LOCK_DOCUMENT: &str = documents::APP_LOCK;
+
+const MIN_PIN_DIGITS: usize = 4;
+const MAX_PIN_DIGITS: usize = 32;
+const MIN_WIPE_AFTER: u32 = 3;
+const MAX_WIPE_AFTER: u32 = 50;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct PinHash {
+    salt: String,
+    hash: String,
+}
+
+impl PinHash {
+    fn new(pin: &str) -> Result<Self> {
+        let mut salt = [0u8; 16];
+        OsRng.fill_bytes(&mut salt);
+        Ok(Self {
+            salt: hex::encode(salt),
+            hash: hex::encode(hash_pin(pin, &salt)?),
+        })
+    }
+
+    // Constant time, so timing doesn't tell how much of a guess matched
+    fn matches(&self, pin: &str) -> bool {
+        let (Ok(salt), Ok(expected)) = (hex::decode(&self.salt), hex::decode(&self.hash)) else {
+            return false;
+        };
+        hash_pin(pin, &salt).is_ok_and(|hash| bool::from(hash.as_slice().ct_eq(expected.as_slice())))
+    }
+}
+
+fn hash_pin(pin: &str, salt: &[u8]) -> Result<[u8; 32]> {
+    let mut hash = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(pin.as_bytes(), salt, &mut hash)
+        .map_err(|e| anyhow!("Failed to hash PIN: {}", e))?;
+    Ok(hash)
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+struct LockDocument {
+    pin: Option<PinHash>,
+    duress_pin: Option<PinHash>,
+    wipe_after: Option<u32>,
+    // Wrong PINs since the last right one
+    failed_attempts: u32,
+    // Hex secret key of the decoy account, made the first time it's opened
+    decoy_secret: Option<String>,
+}
+
+impl LockDocument {
+    fn decoy_keypair(&self) -> Option<Keypair> {
+        let secret: [u8; 32] = hex::decode(self.decoy_secret.as_ref()?).ok()?.try_into().ok()?;
+        Some(Keypair::from_secret_key(&secret))
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AppLockStatus {
+    pub enabled: bool,
+    pub duress_enabled: bool,
+    pub wipe_after: Option<u32>,
+}
+
+pub enum Unlock {
+    // Open the saved session
+    Real,
+    // Sign in as this instead
+    Decoy(Keypair),
+    Wrong,
+    // Too many wrong PINs: the lock is gone, wipe the saved session
+    Wipe,
+}
+
+fn load(store: &LocalStore) -> LockDocument {
+    store.load(LOCK_DOCUMENT).unwrap_or_default()
+}
+
+pub fn is_enabled(store: &LocalStore) -> bool {
+    load(store).pin.is_some()
+}
+
+// Whether `signed_in` is the decoy account
+pub fn is_decoy(store: &LocalStore, signed_in: &PublicKey) -> bool {
+    load(store).decoy_keypair().is_some_and(|decoy| decoy.public_key() == *signed_in)
+}
+
+// The decoy account sees a lock without a duress PIN, like a real one would
+pub fn status(store: &LocalStore, signed_in: Option<&PublicKey>) -> AppLockStatus {
+    let document = load(store);
+    let decoy = signed_in.is_some_and(|signed_in| is_decoy(store, signed_in));
+    AppLockStatus {
+        enabled: document.pin.is_some(),
+        duress_enabled: document.duress_pin.is_some() && !decoy,
+        wipe_after: document.wipe_after,
+    }
+}
+
+fn check_pin(pin: &str) -> Result<()> {
+    let digits = pin.chars().count();
+    if !pin.chars().all(|c| c.is_ascii_digit()) || !(MIN_PIN_DIGITS..=MAX_PIN_DIGITS).contains(&digits) {
+        return Err(anyhow!(MessengerError::InvalidInput(format!(
+            "A PIN is {} to {} digits",
+            MIN_PIN_DIGITS, MAX_PIN_DIGITS
+        ))));
+    }
+    Ok(())
+}
+
+pub fn unlock(store: &LocalStore, pin: &str) -> Result<Unlock> {
+    store.update(LOCK_DOCUMENT, |document: &mut LockDocument| {
+        let Some(real) = &document.pin else {
+            return Unlock::Real;
+        };
+        if real.matches(pin) {
+            document.failed_attempts = 0;
+            return Unlock::Real;
+        }
+        if document.duress_pin.as_ref().is_some_and(|duress| duress.matches(pin)) {
+            document.failed_attempts = 0;
+            let decoy = document.decoy_keypair().unwrap_or_else(Keypair::random);
+            document.decoy_secret = Some(hex::encode(decoy.secret_key()));
+            return Unlock::Decoy(decoy);
+        }
+
+        document.failed_attempts += 1;
+        if document.wipe_after.is_some_and(|limit| document.failed_attempts >= limit) {
+            *document = LockDocument::default();
+            return Unlock::Wipe;
+        }
+        Unlock::Wrong
+    })
+}
+
+// Changing or removing a lock takes the real PIN, so the decoy account
+// can't turn it off. Returns true when the saved session must be wiped.
+fn check_current(store: &LocalStore, current_pin: Option<&str>) -> Result<bool> {
+    if !is_enabled(store) {
+        return Ok(false);
+    }
+    match unlock(store, current_pin.unwrap_or_default())? {
+        Unlock::Real => Ok(false),
+        Unlock::Decoy(_) | Unlock::Wrong => Err(anyhow!(MessengerError::BadPin)),
+        Unlock::Wipe => Ok(true),
+    }
+}
+
+// Returns true, changing nothing, when the current PIN was wrong once too
+// often and the saved session must be wiped
+pub fn set(
+    store: &LocalStore,
+    current_pin: Option<&str>,
+    pin: &str,
+    duress_pin: Option<&str>,
+    wipe_after: Option<u32>,
+) -> Result<bool> {
+    if check_current(store, current_pin)? {
+        return Ok(true);
+    }
+    check_pin(pin)?;
+    if let Some(duress_pin) = duress_pin {
+        check_pin(duress_pin)?;
+        if duress_pin == pin {
+            return Err(anyhow!(MessengerError::InvalidInput("The duress PIN must differ from the PIN".to_string())));
+        }
+    }
+    let wipe_after = wipe_after.map(|limit| limit.clamp(MIN_WIPE_AFTER, MAX_WIPE_AFTER));
+
+    let pin = PinHash::new(pin)?;
+    let duress_pin = duress_pin.map(PinHash::new).transpose()?;
+    store.update(LOCK_DOCUMENT, |document: &mut LockDocument| {
+        // Keep the decoy account across PIN changes so it looks lived in
+        if duress_pin.is_none() {
+            document.decoy_secret = None;
+        }
+        document.pin = Some(pin);
+        document.duress_pin = duress_pin;
+        document.wipe_after = wipe_after;
+        document.failed_attempts = 0;
+    })?;
+    Ok(false)
+}
+
+// Same return as `set`
+pub fn remove(store: &LocalStore, current_pin: &str) -> Result<bool> {
+    if check_current(store, Some(current_pin))? {
+        return Ok(true);
+    }
+    store.remove(LOCK_DOCUMENT)?;
+    Ok(false)
+}
@@ -0,0 +1,225 @@
+// Downloaded attachments kept on disk, so opening one again doesn't fetch
+// it again.
+//
+// Files are cached as the ciphertext the homeserver served, and are only
+// readable with the key from their message, which lives in the encrypted
+// message store. Every read is checked against the message again. Once the
+// cache grows past its cap, the files used least recently are evicted, and
+// files whose sender set an expiry are dropped once it passes.
+use crate::attachments::{self, Attachment};
+use crate::documents;
+use crate::local_store::LocalStore;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+const CACHE_DOCUMENT: &str = documents::ATTACHMENT_CACHE;
+const SETTINGS_DOCUMENT: &str = documents::ATTACHMENT_CACHE_SETTINGS;
+const CACHE_DIR: &str = "attachment_cache";
+
+const MIN_CACHE_BYTES: u64 = 16 * 1024 * 1024;
+const MAX_CACHE_BYTES: u64 = 64 * 1024 * 1024 * 1024;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AttachmentCacheSettings {
+    // Zero turns the cache off
+    pub max_bytes: u64,
+}
+
+impl Default for AttachmentCacheSettings {
+    fn default() -> Self {
+        Self { max_bytes: 1024 * 1024 * 1024 }
+    }
+}
+
+#[derive(Serialize, Clone, Copy, Debug)]
+pub struct CacheUsage {
+    pub bytes: u64,
+    pub files: usize,
+    pub max_bytes: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct CacheEntry {
+    size: u64,
+    last_used: u64,
+    #[serde(default)]
+    expires_at: Option<u64>,
+}
+
+// Cached files by id. The files are the truth; this only orders eviction.
+#[derive(Serialize, Deserialize, Default)]
+struct CacheIndex {
+    entries: HashMap<String, CacheEntry>,
+}
+
+pub fn get_settings(store: &LocalStore) -> AttachmentCacheSettings {
+    store.load(SETTINGS_DOCUMENT).unwrap_or_default()
+}
+
+// Anything but zero is clamped to what we allow. Shrinking the cap evicts
+// straight away.
+pub fn set_settings(store: &LocalStore, settings: AttachmentCacheSettings) -> Result<AttachmentCacheSettings> {
+    let settings = AttachmentCacheSettings {
+        max_bytes: match settings.max_bytes {
+            0 => 0,
+            max_bytes => max_bytes.clamp(MIN_CACHE_BYTES, MAX_CACHE_BYTES),
+        },
+    };
+    store.save(SETTINGS_DOCUMENT, &settings)?;
+    AttachmentCache::new(store).evict(settings.max_bytes)?;
+    Ok(settings)
+}
+
+pub struct AttachmentCache {
+    store: LocalStore,
+    dir: PathBuf,
+}
+
+impl AttachmentCache {
+    pub fn new(store: &LocalStore) -> Self {
+        Self { store: store.clone(), dir: store.dir().join(CACHE_DIR) }
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(id)
+    }
+
+    // The decrypted file, if it's cached and still matches its message
+    pub fn get(&self, attachment: &Attachment, now: u64) -> Option<Vec<u8>> {
+        let id = cache_id(attachment);
+        if attachments::is_expired(attachment, now) {
+            self.remove(&id);
+            return None;
+        }
+        let ciphertext = fs::read(self.path(&id)).ok()?;
+        match attachments::open(attachment, &ciphertext) {
+            Ok(bytes) => {
+                let touched = self.store.update(CACHE_DOCUMENT, |index: &mut CacheIndex| {
+                    let entry = index.entries.entry(id.clone())
+                        .or_insert(CacheEntry { size: ciphertext.len() as u64, last_used: now, expires_at: attachment.expires_at });
+                    entry.last_used = now;
+                });
+                if let Err(e) = touched {
+                    tracing::warn!("⚠️  Failed to update attachment cache: {}", e);
+                }
+                Some(bytes)
+            }
+            Err(e) => {
+                tracing::warn!("⚠️  Dropping cached {}: {}", attachment.name, e);
+                self.remove(&id);
+                None
+            }
+        }
+    }
+
+    // Cache a downloaded file's ciphertext, then evict down to the cap.
+    // Files bigger than the whole cache aren't kept.
+    pub fn put(&self, attachment: &Attachment, ciphertext: &[u8], now: u64) -> Result<()> {
+        let max_bytes = get_settings(&self.store).max_bytes;
+        let size = ciphertext.len() as u64;
+        if size > max_bytes || attachments::is_expired(attachment, now) {
+            return Ok(());
+        }
+
+        let id = cache_id(attachment);
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.path(&id), ciphertext)?;
+        self.store.update(CACHE_DOCUMENT, |index: &mut CacheIndex| {
+            index.entries.insert(id, CacheEntry { size, last_used: now, expires_at: attachment.expires_at });
+        })?;
+        self.evict(max_bytes)?;
+        Ok(())
+    }
+
+    // Drop least recently used files until the cache fits `max_bytes`
+    fn evict(&self, max_bytes: u64) -> Result<()> {
+        let evicted = self.store.update(CACHE_DOCUMENT, |index: &mut CacheIndex| {
+            let mut total: u64 = index.entries.values().map(|entry| entry.size).sum();
+            let mut by_age: Vec<(String, CacheEntry)> = index.entries.iter()
+                .map(|(id, entry)| (id.clone(), entry.clone()))
+                .collect();
+            by_age.sort_by_key(|(_, entry)| entry.last_used);
+
+            let mut evicted = Vec::new();
+            for (id, entry) in by_age {
+                if total <= max_bytes {
+                    break;
+                }
+                total -= entry.size;
+                index.entries.remove(&id);
+                evicted.push(id);
+            }
+            evicted
+        })?;
+
+        for id in &evicted {
+            self.remove_file(id);
+        }
+        if !evicted.is_empty() {
+            tracing::debug!("🧹 Evicted {} cached attachments", evicted.len());
+        }
+        Ok(())
+    }
+
+    // Drop files whose expiry has passed, returning how many went
+    pub fn prune_expired(&self, now: u64) -> Result<usize> {
+        let expired = self.store.update(CACHE_DOCUMENT, |index: &mut CacheIndex| {
+            let expired: Vec<String> = index.entries.iter()
+                .filter(|(_, entry)| entry.expires_at.is_some_and(|expires_at| expires_at <= now))
+                .map(|(id, _)| id.clone())
+                .collect();
+            for id in &expired {
+                index.entries.remove(id);
+            }
+            expired
+        })?;
+
+        for id in &expired {
+            self.remove_file(id);
+        }
+        Ok(expired.len())
+    }
+
+    fn remove(&self, id: &str) {
+        self.remove_file(id);
+        if let Err(e) = self.store.update(CACHE_DOCUMENT, |index: &mut CacheIndex| index.entries.remove(id)) {
+            tracing::warn!("⚠️  Failed to update attachment cache: {}", e);
+        }
+    }
+
+    fn remove_file(&self, id: &str) {
+        if let Err(e) = fs::remove_file(self.path(id)) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                tracing::warn!("⚠️  Failed to remove cached attachment: {}", e);
+            }
+        }
+    }
+
+    pub fn usage(&self) -> CacheUsage {
+        let index: CacheIndex = self.store.load(CACHE_DOCUMENT).unwrap_or_default();
+        CacheUsage {
+            bytes: index.entries.values().map(|entry| entry.size).sum(),
+            files: index.entries.len(),
+            max_bytes: get_settings(&self.store).max_bytes,
+        }
+    }
+
+    // Delete every cached file, returning what was freed
+    pub fn clear(&self) -> Result<CacheUsage> {
+        let usage = self.usage();
+        if let Err(e) = fs::remove_dir_all(&self.dir) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                return Err(e.into());
+            }
+        }
+        self.store.remove(CACHE_DOCUMENT)?;
+        Ok(usage)
+    }
+}
+
+fn cache_id(attachment: &Attachment) -> String {
+    blake3::hash(attachment.url.as_bytes()).to_hex().to_string()
+}
@@ -0,0 +1,134 @@
+// Attachments that delete themselves.
+//
+// A sender can give a file an expiry when sending it. The time travels in
+// the signed Attachment, so recipients stop offering the file once it
+// passes, and our own expiring uploads are remembered here per user. The
+// expiry worker deletes them from our homeserver when they're due and drops
+// expired files from the attachment cache. This is separate from message
+// retention, which only ever removes message blobs.
+use crate::attachment_cache::AttachmentCache;
+use crate::attachments::Attachment;
+use crate::documents;
+use crate::local_store::LocalStore;
+use crate::logging;
+use crate::messaging::{AppState, PrivateMessageHandler};
+use crate::profiles::now_secs;
+use anyhow::Result;
+use pkarr::PublicKey;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+const MIN_EXPIRY_SECS: u64 = 60 * 60;
+const MAX_EXPIRY_SECS: u64 = 365 * 24 * 60 * 60;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ExpiringUpload {
+    pub url: String,
+    pub name: String,
+    pub expires_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ExpiryReport {
+    pub deleted: usize,
+    // Left for the next run
+    pub failed: Vec<String>,
+}
+
+fn uploads_document(owner: &PublicKey) -> String {
+    documents::user_document(documents::ATTACHMENT_EXPIRY, owner)
+}
+
+fn load_uploads(store: &LocalStore, owner: &PublicKey) -> HashMap<String, ExpiringUpload> {
+    store.load(&uploads_document(owner)).unwrap_or_default()
+}
+
+// When a file sent now with `expires_in_secs` expires. Zero or None keeps
+// it; anything else is clamped to what we allow.
+pub fn expires_at(expires_in_secs: Option<u64>, now: u64) -> Option<u64> {
+    expires_in_secs
+        .filter(|secs| *secs > 0)
+        .map(|secs| now + secs.clamp(MIN_EXPIRY_SECS, MAX_EXPIRY_SECS))
+}
+
+// Remember an uploaded attachment so it's deleted when it expires
+pub fn record(store: &LocalStore, owner: &PublicKey, attachment: &Attachment) -> Result<()> {
+    let Some(expires_at) = attachment.expires_at else {
+        return Ok(());
+    };
+    store.update(&uploads_document(owner), |uploads: &mut HashMap<String, ExpiringUpload>| {
+        uploads.insert(attachment.url.clone(), ExpiringUpload {
+            url: attachment.url.clone(),
+            name: attachment.name.clone(),
+            expires_at,
+        });
+    })
+}
+
+// Our uploads still waiting to expire, soonest first
+pub fn pending(store: &LocalStore, owner: &PublicKey) -> Vec<ExpiringUpload> {
+    let mut uploads: Vec<ExpiringUpload> = load_uploads(store, owner).into_values().collect();
+    uploads.sort_by_key(|upload| upload.expires_at);
+    uploads
+}
+
+// Delete our uploads whose expiry has passed. Ones that fail stay recorded
+// and are tried again next time.
+pub async fn run_cleanup(store: &LocalStore, handler: &PrivateMessageHandler, now: u64) -> Result<ExpiryReport> {
+    let owner = handler.keypair.public_key();
+    let due: Vec<ExpiringUpload> = pending(store, &owner)
+        .into_iter()
+        .filter(|upload| upload.expires_at <= now)
+        .collect();
+
+    let mut report = ExpiryReport::default();
+    for upload in due {
+        match handler.delete_attachment(&upload.url).await {
+            Ok(()) => {
+                store.update(&uploads_document(&owner), |uploads: &mut HashMap<String, ExpiringUpload>| {
+                    uploads.remove(&upload.url);
+                })?;
+                report.deleted += 1;
+            }
+            Err(e) => {
+                tracing::warn!("⚠️  Failed to delete expired {}: {}", logging::path(&upload.url), e);
+                report.failed.push(upload.url);
+            }
+        }
+    }
+
+    if report.deleted > 0 {
+        tracing::info!("🧹 Removed {} expired attachments", report.deleted);
+    }
+    Ok(report)
+}
+
+pub async fn run_expiry_worker(state: &AppState) {
+    loop {
+        tokio::time::sleep(CLEANUP_INTERVAL).await;
+        let now = now_secs();
+
+        // Cached files expire whether or not anyone is signed in
+        match AttachmentCache::new(&state.store).prune_expired(now) {
+            Ok(0) => {}
+            Ok(pruned) => tracing::debug!("🧹 Dropped {} expired attachments from the cache", pruned),
+            Err(e) => tracing::warn!("⚠️  Failed to prune attachment cache: {}", e),
+        }
+
+        // The decoy account has nothing uploaded
+        if state.is_decoy_session().await {
+            continue;
+        }
+        let handler = match state.create_handler().await {
+            Ok(Some(handler)) => handler,
+            // Not signed in - nothing of ours to delete
+            _ => continue,
+        };
+        if let Err(e) = run_cleanup(&state.store, &handler, now).await {
+            tracing::warn!("⚠️  Attachment expiry failed: {}", e);
+        }
+    }
+}
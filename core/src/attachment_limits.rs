@@ -0,0 +1,127 @@
+// How big an attachment may be, and what types are allowed, when sending
+// and when receiving.
+//
+// Receiving is checked when a message is read, so an attachment over the
+// limits is dropped from it and only its fallback text shows, and again
+// before anything is downloaded. A received file's type is judged by both
+// the MIME type the sender declared and its name, so relabelling it
+// doesn't get it past a deny list.
+use crate::attachments::{self, format_size, Attachment, MAX_STREAMED_ATTACHMENT_BYTES};
+use crate::documents;
+use crate::error::MessengerError;
+use crate::local_store::LocalStore;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+const LIMITS_DOCUMENT: &str = documents::ATTACHMENT_LIMITS;
+const UNKNOWN_TYPE: &str = "application/octet-stream";
+
+const MAX_TYPE_PATTERNS: usize = 100;
+const MAX_TYPE_PATTERN_CHARS: usize = 100;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AttachmentLimits {
+    pub max_send_bytes: u64,
+    pub max_receive_bytes: u64,
+    // MIME types such as "application/pdf", or whole families like
+    // "image/*". An empty allow list allows everything the deny list doesn't.
+    #[serde(default)]
+    pub allowed_types: Vec<String>,
+    #[serde(default)]
+    pub blocked_types: Vec<String>,
+}
+
+impl Default for AttachmentLimits {
+    fn default() -> Self {
+        Self {
+            max_send_bytes: MAX_STREAMED_ATTACHMENT_BYTES,
+            max_receive_bytes: 1024 * 1024 * 1024,
+            allowed_types: Vec::new(),
+            blocked_types: Vec::new(),
+        }
+    }
+}
+
+pub fn get_settings(store: &LocalStore) -> AttachmentLimits {
+    store.load(LIMITS_DOCUMENT).unwrap_or_default()
+}
+
+// Sizes are clamped to what can be sent at all; type patterns are
+// normalised to lowercase and must look like "type/subtype" or "type/*"
+pub fn set_settings(store: &LocalStore, limits: AttachmentLimits) -> Result<AttachmentLimits> {
+    let limits = AttachmentLimits {
+        max_send_bytes: limits.max_send_bytes.clamp(1, MAX_STREAMED_ATTACHMENT_BYTES),
+        max_receive_bytes: limits.max_receive_bytes.clamp(1, MAX_STREAMED_ATTACHMENT_BYTES),
+        allowed_types: normalize_patterns(limits.allowed_types)?,
+        blocked_types: normalize_patterns(limits.blocked_types)?,
+    };
+    store.save(LIMITS_DOCUMENT, &limits)?;
+    Ok(limits)
+}
+
+fn normalize_patterns(patterns: Vec<String>) -> Result<Vec<String>> {
+    if patterns.len() > MAX_TYPE_PATTERNS {
+        return Err(anyhow!(MessengerError::InvalidInput(format!("At most {} attachment types", MAX_TYPE_PATTERNS))));
+    }
+
+    let mut normalized: Vec<String> = Vec::with_capacity(patterns.len());
+    for pattern in patterns {
+        let pattern = pattern.trim().to_ascii_lowercase();
+        let valid = pattern.chars().count() <= MAX_TYPE_PATTERN_CHARS
+            && matches!(pattern.split_once('/'), Some((kind, subtype)) if !kind.is_empty() && !subtype.is_empty() && kind != "*");
+        if !valid {
+            return Err(anyhow!(MessengerError::InvalidInput(format!("Invalid attachment type: {}", pattern))));
+        }
+        if !normalized.contains(&pattern) {
+            normalized.push(pattern);
+        }
+    }
+    Ok(normalized)
+}
+
+fn matches_pattern(pattern: &str, mime: &str) -> bool {
+    match pattern.strip_suffix("/*") {
+        Some(kind) => mime.split_once('/').is_some_and(|(mime_kind, _)| mime_kind == kind),
+        None => pattern == mime,
+    }
+}
+
+impl AttachmentLimits {
+    fn check_type(&self, name: &str, mimes: &[&str]) -> Result<()> {
+        let mimes: Vec<String> = mimes.iter().map(|mime| mime.trim().to_ascii_lowercase()).collect();
+        let blocked = mimes.iter().find(|mime| self.blocked_types.iter().any(|pattern| matches_pattern(pattern, mime)));
+        let not_allowed = mimes.iter().find(|mime| {
+            !self.allowed_types.is_empty() && !self.allowed_types.iter().any(|pattern| matches_pattern(pattern, mime))
+        });
+        if let Some(mime) = blocked.or(not_allowed) {
+            return Err(anyhow!(MessengerError::AttachmentTypeNotAllowed(format!("{} files like {} aren't allowed", mime, name))));
+        }
+        Ok(())
+    }
+
+    fn check_size(name: &str, size: u64, limit: u64) -> Result<()> {
+        if size > limit {
+            return Err(anyhow!(MessengerError::AttachmentTooLarge(format!(
+                "{} is {}, the limit is {}",
+                name,
+                format_size(size),
+                format_size(limit)
+            ))));
+        }
+        Ok(())
+    }
+
+    pub fn check_send(&self, name: &str, mime: &str, size: u64) -> Result<()> {
+        Self::check_size(name, size, self.max_send_bytes)?;
+        self.check_type(name, &[mime])
+    }
+
+    pub fn check_receive(&self, attachment: &Attachment) -> Result<()> {
+        Self::check_size(&attachment.name, attachment.size, self.max_receive_bytes)?;
+        // A name we don't recognise says nothing about the type
+        match attachments::mime_type(&attachment.name) {
+            UNKNOWN_TYPE => self.check_type(&attachment.name, &[&attachment.mime]),
+            by_name => self.check_type(&attachment.name, &[&attachment.mime, by_name]),
+        }
+    }
+}
@@ -0,0 +1,234 @@
+// Small previews of non-image attachments, made by the sender before the
+// file is sealed and carried in the message's encrypted extras, so the
+// recipient can see what a file is without downloading it.
+//
+// Only formats that can be read without a codec or renderer get one:
+// text, WAV (duration and waveform), Ogg Opus/Vorbis (duration) and PDF
+// (page count and title). Files sealed in chunks are never read whole, so
+// they go without.
+use serde::{Deserialize, Serialize};
+
+const SNIPPET_CHARS: usize = 280;
+const SNIPPET_LINES: usize = 8;
+// Only the start of a text file is looked at for its snippet
+const SNIPPET_SCAN_BYTES: usize = 4096;
+
+const WAVEFORM_BARS: usize = 48;
+const MAX_TITLE_CHARS: usize = 120;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AttachmentPreview {
+    Text {
+        snippet: String,
+        lines: u64,
+    },
+    Audio {
+        duration_ms: u64,
+        // Peak of each slice of the recording, 0-255; empty when the format
+        // needs decoding to tell
+        #[serde(default)]
+        waveform: Vec<u8>,
+    },
+    Document {
+        pages: Option<u32>,
+        title: Option<String>,
+    },
+}
+
+pub fn generate(mime: &str, bytes: &[u8]) -> Option<AttachmentPreview> {
+    match mime {
+        "text/plain" | "application/json" => text_preview(bytes),
+        "audio/wav" | "audio/x-wav" | "audio/wave" => wav_preview(bytes),
+        "audio/ogg" => ogg_preview(bytes),
+        "application/pdf" => pdf_preview(bytes),
+        _ => None,
+    }
+}
+
+fn text_preview(bytes: &[u8]) -> Option<AttachmentPreview> {
+    let head = String::from_utf8_lossy(&bytes[..bytes.len().min(SNIPPET_SCAN_BYTES)]);
+    let snippet: String = head.lines()
+        .take(SNIPPET_LINES)
+        .collect::<Vec<_>>()
+        .join("\n")
+        .chars()
+        .filter(|c| !c.is_control() || *c == '\n' || *c == '\t')
+        .take(SNIPPET_CHARS)
+        .collect();
+    let snippet = snippet.trim_end().to_string();
+    if snippet.is_empty() {
+        return None;
+    }
+
+    let lines = bytes.iter().filter(|byte| **byte == b'\n').count() as u64 + 1;
+    Some(AttachmentPreview::Text { snippet, lines })
+}
+
+fn u16_le(bytes: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(bytes.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn u32_le(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn u64_le(bytes: &[u8], at: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(bytes.get(at..at + 8)?.try_into().ok()?))
+}
+
+// PCM WAV: walk the RIFF chunks for the format and the samples
+fn wav_preview(bytes: &[u8]) -> Option<AttachmentPreview> {
+    if bytes.get(..4)? != b"RIFF" || bytes.get(8..12)? != b"WAVE" {
+        return None;
+    }
+
+    let mut format = None;
+    let mut data = None;
+    let mut at = 12;
+    while at + 8 <= bytes.len() {
+        let id = &bytes[at..at + 4];
+        let len = u32_le(bytes, at + 4)? as usize;
+        let body = &bytes[at + 8..(at + 8).saturating_add(len).min(bytes.len())];
+        match id {
+            b"fmt " => format = Some((u16_le(body, 0)?, u16_le(body, 2)?, u32_le(body, 4)?, u16_le(body, 14)?)),
+            b"data" => data = Some(body),
+            _ => {}
+        }
+        // Chunks are padded to an even length
+        at = at.saturating_add(8).saturating_add(len).saturating_add(len % 2);
+    }
+
+    let (encoding, channels, sample_rate, bits) = format?;
+    let data = data?;
+    // 1 is integer PCM; anything else would need decoding
+    if encoding != 1 || channels == 0 || sample_rate == 0 || !matches!(bits, 8 | 16) {
+        return None;
+    }
+
+    let frame_bytes = channels as usize * bits as usize / 8;
+    let frames = data.len() / frame_bytes;
+    let duration_ms = frames as u64 * 1000 / sample_rate as u64;
+
+    // Peak of the first channel across each slice
+    let mut waveform = Vec::with_capacity(WAVEFORM_BARS);
+    let per_bar = frames.div_ceil(WAVEFORM_BARS).max(1);
+    for bar in data.chunks(per_bar * frame_bytes) {
+        let peak = bar.chunks_exact(frame_bytes)
+            .map(|frame| match bits {
+                8 => (frame[0] as i16 - 128).unsigned_abs() * 256,
+                _ => i16::from_le_bytes([frame[0], frame[1]]).unsigned_abs(),
+            })
+            .max()
+            .unwrap_or(0);
+        waveform.push((peak >> 7).min(255) as u8);
+    }
+
+    Some(AttachmentPreview::Audio { duration_ms, waveform })
+}
+
+// Ogg Opus or Vorbis: the last page's granule position counts samples
+fn ogg_preview(bytes: &[u8]) -> Option<AttachmentPreview> {
+    if bytes.get(..4)? != b"OggS" {
+        return None;
+    }
+
+    // The first packet, after the 27-byte header and its segment table
+    let segments = *bytes.get(26)? as usize;
+    let packet = bytes.get(27 + segments..)?;
+    let (sample_rate, pre_skip) = if packet.starts_with(b"OpusHead") {
+        // Opus granules always count at 48 kHz
+        (48_000u64, u16_le(packet, 10)? as u64)
+    } else if packet.starts_with(b"\x01vorbis") {
+        (u32_le(packet, 12)? as u64, 0)
+    } else {
+        return None;
+    };
+    if sample_rate == 0 {
+        return None;
+    }
+
+    let last_page = bytes.windows(4).rposition(|window| window == b"OggS")?;
+    let granule = u64_le(bytes, last_page + 6)?;
+    // -1 marks a page on which no packet ends
+    if granule == u64::MAX {
+        return None;
+    }
+    let duration_ms = granule.saturating_sub(pre_skip) * 1000 / sample_rate;
+    Some(AttachmentPreview::Audio { duration_ms, waveform: Vec::new() })
+}
+
+// Page count from the page tree's /Count, and the /Title from the info
+// dictionary, when they aren't hidden in compressed object streams
+fn pdf_preview(bytes: &[u8]) -> Option<AttachmentPreview> {
+    if !bytes.starts_with(b"%PDF-") {
+        return None;
+    }
+
+    // The root of the page tree has the biggest count
+    let pages = find_all(bytes, b"/Count")
+        .filter_map(|at| {
+            let digits: String = bytes[at..].iter()
+                .skip_while(|byte| byte.is_ascii_whitespace())
+                .take_while(|byte| byte.is_ascii_digit())
+                .map(|byte| *byte as char)
+                .collect();
+            digits.parse::<u32>().ok()
+        })
+        .max();
+
+    let title = find_all(bytes, b"/Title")
+        .find_map(|at| literal_string(&bytes[at..]))
+        .map(|title| title.trim().chars().take(MAX_TITLE_CHARS).collect::<String>())
+        .filter(|title| !title.is_empty());
+
+    if pages.is_none() && title.is_none() {
+        return None;
+    }
+    Some(AttachmentPreview::Document { pages, title })
+}
+
+// Offsets just past each occurrence of `needle`
+fn find_all<'a>(haystack: &'a [u8], needle: &'a [u8]) -> impl Iterator<Item = usize> + 'a {
+    haystack.windows(needle.len())
+        .enumerate()
+        .filter(move |(_, window)| *window == needle)
+        .map(move |(at, _)| at + needle.len())
+}
+
+// A PDF literal string like "(Annual report)", if one starts here. Only
+// plain printable text is accepted; UTF-16 and hex strings are skipped.
+fn literal_string(bytes: &[u8]) -> Option<String> {
+    let start = bytes.iter().position(|byte| !byte.is_ascii_whitespace())?;
+    if bytes[start] != b'(' {
+        return None;
+    }
+
+    let mut text = String::new();
+    let mut depth = 0usize;
+    let mut escaped = false;
+    for byte in bytes[start + 1..].iter().take(MAX_TITLE_CHARS * 4) {
+        if escaped {
+            text.push(*byte as char);
+            escaped = false;
+            continue;
+        }
+        match *byte {
+            b'\\' => escaped = true,
+            b'(' => {
+                depth += 1;
+                text.push('(');
+            }
+            b')' if depth == 0 => {
+                return text.chars().all(|c| !c.is_control()).then_some(text);
+            }
+            b')' => {
+                depth -= 1;
+                text.push(')');
+            }
+            byte if byte.is_ascii() => text.push(byte as char),
+            _ => return None,
+        }
+    }
+    None
+}
@@ -0,0 +1,297 @@
+// Files sent alongside messages.
+//
+// Each file is encrypted with its own random key and uploaded to the
+// sender's homeserver under a random name. The URL, key and hash travel in
+// the message's signed extras, so only the conversation can find the file,
+// open it, or tell that it was swapped. Files up to MAX_ATTACHMENT_BYTES
+// are sealed in one piece; bigger ones are chunked with `stream_cipher` so
+// neither side holds the whole file in memory. A sender can give a file an
+// expiry, after which `attachment_expiry` deletes it from their homeserver.
+use crate::attachment_preview::{self, AttachmentPreview};
+use crate::crypto_compat;
+use crate::error::MessengerError;
+use crate::stream_cipher;
+use anyhow::{anyhow, Result};
+use image::{ImageFormat, RgbaImage};
+use pkarr::PublicKey;
+use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+use std::path::Path;
+use uuid::Uuid;
+
+pub const MAX_ATTACHMENT_BYTES: u64 = 25 * 1024 * 1024;
+pub const MAX_STREAMED_ATTACHMENT_BYTES: u64 = 4 * 1024 * 1024 * 1024;
+
+// Format byte, nonce and tag around a file sealed in one piece
+const SEALED_OVERHEAD: u64 = 64;
+
+const ATTACHMENTS_PATH: &str = "/pub/private_messages/attachments/";
+
+// What a message carries about its file
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Attachment {
+    pub name: String,
+    pub mime: String,
+    pub size: u64,
+    // The encrypted file, on the sender's homeserver
+    pub url: String,
+    // Hex of the per-file key
+    pub key: String,
+    // Hex blake3 of the decrypted file
+    pub hash: String,
+    // Sealed with stream_cipher rather than in one piece
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub chunked: bool,
+    // What's inside, for files that aren't images
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preview: Option<AttachmentPreview>,
+    // Unix time the sender deletes the file after; None keeps it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+}
+
+// A file picked, dropped or pasted by the user, ready to upload
+pub struct AttachmentFile {
+    pub name: String,
+    pub mime: String,
+    pub bytes: Vec<u8>,
+}
+
+impl AttachmentFile {
+    pub fn new(name: &str, mime: Option<&str>, bytes: Vec<u8>) -> Result<Self> {
+        let name = safe_file_name(name);
+        check_size(bytes.len() as u64)?;
+        Ok(Self {
+            mime: mime.map(str::to_string).unwrap_or_else(|| mime_type(&name).to_string()),
+            name,
+            bytes,
+        })
+    }
+
+    pub fn read(path: &Path) -> Result<Self> {
+        let metadata = std::fs::metadata(path)?;
+        if !metadata.is_file() {
+            return Err(anyhow!(MessengerError::InvalidInput(format!("{} is not a file", path.display()))));
+        }
+        // Refuse before reading the whole thing into memory
+        check_size(metadata.len())?;
+
+        let name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+        Self::new(&name, None, std::fs::read(path)?)
+    }
+
+    // Pasted screenshots and copied images arrive as raw RGBA pixels
+    pub fn png_from_rgba(name: &str, width: u32, height: u32, rgba: Vec<u8>) -> Result<Self> {
+        let image = RgbaImage::from_raw(width, height, rgba)
+            .ok_or_else(|| MessengerError::InvalidInput(format!("Image data doesn't match its {}x{} size", width, height)))?;
+
+        let mut png = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .map_err(|e| anyhow!("Failed to encode PNG: {}", e))?;
+        Self::new(name, Some("image/png"), png)
+    }
+}
+
+fn check_size(size: u64) -> Result<()> {
+    check_size_within(size, MAX_ATTACHMENT_BYTES)
+}
+
+fn check_size_within(size: u64, limit: u64) -> Result<()> {
+    if size == 0 {
+        return Err(anyhow!(MessengerError::InvalidInput("File is empty".to_string())));
+    }
+    if size > limit {
+        return Err(anyhow!(MessengerError::InvalidInput(format!(
+            "File is {}, the limit is {}",
+            format_size(size),
+            format_size(limit)
+        ))));
+    }
+    Ok(())
+}
+
+// Too big to seal in memory, so it has to go through `seal_file`
+pub fn needs_streaming(size: u64) -> bool {
+    size > MAX_ATTACHMENT_BYTES
+}
+
+// Most ciphertext a homeserver should send for `attachment`
+pub fn max_ciphertext_len(attachment: &Attachment) -> u64 {
+    if attachment.chunked {
+        stream_cipher::sealed_len(attachment.size.min(MAX_STREAMED_ATTACHMENT_BYTES))
+    } else {
+        attachment.size.min(MAX_ATTACHMENT_BYTES) + SEALED_OVERHEAD
+    }
+}
+
+// Just the final path component, so a received name can't point elsewhere
+pub fn safe_file_name(name: &str) -> String {
+    let name = name.rsplit(['/', '\\']).next().unwrap_or_default().trim();
+    match name {
+        "" | "." | ".." => "attachment".to_string(),
+        name => name.to_string(),
+    }
+}
+
+pub fn mime_type(name: &str) -> &'static str {
+    let extension = name.rsplit_once('.').map(|(_, extension)| extension.to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("svg") => "image/svg+xml",
+        Some("pdf") => "application/pdf",
+        Some("txt" | "md") => "text/plain",
+        Some("json") => "application/json",
+        Some("zip") => "application/zip",
+        Some("mp3") => "audio/mpeg",
+        Some("wav") => "audio/wav",
+        Some("ogg" | "opus") => "audio/ogg",
+        Some("m4a") => "audio/mp4",
+        Some("mp4") => "video/mp4",
+        Some("webm") => "video/webm",
+        _ => "application/octet-stream",
+    }
+}
+
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+// Plain text fallback for clients that don't understand attachments
+pub fn fallback_text(attachment: &Attachment) -> String {
+    format!("📎 {} ({})", attachment.name, format_size(attachment.size))
+}
+
+fn new_key() -> [u8; 32] {
+    let mut key = [0u8; 32];
+    OsRng.fill_bytes(&mut key);
+    key
+}
+
+fn new_url(owner: &PublicKey) -> String {
+    format!("pubky://{}{}{}", owner, ATTACHMENTS_PATH, Uuid::new_v4())
+}
+
+fn attachment_key(attachment: &Attachment) -> Result<[u8; 32]> {
+    hex::decode(&attachment.key)
+        .ok()
+        .and_then(|key| key.try_into().ok())
+        .ok_or_else(|| anyhow!(MessengerError::Crypto("Invalid attachment key".to_string())))
+}
+
+fn check_matches(attachment: &Attachment, size: u64, hash: &str) -> Result<()> {
+    if size != attachment.size || hash != attachment.hash {
+        return Err(anyhow!(MessengerError::Crypto("Attachment doesn't match the message it was sent with".to_string())));
+    }
+    Ok(())
+}
+
+// Encrypt `file` under a fresh key, returning the reference to send and
+// the ciphertext to upload for `owner`
+pub fn seal(owner: &PublicKey, file: &AttachmentFile) -> Result<(Attachment, Vec<u8>)> {
+    let key = new_key();
+    let ciphertext = crypto_compat::encrypt(&file.bytes, &key)?;
+
+    let attachment = Attachment {
+        name: file.name.clone(),
+        mime: file.mime.clone(),
+        size: file.bytes.len() as u64,
+        url: new_url(owner),
+        key: hex::encode(key),
+        hash: blake3::hash(&file.bytes).to_hex().to_string(),
+        chunked: false,
+        preview: attachment_preview::generate(&file.mime, &file.bytes),
+        expires_at: None,
+    };
+    Ok((attachment, ciphertext))
+}
+
+// Encrypt the file at `path` in chunks into `sealed`, for files too big to
+// read into memory. Returns the reference to send once `sealed` is uploaded.
+pub async fn seal_file(owner: &PublicKey, path: &Path, sealed: &Path) -> Result<Attachment> {
+    let metadata = tokio::fs::metadata(path).await?;
+    if !metadata.is_file() {
+        return Err(anyhow!(MessengerError::InvalidInput(format!("{} is not a file", path.display()))));
+    }
+    check_size_within(metadata.len(), MAX_STREAMED_ATTACHMENT_BYTES)?;
+
+    let key = new_key();
+    let (size, hash) = stream_cipher::seal_file(path, sealed, &key).await?;
+    let name = safe_file_name(&path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default());
+    Ok(Attachment {
+        mime: mime_type(&name).to_string(),
+        name,
+        size,
+        url: new_url(owner),
+        key: hex::encode(key),
+        hash,
+        chunked: true,
+        preview: None,
+        expires_at: None,
+    })
+}
+
+// Decrypt a downloaded file and check it is the one the message signed
+pub fn open(attachment: &Attachment, ciphertext: &[u8]) -> Result<Vec<u8>> {
+    let key = attachment_key(attachment)?;
+    let bytes = if attachment.chunked {
+        stream_cipher::open(ciphertext, &key)?
+    } else {
+        crypto_compat::decrypt(ciphertext, &key)?
+    };
+
+    check_matches(attachment, bytes.len() as u64, blake3::hash(&bytes).to_hex().as_str())?;
+    Ok(bytes)
+}
+
+// Decrypt the downloaded ciphertext at `sealed` into `path`, a chunk at a
+// time when the sender chunked it. Nothing is left at `path` on failure.
+pub async fn open_file(attachment: &Attachment, sealed: &Path, path: &Path) -> Result<()> {
+    if !attachment.chunked {
+        let bytes = open(attachment, &tokio::fs::read(sealed).await?)?;
+        tokio::fs::write(path, bytes).await?;
+        return Ok(());
+    }
+
+    let (size, hash) = stream_cipher::open_file(sealed, path, &attachment_key(attachment)?).await?;
+    if let Err(e) = check_matches(attachment, size, &hash) {
+        let _ = tokio::fs::remove_file(path).await;
+        return Err(e);
+    }
+    Ok(())
+}
+
+pub fn is_expired(attachment: &Attachment, now: u64) -> bool {
+    attachment.expires_at.is_some_and(|expires_at| expires_at <= now)
+}
+
+// The sender has deleted an expired file, or is about to
+pub fn check_not_expired(attachment: &Attachment, now: u64) -> Result<()> {
+    if is_expired(attachment, now) {
+        return Err(anyhow!(MessengerError::AttachmentExpired(format!("{} has expired", attachment.name))));
+    }
+    Ok(())
+}
+
+// Attachments are only ever fetched from a pubky homeserver
+pub fn check_url(attachment: &Attachment) -> Result<()> {
+    if !attachment.url.starts_with("pubky://") || !attachment.url.contains(ATTACHMENTS_PATH) {
+        return Err(anyhow!(MessengerError::InvalidInput("Attachment URL is not a homeserver attachment".to_string())));
+    }
+    Ok(())
+}
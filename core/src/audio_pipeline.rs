@@ -0,0 +1,265 @@
+// Voice notes are transcoded before they are sealed: whatever the recorder
+// produced (WebM or Ogg Opus from a webview, AAC in MP4, MP3, WAV) is
+// decoded, mixed down to mono, normalized and re-encoded as Ogg Opus at a
+// fixed bitrate. That keeps them small and playable on every platform,
+// and drops whatever tags the recorder wrote.
+//
+// A recording that can't be decoded is sent as it is.
+use crate::attachments::AttachmentFile;
+use anyhow::{anyhow, Result};
+use ogg::writing::{PacketWriteEndInfo, PacketWriter};
+use opus::{Application, Bitrate, Channels};
+use rand_core::{OsRng, RngCore};
+use std::io::{Cursor, ErrorKind};
+use std::path::Path;
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{CodecParameters, Decoder as AudioDecoder, DecoderOptions, CODEC_TYPE_NULL, CODEC_TYPE_OPUS};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::{FormatOptions, Packet};
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+// Plenty for speech in mono
+pub const BITRATE: i32 = 24_000;
+// Longer recordings aren't decoded into memory, just sent as they are
+pub const MAX_DURATION_SECS: usize = 15 * 60;
+
+pub const MIME: &str = "audio/ogg";
+
+// Opus is encoded at 48 kHz, in 20 ms frames
+const SAMPLE_RATE: u32 = 48_000;
+const FRAME_SAMPLES: usize = 960;
+// What libopus recommends allocating for one packet
+const MAX_PACKET_BYTES: usize = 4000;
+// The longest Opus packet, 120 ms
+const MAX_PACKET_SAMPLES: usize = 5760;
+
+// Peaks are brought up to about -1 dBFS, but by no more than 20 dB, so a
+// near-silent recording doesn't turn into loud noise
+const TARGET_PEAK: f32 = 0.89;
+const MAX_GAIN: f32 = 10.0;
+
+const VENDOR: &[u8] = b"pubky-private-messenger";
+
+// `file` as a voice note should be sent
+pub fn prepare(file: AttachmentFile) -> AttachmentFile {
+    match transcode(&file.bytes, &file.mime) {
+        Ok(bytes) => {
+            tracing::debug!("🎙️  Transcoded {}: {} -> {} bytes", file.name, file.bytes.len(), bytes.len());
+            AttachmentFile { name: ogg_file_name(&file.name), mime: MIME.to_string(), bytes }
+        }
+        Err(e) => {
+            tracing::warn!("⚠️  Sending {} without transcoding: {}", file.name, e);
+            file
+        }
+    }
+}
+
+fn ogg_file_name(name: &str) -> String {
+    let stem = Path::new(name).file_stem().and_then(|stem| stem.to_str()).unwrap_or("voice-note");
+    format!("{}.ogg", stem)
+}
+
+fn transcode(bytes: &[u8], mime: &str) -> Result<Vec<u8>> {
+    let (samples, source_rate) = decode(bytes, mime)?;
+    let mut samples = resample(samples, source_rate, SAMPLE_RATE);
+    normalize(&mut samples);
+    encode(&samples, source_rate)
+}
+
+// Symphonia decodes everything but Opus, which goes to libopus
+enum TrackDecoder {
+    Opus { decoder: opus::Decoder, channels: usize },
+    Other(Box<dyn AudioDecoder>),
+}
+
+impl TrackDecoder {
+    fn new(params: &CodecParameters) -> Result<(Self, u32)> {
+        if params.codec == CODEC_TYPE_OPUS {
+            let (opus_channels, channels) = match params.channels.map(|channels| channels.count()) {
+                Some(1) => (Channels::Mono, 1),
+                Some(2) | None => (Channels::Stereo, 2),
+                Some(count) => return Err(anyhow!("Opus with {} channels isn't supported", count)),
+            };
+            let decoder = opus::Decoder::new(SAMPLE_RATE, opus_channels)
+                .map_err(|e| anyhow!("Failed to create Opus decoder: {}", e))?;
+            return Ok((TrackDecoder::Opus { decoder, channels }, SAMPLE_RATE));
+        }
+
+        let sample_rate = params.sample_rate.ok_or_else(|| anyhow!("Unknown sample rate"))?;
+        let decoder = symphonia::default::get_codecs()
+            .make(params, &DecoderOptions::default())
+            .map_err(|e| anyhow!("Unsupported audio codec: {}", e))?;
+        Ok((TrackDecoder::Other(decoder), sample_rate))
+    }
+
+    // Append the packet's audio to `mono`, mixed down
+    fn decode(&mut self, packet: &Packet, mono: &mut Vec<f32>) -> Result<()> {
+        match self {
+            TrackDecoder::Opus { decoder, channels } => {
+                let mut decoded = vec![0f32; MAX_PACKET_SAMPLES * *channels];
+                let frames = decoder
+                    .decode_float(packet.buf(), &mut decoded, false)
+                    .map_err(|e| anyhow!("Failed to decode Opus: {}", e))?;
+                mix_down(&decoded[..frames * *channels], *channels, mono);
+            }
+            TrackDecoder::Other(decoder) => {
+                let decoded = match decoder.decode(packet) {
+                    Ok(decoded) => decoded,
+                    // A damaged packet only loses its own few milliseconds
+                    Err(SymphoniaError::DecodeError(_)) => return Ok(()),
+                    Err(e) => return Err(anyhow!("Failed to decode audio: {}", e)),
+                };
+                let spec = *decoded.spec();
+                let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+                buffer.copy_interleaved_ref(decoded);
+                mix_down(buffer.samples(), spec.channels.count(), mono);
+            }
+        }
+        Ok(())
+    }
+}
+
+// Mono samples of the first audio track, and their rate
+fn decode(bytes: &[u8], mime: &str) -> Result<(Vec<f32>, u32)> {
+    let source = MediaSourceStream::new(Box::new(Cursor::new(bytes.to_vec())), Default::default());
+    let mut hint = Hint::new();
+    // Recorders report e.g. "audio/webm;codecs=opus"
+    hint.mime_type(mime.split(';').next().unwrap_or_default().trim());
+    let mut format = symphonia::default::get_probe()
+        .format(&hint, source, &FormatOptions::default(), &MetadataOptions::default())
+        .map_err(|e| anyhow!("Unrecognized audio: {}", e))?
+        .format;
+
+    let track = format
+        .tracks()
+        .iter()
+        .find(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or_else(|| anyhow!("No audio track"))?;
+    let track_id = track.id;
+    let (mut decoder, sample_rate) = TrackDecoder::new(&track.codec_params)?;
+    let max_samples = MAX_DURATION_SECS * sample_rate as usize;
+
+    let mut samples = Vec::new();
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(SymphoniaError::IoError(e)) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(anyhow!("Failed to read audio: {}", e)),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        decoder.decode(&packet, &mut samples)?;
+        if samples.len() > max_samples {
+            return Err(anyhow!("Longer than {} minutes", MAX_DURATION_SECS / 60));
+        }
+    }
+    if samples.is_empty() {
+        return Err(anyhow!("No audio"));
+    }
+    Ok((samples, sample_rate))
+}
+
+fn mix_down(interleaved: &[f32], channels: usize, mono: &mut Vec<f32>) {
+    let channels = channels.max(1);
+    mono.extend(interleaved.chunks_exact(channels).map(|frame| frame.iter().sum::<f32>() / channels as f32));
+}
+
+// Linear interpolation. Recorders use 44.1 or 48 kHz, so there's little
+// to lose for speech.
+fn resample(samples: Vec<f32>, from: u32, to: u32) -> Vec<f32> {
+    if from == to {
+        return samples;
+    }
+    let step = from as f64 / to as f64;
+    let len = (samples.len() as f64 / step) as usize;
+    (0..len)
+        .map(|i| {
+            let position = i as f64 * step;
+            let index = position as usize;
+            let fraction = (position - index as f64) as f32;
+            let current = samples[index];
+            let next = samples.get(index + 1).copied().unwrap_or(current);
+            current + (next - current) * fraction
+        })
+        .collect()
+}
+
+fn normalize(samples: &mut [f32]) {
+    let peak = samples.iter().fold(0f32, |peak, sample| peak.max(sample.abs()));
+    if peak <= f32::EPSILON {
+        return;
+    }
+    let gain = (TARGET_PEAK / peak).min(MAX_GAIN);
+    for sample in samples {
+        *sample = (*sample * gain).clamp(-1.0, 1.0);
+    }
+}
+
+// 48 kHz mono samples as an Ogg Opus file (RFC 7845)
+fn encode(samples: &[f32], input_rate: u32) -> Result<Vec<u8>> {
+    let mut encoder = opus::Encoder::new(SAMPLE_RATE, Channels::Mono, Application::Voip)
+        .map_err(|e| anyhow!("Failed to create Opus encoder: {}", e))?;
+    encoder
+        .set_bitrate(Bitrate::Bits(BITRATE))
+        .map_err(|e| anyhow!("Failed to set Opus bitrate: {}", e))?;
+    let pre_skip = encoder
+        .get_lookahead()
+        .map_err(|e| anyhow!("Failed to read Opus lookahead: {}", e))? as usize;
+
+    // The encoder runs `pre_skip` samples behind, so feed it that much
+    // silence at the end to get all of the recording back out
+    let mut padded = samples.to_vec();
+    padded.resize(samples.len() + pre_skip, 0.0);
+    let frames = padded.len().div_ceil(FRAME_SAMPLES);
+
+    let serial = OsRng.next_u32();
+    let mut out = Vec::new();
+    {
+        let mut writer = PacketWriter::new(&mut out);
+        writer.write_packet(opus_head(pre_skip as u16, input_rate), serial, PacketWriteEndInfo::EndPage, 0)?;
+        writer.write_packet(opus_tags(), serial, PacketWriteEndInfo::EndPage, 0)?;
+
+        let mut frame = [0f32; FRAME_SAMPLES];
+        let mut packet = [0u8; MAX_PACKET_BYTES];
+        for (index, chunk) in padded.chunks(FRAME_SAMPLES).enumerate() {
+            frame[..chunk.len()].copy_from_slice(chunk);
+            frame[chunk.len()..].fill(0.0);
+            let len = encoder
+                .encode_float(&frame, &mut packet)
+                .map_err(|e| anyhow!("Failed to encode Opus: {}", e))?;
+
+            // Granule positions count decoded samples, pre-skip included;
+            // the last one cuts the padding back off
+            let (end, granule) = if index + 1 == frames {
+                (PacketWriteEndInfo::EndStream, pre_skip + samples.len())
+            } else {
+                (PacketWriteEndInfo::NormalPacket, (index + 1) * FRAME_SAMPLES)
+            };
+            writer.write_packet(packet[..len].to_vec(), serial, end, granule as u64)?;
+        }
+    }
+    Ok(out)
+}
+
+// Version 1, mono, no output gain, channel mapping family 0
+fn opus_head(pre_skip: u16, input_rate: u32) -> Vec<u8> {
+    let mut head = b"OpusHead".to_vec();
+    head.extend_from_slice(&[1, 1]);
+    head.extend_from_slice(&pre_skip.to_le_bytes());
+    head.extend_from_slice(&input_rate.to_le_bytes());
+    head.extend_from_slice(&0i16.to_le_bytes());
+    head.push(0);
+    head
+}
+
+// Only a vendor string, no comments
+fn opus_tags() -> Vec<u8> {
+    let mut tags = b"OpusTags".to_vec();
+    tags.extend_from_slice(&(VENDOR.len() as u32).to_le_bytes());
+    tags.extend_from_slice(VENDOR);
+    tags.extend_from_slice(&0u32.to_le_bytes());
+    tags
+}
@@ -0,0 +1,52 @@
+// Profile pictures: shrink whatever the user picked to a small square JPEG
+// and describe it the way pubky.app stores files, so other pubky.app
+// clients show the same avatar.
+use anyhow::{anyhow, Result};
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use serde::{Deserialize, Serialize};
+
+use crate::error::MessengerError;
+
+pub const AVATAR_SIZE: u32 = 400;
+const AVATAR_QUALITY: u8 = 85;
+
+// Anything bigger is refused before decoding
+pub const MAX_SOURCE_BYTES: usize = 20 * 1024 * 1024;
+
+// A pubky.app file record is a few fields of JSON
+pub const MAX_FILE_RECORD_BYTES: usize = 16 * 1024;
+
+pub const AVATAR_CONTENT_TYPE: &str = "image/jpeg";
+
+// pubky.app file record pointing at an uploaded blob
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PubkyAppFile {
+    pub name: String,
+    // Microseconds
+    pub created_at: i64,
+    pub src: String,
+    pub content_type: String,
+    pub size: usize,
+}
+
+// Center-crop to a square, resize and re-encode. Re-encoding also drops
+// EXIF data like the camera's GPS position.
+pub fn prepare(image_bytes: &[u8]) -> Result<Vec<u8>> {
+    if image_bytes.len() > MAX_SOURCE_BYTES {
+        return Err(anyhow!(MessengerError::InvalidInput(format!(
+            "Image must be at most {} MB",
+            MAX_SOURCE_BYTES / (1024 * 1024)
+        ))));
+    }
+
+    let image = image::load_from_memory(image_bytes)
+        .map_err(|e| MessengerError::InvalidInput(format!("Unsupported image: {}", e)))?;
+    let avatar = image.resize_to_fill(AVATAR_SIZE, AVATAR_SIZE, FilterType::Lanczos3).to_rgb8();
+
+    let mut jpeg = Vec::new();
+    JpegEncoder::new_with_quality(&mut jpeg, AVATAR_QUALITY)
+        .encode_image(&avatar)
+        .map_err(|e| anyhow!("Failed to encode avatar: {}", e))?;
+    Ok(jpeg)
+}
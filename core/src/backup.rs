@@ -0,0 +1,164 @@
+use crate::crypto_compat::{self, CipherFormat};
+use crate::documents;
+use crate::error::MessengerError;
+use crate::local_store::LocalStore;
+use crate::messaging::ChatMessage;
+use crate::storage::{Storage, StoredContact, StoredMessage, SyncCursor};
+use anyhow::{anyhow, Result};
+use argon2::Argon2;
+use pkarr::PublicKey;
+use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+const BACKUP_VERSION: u32 = 1;
+const SALT_LEN: usize = 16;
+
+// On-disk archive: everything but the header is encrypted with a key
+// derived from the backup passphrase
+#[derive(Serialize, Deserialize)]
+struct BackupArchive {
+    version: u32,
+    owner: String,
+    created_at: u64,
+    salt: Vec<u8>,
+    ciphertext: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BackupMessage {
+    conversation: String,
+    cipher_format: String,
+    message: ChatMessage,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct BackupContents {
+    messages: Vec<BackupMessage>,
+    contacts: Vec<StoredContact>,
+    sync_cursors: HashMap<String, SyncCursor>,
+    documents: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RestoreSummary {
+    pub messages: usize,
+    pub contacts: usize,
+    pub documents: usize,
+}
+
+// Every document the registry marks for backup, as named for `owner`
+pub fn document_names(owner: &PublicKey) -> Vec<String> {
+    documents::USER_DOCUMENTS.iter()
+        .map(|kind| documents::user_document(kind, owner))
+        .chain(documents::SETTINGS_DOCUMENTS.iter().map(|name| name.to_string()))
+        .collect()
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<[u8; 32]> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow!("Failed to derive backup key: {}", e))?;
+    Ok(key)
+}
+
+// Serialize and encrypt the user's local database and settings
+pub fn create(storage: &Storage, store: &LocalStore, owner: &PublicKey, passphrase: &str, now: u64) -> Result<Vec<u8>> {
+    if passphrase.is_empty() {
+        return Err(anyhow!(MessengerError::InvalidInput("Backup passphrase must not be empty".to_string())));
+    }
+
+    let mut contents = BackupContents {
+        messages: storage.all_messages()?
+            .into_iter()
+            .map(|(conversation, stored)| BackupMessage {
+                conversation,
+                cipher_format: stored.cipher_format.as_str().to_string(),
+                message: stored.message,
+            })
+            .collect(),
+        contacts: storage.all_contacts()?,
+        sync_cursors: storage.all_sync_cursors()?,
+        ..Default::default()
+    };
+
+    for name in document_names(owner) {
+        let document: serde_json::Value = store.load(&name)?;
+        if !document.is_null() {
+            contents.documents.insert(name, document);
+        }
+    }
+
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let key = derive_key(passphrase, &salt)?;
+
+    let archive = BackupArchive {
+        version: BACKUP_VERSION,
+        owner: owner.to_string(),
+        created_at: now,
+        salt: salt.to_vec(),
+        ciphertext: crypto_compat::encrypt(&serde_json::to_vec(&contents)?, &key)?,
+    };
+
+    tracing::debug!("💾 Backed up {} messages and {} contacts", contents.messages.len(), contents.contacts.len());
+    Ok(serde_json::to_vec(&archive)?)
+}
+
+// Merge a backup into the local database. Messages already present are
+// kept, settings documents are replaced by the backed up copies.
+pub fn restore(bytes: &[u8], passphrase: &str, storage: &Storage, store: &LocalStore, owner: &PublicKey) -> Result<RestoreSummary> {
+    let archive: BackupArchive = serde_json::from_slice(bytes)
+        .map_err(|e| MessengerError::InvalidInput(format!("Not a backup file: {}", e)))?;
+
+    if archive.version != BACKUP_VERSION {
+        return Err(anyhow!(MessengerError::InvalidInput(format!("Unsupported backup version: {}", archive.version))));
+    }
+    // The cache is keyed to the account, so a backup only restores into it
+    if archive.owner != owner.to_string() {
+        return Err(anyhow!(MessengerError::InvalidInput(format!("Backup belongs to a different account ({})", archive.owner))));
+    }
+
+    let key = derive_key(passphrase, &archive.salt)?;
+    let plaintext = crypto_compat::decrypt(&archive.ciphertext, &key)
+        .map_err(|_| MessengerError::BadPassphrase)?;
+    let contents: BackupContents = serde_json::from_slice(&plaintext)?;
+
+    let mut by_conversation: HashMap<String, Vec<StoredMessage>> = HashMap::new();
+    for backed_up in contents.messages {
+        by_conversation.entry(backed_up.conversation).or_default().push(StoredMessage {
+            cipher_format: CipherFormat::parse(&backed_up.cipher_format).unwrap_or(crypto_compat::CURRENT_CIPHER_FORMAT),
+            message: backed_up.message,
+        });
+    }
+
+    let mut restored_messages = 0;
+    for (conversation, messages) in &by_conversation {
+        restored_messages += storage.insert_messages(conversation, messages)?.len();
+    }
+
+    for contact in &contents.contacts {
+        storage.restore_contact(contact)?;
+    }
+
+    for (conversation, cursor) in contents.sync_cursors {
+        storage.set_sync_cursor(&conversation, cursor)?;
+    }
+
+    // Only restore documents we know about, never arbitrary file names
+    let allowed = document_names(owner);
+    let mut restored_documents = 0;
+    for (name, document) in contents.documents {
+        if allowed.contains(&name) {
+            store.save(&name, &document)?;
+            restored_documents += 1;
+        }
+    }
+
+    Ok(RestoreSummary {
+        messages: restored_messages,
+        contacts: contents.contacts.len(),
+        documents: restored_documents,
+    })
+}
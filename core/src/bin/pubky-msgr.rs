@@ -0,0 +1,286 @@
+// Headless client for scripting, bots and debugging, driven by the same
+// PrivateMessageHandler as the desktop app. Nothing is cached locally:
+// every command reads straight from the homeservers.
+use pkarr::{Keypair, PublicKey};
+use pubky_messenger_core::crypto_compat;
+use pubky_messenger_core::error::{ErrorContext, MessengerError, MessengerResult};
+use pubky_messenger_core::http_cache::HttpCache;
+use pubky_messenger_core::messaging::{ChatMessage, FollowedUser, PrivateMessageHandler};
+use pubky_messenger_core::net::{self, NetworkSettings};
+use pubky_messenger_core::watcher::ListingWatcher;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, Read, Write};
+use std::process::ExitCode;
+use std::sync::Arc;
+
+const RECOVERY_FILE_ENV: &str = "PUBKY_MSGR_RECOVERY_FILE";
+const PASSPHRASE_ENV: &str = "PUBKY_MSGR_PASSPHRASE";
+
+const USAGE: &str = "\
+Usage: pubky-msgr [options] <command>
+
+Commands:
+  send <pubkey> <message>...    Send a message; use - to read it from stdin
+  inbox                         Messages from everyone you follow, newest first
+  conversation <pubkey>         The whole conversation with one contact
+  contacts                      Everyone you follow
+
+Options:
+  --recovery-file <path>        Pubky recovery file (or $PUBKY_MSGR_RECOVERY_FILE)
+  --testnet                     Use the local testnet instead of mainnet
+  --limit <n>                   Show at most n messages
+  --json                        Print JSON instead of text
+  -v, --verbose                 Log to stderr
+  -h, --help                    Show this help
+
+The recovery file passphrase is read from $PUBKY_MSGR_PASSPHRASE, or from
+stdin when that is unset.";
+
+enum Command {
+    Help,
+    Send { recipient: String, content: String },
+    Inbox,
+    Conversation { other: String },
+    Contacts,
+}
+
+struct Options {
+    recovery_file: Option<String>,
+    testnet: bool,
+    limit: Option<usize>,
+    json: bool,
+    verbose: bool,
+}
+
+fn parse_args(args: Vec<String>) -> Result<(Options, Command), String> {
+    let mut options = Options { recovery_file: None, testnet: false, limit: None, json: false, verbose: false };
+    let mut positional = Vec::new();
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--recovery-file" => options.recovery_file = Some(args.next().ok_or("--recovery-file needs a path")?),
+            "--testnet" => options.testnet = true,
+            "--limit" => {
+                let limit = args.next().ok_or("--limit needs a number")?;
+                options.limit = Some(limit.parse().map_err(|_| format!("Invalid limit: {}", limit))?);
+            }
+            "--json" => options.json = true,
+            "-v" | "--verbose" => options.verbose = true,
+            "-h" | "--help" => positional.insert(0, "help".to_string()),
+            flag if flag.starts_with("--") => return Err(format!("Unknown option: {}", flag)),
+            _ => positional.push(arg),
+        }
+    }
+
+    let mut positional = positional.into_iter();
+    let command = match positional.next().as_deref() {
+        Some("send") => {
+            let recipient = positional.next().ok_or("send needs a recipient pubkey")?;
+            let words: Vec<String> = positional.by_ref().collect();
+            if words.is_empty() {
+                return Err("send needs a message".to_string());
+            }
+            Command::Send { recipient, content: words.join(" ") }
+        }
+        Some("inbox") => Command::Inbox,
+        Some("conversation") => Command::Conversation {
+            other: positional.next().ok_or("conversation needs a contact pubkey")?,
+        },
+        Some("contacts") => Command::Contacts,
+        Some("help") => Command::Help,
+        Some(other) => return Err(format!("Unknown command: {}", other)),
+        None => return Err(USAGE.to_string()),
+    };
+
+    if let Some(extra) = positional.next() {
+        return Err(format!("Unexpected argument: {}", extra));
+    }
+    Ok((options, command))
+}
+
+fn parse_pubkey(pubkey: &str) -> MessengerResult<PublicKey> {
+    PublicKey::try_from(pubkey)
+        .map_err(|e| MessengerError::InvalidPublicKey(format!("Invalid public key {}: {}", pubkey, e)))
+}
+
+fn read_passphrase() -> MessengerResult<String> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        return Ok(passphrase);
+    }
+
+    eprint!("Passphrase: ");
+    std::io::stderr().flush().err_context("Failed to prompt for passphrase")?;
+    let mut passphrase = String::new();
+    std::io::stdin().lock().read_line(&mut passphrase).err_context("Failed to read passphrase")?;
+    Ok(passphrase.trim_end_matches(['\r', '\n']).to_string())
+}
+
+fn load_keypair(options: &Options) -> MessengerResult<Keypair> {
+    let path = options.recovery_file.clone()
+        .or_else(|| std::env::var(RECOVERY_FILE_ENV).ok())
+        .ok_or_else(|| MessengerError::InvalidInput(format!("Pass --recovery-file or set {}", RECOVERY_FILE_ENV)))?;
+    let recovery_file = std::fs::read(&path).err_context("Failed to read recovery file")?;
+    Ok(crypto_compat::decrypt_recovery_file(&recovery_file, &read_passphrase()?)?)
+}
+
+fn print_json(value: &impl Serialize) -> MessengerResult<()> {
+    let json = serde_json::to_string_pretty(value).err_context("Failed to encode output")?;
+    println!("{}", json);
+    Ok(())
+}
+
+fn format_time(timestamp: u64) -> String {
+    chrono::DateTime::from_timestamp(timestamp as i64, 0)
+        .map(|time| time.format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_else(|| timestamp.to_string())
+}
+
+fn verified_mark(verified: bool) -> &'static str {
+    if verified { "" } else { " [unverified]" }
+}
+
+#[derive(Serialize)]
+struct InboxMessage {
+    sender: String,
+    sender_name: Option<String>,
+    content: String,
+    timestamp: u64,
+    verified: bool,
+}
+
+async fn send(handler: &PrivateMessageHandler, recipient: &str, content: String) -> MessengerResult<()> {
+    let recipient = parse_pubkey(recipient)?;
+    let content = if content == "-" {
+        let mut stdin = String::new();
+        std::io::stdin().read_to_string(&mut stdin).err_context("Failed to read message from stdin")?;
+        stdin
+    } else {
+        content
+    };
+    if content.trim().is_empty() {
+        return Err(MessengerError::InvalidInput("Message is empty".to_string()));
+    }
+
+    handler.send_message(&recipient, &content).await.err_context("Failed to send message")
+}
+
+async fn inbox(handler: &PrivateMessageHandler, options: &Options) -> MessengerResult<()> {
+    let users = handler.get_followed_users_with_profiles().await.err_context("Failed to load contacts")?;
+    let contacts: Vec<PublicKey> = users.iter()
+        .filter_map(|user| PublicKey::try_from(user.pubky.as_str()).ok())
+        .collect();
+    let names: HashMap<String, String> = users.into_iter()
+        .filter_map(|user| Some((user.pubky, user.name?)))
+        .collect();
+    let own_pubkey = handler.keypair.public_key().to_string();
+
+    let messages: Vec<InboxMessage> = handler.get_all_new_messages_from_contacts_with_timestamp(&contacts).await
+        .err_context("Failed to load messages")?
+        .into_iter()
+        .filter(|(sender, ..)| *sender != own_pubkey)
+        .take(options.limit.unwrap_or(usize::MAX))
+        .map(|(sender, content, timestamp, verified)| InboxMessage {
+            sender_name: names.get(&sender).cloned(),
+            sender,
+            content,
+            timestamp,
+            verified,
+        })
+        .collect();
+
+    if options.json {
+        return print_json(&messages);
+    }
+    for message in &messages {
+        let sender = message.sender_name.as_deref().unwrap_or(&message.sender);
+        println!("{}  {}{}: {}", format_time(message.timestamp), sender, verified_mark(message.verified), message.content);
+    }
+    Ok(())
+}
+
+async fn conversation(handler: &PrivateMessageHandler, other: &str, options: &Options) -> MessengerResult<()> {
+    let other = parse_pubkey(other)?;
+    let mut messages: Vec<ChatMessage> = handler.get_new_chat_messages(&other, &HashSet::new()).await
+        .err_context("Failed to load conversation")?
+        .into_iter()
+        .map(|stored| stored.message)
+        .collect();
+    // Oldest first, keeping the most recent `limit`
+    if let Some(limit) = options.limit {
+        messages.drain(..messages.len().saturating_sub(limit));
+    }
+
+    if options.json {
+        return print_json(&messages);
+    }
+    for message in &messages {
+        let sender = if message.is_own_message { "me" } else { message.sender.as_str() };
+        println!("{}  {}{}: {}", format_time(message.timestamp), sender, verified_mark(message.verified), message.content);
+    }
+    Ok(())
+}
+
+async fn contacts(handler: &PrivateMessageHandler, options: &Options) -> MessengerResult<()> {
+    let users: Vec<FollowedUser> = handler.get_followed_users_with_profiles().await
+        .err_context("Failed to load contacts")?;
+
+    if options.json {
+        return print_json(&users);
+    }
+    for user in &users {
+        let mutual = if user.mutual { " (mutual)" } else { "" };
+        let status = user.status.as_deref().map(|status| format!("  \"{}\"", status)).unwrap_or_default();
+        println!("{}  {}{}{}", user.pubky, user.name.as_deref().unwrap_or("-"), mutual, status);
+    }
+    Ok(())
+}
+
+async fn run(options: Options, command: Command) -> MessengerResult<()> {
+    if let Command::Help = command {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+
+    let keypair = load_keypair(&options)?;
+    let settings = NetworkSettings { testnet: options.testnet, ..NetworkSettings::default() };
+    net::apply_settings(&settings);
+    let client = net::build_client(&settings)?;
+
+    let handler = PrivateMessageHandler::new(Arc::new(client), keypair, HttpCache::new(), ListingWatcher::new());
+    handler.sign_in().await.err_context("Failed to sign in")?;
+
+    match command {
+        Command::Send { recipient, content } => send(&handler, &recipient, content).await,
+        Command::Inbox => inbox(&handler, &options).await,
+        Command::Conversation { other } => conversation(&handler, &other, &options).await,
+        Command::Contacts => contacts(&handler, &options).await,
+        Command::Help => Ok(()),
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let (options, command) = match parse_args(std::env::args().skip(1).collect()) {
+        Ok(parsed) => parsed,
+        Err(message) => {
+            eprintln!("{}", message);
+            return ExitCode::from(2);
+        }
+    };
+
+    let level = if options.verbose { tracing::Level::DEBUG } else { tracing::Level::WARN };
+    tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_writer(std::io::stderr)
+        .init();
+
+    match run(options, command).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error[{}]: {}", e.code(), e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use crate::documents;
+use crate::local_store::LocalStore;
+use anyhow::Result;
+use pkarr::PublicKey;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct BlockEntry {
+    pub blocked_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct BlockDocument {
+    contacts: HashMap<String, BlockEntry>,
+}
+
+// Per-user blocked contacts. Unlike muting, blocking stops syncing their
+// conversation, drops their notifications unread and refuses to message them.
+pub struct BlockList<'a> {
+    store: &'a LocalStore,
+    document: String,
+}
+
+impl<'a> BlockList<'a> {
+    pub fn new(store: &'a LocalStore, owner: &PublicKey) -> Self {
+        Self {
+            store,
+            document: documents::user_document(documents::BLOCKS, owner),
+        }
+    }
+
+    pub fn block(&self, contact: &str, now: u64) -> Result<()> {
+        self.store.update(&self.document, |document: &mut BlockDocument| {
+            document.contacts.entry(contact.to_string()).or_insert(BlockEntry { blocked_at: now });
+        })
+    }
+
+    pub fn unblock(&self, contact: &str) -> Result<bool> {
+        self.store.update(&self.document, |document: &mut BlockDocument| {
+            document.contacts.remove(contact).is_some()
+        })
+    }
+
+    pub fn is_blocked(&self, contact: &str) -> bool {
+        self.blocked()
+            .map(|blocked| blocked.contains_key(contact))
+            .unwrap_or(false)
+    }
+
+    pub fn blocked(&self) -> Result<HashMap<String, BlockEntry>> {
+        let document: BlockDocument = self.store.load(&self.document)?;
+        Ok(document.contacts)
+    }
+}
@@ -0,0 +1,69 @@
+use crate::error::MessengerResult;
+use crate::messaging::{AppState, PrivateMessageHandler};
+use pkarr::PublicKey;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HomeserverStatus {
+    pub public_key: String,
+    // The homeserver's public key, when it could be resolved
+    pub homeserver: Option<String>,
+    pub reachable: bool,
+    pub error: Option<String>,
+}
+
+// Lets the UI tell "no new messages" apart from "offline"
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ConnectionStatus {
+    pub own_homeserver: HomeserverStatus,
+    pub contact_homeserver: Option<HomeserverStatus>,
+    pub session_valid: bool,
+    // When any conversation last synced without errors
+    pub last_synced_at: Option<u64>,
+}
+
+async fn homeserver_status(handler: &PrivateMessageHandler, public_key: &PublicKey) -> HomeserverStatus {
+    let homeserver = handler.get_homeserver(public_key.to_string()).await;
+    let probe = match &homeserver {
+        Ok(_) => handler.probe_homeserver(public_key).await,
+        Err(e) => Err(anyhow::anyhow!("{}", e)),
+    };
+
+    HomeserverStatus {
+        public_key: public_key.to_string(),
+        homeserver: homeserver.ok(),
+        reachable: probe.is_ok(),
+        error: probe.err().map(|e| e.to_string()),
+    }
+}
+
+pub async fn check(
+    state: &AppState,
+    handler: &PrivateMessageHandler,
+    contact: Option<PublicKey>,
+) -> MessengerResult<ConnectionStatus> {
+    let own_public_key = handler.keypair.public_key();
+
+    let own_check = homeserver_status(handler, &own_public_key);
+    let session_check = handler.session_valid();
+    let contact_check = async {
+        match &contact {
+            Some(public_key) => Some(homeserver_status(handler, public_key).await),
+            None => None,
+        }
+    };
+    let (own_homeserver, session, contact_homeserver) = tokio::join!(own_check, session_check, contact_check);
+
+    if let Err(e) = &session {
+        tracing::warn!("⚠️  Failed to check session: {}", e);
+    }
+
+    let last_synced_at = state.with_storage(|storage| storage.last_synced_at()).await?;
+
+    Ok(ConnectionStatus {
+        own_homeserver,
+        contact_homeserver,
+        session_valid: session.unwrap_or(false),
+        last_synced_at,
+    })
+}
@@ -0,0 +1,70 @@
+use crate::messaging::Contact;
+use crate::storage::StoredConversation;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// Same length the frontend uses for contact list previews
+const SNIPPET_CHARS: usize = 30;
+
+// One row of the chat list
+#[derive(Serialize, Deserialize)]
+pub struct ConversationSummary {
+    pub contact: Contact,
+    pub last_message_is_own: bool,
+    pub unread_count: usize,
+    pub message_count: usize,
+    pub verified: bool,
+    pub muted: bool,
+    pub muted_until: Option<u64>,
+}
+
+fn snippet(content: &str) -> String {
+    if content.chars().count() > SNIPPET_CHARS {
+        format!("{}...", content.chars().take(SNIPPET_CHARS).collect::<String>())
+    } else {
+        content.to_string()
+    }
+}
+
+impl ConversationSummary {
+    pub fn from_stored(stored: StoredConversation, unread: &HashMap<String, usize>) -> Self {
+        let last = stored.last_message;
+        Self {
+            unread_count: unread.get(&stored.conversation).copied().unwrap_or(0),
+            last_message_is_own: last.is_own_message,
+            message_count: stored.message_count,
+            verified: stored.unverified_count == 0,
+            muted: false,
+            muted_until: None,
+            contact: Contact {
+                public_key: stored.conversation,
+                name: None,
+                last_message: Some(snippet(&last.content)),
+                // Arrival, so a post-dated message can't pin the chat to the top
+                last_message_time: Some(last.arrived_at()),
+                health: None,
+                verification: None,
+            },
+        }
+    }
+
+    // A conversation with no messages yet, e.g. a contact just added
+    pub fn empty(public_key: String) -> Self {
+        Self {
+            unread_count: 0,
+            last_message_is_own: false,
+            message_count: 0,
+            verified: true,
+            muted: false,
+            muted_until: None,
+            contact: Contact {
+                public_key,
+                name: None,
+                last_message: None,
+                last_message_time: None,
+                health: None,
+                verification: None,
+            },
+        }
+    }
+}
@@ -0,0 +1,146 @@
+// Compatibility layer around pubky_common's crypto primitives.
+//
+// Every ciphertext we write to a homeserver and every recovery file we open
+// goes through this module, so that bumping the pubky dependencies can't
+// silently change the on-wire format or lock users out of old recovery files.
+use anyhow::{anyhow, Result};
+use crypto_secretbox::aead::generic_array::GenericArray;
+use crypto_secretbox::aead::{Aead, AeadCore, KeyInit, OsRng};
+use crypto_secretbox::XSalsa20Poly1305;
+use once_cell::sync::Lazy;
+use pkarr::Keypair;
+use pubky_common::{crypto, recovery_file};
+
+use crate::error::MessengerError;
+
+// Ciphertext layouts this build knows how to read and write
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CipherFormat {
+    // XSalsa20-Poly1305 with the 24-byte nonce prepended (pubky_common 0.3)
+    V1,
+}
+
+impl CipherFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CipherFormat::V1 => "v1",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "v1" => Some(CipherFormat::V1),
+            _ => None,
+        }
+    }
+}
+
+// Format used for everything we write
+pub const CURRENT_CIPHER_FORMAT: CipherFormat = CipherFormat::V1;
+
+const V1_NONCE_LEN: usize = 24;
+const V1_TAG_LEN: usize = 16;
+
+// Recovery file spec lines accepted by pubky_common 0.3
+const RECOVERY_SPEC_LINES: [&[u8]; 2] = [b"pubky.org/recovery", b"pkarr.org/recovery"];
+
+// Whether the linked pubky_common still speaks V1 in both directions
+static UPSTREAM_SPEAKS_V1: Lazy<bool> = Lazy::new(detect_upstream_v1);
+
+fn detect_upstream_v1() -> bool {
+    let key = [7u8; 32];
+    let probe = b"pubky-messenger-compat-probe";
+
+    let upstream_ciphertext = crypto::encrypt(probe, &key);
+    if upstream_ciphertext.len() != V1_NONCE_LEN + probe.len() + V1_TAG_LEN {
+        return false;
+    }
+
+    // Our pinned implementation must read upstream output and vice versa
+    let we_read_upstream = decrypt_v1(&upstream_ciphertext, &key)
+        .map(|plain| plain == probe)
+        .unwrap_or(false);
+    let upstream_reads_us = encrypt_v1(probe, &key)
+        .ok()
+        .and_then(|ciphertext| crypto::decrypt(&ciphertext, &key).ok())
+        .map(|plain| plain == probe)
+        .unwrap_or(false);
+
+    we_read_upstream && upstream_reads_us
+}
+
+fn encrypt_v1(plain_text: &[u8], key: &[u8; 32]) -> Result<Vec<u8>> {
+    let cipher = XSalsa20Poly1305::new(key.into());
+    let nonce = XSalsa20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plain_text)
+        .map_err(|_| anyhow!(MessengerError::Crypto("V1 encryption failed".to_string())))?;
+
+    let mut out = Vec::with_capacity(V1_NONCE_LEN + ciphertext.len());
+    out.extend_from_slice(nonce.as_slice());
+    out.extend_from_slice(&ciphertext);
+    Ok(out)
+}
+
+fn decrypt_v1(bytes: &[u8], key: &[u8; 32]) -> Result<Vec<u8>> {
+    if bytes.len() < V1_NONCE_LEN + V1_TAG_LEN {
+        return Err(anyhow!(MessengerError::Crypto("Ciphertext too short for V1 format".to_string())));
+    }
+
+    let cipher = XSalsa20Poly1305::new(key.into());
+    let nonce = GenericArray::from_slice(&bytes[..V1_NONCE_LEN]);
+    cipher
+        .decrypt(nonce, &bytes[V1_NONCE_LEN..])
+        .map_err(|_| anyhow!(MessengerError::Crypto("V1 decryption failed".to_string())))
+}
+
+// Log once at startup whether upstream primitives still match our pinned format
+pub fn check_upstream_compat() -> bool {
+    let compatible = *UPSTREAM_SPEAKS_V1;
+    if compatible {
+        tracing::info!("🔐 pubky_common crypto matches pinned format {:?}", CURRENT_CIPHER_FORMAT);
+    } else {
+        tracing::warn!("⚠️  pubky_common crypto no longer matches {:?}; using pinned implementation", CURRENT_CIPHER_FORMAT);
+    }
+    compatible
+}
+
+// Encrypt using the pinned current format, independent of upstream changes
+pub fn encrypt(plain_text: &[u8], key: &[u8; 32]) -> Result<Vec<u8>> {
+    match CURRENT_CIPHER_FORMAT {
+        CipherFormat::V1 => encrypt_v1(plain_text, key),
+    }
+}
+
+// Decrypt a ciphertext in any format we know about.
+//
+// If upstream has moved to a newer format, peers running newer builds may
+// send it to us, so fall back to upstream's decrypt before giving up.
+pub fn decrypt(bytes: &[u8], key: &[u8; 32]) -> Result<Vec<u8>> {
+    match decrypt_v1(bytes, key) {
+        Ok(plain) => Ok(plain),
+        Err(e) if *UPSTREAM_SPEAKS_V1 => Err(e),
+        Err(_) => crypto::decrypt(bytes, key)
+            .map_err(|e| anyhow!(MessengerError::Crypto(format!("Ciphertext is not in a supported format: {}", e)))),
+    }
+}
+
+// Open a recovery file, rejecting unknown spec versions with a clear error
+// instead of reporting them as a wrong passphrase
+pub fn decrypt_recovery_file(recovery_file_bytes: &[u8], passphrase: &str) -> Result<Keypair> {
+    let spec_line = recovery_file_bytes
+        .iter()
+        .position(|&b| b == b'\n')
+        .map(|newline| &recovery_file_bytes[..newline])
+        .ok_or_else(|| MessengerError::InvalidRecoveryFile("Recovery file is missing its spec line".to_string()))?;
+
+    if !RECOVERY_SPEC_LINES.iter().any(|spec| spec_line.starts_with(spec)) {
+        return Err(anyhow!(MessengerError::InvalidRecoveryFile(format!(
+            "Unsupported recovery file version: {}",
+            String::from_utf8_lossy(spec_line)
+        ))));
+    }
+
+    recovery_file::decrypt_recovery_file(recovery_file_bytes, passphrase)
+        .map_err(|_| anyhow!(MessengerError::BadPassphrase))
+}
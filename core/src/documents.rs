@@ -0,0 +1,73 @@
+// Names of every LocalStore document, kept in one place so a backup can't
+// miss one. Modules take their document name from here, and a new
+// document goes into exactly one of the lists below.
+//
+// Per-user documents are stored as `<kind>_<pubkey>`, app-wide ones under
+// their name.
+use std::fmt::Display;
+
+pub const ATTACHMENT_EXPIRY: &str = "attachment_expiry";
+pub const BLOCKS: &str = "blocks";
+pub const INBOX_FEED: &str = "inbox_feed";
+pub const MENTIONS: &str = "mentions";
+pub const MUTES: &str = "mutes";
+pub const OUTBOX: &str = "outbox";
+pub const PRESENCE: &str = "presence";
+pub const PROFILES: &str = "profiles";
+pub const PROTOCOL_PEERS: &str = "protocol_peers";
+pub const PUSH: &str = "push";
+pub const QUARANTINE: &str = "quarantine";
+pub const READ_STATE: &str = "read_state";
+pub const RETENTION: &str = "retention";
+pub const SECURITY_LOG: &str = "security_log";
+
+pub const APP_LOCK: &str = "app_lock";
+pub const ATTACHMENT_CACHE: &str = "attachment_cache";
+pub const ATTACHMENT_CACHE_SETTINGS: &str = "attachment_cache_settings";
+pub const ATTACHMENT_LIMITS: &str = "attachment_limits";
+pub const LINK_PREVIEWS: &str = "link_previews";
+pub const LOG_SETTINGS: &str = "log_settings";
+pub const NETWORK_SETTINGS: &str = "network_settings";
+pub const ONBOARDING: &str = "onboarding";
+pub const SETTINGS: &str = "settings";
+pub const SYNC_SETTINGS: &str = "sync_settings";
+pub const WEBHOOK: &str = "webhook";
+
+// Per-user documents, backed up with the message cache
+pub const USER_DOCUMENTS: &[&str] = &[
+    ATTACHMENT_EXPIRY,
+    BLOCKS,
+    INBOX_FEED,
+    MENTIONS,
+    MUTES,
+    OUTBOX,
+    PRESENCE,
+    PROFILES,
+    PROTOCOL_PEERS,
+    PUSH,
+    QUARANTINE,
+    READ_STATE,
+    RETENTION,
+    SECURITY_LOG,
+];
+
+// App-wide settings, backed up too
+pub const SETTINGS_DOCUMENTS: &[&str] = &[
+    ATTACHMENT_CACHE_SETTINGS,
+    ATTACHMENT_LIMITS,
+    LINK_PREVIEWS,
+    LOG_SETTINGS,
+    NETWORK_SETTINGS,
+    ONBOARDING,
+    SETTINGS,
+    SYNC_SETTINGS,
+    WEBHOOK,
+];
+
+// Never backed up: the app lock guards this install only, and the
+// attachment cache points at files on this disk
+pub const DEVICE_DOCUMENTS: &[&str] = &[APP_LOCK, ATTACHMENT_CACHE];
+
+pub fn user_document(kind: &str, owner: impl Display) -> String {
+    format!("{}_{}", kind, owner)
+}
@@ -0,0 +1,282 @@
+// Attachment downloads that can be paused, resumed and cancelled.
+//
+// Ciphertext is appended to `<id>.part` as it arrives, and a download's id
+// comes from the attachment URL, so an interrupted download picks up where
+// it stopped, even after a restart. Homeservers that honour Range requests
+// send only the rest; others send the whole file again. Nothing is
+// decrypted until the file is complete.
+use crate::attachment_cache::AttachmentCache;
+use crate::attachments::{self, Attachment};
+use crate::error::MessengerError;
+use crate::messaging::PrivateMessageHandler;
+use crate::profiles;
+use crate::progress::{AttachmentDownloadProgress, Progress, ATTACHMENT_DOWNLOAD_PROGRESS_EVENT};
+use anyhow::{anyhow, Result};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::fs::{self, OpenOptions};
+use tokio::io::AsyncWriteExt;
+
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DownloadState {
+    Queued,
+    Downloading,
+    Paused,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Download {
+    pub id: String,
+    pub attachment: Attachment,
+    pub state: DownloadState,
+    // Ciphertext on disk so far
+    pub received_bytes: u64,
+    // Once the homeserver said
+    pub total_bytes: Option<u64>,
+    pub error: Option<String>,
+}
+
+impl Download {
+    fn progress(&self) -> AttachmentDownloadProgress {
+        AttachmentDownloadProgress {
+            id: self.id.clone(),
+            name: self.attachment.name.clone(),
+            state: self.state,
+            received_bytes: self.received_bytes,
+            total_bytes: self.total_bytes,
+        }
+    }
+}
+
+pub fn download_id(attachment: &Attachment) -> String {
+    blake3::hash(attachment.url.as_bytes()).to_hex()[..32].to_string()
+}
+
+// Shared by every handler made from the same AppState
+#[derive(Clone)]
+pub struct DownloadManager {
+    dir: PathBuf,
+    downloads: Arc<Mutex<HashMap<String, Download>>>,
+}
+
+impl DownloadManager {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir, downloads: Arc::new(Mutex::new(HashMap::new())) }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Download>> {
+        self.downloads.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn part_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.part", id))
+    }
+
+    pub fn list(&self) -> Vec<Download> {
+        self.lock().values().cloned().collect()
+    }
+
+    fn get(&self, id: &str) -> Result<Download> {
+        self.lock().get(id).cloned()
+            .ok_or_else(|| anyhow!(MessengerError::InvalidInput(format!("No download {}", id))))
+    }
+
+    fn update(&self, id: &str, modify: impl FnOnce(&mut Download)) -> Result<Download> {
+        let mut downloads = self.lock();
+        let download = downloads.get_mut(id)
+            .ok_or_else(|| anyhow!(MessengerError::InvalidInput(format!("No download {}", id))))?;
+        modify(download);
+        Ok(download.clone())
+    }
+
+    // Queue `attachment`, or return the download already tracking it. A
+    // failed or cancelled one is queued again, keeping what's on disk.
+    pub async fn enqueue(&self, attachment: &Attachment) -> Result<Download> {
+        attachments::check_url(attachment)?;
+        let id = download_id(attachment);
+        let received_bytes = fs::metadata(self.part_path(&id)).await.map(|metadata| metadata.len()).unwrap_or(0);
+
+        let mut downloads = self.lock();
+        let download = downloads.entry(id.clone()).or_insert_with(|| Download {
+            id,
+            attachment: attachment.clone(),
+            state: DownloadState::Queued,
+            received_bytes,
+            total_bytes: None,
+            error: None,
+        });
+        if matches!(download.state, DownloadState::Failed | DownloadState::Cancelled) {
+            download.state = DownloadState::Queued;
+            download.received_bytes = received_bytes;
+            download.error = None;
+        }
+        Ok(download.clone())
+    }
+
+    // Takes effect after the chunk being written
+    pub fn pause(&self, id: &str) -> Result<Download> {
+        self.update(id, |download| {
+            if matches!(download.state, DownloadState::Queued | DownloadState::Downloading) {
+                download.state = DownloadState::Paused;
+            }
+        })
+    }
+
+    pub async fn cancel(&self, id: &str) -> Result<()> {
+        let download = self.update(id, |download| download.state = DownloadState::Cancelled)?;
+        self.remove_part(id).await;
+        self.lock().remove(id);
+        tracing::debug!("🗑️ Cancelled download of {}", download.attachment.name);
+        Ok(())
+    }
+
+    async fn remove_part(&self, id: &str) {
+        if let Err(e) = fs::remove_file(self.part_path(id)).await {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                tracing::warn!("⚠️  Failed to remove partial download: {}", e);
+            }
+        }
+    }
+
+    // Download until complete, paused or cancelled, reporting progress.
+    // Queued, paused and failed downloads can all be run again.
+    pub async fn run(&self, handler: &PrivateMessageHandler, progress: &Progress, id: &str) -> Result<Download> {
+        let mut already_running = false;
+        let download = self.update(id, |download| match download.state {
+            DownloadState::Downloading => already_running = true,
+            DownloadState::Completed | DownloadState::Cancelled => {}
+            _ => {
+                download.state = DownloadState::Downloading;
+                download.error = None;
+            }
+        })?;
+        if already_running || download.state != DownloadState::Downloading {
+            return Ok(download);
+        }
+        progress.report(ATTACHMENT_DOWNLOAD_PROGRESS_EVENT, &download.progress());
+
+        if let Err(e) = self.transfer(handler, progress, &download).await {
+            let failed = self.update(id, |download| {
+                if download.state == DownloadState::Downloading {
+                    download.state = DownloadState::Failed;
+                    download.error = Some(e.to_string());
+                }
+            });
+            if let Ok(failed) = failed {
+                progress.report(ATTACHMENT_DOWNLOAD_PROGRESS_EVENT, &failed.progress());
+            }
+            return Err(e);
+        }
+
+        let download = self.get(id)?;
+        if download.state == DownloadState::Cancelled {
+            self.remove_part(id).await;
+        }
+        progress.report(ATTACHMENT_DOWNLOAD_PROGRESS_EVENT, &download.progress());
+        Ok(download)
+    }
+
+    async fn transfer(&self, handler: &PrivateMessageHandler, progress: &Progress, download: &Download) -> Result<()> {
+        let max_bytes = attachments::max_ciphertext_len(&download.attachment);
+        fs::create_dir_all(&self.dir).await?;
+        let path = self.part_path(&download.id);
+        let offset = fs::metadata(&path).await.map(|metadata| metadata.len()).unwrap_or(0);
+
+        let mut response = handler.fetch_attachment(&download.attachment, offset).await?;
+        let (mut received, truncate) = match response.status() {
+            StatusCode::PARTIAL_CONTENT => (offset, false),
+            // Range ignored: the whole file again
+            status if status.is_success() => (0, true),
+            // Nothing past what we have
+            StatusCode::RANGE_NOT_SATISFIABLE if offset > 0 => {
+                self.update(&download.id, |download| {
+                    download.state = DownloadState::Completed;
+                    download.total_bytes = Some(offset);
+                })?;
+                return Ok(());
+            }
+            status => {
+                return Err(anyhow!(MessengerError::HomeserverRejected(format!("Failed to download attachment: {}", status))));
+            }
+        };
+        let total_bytes = response.content_length().map(|length| received + length);
+        if total_bytes.is_some_and(|total| total > max_bytes) {
+            return Err(anyhow!("Attachment is larger than its message says"));
+        }
+
+        let mut file = OpenOptions::new().create(true).write(true).append(!truncate).truncate(truncate).open(&path).await?;
+        self.update(&download.id, |download| {
+            download.received_bytes = received;
+            download.total_bytes = total_bytes;
+        })?;
+
+        let mut last_report = Instant::now();
+        while let Some(chunk) = response.chunk().await? {
+            received += chunk.len() as u64;
+            if received > max_bytes {
+                return Err(anyhow!("Attachment is larger than its message says"));
+            }
+            file.write_all(&chunk).await?;
+
+            let current = self.update(&download.id, |download| download.received_bytes = received)?;
+            if current.state != DownloadState::Downloading {
+                // Paused or cancelled; what's written so far stays for a resume
+                file.flush().await?;
+                return Ok(());
+            }
+            if last_report.elapsed() >= PROGRESS_INTERVAL {
+                progress.report(ATTACHMENT_DOWNLOAD_PROGRESS_EVENT, &current.progress());
+                last_report = Instant::now();
+            }
+        }
+        file.flush().await?;
+
+        self.update(&download.id, |download| {
+            if download.state == DownloadState::Downloading {
+                download.state = DownloadState::Completed;
+                download.total_bytes = Some(received);
+            }
+        })?;
+        Ok(())
+    }
+
+    // Decrypt and check a completed download into `path`, then forget it.
+    // Files small enough to open in memory are kept in `cache` too. A file
+    // that doesn't match its message is thrown away.
+    pub async fn save(&self, id: &str, path: &Path, cache: &AttachmentCache) -> Result<()> {
+        let download = self.get(id)?;
+        if download.state != DownloadState::Completed {
+            return Err(anyhow!(MessengerError::InvalidInput(format!("{} hasn't finished downloading", download.attachment.name))));
+        }
+
+        let part_path = self.part_path(id);
+        let saved = if download.attachment.chunked {
+            attachments::open_file(&download.attachment, &part_path, path).await
+        } else {
+            self.save_whole(&download.attachment, &part_path, path, cache).await
+        };
+        self.remove_part(id).await;
+        self.lock().remove(id);
+        saved
+    }
+
+    async fn save_whole(&self, attachment: &Attachment, part_path: &Path, path: &Path, cache: &AttachmentCache) -> Result<()> {
+        let ciphertext = fs::read(part_path).await?;
+        let bytes = attachments::open(attachment, &ciphertext)?;
+        if let Err(e) = cache.put(attachment, &ciphertext, profiles::now_secs()) {
+            tracing::warn!("⚠️  Failed to cache {}: {}", attachment.name, e);
+        }
+        fs::write(path, bytes).await?;
+        Ok(())
+    }
+}
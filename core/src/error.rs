@@ -0,0 +1,165 @@
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+
+use crate::net::TimedOut;
+
+// Errors handed to the frontend. Each kind has a stable code the UI can
+// match on; the message is for people and may change.
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum MessengerError {
+    #[error("Not signed in")]
+    NotSignedIn,
+    #[error("Wrong passphrase")]
+    BadPassphrase,
+    #[error("Wrong PIN")]
+    BadPin,
+    // The saved session is behind an app lock PIN
+    #[error("Enter your PIN to unlock")]
+    SessionLocked,
+    #[error("{0}")]
+    InvalidRecoveryFile(String),
+    #[error("{0}")]
+    InvalidSession(String),
+    #[error("{0}")]
+    InvalidPublicKey(String),
+    #[error("{0}")]
+    InvalidInput(String),
+    #[error("This contact is blocked. Unblock them to send messages.")]
+    ContactBlocked,
+    #[error("{0}")]
+    AttachmentTooLarge(String),
+    #[error("{0}")]
+    AttachmentTypeNotAllowed(String),
+    #[error("{0}")]
+    AttachmentExpired(String),
+    #[error("{0}")]
+    HomeserverNotFound(String),
+    #[error("{0}")]
+    HomeserverUnreachable(String),
+    #[error("{0}")]
+    HomeserverRejected(String),
+    #[error("{0}")]
+    Timeout(String),
+    #[error("{0}")]
+    Cancelled(String),
+    #[error("{0}")]
+    Crypto(String),
+    #[error("{0}")]
+    Storage(String),
+    #[error("{0}")]
+    Io(String),
+    #[error("{0}")]
+    Internal(String),
+}
+
+pub type MessengerResult<T> = std::result::Result<T, MessengerError>;
+
+impl MessengerError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::NotSignedIn => "not_signed_in",
+            Self::BadPassphrase => "bad_passphrase",
+            Self::BadPin => "bad_pin",
+            Self::SessionLocked => "session_locked",
+            Self::InvalidRecoveryFile(_) => "invalid_recovery_file",
+            Self::InvalidSession(_) => "invalid_session",
+            Self::InvalidPublicKey(_) => "invalid_public_key",
+            Self::InvalidInput(_) => "invalid_input",
+            Self::ContactBlocked => "contact_blocked",
+            Self::AttachmentTooLarge(_) => "attachment_too_large",
+            Self::AttachmentTypeNotAllowed(_) => "attachment_type_not_allowed",
+            Self::AttachmentExpired(_) => "attachment_expired",
+            Self::HomeserverNotFound(_) => "homeserver_not_found",
+            Self::HomeserverUnreachable(_) => "homeserver_unreachable",
+            Self::HomeserverRejected(_) => "homeserver_rejected",
+            Self::Timeout(_) => "timeout",
+            Self::Cancelled(_) => "cancelled",
+            Self::Crypto(_) => "crypto",
+            Self::Storage(_) => "storage",
+            Self::Io(_) => "io",
+            Self::Internal(_) => "internal",
+        }
+    }
+
+    // Same kind with a different message; kinds with a fixed message keep it
+    fn with_message(&self, message: String) -> Self {
+        match self {
+            Self::NotSignedIn | Self::BadPassphrase | Self::BadPin | Self::SessionLocked | Self::ContactBlocked => self.clone(),
+            Self::InvalidRecoveryFile(_) => Self::InvalidRecoveryFile(message),
+            Self::InvalidSession(_) => Self::InvalidSession(message),
+            Self::InvalidPublicKey(_) => Self::InvalidPublicKey(message),
+            Self::InvalidInput(_) => Self::InvalidInput(message),
+            Self::AttachmentTooLarge(_) => Self::AttachmentTooLarge(message),
+            Self::AttachmentTypeNotAllowed(_) => Self::AttachmentTypeNotAllowed(message),
+            Self::AttachmentExpired(_) => Self::AttachmentExpired(message),
+            Self::HomeserverNotFound(_) => Self::HomeserverNotFound(message),
+            Self::HomeserverUnreachable(_) => Self::HomeserverUnreachable(message),
+            Self::HomeserverRejected(_) => Self::HomeserverRejected(message),
+            Self::Timeout(_) => Self::Timeout(message),
+            Self::Cancelled(_) => Self::Cancelled(message),
+            Self::Crypto(_) => Self::Crypto(message),
+            Self::Storage(_) => Self::Storage(message),
+            Self::Io(_) => Self::Io(message),
+            Self::Internal(_) => Self::Internal(message),
+        }
+    }
+
+    // Prefix the message with what we were doing, e.g. "Failed to load outbox"
+    pub fn context(self, context: &str) -> Self {
+        self.with_message(format!("{}: {}", context, self))
+    }
+}
+
+// Core modules use anyhow; find the most specific kind in the cause chain
+impl From<anyhow::Error> for MessengerError {
+    fn from(error: anyhow::Error) -> Self {
+        let message = format!("{:#}", error);
+        for cause in error.chain() {
+            if let Some(known) = cause.downcast_ref::<MessengerError>() {
+                return known.with_message(message);
+            }
+            if cause.is::<TimedOut>() {
+                return Self::Timeout(message);
+            }
+            if let Some(reqwest_error) = cause.downcast_ref::<reqwest::Error>() {
+                return if reqwest_error.is_timeout() {
+                    Self::Timeout(message)
+                } else if reqwest_error.is_status() {
+                    Self::HomeserverRejected(message)
+                } else {
+                    Self::HomeserverUnreachable(message)
+                };
+            }
+            if cause.is::<rusqlite::Error>() {
+                return Self::Storage(message);
+            }
+            if cause.is::<std::io::Error>() {
+                return Self::Io(message);
+            }
+        }
+        Self::Internal(message)
+    }
+}
+
+// Sent to the frontend as { code, message }
+impl Serialize for MessengerError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut error = serializer.serialize_struct("MessengerError", 2)?;
+        error.serialize_field("code", self.code())?;
+        error.serialize_field("message", &self.to_string())?;
+        error.end()
+    }
+}
+
+// Attach context to any failure on its way to the frontend
+pub trait ErrorContext<T> {
+    fn err_context(self, context: &str) -> MessengerResult<T>;
+}
+
+impl<T, E> ErrorContext<T> for std::result::Result<T, E>
+where
+    E: Into<anyhow::Error>,
+{
+    fn err_context(self, context: &str) -> MessengerResult<T> {
+        self.map_err(|e| MessengerError::from(e.into()).context(context))
+    }
+}
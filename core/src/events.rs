@@ -0,0 +1,21 @@
+use serde::Serialize;
+
+// Where the core reports things the UI should react to (new messages,
+// outbox progress, key changes, ...). The desktop app forwards them as
+// Tauri events; other frontends can log or ignore them.
+pub trait EventSink: Send + Sync {
+    fn emit_json(&self, event: &str, payload: serde_json::Value) -> anyhow::Result<()>;
+}
+
+// Drops every event, for callers without a UI
+pub struct NoEvents;
+
+impl EventSink for NoEvents {
+    fn emit_json(&self, _event: &str, _payload: serde_json::Value) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+pub fn emit<T: Serialize>(events: &dyn EventSink, event: &str, payload: &T) -> anyhow::Result<()> {
+    events.emit_json(event, serde_json::to_value(payload)?)
+}
@@ -0,0 +1,124 @@
+use crate::messaging::ChatMessage;
+use anyhow::Result;
+use chrono::{TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    Json,
+    Text,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Json => "json",
+            ExportFormat::Text => "txt",
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonExport<'a> {
+    conversation: &'a str,
+    contact_name: Option<&'a str>,
+    exported_at: u64,
+    messages: Vec<JsonExportMessage<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonExportMessage<'a> {
+    id: &'a str,
+    sender: &'a str,
+    timestamp: u64,
+    verified: bool,
+    is_own_message: bool,
+    content: &'a str,
+    // Structured payloads (contact cards, link previews, files), only when asked for
+    #[serde(skip_serializing_if = "Option::is_none")]
+    contact_card: Option<&'a crate::messaging::ContactCard>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    link_preview: Option<&'a crate::link_preview::LinkPreview>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    attachment: Option<&'a crate::attachments::Attachment>,
+}
+
+fn format_time(timestamp: u64) -> String {
+    Utc.timestamp_opt(timestamp as i64, 0)
+        .single()
+        .map(|time| time.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+        .unwrap_or_else(|| timestamp.to_string())
+}
+
+fn short(pubky: &str) -> String {
+    pubky.chars().take(8).collect()
+}
+
+// Suggested file name for the save dialog
+pub fn default_file_name(conversation: &str, contact_name: Option<&str>, format: ExportFormat) -> String {
+    let label: String = contact_name
+        .unwrap_or(conversation)
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+        .collect();
+    format!("chat-{}.{}", label, format.extension())
+}
+
+pub fn render(
+    conversation: &str,
+    contact_name: Option<&str>,
+    own_pubkey: &str,
+    messages: &[ChatMessage],
+    format: ExportFormat,
+    include_attachments: bool,
+    exported_at: u64,
+) -> Result<String> {
+    match format {
+        ExportFormat::Json => {
+            let export = JsonExport {
+                conversation,
+                contact_name,
+                exported_at,
+                messages: messages.iter().map(|msg| JsonExportMessage {
+                    id: &msg.id,
+                    sender: &msg.sender,
+                    timestamp: msg.timestamp,
+                    verified: msg.verified,
+                    is_own_message: msg.is_own_message,
+                    content: &msg.content,
+                    contact_card: msg.contact_card.as_ref().filter(|_| include_attachments),
+                    link_preview: msg.link_preview.as_ref().filter(|_| include_attachments),
+                    attachment: msg.attachment.as_ref().filter(|_| include_attachments),
+                }).collect(),
+            };
+            Ok(serde_json::to_string_pretty(&export)?)
+        }
+        ExportFormat::Text => {
+            let their_name = contact_name.map(|name| name.to_string()).unwrap_or_else(|| short(conversation));
+            let mut transcript = format!(
+                "Conversation with {} ({})\nExported {}\n\n",
+                their_name,
+                conversation,
+                format_time(exported_at)
+            );
+
+            for msg in messages {
+                let sender = if msg.sender == own_pubkey { "You".to_string() } else { their_name.clone() };
+                let unverified = if msg.verified { "" } else { " [unverified]" };
+                transcript.push_str(&format!("[{}] {}{}: {}\n", format_time(msg.timestamp), sender, unverified, msg.content));
+
+                if include_attachments {
+                    if let Some(card) = &msg.contact_card {
+                        transcript.push_str(&format!("    📇 {} ({})\n", card.name.as_deref().unwrap_or("Contact"), card.pubky));
+                    }
+                    if let Some(preview) = &msg.link_preview {
+                        transcript.push_str(&format!("    🔗 {}\n", preview.url));
+                    }
+                }
+            }
+
+            Ok(transcript)
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use crate::crypto_compat::{CipherFormat, CURRENT_CIPHER_FORMAT};
+use crate::protocol;
+use crate::verification::VerificationState;
+use serde::{Deserialize, Serialize};
+
+// A single static key pair has protected the conversation for longer than this
+const STALE_KEY_EPOCH_SECS: u64 = 90 * 24 * 60 * 60;
+
+// Cached results older than this are evaluated again, so time-based
+// warnings show up without the conversation changing
+pub const REEVALUATE_AFTER_SECS: u64 = 60 * 60;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthSeverity {
+    Info,
+    Warning,
+    Critical,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthWarning {
+    LegacyFormat,
+    NoForwardSecrecy,
+    UnverifiedContact,
+    StaleKeyEpoch,
+}
+
+// What the UI should offer the user to fix a warning
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Remediation {
+    Migrate,
+    Rekey,
+    Verify,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HealthIssue {
+    pub warning: HealthWarning,
+    pub severity: HealthSeverity,
+    pub remediation: Remediation,
+    pub detail: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ConversationHealth {
+    pub severity: Option<HealthSeverity>,
+    pub issues: Vec<HealthIssue>,
+    pub evaluated_at: u64,
+}
+
+impl ConversationHealth {
+    pub fn is_stale(&self, now: u64) -> bool {
+        now.saturating_sub(self.evaluated_at) >= REEVALUATE_AFTER_SECS
+    }
+}
+
+// Facts about a conversation gathered while loading it
+#[derive(Default)]
+pub struct HealthInputs {
+    // Messages in an older cipher layout or wire format
+    pub legacy_messages: usize,
+    // Messages whose signature didn't verify
+    pub unverified_messages: usize,
+    // Whether the user verified the contact's keys; None if they never did
+    pub verification: Option<VerificationState>,
+    pub oldest_message_time: Option<u64>,
+    pub forward_secrecy: bool,
+}
+
+// Written in a format we no longer write: before the current cipher layout,
+// or a bare JSON blob or envelope from before versions were recorded.
+// Version 0 means the version wasn't tracked when the message was cached.
+pub fn is_legacy_message(format: CipherFormat, protocol_version: u32) -> bool {
+    format != CURRENT_CIPHER_FORMAT || (1..protocol::MIN_WRITE_VERSION).contains(&protocol_version)
+}
+
+pub fn evaluate(inputs: &HealthInputs, now: u64) -> ConversationHealth {
+    let mut issues = Vec::new();
+
+    let legacy_messages = inputs.legacy_messages;
+    if legacy_messages > 0 {
+        issues.push(HealthIssue {
+            warning: HealthWarning::LegacyFormat,
+            severity: HealthSeverity::Warning,
+            remediation: Remediation::Migrate,
+            detail: format!("{} messages use an older encryption format", legacy_messages),
+        });
+    }
+
+    if !inputs.forward_secrecy {
+        issues.push(HealthIssue {
+            warning: HealthWarning::NoForwardSecrecy,
+            severity: HealthSeverity::Info,
+            remediation: Remediation::Rekey,
+            detail: "Messages are encrypted with long-term keys; a leaked key exposes past messages".to_string(),
+        });
+    }
+
+    let unverified = match inputs.verification {
+        Some(VerificationState::Verified) => None,
+        Some(VerificationState::Broken) => Some((HealthSeverity::Critical, "Their keys changed since you verified them".to_string())),
+        None => Some((HealthSeverity::Warning, "You haven't verified this contact's keys".to_string())),
+    };
+    // Forged or tampered messages matter even from a verified contact
+    let unverified = match (unverified, inputs.unverified_messages) {
+        (unverified, 0) => unverified,
+        (Some((_, detail)), failed) => Some((HealthSeverity::Critical, format!("{}, and {} messages failed signature verification", detail, failed))),
+        (None, failed) => Some((HealthSeverity::Critical, format!("{} messages failed signature verification", failed))),
+    };
+    if let Some((severity, detail)) = unverified {
+        issues.push(HealthIssue {
+            warning: HealthWarning::UnverifiedContact,
+            severity,
+            remediation: Remediation::Verify,
+            detail,
+        });
+    }
+
+    if let Some(oldest) = inputs.oldest_message_time {
+        if !inputs.forward_secrecy && now.saturating_sub(oldest) > STALE_KEY_EPOCH_SECS {
+            issues.push(HealthIssue {
+                warning: HealthWarning::StaleKeyEpoch,
+                severity: HealthSeverity::Warning,
+                remediation: Remediation::Rekey,
+                detail: format!("The same key has been in use for {} days", now.saturating_sub(oldest) / 86_400),
+            });
+        }
+    }
+
+    ConversationHealth {
+        severity: issues.iter().map(|issue| issue.severity).max(),
+        issues,
+        evaluated_at: now,
+    }
+}
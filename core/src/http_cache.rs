@@ -0,0 +1,164 @@
+use crate::metrics;
+use crate::transport::Transport;
+use anyhow::{anyhow, Result};
+use reqwest::header::{HeaderMap, HeaderName, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::StatusCode;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+// Enough for every profile, follow list and notification we touch in a session
+const MAX_ENTRIES: usize = 1024;
+// Larger bodies aren't worth keeping in memory
+const MAX_BODY_BYTES: usize = 256 * 1024;
+
+// A body over the caller's limit; the rest of it was never read
+#[derive(Debug, thiserror::Error)]
+#[error("Response from {url} is over the {limit} byte limit")]
+pub struct BodyTooLarge {
+    pub url: String,
+    pub limit: usize,
+}
+
+fn header_value(response: &reqwest::Response, name: HeaderName) -> Option<String> {
+    response.headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_string())
+}
+
+struct CachedResponse {
+    etag: Option<String>,
+    last_modified: Option<String>,
+    body: Vec<u8>,
+}
+
+#[derive(Default)]
+struct CacheEntries {
+    responses: HashMap<String, CachedResponse>,
+    // Insertion order, oldest first, for eviction
+    order: VecDeque<String>,
+}
+
+// In-memory validator cache for homeserver GETs, so re-fetching an
+// unchanged resource costs a 304 instead of the full body
+#[derive(Clone, Default)]
+pub struct HttpCache {
+    entries: Arc<Mutex<CacheEntries>>,
+}
+
+impl HttpCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, CacheEntries> {
+        self.entries.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub async fn get_text(&self, transport: &dyn Transport, url: &str) -> Result<Option<String>> {
+        self.get_bytes(transport, url)
+            .await?
+            .map(|body| String::from_utf8(body).map_err(|e| anyhow!("Response from {} is not text: {}", url, e)))
+            .transpose()
+    }
+
+    // GET `url`, revalidating any cached copy. Returns None for non-success
+    // responses (missing resources aren't an error for our callers).
+    pub async fn get_bytes(&self, transport: &dyn Transport, url: &str) -> Result<Option<Vec<u8>>> {
+        self.get_bytes_limited(transport, url, usize::MAX).await
+    }
+
+    // Like get_bytes, but fails with BodyTooLarge instead of reading past
+    // `max_bytes`
+    pub async fn get_bytes_limited(&self, transport: &dyn Transport, url: &str, max_bytes: usize) -> Result<Option<Vec<u8>>> {
+        let (etag, last_modified) = self.lock()
+            .responses
+            .get(url)
+            .map(|cached| (cached.etag.clone(), cached.last_modified.clone()))
+            .unwrap_or_default();
+
+        let mut headers = HeaderMap::new();
+        if let Some(etag) = etag {
+            headers.insert(IF_NONE_MATCH, etag.parse()?);
+        }
+        if let Some(last_modified) = last_modified {
+            headers.insert(IF_MODIFIED_SINCE, last_modified.parse()?);
+        }
+        let response = transport.get_with_headers(url, headers).await?;
+
+        if response.status() == StatusCode::NOT_MODIFIED {
+            let cached_body = self.lock().responses.get(url).map(|cached| cached.body.clone());
+            if cached_body.is_some() {
+                return Ok(cached_body);
+            }
+            // Evicted between request and response - fetch unconditionally
+            return self.fetch_uncached(transport, url, max_bytes).await;
+        }
+
+        self.store_response(url, response, max_bytes).await
+    }
+
+    async fn fetch_uncached(&self, transport: &dyn Transport, url: &str, max_bytes: usize) -> Result<Option<Vec<u8>>> {
+        let response = transport.get(url).await?;
+        self.store_response(url, response, max_bytes).await
+    }
+
+    async fn store_response(&self, url: &str, mut response: reqwest::Response, max_bytes: usize) -> Result<Option<Vec<u8>>> {
+        if !response.status().is_success() {
+            self.invalidate(url);
+            return Ok(None);
+        }
+
+        let etag = header_value(&response, ETAG);
+        let last_modified = header_value(&response, LAST_MODIFIED);
+        let too_large = || {
+            self.invalidate(url);
+            anyhow!(BodyTooLarge { url: url.to_string(), limit: max_bytes })
+        };
+        if response.content_length().is_some_and(|length| length > max_bytes as u64) {
+            return Err(too_large());
+        }
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            if body.len() + chunk.len() > max_bytes {
+                metrics::record_received(body.len() + chunk.len());
+                return Err(too_large());
+            }
+            body.extend_from_slice(&chunk);
+        }
+        metrics::record_received(body.len());
+
+        if (etag.is_some() || last_modified.is_some()) && body.len() <= MAX_BODY_BYTES {
+            self.insert(url, CachedResponse { etag, last_modified, body: body.clone() });
+        } else {
+            self.invalidate(url);
+        }
+
+        Ok(Some(body))
+    }
+
+    fn insert(&self, url: &str, response: CachedResponse) {
+        let mut entries = self.lock();
+        if entries.responses.insert(url.to_string(), response).is_none() {
+            entries.order.push_back(url.to_string());
+        }
+
+        while entries.order.len() > MAX_ENTRIES {
+            if let Some(oldest) = entries.order.pop_front() {
+                entries.responses.remove(&oldest);
+            }
+        }
+    }
+
+    // Drop a cached copy, e.g. after we deleted or overwrote the resource
+    pub fn invalidate(&self, url: &str) {
+        let mut entries = self.lock();
+        if entries.responses.remove(url).is_some() {
+            entries.order.retain(|cached_url| cached_url != url);
+        }
+    }
+
+    pub fn clear(&self) {
+        *self.lock() = CacheEntries::default();
+    }
+}
@@ -0,0 +1,206 @@
+// Image attachments are prepared before they are sealed: scaled down to a
+// maximum size, re-encoded, and stripped of EXIF and other metadata, which
+// can carry the GPS position a photo was taken at.
+//
+// Only JPEG and PNG are touched; other formats are sent as they are.
+// Re-encoding drops all metadata. Without it, metadata segments are cut
+// out losslessly, unless the photo relies on its EXIF orientation, which
+// is then applied to the pixels first.
+use crate::attachments::AttachmentFile;
+use anyhow::{anyhow, Result};
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::metadata::Orientation;
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+pub const MIN_MAX_DIMENSION: u32 = 256;
+pub const MAX_MAX_DIMENSION: u32 = 8192;
+pub const MIN_QUALITY: u8 = 30;
+pub const MAX_QUALITY: u8 = 100;
+
+// For re-encoding only to apply an orientation
+const ROTATE_QUALITY: u8 = 92;
+
+// JPEG segments with EXIF, XMP, IPTC and comments
+const JPEG_APP1: u8 = 0xE1;
+const JPEG_APP13: u8 = 0xED;
+const JPEG_COMMENT: u8 = 0xFE;
+const JPEG_START_OF_SCAN: u8 = 0xDA;
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+const PNG_METADATA_CHUNKS: [&[u8; 4]; 5] = [b"eXIf", b"tEXt", b"zTXt", b"iTXt", b"tIME"];
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ImageSettings {
+    // Scale down and re-encode before sending
+    pub compress: bool,
+    // Longest side, in pixels, after scaling
+    pub max_dimension: u32,
+    // JPEG quality, 30-100
+    pub quality: u8,
+    pub strip_metadata: bool,
+}
+
+impl Default for ImageSettings {
+    fn default() -> Self {
+        Self {
+            compress: true,
+            max_dimension: 2048,
+            quality: 80,
+            strip_metadata: true,
+        }
+    }
+}
+
+impl ImageSettings {
+    pub fn clamped(self) -> Self {
+        Self {
+            max_dimension: self.max_dimension.clamp(MIN_MAX_DIMENSION, MAX_MAX_DIMENSION),
+            quality: self.quality.clamp(MIN_QUALITY, MAX_QUALITY),
+            ..self
+        }
+    }
+}
+
+// `file` as it should be sent under `settings`. Files that claim to be an
+// image but can't be decoded only have their metadata cut out.
+pub fn prepare(file: AttachmentFile, settings: &ImageSettings) -> AttachmentFile {
+    let format = match file.mime.as_str() {
+        "image/jpeg" => ImageFormat::Jpeg,
+        "image/png" => ImageFormat::Png,
+        _ => return file,
+    };
+    if !settings.compress && !settings.strip_metadata {
+        return file;
+    }
+
+    match reencode(&file.bytes, format, settings) {
+        Ok(Some(bytes)) => {
+            tracing::debug!("🖼️  Prepared {}: {} -> {} bytes", file.name, file.bytes.len(), bytes.len());
+            AttachmentFile { bytes, ..file }
+        }
+        Ok(None) => strip(file, format, settings),
+        Err(e) => {
+            tracing::warn!("⚠️  Sending {} without re-encoding: {}", file.name, e);
+            strip(file, format, settings)
+        }
+    }
+}
+
+// The image scaled, re-encoded and turned the right way up, or None if it
+// can go as it is
+fn reencode(bytes: &[u8], format: ImageFormat, settings: &ImageSettings) -> Result<Option<Vec<u8>>> {
+    let mut decoder = ImageReader::with_format(Cursor::new(bytes), format).into_decoder()?;
+    let orientation = decoder.orientation()?;
+    let (width, height) = decoder.dimensions();
+    let oversized = width.max(height) > settings.max_dimension;
+    let rotated = orientation != Orientation::NoTransforms;
+
+    let compress = settings.compress && (oversized || format == ImageFormat::Jpeg);
+    // Cutting out EXIF would lose which way up the photo goes
+    let rotate = settings.strip_metadata && rotated && format == ImageFormat::Jpeg;
+    if !compress && !rotate {
+        return Ok(None);
+    }
+
+    let mut image = DynamicImage::from_decoder(decoder)?;
+    image.apply_orientation(orientation);
+    if compress && oversized {
+        image = image.resize(settings.max_dimension, settings.max_dimension, FilterType::Lanczos3);
+    }
+    let quality = if compress { settings.quality } else { ROTATE_QUALITY };
+    let encoded = encode(&image, format, quality)?;
+
+    // Re-encoding a small JPEG can grow it; then only take the metadata out
+    if !oversized && !rotated && encoded.len() >= bytes.len() {
+        return Ok(None);
+    }
+    Ok(Some(encoded))
+}
+
+fn encode(image: &DynamicImage, format: ImageFormat, quality: u8) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    match format {
+        ImageFormat::Jpeg => JpegEncoder::new_with_quality(&mut bytes, quality)
+            .encode_image(&image.to_rgb8())
+            .map_err(|e| anyhow!("Failed to encode JPEG: {}", e))?,
+        _ => image
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+            .map_err(|e| anyhow!("Failed to encode PNG: {}", e))?,
+    }
+    Ok(bytes)
+}
+
+fn strip(file: AttachmentFile, format: ImageFormat, settings: &ImageSettings) -> AttachmentFile {
+    if !settings.strip_metadata {
+        return file;
+    }
+    let stripped = match format {
+        ImageFormat::Jpeg => strip_jpeg(&file.bytes),
+        _ => strip_png(&file.bytes),
+    };
+    match stripped {
+        Some(bytes) => AttachmentFile { bytes, ..file },
+        // Decoded fine but isn't laid out as expected; keep it whole
+        None => file,
+    }
+}
+
+// Copy every segment before the image data except the metadata ones
+fn strip_jpeg(bytes: &[u8]) -> Option<Vec<u8>> {
+    if !bytes.starts_with(&[0xFF, 0xD8]) {
+        return None;
+    }
+
+    let mut out = Vec::with_capacity(bytes.len());
+    out.extend_from_slice(&bytes[..2]);
+    let mut at = 2;
+    loop {
+        if *bytes.get(at)? != 0xFF {
+            return None;
+        }
+        let marker = *bytes.get(at + 1)?;
+        // Fill bytes before a marker
+        if marker == 0xFF {
+            at += 1;
+            continue;
+        }
+        if marker == JPEG_START_OF_SCAN {
+            out.extend_from_slice(&bytes[at..]);
+            return Some(out);
+        }
+
+        let len = u16::from_be_bytes([*bytes.get(at + 2)?, *bytes.get(at + 3)?]) as usize;
+        let end = at + 2 + len;
+        let segment = bytes.get(at..end)?;
+        if !matches!(marker, JPEG_APP1 | JPEG_APP13 | JPEG_COMMENT) {
+            out.extend_from_slice(segment);
+        }
+        at = end;
+    }
+}
+
+// Copy every chunk except the metadata ones
+fn strip_png(bytes: &[u8]) -> Option<Vec<u8>> {
+    if !bytes.starts_with(PNG_SIGNATURE) {
+        return None;
+    }
+
+    let mut out = Vec::with_capacity(bytes.len());
+    out.extend_from_slice(PNG_SIGNATURE);
+    let mut at = PNG_SIGNATURE.len();
+    while at < bytes.len() {
+        let len = u32::from_be_bytes(bytes.get(at..at + 4)?.try_into().ok()?) as usize;
+        let kind = bytes.get(at + 4..at + 8)?;
+        // Length, type, data and CRC
+        let end = at.checked_add(12)?.checked_add(len)?;
+        let chunk = bytes.get(at..end)?;
+        if !PNG_METADATA_CHUNKS.iter().any(|metadata| kind == metadata.as_slice()) {
+            out.extend_from_slice(chunk);
+        }
+        at = end;
+    }
+    Some(out)
+}
@@ -0,0 +1,88 @@
+// Housekeeping for our /pub/notifications/ inbox. Anyone can announce a
+// message there, so each sync pass reads a bounded number of
+// notifications, lets only a few unknown senders into the sync, and
+// deletes what is done with or was never going to lead anywhere.
+use crate::blocks::BlockList;
+use crate::logging;
+use crate::mentions::NotificationPriority;
+use crate::messaging::{AppState, PendingNotification, PrivateMessageHandler};
+use futures::stream::{self, StreamExt};
+use pkarr::Keypair;
+
+// Notifications read per sync pass; the rest wait for later passes
+pub const MAX_NOTIFICATIONS_PER_PASS: usize = 100;
+// Senders we don't know yet that one pass will sync because of notifications
+pub const MAX_NEW_SENDERS_PER_PASS: usize = 20;
+// A notification whose message still isn't there after this long is dropped
+pub const MAX_NOTIFICATION_AGE_SECS: u64 = 7 * 24 * 60 * 60;
+// Deletes per pass, and how many run at once
+const DELETE_BATCH: usize = 50;
+const DELETE_CONCURRENCY: usize = 4;
+
+// Senders announced by `notifications` that aren't in `candidates` yet,
+// leaving out blocked ones and anything past the per-pass cap. Senders of
+// mentions are let in first, so a flood of plain messages can't hold them back.
+pub fn new_senders(state: &AppState, keypair: &Keypair, notifications: &[PendingNotification], candidates: &[String]) -> Vec<String> {
+    let blocks = BlockList::new(&state.store, &keypair.public_key());
+    let mut by_priority: Vec<&PendingNotification> = notifications.iter().collect();
+    by_priority.sort_by_key(|notification| notification.kind.priority() != NotificationPriority::High);
+
+    let mut senders: Vec<String> = Vec::new();
+    for notification in by_priority {
+        if candidates.contains(&notification.sender) || senders.contains(&notification.sender) {
+            continue;
+        }
+        if blocks.is_blocked(&notification.sender) {
+            continue;
+        }
+        if senders.len() == MAX_NEW_SENDERS_PER_PASS {
+            tracing::warn!("📬 Notifications from more than {} new senders, deferring the rest", MAX_NEW_SENDERS_PER_PASS);
+            break;
+        }
+        senders.push(notification.sender.clone());
+    }
+    senders
+}
+
+// Delete notifications whose messages are now in the local cache, and ones
+// too old to ever be, a batch at a time. The rest wait for the next pass.
+pub async fn clean_up(state: &AppState, handler: &PrivateMessageHandler, notifications: &[PendingNotification], now: u64) {
+    let mut done = Vec::new();
+    let mut stale = 0;
+    for notification in notifications {
+        let cached = state.with_storage(|storage| {
+            for msg_id in &notification.msg_ids {
+                if storage.message_timestamp(msg_id)?.is_none() {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        }).await;
+        if matches!(cached, Ok(true)) {
+            done.push(notification);
+        } else if notification.timestamp.saturating_add(MAX_NOTIFICATION_AGE_SECS) < now {
+            tracing::debug!("🗑️  Dropping stale notification from {}", logging::pubkey(&notification.sender));
+            done.push(notification);
+            stale += 1;
+        }
+        if done.len() == DELETE_BATCH {
+            break;
+        }
+    }
+    if done.is_empty() {
+        return;
+    }
+
+    let deleted = stream::iter(done)
+        .map(|notification| handler.acknowledge_notification(&notification.url))
+        .buffer_unordered(DELETE_CONCURRENCY)
+        .filter(|result| {
+            if let Err(e) = result {
+                tracing::debug!("⚠️  Failed to delete notification: {}", e);
+            }
+            futures::future::ready(result.is_ok())
+        })
+        .count()
+        .await;
+    tracing::debug!("📬 Deleted {} notifications ({} stale)", deleted, stale);
+}
@@ -0,0 +1,145 @@
+// The home screen's one newest-first feed of everything worth a look: new
+// messages, first messages from strangers (message requests), mentions
+// and key changes. Items are added at the points in the sync pipeline that
+// raise the matching events, and kept on disk so the feed survives restarts.
+use crate::documents;
+use crate::local_store::LocalStore;
+use crate::messaging::ChatMessage;
+use crate::verification::KeyChangedEvent;
+use anyhow::{anyhow, Result};
+use pkarr::PublicKey;
+use serde::{Deserialize, Serialize};
+
+// Older items fall off the end
+const MAX_ITEMS: usize = 500;
+const PREVIEW_CHARS: usize = 100;
+pub const MAX_PAGE_SIZE: usize = 200;
+pub const DEFAULT_PAGE_SIZE: usize = 50;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum InboxItemKind {
+    Message,
+    // A message from someone who isn't a contact yet
+    MessageRequest,
+    Mention,
+    KeyChanged,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct InboxItem {
+    pub id: String,
+    pub kind: InboxItemKind,
+    pub conversation: String,
+    pub sender: Option<String>,
+    pub sender_name: Option<String>,
+    // Start of the message text; None for key changes
+    pub preview: Option<String>,
+    pub message_id: Option<String>,
+    pub timestamp: u64,
+}
+
+impl InboxItem {
+    pub fn message(kind: InboxItemKind, conversation: &str, message: &ChatMessage) -> Self {
+        let prefix = match kind {
+            InboxItemKind::Mention => "mention",
+            _ => "message",
+        };
+        Self {
+            id: format!("{}:{}", prefix, message.id),
+            kind,
+            conversation: conversation.to_string(),
+            sender: Some(message.sender.clone()),
+            sender_name: message.sender_name.clone(),
+            preview: Some(message.content.chars().take(PREVIEW_CHARS).collect()),
+            message_id: Some(message.id.clone()),
+            timestamp: message.timestamp,
+        }
+    }
+
+    pub fn key_changed(event: &KeyChangedEvent) -> Self {
+        Self {
+            id: format!("key_changed:{}:{}", event.public_key, event.detected_at),
+            kind: InboxItemKind::KeyChanged,
+            conversation: event.public_key.clone(),
+            sender: None,
+            sender_name: None,
+            preview: None,
+            message_id: None,
+            timestamp: event.detected_at,
+        }
+    }
+
+    fn sort_key(&self) -> (u64, &str) {
+        (self.timestamp, self.id.as_str())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct InboxPage {
+    pub items: Vec<InboxItem>,
+    // Pass back to get the next, older page; None on the last one
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct FeedDocument {
+    // Newest first
+    items: Vec<InboxItem>,
+}
+
+fn cursor_of(item: &InboxItem) -> String {
+    format!("{}:{}", item.timestamp, item.id)
+}
+
+fn parse_cursor(cursor: &str) -> Result<(u64, &str)> {
+    let (timestamp, id) = cursor.split_once(':').ok_or_else(|| anyhow!("Invalid inbox cursor"))?;
+    Ok((timestamp.parse().map_err(|_| anyhow!("Invalid inbox cursor"))?, id))
+}
+
+// Per-user feed
+pub struct InboxFeed<'a> {
+    store: &'a LocalStore,
+    document: String,
+}
+
+impl<'a> InboxFeed<'a> {
+    pub fn new(store: &'a LocalStore, owner: &PublicKey) -> Self {
+        Self {
+            store,
+            document: documents::user_document(documents::INBOX_FEED, owner),
+        }
+    }
+
+    // Items already in the feed are left as they are
+    pub fn add(&self, items: Vec<InboxItem>) -> Result<()> {
+        if items.is_empty() {
+            return Ok(());
+        }
+        self.store.update(&self.document, |document: &mut FeedDocument| {
+            for item in items {
+                if !document.items.iter().any(|existing| existing.id == item.id) {
+                    document.items.push(item);
+                }
+            }
+            document.items.sort_by(|a, b| b.sort_key().cmp(&a.sort_key()));
+            document.items.truncate(MAX_ITEMS);
+        })
+    }
+
+    // Up to `limit` items older than `cursor`, or the newest without one
+    pub fn page(&self, limit: usize, cursor: Option<&str>) -> Result<InboxPage> {
+        let limit = limit.clamp(1, MAX_PAGE_SIZE);
+        let after = cursor.map(parse_cursor).transpose()?;
+        let document: FeedDocument = self.store.load(&self.document)?;
+
+        let mut older = document.items.into_iter()
+            .filter(|item| after.map_or(true, |after| item.sort_key() < after));
+        let items: Vec<InboxItem> = older.by_ref().take(limit).collect();
+        let next_cursor = match (older.next(), items.last()) {
+            (Some(_), Some(last)) => Some(cursor_of(last)),
+            _ => None,
+        };
+        Ok(InboxPage { items, next_cursor })
+    }
+}
@@ -0,0 +1,62 @@
+// Messaging, crypto, sync and local storage for Pubky Private Messenger.
+// Nothing in here depends on Tauri; the desktop app wraps it in commands.
+pub mod app_lock;
+pub mod attachment_cache;
+pub mod attachment_expiry;
+pub mod attachment_limits;
+pub mod attachment_preview;
+pub mod attachments;
+pub mod audio_pipeline;
+pub mod avatar;
+pub mod backup;
+pub mod blocks;
+pub mod connection;
+pub mod conversations;
+pub mod crypto_compat;
+pub mod documents;
+pub mod downloads;
+pub mod error;
+pub mod events;
+pub mod export;
+pub mod health;
+pub mod http_cache;
+pub mod image_pipeline;
+pub mod inbox;
+pub mod inbox_feed;
+pub mod limits;
+pub mod link_preview;
+pub mod link_verification;
+pub mod local_store;
+pub mod logging;
+pub mod media_gallery;
+pub mod mentions;
+pub mod messaging;
+pub mod metrics;
+pub mod mutes;
+pub mod names;
+pub mod net;
+pub mod notification_batch;
+pub mod onboarding;
+pub mod operations;
+pub mod outbox;
+pub mod padding;
+pub mod panic_wipe;
+pub mod presence;
+pub mod profiles;
+pub mod progress;
+pub mod protocol;
+pub mod push;
+pub mod qr;
+pub mod quarantine;
+pub mod read_state;
+pub mod retention;
+pub mod security_log;
+pub mod settings;
+pub mod storage;
+pub mod stream_cipher;
+pub mod sync;
+pub mod transport;
+pub mod verification;
+pub mod watcher;
+pub mod webhook;
+pub mod wire;
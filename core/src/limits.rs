@@ -0,0 +1,57 @@
+// Hard limits on records read from homeservers, checked before anything is
+// decrypted, so a hostile homeserver can't make us hold or chew on
+// arbitrarily large or malformed data. Records that break them are logged
+// as quarantined and skipped.
+use crate::logging;
+use crate::padding;
+use anyhow::{anyhow, Result};
+
+// Anything bigger than this isn't a message we wrote
+pub const MAX_MESSAGE_BLOB_BYTES: usize = 256 * 1024;
+pub const MAX_NOTIFICATION_BYTES: usize = 4 * 1024;
+
+// Plaintext limits; we refuse to send past them too
+pub const MAX_CONTENT_BYTES: usize = 64 * 1024;
+pub const MAX_EXTRAS_BYTES: usize = 64 * 1024;
+
+// Ciphertexts are the plaintext plus format byte, nonce and tag
+const ENCRYPTION_OVERHEAD: usize = 64;
+// A z-base32 pubky is 52 characters
+const MAX_SENDER_BYTES: usize = 64;
+pub const MAX_ENCRYPTED_SENDER_BYTES: usize = MAX_SENDER_BYTES + ENCRYPTION_OVERHEAD;
+// Content and extras may be padded (see padding.rs)
+pub const MAX_ENCRYPTED_CONTENT_BYTES: usize = padding::padded_len(MAX_CONTENT_BYTES) + ENCRYPTION_OVERHEAD;
+pub const MAX_ENCRYPTED_EXTRAS_BYTES: usize = padding::padded_len(MAX_EXTRAS_BYTES) + ENCRYPTION_OVERHEAD;
+pub const SIGNATURE_BYTES: usize = 64;
+pub const MAX_MSG_ID_CHARS: usize = 64;
+
+// 2024-01-01; nothing legitimate is older
+pub const MIN_TIMESTAMP: u64 = 1_704_067_200;
+// How far ahead of our clock a sender's may be
+pub const MAX_FUTURE_SECS: u64 = 24 * 60 * 60;
+
+pub fn check_len(field: &str, len: usize, max: usize) -> Result<()> {
+    if len > max {
+        return Err(anyhow!("{} is {} bytes, over the {} byte limit", field, len, max));
+    }
+    Ok(())
+}
+
+pub fn check_timestamp(timestamp: u64, now: u64) -> Result<()> {
+    if timestamp < MIN_TIMESTAMP || timestamp > now.saturating_add(MAX_FUTURE_SECS) {
+        return Err(anyhow!("Timestamp {} is out of range", timestamp));
+    }
+    Ok(())
+}
+
+// msg_ids are UUIDs; empty for legacy messages, which use the file name
+pub fn check_msg_id(msg_id: &str) -> Result<()> {
+    if msg_id.chars().count() > MAX_MSG_ID_CHARS || !msg_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return Err(anyhow!("Invalid msg_id"));
+    }
+    Ok(())
+}
+
+pub fn quarantine(url: &str, reason: &anyhow::Error) {
+    tracing::warn!("🚫 Quarantined {}: {}", logging::path(url), reason);
+}
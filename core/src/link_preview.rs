@@ -0,0 +1,227 @@
+use crate::documents;
+use crate::local_store::LocalStore;
+use crate::net;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use url::Url;
+
+const LINK_PREVIEW_DOCUMENT: &str = documents::LINK_PREVIEWS;
+
+// Never read more than this much of a page looking for metadata
+const MAX_HTML_BYTES: usize = 256 * 1024;
+const MAX_FIELD_CHARS: usize = 300;
+const FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+
+// OpenGraph summary generated by the sender and encrypted with the message
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct LinkPreview {
+    pub url: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub site_name: Option<String>,
+    pub image_url: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct LinkPreviewConfig {
+    enabled: bool,
+}
+
+impl Default for LinkPreviewConfig {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+pub fn is_enabled(store: &LocalStore) -> bool {
+    store
+        .load::<LinkPreviewConfig>(LINK_PREVIEW_DOCUMENT)
+        .map(|config| config.enabled)
+        .unwrap_or(true)
+}
+
+pub fn set_enabled(store: &LocalStore, enabled: bool) -> Result<()> {
+    store.save(LINK_PREVIEW_DOCUMENT, &LinkPreviewConfig { enabled })
+}
+
+// First http(s) URL in a message, if any
+pub fn find_first_url(text: &str) -> Option<Url> {
+    text.split_whitespace()
+        .filter(|word| word.starts_with("http://") || word.starts_with("https://"))
+        .map(|word| word.trim_end_matches(|c: char| matches!(c, '.' | ',' | ')' | '!' | '?' | ';' | ':')))
+        .find_map(|word| Url::parse(word).ok())
+}
+
+// Build a preview for the first link in a message; failures just mean no preview
+pub async fn preview_for_message(text: &str) -> Option<LinkPreview> {
+    let url = find_first_url(text)?;
+    match fetch_preview(&url).await {
+        Ok(preview) => preview,
+        Err(e) => {
+            tracing::warn!("⚠️  Link preview failed for {}: {}", url.host_str().unwrap_or(""), e);
+            None
+        }
+    }
+}
+
+// For fetching pages outside pubky: short timeout, few redirects, and the
+// configured proxy
+pub(crate) fn web_client() -> Result<reqwest::Client> {
+    let mut builder = reqwest::Client::builder()
+        .timeout(FETCH_TIMEOUT)
+        .redirect(reqwest::redirect::Policy::limited(3));
+    if let Some(proxy) = net::proxy() {
+        builder = builder.proxy(reqwest::Proxy::all(proxy.as_str())?);
+    }
+    Ok(builder.build()?)
+}
+
+pub(crate) fn content_type_contains(response: &reqwest::Response, content_type: &str) -> bool {
+    response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.contains(content_type))
+        .unwrap_or(false)
+}
+
+// Read at most MAX_HTML_BYTES of a page, as text
+pub(crate) async fn read_page(mut response: reqwest::Response) -> Result<String> {
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        let remaining = MAX_HTML_BYTES - body.len();
+        body.extend_from_slice(&chunk[..chunk.len().min(remaining)]);
+        if body.len() >= MAX_HTML_BYTES {
+            break;
+        }
+    }
+    Ok(String::from_utf8_lossy(&body).into_owned())
+}
+
+pub async fn fetch_preview(url: &Url) -> Result<Option<LinkPreview>> {
+    let response = web_client()?.get(url.clone()).send().await?;
+    if !response.status().is_success() {
+        return Err(anyhow!("Unexpected status {}", response.status()));
+    }
+    if !content_type_contains(&response, "text/html") {
+        return Ok(None);
+    }
+
+    // The <head> is all we care about
+    let html = read_page(response).await?;
+    let preview = parse_preview(url, &html);
+
+    if preview.title.is_none() && preview.description.is_none() {
+        return Ok(None);
+    }
+    Ok(Some(preview))
+}
+
+fn parse_preview(url: &Url, html: &str) -> LinkPreview {
+    let mut preview = LinkPreview {
+        url: url.to_string(),
+        ..Default::default()
+    };
+
+    for (key, value) in meta_tags(html) {
+        let slot = match key.as_str() {
+            "og:title" | "twitter:title" => &mut preview.title,
+            "og:description" | "twitter:description" | "description" => &mut preview.description,
+            "og:site_name" => &mut preview.site_name,
+            "og:image" | "twitter:image" => &mut preview.image_url,
+            _ => continue,
+        };
+        if slot.is_none() {
+            *slot = Some(limit_field(&value));
+        }
+    }
+
+    if preview.title.is_none() {
+        preview.title = title_tag(html).map(|title| limit_field(&title));
+    }
+
+    // Only keep absolute http(s) image references
+    preview.image_url = preview
+        .image_url
+        .and_then(|image| url.join(&image).ok())
+        .filter(|image| matches!(image.scheme(), "http" | "https"))
+        .map(|image| image.to_string());
+
+    preview
+}
+
+// (property or name, content) pairs of every <meta> tag
+fn meta_tags(html: &str) -> Vec<(String, String)> {
+    let lower = html.to_ascii_lowercase();
+    let mut tags = Vec::new();
+    let mut offset = 0;
+
+    while let Some(start) = lower[offset..].find("<meta") {
+        let tag_start = offset + start;
+        let Some(end) = lower[tag_start..].find('>') else {
+            break;
+        };
+        let tag = &html[tag_start..tag_start + end];
+        offset = tag_start + end;
+
+        let key = attribute(tag, "property").or_else(|| attribute(tag, "name"));
+        if let (Some(key), Some(content)) = (key, attribute(tag, "content")) {
+            tags.push((key.to_ascii_lowercase(), content));
+        }
+    }
+
+    tags
+}
+
+pub(crate) fn attribute(tag: &str, name: &str) -> Option<String> {
+    let lower = tag.to_ascii_lowercase();
+    let needle = format!("{}=", name);
+    let mut search_from = 0;
+
+    while let Some(pos) = lower[search_from..].find(&needle) {
+        let at = search_from + pos;
+        search_from = at + needle.len();
+
+        // Make sure we matched a whole attribute name (e.g. not data-name=)
+        let boundary = at == 0 || lower.as_bytes()[at - 1].is_ascii_whitespace();
+        if !boundary {
+            continue;
+        }
+
+        let rest = &tag[at + needle.len()..];
+        let quote = rest.chars().next()?;
+        let value = if quote == '"' || quote == '\'' {
+            let inner = &rest[1..];
+            &inner[..inner.find(quote)?]
+        } else {
+            rest.split(|c: char| c.is_whitespace() || c == '/').next()?
+        };
+        return Some(decode_entities(value));
+    }
+
+    None
+}
+
+fn title_tag(html: &str) -> Option<String> {
+    let lower = html.to_ascii_lowercase();
+    let open = lower.find("<title")?;
+    let content_start = open + lower[open..].find('>')? + 1;
+    let content_end = content_start + lower[content_start..].find("</title")?;
+    let title = decode_entities(html[content_start..content_end].trim());
+    (!title.is_empty()).then_some(title)
+}
+
+fn decode_entities(value: &str) -> String {
+    value
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+fn limit_field(value: &str) -> String {
+    value.trim().chars().take(MAX_FIELD_CHARS).collect()
+}
@@ -0,0 +1,96 @@
+// rel="me" style verification of the links in a profile: a link counts as
+// verified when the page it points to links back to the profile's pubky.
+// Anyone can put any URL in their profile, so this is what tells a real
+// "my blog" link from a borrowed one.
+//
+// Checking fetches the linked pages from this device, so it only happens
+// when asked for.
+use crate::link_preview;
+use crate::logging;
+use anyhow::{anyhow, Result};
+use futures::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+const VERIFY_CONCURRENCY: usize = 4;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ProfileLink {
+    pub title: String,
+    pub url: String,
+    // None until checked; Some(false) also covers pages we couldn't load
+    #[serde(default)]
+    pub verified: Option<bool>,
+}
+
+// Whether `url` links back to `pubky` with rel="me" (HTML), or mentions it
+// at all (plain text, e.g. a raw gist or a .well-known file)
+pub async fn verify_link(url: &str, pubky: &str) -> Result<bool> {
+    let url = Url::parse(url)?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(anyhow!("Only http(s) links can be verified"));
+    }
+
+    let response = link_preview::web_client()?.get(url).send().await?;
+    if !response.status().is_success() {
+        return Err(anyhow!("Unexpected status {}", response.status()));
+    }
+    let is_html = link_preview::content_type_contains(&response, "text/html");
+    let page = link_preview::read_page(response).await?;
+
+    if is_html {
+        Ok(rel_me_links(&page).iter().any(|href| href.contains(pubky)))
+    } else {
+        Ok(page.contains(pubky))
+    }
+}
+
+// Check every link of `pubky`'s profile, a few at a time
+pub async fn verify_links(pubky: &str, links: &mut [ProfileLink]) {
+    let results: Vec<(usize, bool)> = stream::iter(links.iter().enumerate())
+        .map(|(index, link)| async move {
+            let verified = match verify_link(&link.url, pubky).await {
+                Ok(verified) => verified,
+                Err(e) => {
+                    tracing::debug!("⚠️  Failed to verify link of {}: {}", logging::pubkey(pubky), e);
+                    false
+                }
+            };
+            (index, verified)
+        })
+        .buffer_unordered(VERIFY_CONCURRENCY)
+        .collect()
+        .await;
+
+    for (index, verified) in results {
+        links[index].verified = Some(verified);
+    }
+}
+
+// href of every <a> and <link> tag marked rel="me"
+fn rel_me_links(html: &str) -> Vec<String> {
+    let lower = html.to_ascii_lowercase();
+    let mut links = Vec::new();
+    let mut offset = 0;
+
+    while let Some(start) = lower[offset..].find('<') {
+        let tag_start = offset + start;
+        let Some(end) = lower[tag_start..].find('>') else {
+            break;
+        };
+        let tag = &html[tag_start..tag_start + end];
+        offset = tag_start + end;
+
+        let tag_name = lower[tag_start + 1..tag_start + end].split_whitespace().next().unwrap_or_default();
+        if tag_name != "a" && tag_name != "link" {
+            continue;
+        }
+        let is_me = link_preview::attribute(tag, "rel")
+            .is_some_and(|rel| rel.split_whitespace().any(|value| value.eq_ignore_ascii_case("me")));
+        if let (true, Some(href)) = (is_me, link_preview::attribute(tag, "href")) {
+            links.push(href);
+        }
+    }
+
+    links
+}
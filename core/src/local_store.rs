@@ -0,0 +1,84 @@
+use anyhow::{anyhow, Result};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+// Small JSON documents persisted in the app data directory
+// (onboarding progress, per-user preferences, queues)
+#[derive(Clone)]
+pub struct LocalStore {
+    dir: PathBuf,
+    // Serializes read-modify-write cycles done through `update`
+    write_lock: Arc<Mutex<()>>,
+}
+
+impl LocalStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            write_lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn document_path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", name))
+    }
+
+    // Load a document, falling back to its default when it doesn't exist yet
+    pub fn load<T: DeserializeOwned + Default>(&self, name: &str) -> Result<T> {
+        let path = self.document_path(name);
+        if !path.exists() {
+            return Ok(T::default());
+        }
+
+        let data = fs::read(&path)
+            .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+        serde_json::from_slice(&data)
+            .map_err(|e| anyhow!("Failed to parse {}: {}", path.display(), e))
+    }
+
+    // Write via a temp file + rename so a crash never leaves a torn document
+    pub fn save<T: Serialize>(&self, name: &str, value: &T) -> Result<()> {
+        fs::create_dir_all(&self.dir)
+            .map_err(|e| anyhow!("Failed to create {}: {}", self.dir.display(), e))?;
+
+        let path = self.document_path(name);
+        let tmp_path = path.with_extension("json.tmp");
+        let data = serde_json::to_vec_pretty(value)?;
+
+        fs::write(&tmp_path, data)
+            .map_err(|e| anyhow!("Failed to write {}: {}", tmp_path.display(), e))?;
+        fs::rename(&tmp_path, &path)
+            .map_err(|e| anyhow!("Failed to replace {}: {}", path.display(), e))?;
+
+        Ok(())
+    }
+
+    // Load, modify and save a document without racing other updates
+    pub fn update<T, R, F>(&self, name: &str, modify: F) -> Result<R>
+    where
+        T: Serialize + DeserializeOwned + Default,
+        F: FnOnce(&mut T) -> R,
+    {
+        let _guard = self.write_lock.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut value: T = self.load(name)?;
+        let result = modify(&mut value);
+        self.save(name, &value)?;
+        Ok(result)
+    }
+
+    pub fn remove(&self, name: &str) -> Result<()> {
+        let path = self.document_path(name);
+        if path.exists() {
+            fs::remove_file(&path)
+                .map_err(|e| anyhow!("Failed to remove {}: {}", path.display(), e))?;
+        }
+        Ok(())
+    }
+}
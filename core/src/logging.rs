@@ -0,0 +1,242 @@
+use crate::documents;
+use crate::local_store::LocalStore;
+use anyhow::{anyhow, Result};
+use once_cell::sync::{Lazy, OnceCell};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, reload, Layer, Registry};
+
+const LOG_SETTINGS_DOCUMENT: &str = documents::LOG_SETTINGS;
+
+const LOG_FILE_PREFIX: &str = "messenger";
+const MAX_LOG_FILES: usize = 7;
+
+// Events kept in memory for get_recent_logs
+const RECENT_CAPACITY: usize = 1000;
+
+// Our crates log at the configured level; dependencies only warn and worse
+const OWN_TARGETS: &[&str] = &["pubky_messenger_core", "pubky_private_messenger_lib", "pubky_private_messenger"];
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    fn from_tracing(level: &tracing::Level) -> Self {
+        match *level {
+            tracing::Level::ERROR => LogLevel::Error,
+            tracing::Level::WARN => LogLevel::Warn,
+            tracing::Level::INFO => LogLevel::Info,
+            tracing::Level::DEBUG => LogLevel::Debug,
+            tracing::Level::TRACE => LogLevel::Trace,
+        }
+    }
+
+    fn to_tracing(self) -> tracing::Level {
+        match self {
+            LogLevel::Error => tracing::Level::ERROR,
+            LogLevel::Warn => tracing::Level::WARN,
+            LogLevel::Info => tracing::Level::INFO,
+            LogLevel::Debug => tracing::Level::DEBUG,
+            LogLevel::Trace => tracing::Level::TRACE,
+        }
+    }
+}
+
+// With `redact` on (the default) pubkeys, paths and message text never
+// reach the logs, only short hashes and lengths
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct LogSettings {
+    #[serde(default)]
+    pub level: LogLevel,
+    #[serde(default = "default_redact")]
+    pub redact: bool,
+}
+
+fn default_redact() -> bool {
+    true
+}
+
+impl Default for LogSettings {
+    fn default() -> Self {
+        Self { level: LogLevel::default(), redact: true }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LogEntry {
+    pub timestamp: u64,
+    pub level: LogLevel,
+    pub target: String,
+    pub message: String,
+}
+
+static REDACT: AtomicBool = AtomicBool::new(true);
+static RECENT: Lazy<Mutex<VecDeque<LogEntry>>> = Lazy::new(|| Mutex::new(VecDeque::with_capacity(RECENT_CAPACITY)));
+static FILTER: OnceCell<reload::Handle<Targets, Registry>> = OnceCell::new();
+static FILE_GUARD: OnceCell<WorkerGuard> = OnceCell::new();
+
+pub fn get_settings(store: &LocalStore) -> LogSettings {
+    store.load(LOG_SETTINGS_DOCUMENT).unwrap_or_default()
+}
+
+pub fn set_settings(store: &LocalStore, settings: LogSettings) -> Result<LogSettings> {
+    store.save(LOG_SETTINGS_DOCUMENT, &settings)?;
+    apply_settings(&settings);
+    Ok(settings)
+}
+
+fn targets(level: LogLevel) -> Targets {
+    OWN_TARGETS.iter().fold(
+        Targets::new().with_default(tracing::Level::WARN),
+        |targets, target| targets.with_target(*target, level.to_tracing()),
+    )
+}
+
+pub fn apply_settings(settings: &LogSettings) {
+    REDACT.store(settings.redact, Ordering::Relaxed);
+    if let Some(filter) = FILTER.get() {
+        if let Err(e) = filter.reload(targets(settings.level)) {
+            eprintln!("Failed to apply log level: {}", e);
+        }
+    }
+}
+
+// Log to stdout, to daily files in `log_dir` (keeping a week) and to the
+// in-memory buffer behind get_recent_logs. Call once at startup.
+pub fn init(log_dir: &Path, settings: &LogSettings) -> Result<()> {
+    let appender = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(LOG_FILE_PREFIX)
+        .filename_suffix("log")
+        .max_log_files(MAX_LOG_FILES)
+        .build(log_dir)
+        .map_err(|e| anyhow!("Failed to open log directory: {}", e))?;
+    let (file_writer, guard) = tracing_appender::non_blocking(appender);
+
+    let (filter, handle) = reload::Layer::new(targets(settings.level));
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt::layer())
+        .with(fmt::layer().with_ansi(false).with_writer(file_writer))
+        .with(RecentLayer)
+        .try_init()
+        .map_err(|e| anyhow!("Failed to set up logging: {}", e))?;
+
+    let _ = FILTER.set(handle);
+    let _ = FILE_GUARD.set(guard);
+    REDACT.store(settings.redact, Ordering::Relaxed);
+    Ok(())
+}
+
+// Newest last; only events at `level` or more severe
+pub fn recent(level: LogLevel, limit: usize) -> Vec<LogEntry> {
+    let recent = RECENT.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let mut entries: Vec<LogEntry> = recent.iter()
+        .rev()
+        .filter(|entry| entry.level <= level)
+        .take(limit)
+        .cloned()
+        .collect();
+    entries.reverse();
+    entries
+}
+
+fn short_hash(value: &str) -> String {
+    blake3::hash(value.as_bytes()).to_hex()[..8].to_string()
+}
+
+// A public key as it should appear in logs
+pub fn pubkey(public_key: &impl std::fmt::Display) -> String {
+    let public_key = public_key.to_string();
+    if REDACT.load(Ordering::Relaxed) {
+        format!("pk#{}", short_hash(&public_key))
+    } else {
+        public_key.chars().take(8).collect()
+    }
+}
+
+// A homeserver path or URL as it should appear in logs
+pub fn path(path: &str) -> String {
+    if REDACT.load(Ordering::Relaxed) {
+        format!("path#{}", short_hash(path))
+    } else {
+        path.to_string()
+    }
+}
+
+// Message text or names as they should appear in logs
+pub fn text(text: &str) -> String {
+    if REDACT.load(Ordering::Relaxed) {
+        format!("<{} chars>", text.chars().count())
+    } else {
+        format!("'{}'", text.chars().take(30).collect::<String>())
+    }
+}
+
+struct RecentLayer;
+
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+}
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{:?}", value);
+        } else {
+            let _ = write!(self.message, " {}={:?}", field.name(), value);
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else {
+            let _ = write!(self.message, " {}={}", field.name(), value);
+        }
+    }
+}
+
+impl<S: Subscriber> Layer<S> for RecentLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+
+        let entry = LogEntry {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            level: LogLevel::from_tracing(event.metadata().level()),
+            target: event.metadata().target().to_string(),
+            message: visitor.message,
+        };
+
+        let mut recent = RECENT.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if recent.len() == RECENT_CAPACITY {
+            recent.pop_front();
+        }
+        recent.push_back(entry);
+    }
+}
@@ -0,0 +1,61 @@
+// A conversation's shared media, files and links, for the gallery tabs.
+//
+// Each cached message is tagged with the kind of thing it shares when it's
+// stored, so a tab is one indexed query rather than a walk through the
+// whole history.
+use crate::messaging::ChatMessage;
+use crate::link_preview;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+pub const MAX_PAGE_SIZE: usize = 200;
+pub const DEFAULT_PAGE_SIZE: usize = 60;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MediaKind {
+    // Images and videos
+    Media,
+    File,
+    Link,
+}
+
+impl MediaKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MediaKind::Media => "media",
+            MediaKind::File => "file",
+            MediaKind::Link => "link",
+        }
+    }
+}
+
+// What a message shares, if anything. An attachment wins over links in
+// the text that goes with it.
+pub fn media_kind(message: &ChatMessage) -> Option<MediaKind> {
+    if let Some(attachment) = &message.attachment {
+        let media = attachment.mime.starts_with("image/") || attachment.mime.starts_with("video/");
+        return Some(if media { MediaKind::Media } else { MediaKind::File });
+    }
+    if message.link_preview.is_some() || link_preview::find_first_url(&message.content).is_some() {
+        return Some(MediaKind::Link);
+    }
+    None
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct MediaPage {
+    // Newest first
+    pub messages: Vec<ChatMessage>,
+    // Pass back to get the next, older page; None on the last one
+    pub next_cursor: Option<String>,
+}
+
+pub fn cursor_of(message: &ChatMessage) -> String {
+    format!("{}:{}", message.timestamp, message.id)
+}
+
+pub fn parse_cursor(cursor: &str) -> Result<(u64, &str)> {
+    let (timestamp, id) = cursor.split_once(':').ok_or_else(|| anyhow!("Invalid media cursor"))?;
+    Ok((timestamp.parse().map_err(|_| anyhow!("Invalid media cursor"))?, id))
+}
@@ -0,0 +1,109 @@
+use crate::documents;
+use crate::local_store::LocalStore;
+use anyhow::Result;
+use pkarr::PublicKey;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+pub const MENTION_RECEIVED_EVENT: &str = "mention-received";
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationPriority {
+    #[default]
+    Normal,
+    High,
+}
+
+// What a notification record announces. Call invites will join these.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+    #[default]
+    Message,
+    // A message that mentions the recipient
+    Mention,
+    // Written by a newer client; handled like a plain message
+    #[serde(other)]
+    Unknown,
+}
+
+impl NotificationKind {
+    pub fn priority(self) -> NotificationPriority {
+        match self {
+            NotificationKind::Mention => NotificationPriority::High,
+            NotificationKind::Message | NotificationKind::Unknown => NotificationPriority::Normal,
+        }
+    }
+}
+
+// Payload of the mention-received event
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MentionEvent {
+    pub conversation: String,
+    pub sender: String,
+    pub timestamp: u64,
+    // When it reached us; watermarks use this rather than the claimed time
+    #[serde(default)]
+    pub received_at: u64,
+    pub priority: NotificationPriority,
+}
+
+// Arrival time of the newest mention we already raised an event for, per conversation
+#[derive(Serialize, Deserialize, Default)]
+struct MentionWatermarks {
+    last_notified: HashMap<String, u64>,
+}
+
+fn watermark_document(own_pubkey: &str) -> String {
+    documents::user_document(documents::MENTIONS, own_pubkey)
+}
+
+// Every distinct `@<pubky>` in a message, in order of appearance
+pub fn parse_mentions(content: &str) -> Vec<String> {
+    let mut mentions: Vec<String> = Vec::new();
+
+    for word in content.split_whitespace() {
+        let Some(candidate) = word.strip_prefix('@') else {
+            continue;
+        };
+        let candidate = candidate
+            .strip_prefix("pk:")
+            .unwrap_or(candidate)
+            .trim_end_matches(|c: char| !c.is_ascii_alphanumeric());
+
+        if let Ok(pubkey) = PublicKey::try_from(candidate) {
+            let pubky = pubkey.to_string();
+            if !mentions.contains(&pubky) {
+                mentions.push(pubky);
+            }
+        }
+    }
+
+    mentions
+}
+
+// Filter mentions of the current user down to ones that haven't raised an
+// event yet and advance the persisted watermark past them
+pub fn take_new_mentions(
+    store: &LocalStore,
+    own_pubkey: &str,
+    conversation: &str,
+    candidates: Vec<MentionEvent>,
+) -> Result<Vec<MentionEvent>> {
+    let document = watermark_document(own_pubkey);
+    let mut watermarks: MentionWatermarks = store.load(&document)?;
+    let last_notified = watermarks.last_notified.get(conversation).copied().unwrap_or(0);
+
+    let fresh: Vec<MentionEvent> = candidates
+        .into_iter()
+        .filter(|mention| mention.received_at > last_notified)
+        .collect();
+
+    if let Some(newest) = fresh.iter().map(|mention| mention.received_at).max() {
+        watermarks.last_notified.insert(conversation.to_string(), newest);
+        store.save(&document, &watermarks)?;
+    }
+
+    Ok(fresh)
+}
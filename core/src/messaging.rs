@@ -0,0 +1,2397 @@
+use anyhow::{anyhow, Result};
+use pkarr::{Keypair, PublicKey};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::app_lock;
+use crate::attachment_limits::{self, AttachmentLimits};
+use crate::attachments::{self, Attachment, AttachmentFile};
+use crate::avatar::{self, PubkyAppFile};
+use crate::crypto_compat::{self, decrypt, encrypt, CipherFormat};
+use crate::downloads::DownloadManager;
+use crate::error::{ErrorContext, MessengerError, MessengerResult};
+use crate::health::{self, ConversationHealth, HealthInputs};
+use crate::http_cache::{BodyTooLarge, HttpCache};
+use crate::limits;
+use crate::link_preview::LinkPreview;
+use crate::link_verification::{self, ProfileLink};
+use crate::local_store::LocalStore;
+use crate::logging;
+use crate::mentions::{NotificationKind, NotificationPriority};
+use crate::net;
+use crate::notification_batch::{self, NotificationBatcher};
+use crate::operations::Operations;
+use crate::profiles::{self, ProfileCache, PROFILE_CHANGED_EVENT};
+use crate::progress::{AttachmentUploadProgress, Progress, SyncProgress, ATTACHMENT_UPLOAD_PROGRESS_EVENT, SYNC_PROGRESS_EVENT};
+use crate::padding;
+use crate::protocol::{self, Migration, PeerVersions, PROTOCOL_VERSION};
+use crate::push::{self, PushRecord};
+use crate::quarantine::{self, DecryptionFailuresEvent, Quarantine};
+use crate::security_log::{SecurityEventKind, SecurityLog};
+use crate::storage::{Storage, StoredMessage};
+use crate::transport::Transport;
+use crate::verification::VerificationState;
+use crate::watcher::ListingWatcher;
+use crate::wire;
+use blake3::Hasher;
+use sha2::{Digest, Sha512};
+use uuid::Uuid;
+use x25519_dalek::{PublicKey as X25519PublicKey, StaticSecret};
+use curve25519_dalek::edwards::CompressedEdwardsY;
+use ed25519_dalek::{Signature};
+use base64;
+use hex;
+use tokio::sync::{Mutex, Notify};
+use futures::stream::{self, StreamExt};
+
+// Function for proper Edwards to Montgomery curve conversion
+fn ed25519_public_to_x25519(ed_pub: &[u8; 32]) -> Option<X25519PublicKey> {
+    let compressed = CompressedEdwardsY(*ed_pub);
+    let edwards_point = compressed.decompress()?;
+    Some(X25519PublicKey::from(edwards_point.to_montgomery().to_bytes()))
+}
+
+// Function to properly convert Ed25519 secret key to X25519
+fn ed25519_secret_to_x25519(ed_secret: &[u8; 32]) -> StaticSecret {
+    let mut hasher = Sha512::new();
+    hasher.update(ed_secret);
+    let hash = hasher.finalize();
+
+    let mut x25519_secret_bytes = [0u8; 32];
+    x25519_secret_bytes.copy_from_slice(&hash[0..32]);
+
+    // Apply clamping as per RFC 7748
+    x25519_secret_bytes[0] &= 248;
+    x25519_secret_bytes[31] &= 127;
+    x25519_secret_bytes[31] |= 64;
+
+    StaticSecret::from(x25519_secret_bytes)
+}
+
+fn generate_shared_secret(keypair: &Keypair, other_pubkey: &PublicKey) -> Result<String> {
+    // Convert Ed25519 secret to X25519 using proper conversion
+    let ed25519_secret = keypair.secret_key();
+    let x25519_secret = ed25519_secret_to_x25519(&ed25519_secret);
+
+    // Convert Ed25519 public to X25519 using proper curve conversion
+    let other_pubkey_bytes = other_pubkey.as_bytes();
+    if other_pubkey_bytes.len() != 32 {
+        return Err(anyhow!(MessengerError::Crypto("Invalid public key length".to_string())));
+    }
+
+    let mut other_ed_bytes = [0u8; 32];
+    other_ed_bytes.copy_from_slice(other_pubkey_bytes);
+
+    let other_x25519 = ed25519_public_to_x25519(&other_ed_bytes)
+        .ok_or_else(|| MessengerError::Crypto("Failed to convert pubkey to X25519".to_string()))?;
+
+    let shared = x25519_secret.diffie_hellman(&other_x25519);
+    Ok(hex::encode(shared.as_bytes()))
+}
+
+// Symmetric key for a conversation, derived from the ECDH shared secret
+fn conversation_encryption_key(keypair: &Keypair, other_pubkey: &PublicKey) -> Result<[u8; 32]> {
+    let shared_secret = generate_shared_secret(keypair, other_pubkey)?;
+    let shared_secret_bytes = hex::decode(&shared_secret)
+        .map_err(|e| MessengerError::Crypto(format!("Failed to decode shared secret: {}", e)))?;
+
+    if shared_secret_bytes.len() != 32 {
+        return Err(anyhow!(MessengerError::Crypto(format!("Shared secret must be 32 bytes, got {}", shared_secret_bytes.len()))));
+    }
+
+    let mut encryption_key = [0u8; 32];
+    encryption_key.copy_from_slice(&shared_secret_bytes);
+    Ok(encryption_key)
+}
+
+// Structured data carried alongside the plain text body.
+// Older clients ignore it and just show the text.
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct MessageExtras {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contact_card: Option<ContactCard>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link_preview: Option<LinkPreview>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mentions: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attachment: Option<Attachment>,
+    // Newest protocol the sender's client understands, so the recipient
+    // can start writing it before the sender does. Set on every send.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub supported_protocol: Option<u32>,
+}
+
+// A contact shared into a conversation
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ContactCard {
+    pub pubky: String,
+    pub name: Option<String>,
+}
+
+const PUBLIC_PRESENCE_NAME: &str = "public";
+// Key derivation context for per-contact presence record names, so they
+// can't be linked to the conversation directory
+const PRESENCE_PATH_CONTEXT: &str = "pubky-private-messenger 2025 presence path";
+const PRESENCE_DIGEST_CONTEXT: &[u8] = b"pubky-private-messenger presence v1";
+
+// Signed "last active" timestamp a user publishes on their homeserver
+#[derive(Serialize, Deserialize)]
+struct PresenceRecord {
+    #[serde(default)]
+    protocol_version: u32,
+    last_active: u64,
+    #[serde(with = "serde_bytes")]
+    signature: Vec<u8>,
+}
+
+fn presence_digest(owner: &PublicKey, last_active: u64) -> blake3::Hash {
+    let mut hasher = Hasher::new();
+    hasher.update(PRESENCE_DIGEST_CONTEXT);
+    hasher.update(owner.as_bytes());
+    hasher.update(&last_active.to_be_bytes());
+    hasher.finalize()
+}
+
+// Message structure with metadata and encrypted content
+#[derive(Serialize, Deserialize)]
+pub(crate) struct PrivateMessage {
+    // Version the sender wrote; set by wire::decode_message for older payloads
+    #[serde(default)]
+    pub(crate) protocol_version: u32,
+    pub(crate) timestamp: u64,
+    // Byte strings in CBOR; serde_bytes still reads the JSON arrays of legacy blobs
+    #[serde(with = "serde_bytes")]
+    encrypted_sender: Vec<u8>,  // Changed from plaintext sender
+    #[serde(with = "serde_bytes")]
+    encrypted_content: Vec<u8>,
+    #[serde(with = "serde_bytes")]
+    signature_bytes: Vec<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "serde_bytes")]
+    encrypted_extras: Option<Vec<u8>>,
+    // Older clients didn't embed it; those messages fall back to the blob's file name
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub(crate) msg_id: String,
+    // Set when msg_id came from the file name, so it isn't part of the signature
+    #[serde(skip)]
+    legacy_msg_id: bool,
+}
+
+// Blobs are stored as .../<msg_id>.cbor (or .json for legacy ones)
+pub fn msg_id_from_url(url: &str) -> String {
+    let file_name = url.rsplit('/').next().unwrap_or(url);
+    file_name
+        .strip_suffix(wire::BLOB_EXTENSION)
+        .or_else(|| file_name.strip_suffix(wire::LEGACY_BLOB_EXTENSION))
+        .unwrap_or(file_name)
+        .to_string()
+}
+
+// Digest covering everything the sender signs; extras and msg_id are only
+// mixed in when present so messages from older clients still verify
+fn message_digest(content: &[u8], sender_pk: &PublicKey, timestamp: u64, extras: Option<&[u8]>, msg_id: &str) -> blake3::Hash {
+    let mut hasher = Hasher::new();
+    hasher.update(content);
+    hasher.update(sender_pk.as_bytes());
+    hasher.update(&timestamp.to_be_bytes());
+    if let Some(extras) = extras {
+        hasher.update(extras);
+    }
+    if !msg_id.is_empty() {
+        hasher.update(msg_id.as_bytes());
+    }
+    hasher.finalize()
+}
+
+// Clients before this version verify only content, sender and timestamp,
+// so they get that digest until they're known to understand the extended one
+const EXTENDED_DIGEST_VERSION: u32 = 2;
+
+impl PrivateMessage {
+    // Field sizes and ranges checked before any decryption
+    fn check_limits(&self, now: u64) -> Result<()> {
+        limits::check_timestamp(self.timestamp, now)?;
+        limits::check_msg_id(&self.msg_id)?;
+        limits::check_len("Encrypted sender", self.encrypted_sender.len(), limits::MAX_ENCRYPTED_SENDER_BYTES)?;
+        limits::check_len("Encrypted content", self.encrypted_content.len(), limits::MAX_ENCRYPTED_CONTENT_BYTES)?;
+        if let Some(extras) = &self.encrypted_extras {
+            limits::check_len("Encrypted extras", extras.len(), limits::MAX_ENCRYPTED_EXTRAS_BYTES)?;
+        }
+        if self.signature_bytes.len() != limits::SIGNATURE_BYTES {
+            return Err(anyhow!("Signature is {} bytes, not {}", self.signature_bytes.len(), limits::SIGNATURE_BYTES));
+        }
+        Ok(())
+    }
+
+    // Written at `protocol_version`, padded from padding::PADDING_VERSION.
+    // Extras and msg_id are signed only with `extended_digest`.
+    fn new(
+        sender_keypair: &Keypair,
+        recipient_pk: &PublicKey,
+        content: &str,
+        extras: Option<&MessageExtras>,
+        protocol_version: u32,
+        extended_digest: bool,
+    ) -> Result<Self> {
+        let content_bytes = content.as_bytes();
+        // Recipients would quarantine anything bigger
+        if content_bytes.len() > limits::MAX_CONTENT_BYTES {
+            return Err(anyhow!(MessengerError::InvalidInput(format!("Message is over the {} byte limit", limits::MAX_CONTENT_BYTES))));
+        }
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let msg_id = Uuid::new_v4().to_string();
+
+        let extras = MessageExtras {
+            supported_protocol: Some(PROTOCOL_VERSION),
+            ..extras.cloned().unwrap_or_default()
+        };
+        let extras_bytes = serde_json::to_vec(&extras)?;
+        if extras_bytes.len() > limits::MAX_EXTRAS_BYTES {
+            return Err(anyhow!(MessengerError::InvalidInput(format!("Message extras are over the {} byte limit", limits::MAX_EXTRAS_BYTES))));
+        }
+
+        // Create message digest for signing (same as before)
+        let message_digest = if extended_digest {
+            message_digest(content_bytes, &sender_keypair.public_key(), timestamp, Some(&extras_bytes), &msg_id)
+        } else {
+            message_digest(content_bytes, &sender_keypair.public_key(), timestamp, None, "")
+        };
+
+        // Sign the message
+        let signature = sender_keypair.sign(message_digest.as_bytes());
+        let signature_bytes = signature.to_bytes().to_vec();
+
+        // Generate shared secret and encryption key
+        let shared_secret = generate_shared_secret(sender_keypair, recipient_pk)?;
+        let shared_secret_bytes = hex::decode(&shared_secret)
+            .map_err(|e| MessengerError::Crypto(format!("Failed to decode shared secret: {}", e)))?;
+
+        if shared_secret_bytes.len() != 32 {
+            return Err(anyhow!(MessengerError::Crypto(format!("Shared secret must be 32 bytes, got {}", shared_secret_bytes.len()))));
+        }
+
+        let mut encryption_key = [0u8; 32];
+        encryption_key.copy_from_slice(&shared_secret_bytes);
+
+        // Padding goes inside the ciphertext; the signature covers the unpadded bytes
+        let padded = protocol_version >= padding::PADDING_VERSION;
+        let seal = |bytes: &[u8]| if padded { encrypt(&padding::pad(bytes), &encryption_key) } else { encrypt(bytes, &encryption_key) };
+
+        // Encrypt content (same as before)
+        let encrypted_content = seal(content_bytes)?;
+
+        // NEW: Encrypt sender public key
+        let sender_string = sender_keypair.public_key().to_string();
+        let sender_bytes = sender_string.as_bytes();
+        let encrypted_sender = encrypt(sender_bytes, &encryption_key)?;
+
+        let encrypted_extras = Some(seal(&extras_bytes)?);
+
+        Ok(Self {
+            protocol_version,
+            timestamp,
+            encrypted_sender,    // Now encrypted!
+            encrypted_content,
+            signature_bytes,
+            encrypted_extras,
+            msg_id,
+            legacy_msg_id: false,
+        })
+    }
+
+    fn decrypt_extras_bytes(&self, receiver_keypair: &Keypair, other_participant: &PublicKey) -> Result<Option<Vec<u8>>> {
+        let Some(encrypted_extras) = &self.encrypted_extras else {
+            return Ok(None);
+        };
+
+        let encryption_key = conversation_encryption_key(receiver_keypair, other_participant)?;
+        Ok(Some(self.unpad(decrypt(encrypted_extras, &encryption_key)?)?))
+    }
+
+    fn unpad(&self, plaintext: Vec<u8>) -> Result<Vec<u8>> {
+        if self.protocol_version >= padding::PADDING_VERSION {
+            padding::unpad(&plaintext)
+        } else {
+            Ok(plaintext)
+        }
+    }
+
+    pub fn decrypt_extras(&self, receiver_keypair: &Keypair, other_participant: &PublicKey) -> Result<MessageExtras> {
+        match self.decrypt_extras_bytes(receiver_keypair, other_participant)? {
+            Some(bytes) => Ok(serde_json::from_slice(&bytes)?),
+            None => Ok(MessageExtras::default()),
+        }
+    }
+
+    fn decrypt_content(&self, receiver_keypair: &Keypair, other_participant: &PublicKey) -> Result<String> {
+        // Same as before - decrypt content
+        let shared_secret = generate_shared_secret(receiver_keypair, other_participant)?;
+        let shared_secret_bytes = hex::decode(&shared_secret)
+            .map_err(|e| MessengerError::Crypto(format!("Failed to decode shared secret: {}", e)))?;
+
+        if shared_secret_bytes.len() != 32 {
+            return Err(anyhow!(MessengerError::Crypto(format!("Shared secret must be 32 bytes, got {}", shared_secret_bytes.len()))));
+        }
+
+        let mut encryption_key = [0u8; 32];
+        encryption_key.copy_from_slice(&shared_secret_bytes);
+
+        let decrypted = self.unpad(decrypt(&self.encrypted_content, &encryption_key)?)?;
+        Ok(String::from_utf8(decrypted)?)
+    }
+
+    // NEW: Method to decrypt sender
+    pub fn decrypt_sender(&self, receiver_keypair: &Keypair, other_participant: &PublicKey) -> Result<String> {
+        let shared_secret = generate_shared_secret(receiver_keypair, other_participant)?;
+        let shared_secret_bytes = hex::decode(&shared_secret)
+            .map_err(|e| MessengerError::Crypto(format!("Failed to decode shared secret: {}", e)))?;
+
+        if shared_secret_bytes.len() != 32 {
+            return Err(anyhow!(MessengerError::Crypto(format!("Shared secret must be 32 bytes, got {}", shared_secret_bytes.len()))));
+        }
+
+        let mut encryption_key = [0u8; 32];
+        encryption_key.copy_from_slice(&shared_secret_bytes);
+
+        let decrypted = decrypt(&self.encrypted_sender, &encryption_key)?;
+        Ok(String::from_utf8(decrypted)?)
+    }
+
+    // Every protocol version so far seals with the V1 layout. Older wire
+    // formats are told apart by `protocol_version`; see health::is_legacy_message.
+    pub fn cipher_format(&self) -> CipherFormat {
+        CipherFormat::V1
+    }
+
+    fn verify_signature(&self, decrypted_content: &str, decrypted_sender: &str, decrypted_extras: Option<&[u8]>) -> Result<bool> {
+        let sender_pk = PublicKey::try_from(decrypted_sender)?;
+
+        // Recreate the message digest (same as before)
+        let message_digest = message_digest(
+            decrypted_content.as_bytes(),
+            &sender_pk,
+            self.timestamp,
+            decrypted_extras,
+            if self.legacy_msg_id { "" } else { &self.msg_id },
+        );
+        // Written for a peer not yet known to read the extended digest; the
+        // extras are still authenticated by their encryption
+        let legacy_digest = message_digest(decrypted_content.as_bytes(), &sender_pk, self.timestamp, None, "");
+
+        if self.signature_bytes.len() != 64 {
+            return Err(anyhow!(MessengerError::Crypto("Invalid signature length".to_string())));
+        }
+
+        let mut sig_bytes = [0u8; 64];
+        sig_bytes.copy_from_slice(&self.signature_bytes);
+        let signature = Signature::from_bytes(&sig_bytes);
+
+        Ok(sender_pk.verify(message_digest.as_bytes(), &signature).is_ok()
+            || sender_pk.verify(legacy_digest.as_bytes(), &signature).is_ok())
+    }
+}
+
+// Key derivation context for the notes-to-self conversation path
+const SAVED_MESSAGES_CONTEXT: &str = "pubky-private-messenger 2025 saved messages path";
+
+// Display name for the notes-to-self conversation
+pub const SAVED_MESSAGES_NAME: &str = "Saved messages";
+
+const NOTIFICATION_DIGEST_CONTEXT: &[u8] = b"pubky-private-messenger notification v1";
+
+// Written to the recipient's /pub/notifications/ on send, so messages from
+// people they don't know yet still get found. Homeservers that only take
+// writes from their owner refuse it, and the message is then only found
+// through the conversation listing. (Stores sender publicly for now.)
+// Anyone can write there, so records are signed by the sender and
+// unsigned or forged ones are dropped.
+#[derive(Serialize, Deserialize)]
+struct PrivateNotification {
+    #[serde(default)]
+    protocol_version: u32,
+    timestamp: u64,
+    sender: String, // Store sender publicly for simplicity
+    // The newest message announced
+    msg_id: String,
+    // Every message announced, when several were batched into one record.
+    // Older readers only see msg_id, which still gets them to sync.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    msg_ids: Vec<String>,
+    // Lets the recipient sync mentions first; the message's signed extras
+    // still decide how it is shown
+    #[serde(default)]
+    kind: NotificationKind,
+    // Over notification_digest, by the sender
+    #[serde(default, with = "serde_bytes")]
+    signature: Vec<u8>,
+}
+
+impl PrivateNotification {
+    // Bound to the recipient, so a record can't be replayed into someone
+    // else's inbox
+    fn digest(&self, recipient: &PublicKey) -> Result<blake3::Hash> {
+        let mut hasher = Hasher::new();
+        hasher.update(NOTIFICATION_DIGEST_CONTEXT);
+        hasher.update(recipient.as_bytes());
+        hasher.update(self.sender.as_bytes());
+        hasher.update(&self.timestamp.to_be_bytes());
+        hasher.update(serde_json::to_string(&self.kind)?.as_bytes());
+        for msg_id in std::iter::once(&self.msg_id).chain(&self.msg_ids) {
+            hasher.update(&(msg_id.len() as u64).to_be_bytes());
+            hasher.update(msg_id.as_bytes());
+        }
+        Ok(hasher.finalize())
+    }
+
+    fn verify(&self, sender: &PublicKey, recipient: &PublicKey) -> bool {
+        let Ok(signature) = <[u8; 64]>::try_from(self.signature.as_slice()) else {
+            return false;
+        };
+        self.digest(recipient)
+            .is_ok_and(|digest| sender.verify(digest.as_bytes(), &Signature::from_bytes(&signature)).is_ok())
+    }
+}
+
+// Version 1 notifications encrypted the sender with a scheme we no longer
+// have, so they can't be upgraded and are deleted unread
+const NOTIFICATION_MIGRATIONS: &[Migration<serde_json::Value>] = &[
+    Migration { from: 2, description: "explicit protocol version", upgrade: protocol::unchanged },
+    Migration { from: 3, description: "padded plaintext", upgrade: protocol::unchanged },
+];
+
+// None for notifications from a newer protocol, which we leave alone
+fn decode_notification(text: &str) -> Result<Option<PrivateNotification>> {
+    let mut value: serde_json::Value = serde_json::from_str(text)?;
+    let version = match value.get("protocol_version").and_then(|version| version.as_u64()) {
+        Some(version) => u32::try_from(version)?,
+        None if value.get("encrypted_sender").is_some() => 1,
+        None => 2,
+    };
+    if version > PROTOCOL_VERSION {
+        return Ok(None);
+    }
+
+    protocol::upgrade("notification", NOTIFICATION_MIGRATIONS, &mut value, version)?;
+    let mut notification: PrivateNotification = serde_json::from_value(value)?;
+    notification.protocol_version = version;
+    Ok(Some(notification))
+}
+
+#[derive(Clone)]
+pub struct PrivateMessageHandler {
+    transport: Arc<dyn Transport>,
+    pub keypair: Keypair,
+    http_cache: HttpCache,
+    watcher: ListingWatcher,
+    progress: Progress,
+    profile_cache: Option<ProfileCache>,
+    notification_batcher: Option<NotificationBatcher>,
+    quarantine: Option<Quarantine>,
+    security_log: Option<SecurityLog>,
+    peer_versions: Option<PeerVersions>,
+    attachment_limits: Option<AttachmentLimits>,
+}
+
+impl PrivateMessageHandler {
+    pub fn new(transport: Arc<dyn Transport>, keypair: Keypair, http_cache: HttpCache, watcher: ListingWatcher) -> Self {
+        Self {
+            transport,
+            keypair,
+            http_cache,
+            watcher,
+            progress: Progress::new(),
+            profile_cache: None,
+            notification_batcher: None,
+            quarantine: None,
+            security_log: None,
+            peer_versions: None,
+            attachment_limits: None,
+        }
+    }
+
+    // Report sync and upload progress to `progress` instead of nowhere
+    pub fn with_progress(mut self, progress: Progress) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    // Coalesce notifications for quick successive sends instead of writing
+    // each one right away. Only for long-running processes, since a batch
+    // is written a moment after the send returns.
+    pub fn with_notification_batcher(mut self, notification_batcher: NotificationBatcher) -> Self {
+        self.notification_batcher = Some(notification_batcher);
+        self
+    }
+
+    // Hold back blobs that keep failing instead of retrying them every poll
+    pub fn with_quarantine(mut self, quarantine: Quarantine) -> Self {
+        self.quarantine = Some(quarantine);
+        self
+    }
+
+    // Where spikes of decryption failures are recorded
+    pub fn with_security_log(mut self, security_log: SecurityLog) -> Self {
+        self.security_log = Some(security_log);
+        self
+    }
+
+    // Without it messages go out at protocol::MIN_WRITE_VERSION
+    pub fn with_peer_versions(mut self, peer_versions: PeerVersions) -> Self {
+        self.peer_versions = Some(peer_versions);
+        self
+    }
+
+    // Refuse to send, and drop from received messages, attachments outside
+    // `attachment_limits`
+    pub fn with_attachment_limits(mut self, attachment_limits: AttachmentLimits) -> Self {
+        self.attachment_limits = Some(attachment_limits);
+        self
+    }
+
+    // Serve contact profiles from `profile_cache` until they go stale
+    pub fn with_profile_cache(mut self, profile_cache: ProfileCache) -> Self {
+        self.profile_cache = Some(profile_cache);
+        self
+    }
+
+    // Both listings a conversation's messages can appear in
+    fn conversation_listing_paths(&self, other_pubkey: &PublicKey) -> Result<Vec<String>> {
+        let private_path = self.private_conversation_path(other_pubkey)?;
+        let mut paths = vec![format!("pubky://{}{}", self.keypair.public_key(), private_path)];
+        // Notes-to-self only ever live on our own homeserver
+        if !self.is_self(other_pubkey) {
+            paths.push(format!("pubky://{}{}", other_pubkey, private_path));
+        }
+        Ok(paths)
+    }
+
+    // Whether either listing of a conversation changed since the last check.
+    // Listings that can't be fetched count as unchanged.
+    pub async fn conversation_changed(&self, other_pubkey: &PublicKey) -> Result<bool> {
+        let mut changed = false;
+        for path in self.conversation_listing_paths(other_pubkey)? {
+            if let Ok(urls) = self.transport.list(&path).await {
+                changed |= self.watcher.observe(&path, &urls);
+            }
+        }
+        Ok(changed)
+    }
+
+    // Make the next check report the conversation as changed
+    pub fn forget_conversation(&self, other_pubkey: &PublicKey) {
+        if let Ok(paths) = self.conversation_listing_paths(other_pubkey) {
+            for path in paths {
+                self.watcher.forget(&path);
+            }
+        }
+    }
+
+    // Whether anything was added to or removed from our notifications directory
+    pub async fn notifications_changed(&self) -> Result<bool> {
+        let notifications_path = format!("pubky://{}/pub/notifications/", self.keypair.public_key());
+        let urls = self.transport.list(&notifications_path).await?;
+        Ok(self.watcher.observe(&notifications_path, &urls))
+    }
+
+    pub async fn get_all_new_messages_from_contacts_with_timestamp(&self, contacts: &[PublicKey]) -> Result<Vec<(String, String, u64, bool)>> {
+        let mut all_messages = Vec::new();
+        let mut seen_ids: HashSet<String> = HashSet::new();
+
+        for contact in contacts {
+            let conversation_messages = self.get_messages(contact).await?;
+            for (msg, content, verified) in conversation_messages {
+                // The same contact may be listed twice (e.g. follows plus a shared card)
+                if !seen_ids.insert(msg.msg_id.clone()) {
+                    continue;
+                }
+                // Decrypt the sender field using the contact as the other participant
+                match msg.decrypt_sender(&self.keypair, contact) {
+                    Ok(sender) => {
+                        all_messages.push((sender, content, msg.timestamp, verified));
+                    }
+                    Err(e) => {
+                        tracing::warn!("❌ Failed to decrypt sender for message: {}", e);
+                        continue;
+                    }
+                }
+            }
+        }
+
+        // Sort by timestamp (most recent first)
+        all_messages.sort_by(|a, b| b.2.cmp(&a.2));
+
+        Ok(all_messages)
+    }
+
+    // Whether a conversation is the user's notes-to-self
+    pub fn is_self(&self, other_pubkey: &PublicKey) -> bool {
+        other_pubkey.as_bytes() == self.keypair.public_key().as_bytes()
+    }
+
+    // Saved messages live next to regular conversations so the listing
+    // doesn't reveal which directory holds them
+    fn self_conversation_path(&self) -> String {
+        let path_id = blake3::derive_key(SAVED_MESSAGES_CONTEXT, &self.keypair.secret_key());
+        format!("/pub/private_messages/{}/", hex::encode(path_id))
+    }
+
+    // Add this debugging version to your PrivateMessageHandler in messaging.rs
+    fn private_conversation_path(&self, other_pubkey: &PublicKey) -> Result<String> {
+        if self.is_self(other_pubkey) {
+            return Ok(self.self_conversation_path());
+        }
+
+        let shared_secret = generate_shared_secret(&self.keypair, other_pubkey)?;
+        let path_id = blake3::hash(shared_secret.as_bytes()).to_hex();
+        let path = format!("/pub/private_messages/{}/", path_id);
+
+        // Never log the shared secret or anything derived from it in the clear
+        tracing::trace!("🔑 Conversation path with {}: {}", logging::pubkey(other_pubkey), logging::path(&path));
+
+        Ok(path)
+    }
+
+    // Where `owner` publishes presence: one encrypted record per contact, or
+    // a single public one when `contact` is None
+    fn presence_url(&self, owner: &PublicKey, contact: Option<&PublicKey>) -> Result<String> {
+        let name = match contact {
+            Some(contact) => {
+                let shared_secret = generate_shared_secret(&self.keypair, contact)?;
+                hex::encode(blake3::derive_key(PRESENCE_PATH_CONTEXT, shared_secret.as_bytes()))
+            }
+            None => PUBLIC_PRESENCE_NAME.to_string(),
+        };
+        Ok(format!("pubky://{}/pub/private_messages/presence/{}", owner, name))
+    }
+
+    // Publish a signed "last active" timestamp, readable by each of
+    // `contacts` or, with None, by anyone
+    pub async fn publish_presence(&self, contacts: Option<&[PublicKey]>, last_active: u64) -> Result<()> {
+        let record = PresenceRecord {
+            protocol_version: protocol::MIN_WRITE_VERSION,
+            last_active,
+            signature: self.keypair.sign(presence_digest(&self.keypair.public_key(), last_active).as_bytes()).to_bytes().to_vec(),
+        };
+        let record_json = serde_json::to_vec(&record)?;
+
+        let Some(contacts) = contacts else {
+            let url = self.presence_url(&self.keypair.public_key(), None)?;
+            let response = self.transport.put(&url, record_json).await?;
+            if !response.status().is_success() {
+                return Err(anyhow!(MessengerError::HomeserverRejected(format!("Failed to publish presence: {}", response.status()))));
+            }
+            return Ok(());
+        };
+
+        for contact in contacts.iter().filter(|contact| !self.is_self(contact)) {
+            let url = self.presence_url(&self.keypair.public_key(), Some(contact))?;
+            let encrypted = encrypt(&record_json, &conversation_encryption_key(&self.keypair, contact)?)?;
+            let response = self.transport.put(&url, encrypted).await?;
+            if !response.status().is_success() {
+                return Err(anyhow!(MessengerError::HomeserverRejected(format!("Failed to publish presence: {}", response.status()))));
+            }
+        }
+        Ok(())
+    }
+
+    // Stop publishing presence
+    pub async fn clear_presence(&self) -> Result<()> {
+        let presence_path = format!("pubky://{}/pub/private_messages/presence/", self.keypair.public_key());
+        for url in self.transport.list(&presence_path).await.unwrap_or_default() {
+            self.transport.delete(&url).await?;
+        }
+        Ok(())
+    }
+
+    // A contact's last-active time, from the record they encrypted for us or
+    // else their public one. None when they don't publish presence or the
+    // signature doesn't check out.
+    pub async fn fetch_presence(&self, contact: &PublicKey) -> Result<Option<u64>> {
+        let private_url = self.presence_url(contact, Some(contact))?;
+        let record = match self.http_cache.get_bytes(self.transport.as_ref(), &private_url).await? {
+            Some(encrypted) => decrypt(&encrypted, &conversation_encryption_key(&self.keypair, contact)?).ok(),
+            None => {
+                let public_url = self.presence_url(contact, None)?;
+                self.http_cache.get_bytes(self.transport.as_ref(), &public_url).await?
+            }
+        };
+
+        let Some(record) = record.and_then(|bytes| serde_json::from_slice::<PresenceRecord>(&bytes).ok()) else {
+            return Ok(None);
+        };
+        if record.signature.len() != 64 {
+            return Ok(None);
+        }
+        let mut sig_bytes = [0u8; 64];
+        sig_bytes.copy_from_slice(&record.signature);
+        let digest = presence_digest(contact, record.last_active);
+        Ok(contact.verify(digest.as_bytes(), &Signature::from_bytes(&sig_bytes)).ok().map(|_| record.last_active))
+    }
+
+    // Publish where senders should ping to wake us, or stop with None
+    pub async fn publish_push_endpoint(&self, endpoint: Option<&str>) -> Result<()> {
+        let url = format!("pubky://{}{}", self.keypair.public_key(), push::PUSH_RECORD_PATH);
+        let Some(endpoint) = endpoint else {
+            self.transport.delete(&url).await?;
+            return Ok(());
+        };
+
+        push::check_endpoint(endpoint)?;
+        let record = PushRecord { protocol_version: protocol::MIN_WRITE_VERSION, endpoint: endpoint.to_string() };
+        let response = self.transport.put(&url, serde_json::to_vec(&record)?).await?;
+        if !response.status().is_success() {
+            return Err(anyhow!(MessengerError::HomeserverRejected(format!("Failed to publish push endpoint: {}", response.status()))));
+        }
+        Ok(())
+    }
+
+    // Where `contact` wants to be pinged, if they use push
+    pub async fn fetch_push_endpoint(&self, contact: &PublicKey) -> Result<Option<String>> {
+        let url = format!("pubky://{}{}", contact, push::PUSH_RECORD_PATH);
+        let Some(bytes) = self.http_cache.get_bytes(self.transport.as_ref(), &url).await? else {
+            return Ok(None);
+        };
+        let record: PushRecord = serde_json::from_slice(&bytes)?;
+        if record.protocol_version > PROTOCOL_VERSION {
+            return Ok(None);
+        }
+        Ok(Some(record.endpoint))
+    }
+
+    // Ping the recipient's push endpoint, if they have one
+    async fn wake(&self, recipient: &PublicKey) {
+        let result = match self.fetch_push_endpoint(recipient).await {
+            Ok(Some(endpoint)) => push::ping(&endpoint).await,
+            Ok(None) => return,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            tracing::debug!("📭 Failed to wake {}: {}", logging::pubkey(recipient), e);
+        }
+    }
+
+    // One record announcing every message in `msg_ids`
+    async fn create_notification(&self, recipient: &PublicKey, msg_ids: &[String], kind: NotificationKind) -> Result<()> {
+        let Some(newest) = msg_ids.last() else {
+            return Ok(());
+        };
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)?
+            .as_secs();
+
+        let mut notification = PrivateNotification {
+            protocol_version: self.version_for(recipient),
+            timestamp,
+            sender: self.keypair.public_key().to_string(),
+            msg_id: newest.clone(),
+            msg_ids: if msg_ids.len() > 1 { msg_ids.to_vec() } else { Vec::new() },
+            kind,
+            signature: Vec::new(),
+        };
+        notification.signature = self.keypair.sign(notification.digest(recipient)?.as_bytes()).to_bytes().to_vec();
+
+        let notification_id = Uuid::new_v4().to_string();
+        let notification_path = format!(
+            "pubky://{}/pub/notifications/{}.json",
+            recipient,
+            notification_id
+        );
+
+        let notification_json = serde_json::to_string(&notification)?;
+        let response = self.transport.put(&notification_path, notification_json.into_bytes()).await?;
+
+        if !response.status().is_success() {
+            return Err(anyhow!(MessengerError::HomeserverRejected(format!("Failed to store notification: {}", response.status()))));
+        }
+
+        Ok(())
+    }
+
+    pub async fn send_message(&self, recipient: &PublicKey, content: &str) -> Result<()> {
+        self.send_message_with_extras(recipient, content, None).await
+    }
+
+    // Share a contact's pubky and profile name as a structured message
+    pub async fn share_contact(&self, recipient: &PublicKey, contact: &PublicKey) -> Result<ContactCard> {
+        let card = ContactCard {
+            pubky: contact.to_string(),
+            name: self.get_profile_name(&contact.to_string()).await.unwrap_or(None),
+        };
+
+        // Plain text fallback for clients that don't understand contact cards
+        let content = match &card.name {
+            Some(name) => format!("📇 Contact: {} ({})", name, card.pubky),
+            None => format!("📇 Contact: {}", card.pubky),
+        };
+
+        let extras = MessageExtras {
+            contact_card: Some(card.clone()),
+            ..Default::default()
+        };
+        self.send_message_with_extras(recipient, &content, Some(&extras)).await?;
+
+        Ok(card)
+    }
+
+    // Encrypt and upload a file to our homeserver; send the returned
+    // reference in a message's extras to share it
+    pub async fn upload_attachment(&self, file: &AttachmentFile) -> Result<Attachment> {
+        if let Some(limits) = &self.attachment_limits {
+            limits.check_send(&file.name, &file.mime, file.bytes.len() as u64)?;
+        }
+        let (attachment, ciphertext) = attachments::seal(&self.keypair.public_key(), file)?;
+        tracing::debug!("📎 Uploading {} byte attachment to {}", ciphertext.len(), logging::path(&attachment.url));
+
+        let total_bytes = ciphertext.len() as u64;
+        let report = |sent_bytes| self.progress.report(ATTACHMENT_UPLOAD_PROGRESS_EVENT, &AttachmentUploadProgress {
+            name: attachment.name.clone(),
+            sent_bytes,
+            total_bytes,
+        });
+
+        // Homeservers take a file in one PUT, so this only moves at the end
+        report(0);
+        let response = self.transport.put(&attachment.url, ciphertext).await?;
+        if !response.status().is_success() {
+            return Err(anyhow!(MessengerError::HomeserverRejected(format!("Failed to upload attachment: {}", response.status()))));
+        }
+        report(total_bytes);
+        Ok(attachment)
+    }
+
+    // Encrypt and upload a file too big to hold in memory, a chunk at a
+    // time. The ciphertext is staged in a temp file, which is always removed.
+    pub async fn upload_attachment_file(&self, path: &Path) -> Result<Attachment> {
+        let sealed = std::env::temp_dir().join(format!("pubky-upload-{}", Uuid::new_v4()));
+        let uploaded = self.upload_sealed_file(path, &sealed).await;
+        if let Err(e) = tokio::fs::remove_file(&sealed).await {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                tracing::warn!("⚠️  Failed to remove staged upload: {}", e);
+            }
+        }
+        uploaded
+    }
+
+    async fn upload_sealed_file(&self, path: &Path, sealed: &Path) -> Result<Attachment> {
+        if let Some(limits) = &self.attachment_limits {
+            let name = attachments::safe_file_name(&path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default());
+            limits.check_send(&name, attachments::mime_type(&name), tokio::fs::metadata(path).await?.len())?;
+        }
+        let attachment = attachments::seal_file(&self.keypair.public_key(), path, sealed).await?;
+        let total_bytes = tokio::fs::metadata(sealed).await?.len();
+        tracing::debug!("📎 Uploading {} byte chunked attachment to {}", total_bytes, logging::path(&attachment.url));
+
+        let report = |sent_bytes| self.progress.report(ATTACHMENT_UPLOAD_PROGRESS_EVENT, &AttachmentUploadProgress {
+            name: attachment.name.clone(),
+            sent_bytes,
+            total_bytes,
+        });
+
+        report(0);
+        let response = self.transport.put_file(&attachment.url, sealed).await?;
+        if !response.status().is_success() {
+            return Err(anyhow!(MessengerError::HomeserverRejected(format!("Failed to upload attachment: {}", response.status()))));
+        }
+        report(total_bytes);
+        Ok(attachment)
+    }
+
+    // An attachment's ciphertext from byte `offset` on. Homeservers that
+    // ignore Range answer 200 with the whole file.
+    pub async fn fetch_attachment(&self, attachment: &Attachment, offset: u64) -> Result<reqwest::Response> {
+        attachments::check_url(attachment)?;
+        let mut headers = reqwest::header::HeaderMap::new();
+        if offset > 0 {
+            headers.insert(reqwest::header::RANGE, format!("bytes={}-", offset).parse()?);
+        }
+        self.transport.get_with_headers(&attachment.url, headers).await
+    }
+
+    // Download and decrypt an attachment from whoever sent it
+    pub async fn download_attachment(&self, attachment: &Attachment) -> Result<Vec<u8>> {
+        attachments::check_url(attachment)?;
+        let response = self.transport.get(&attachment.url).await?;
+        if !response.status().is_success() {
+            return Err(anyhow!(MessengerError::HomeserverRejected(format!("Failed to download attachment: {}", response.status()))));
+        }
+        attachments::open(attachment, &response.bytes().await?)
+    }
+
+    // Remove one of our uploaded attachments from our homeserver
+    pub async fn delete_attachment(&self, url: &str) -> Result<()> {
+        if !url.starts_with(&format!("pubky://{}/", self.keypair.public_key())) {
+            return Err(anyhow!(MessengerError::InvalidInput("Only our own attachments can be deleted".to_string())));
+        }
+        let response = self.transport.delete(url).await?;
+        // Already gone is fine
+        if !response.status().is_success() && response.status() != reqwest::StatusCode::NOT_FOUND {
+            return Err(anyhow!(MessengerError::HomeserverRejected(format!("Failed to delete attachment: {}", response.status()))));
+        }
+        Ok(())
+    }
+
+    // Add this debugging version to your PrivateMessageHandler in messaging.rs
+    pub async fn send_message_with_extras(&self, recipient: &PublicKey, content: &str, extras: Option<&MessageExtras>) -> Result<()> {
+        tracing::info!("📤 Sending message to {}: {}",
+                 logging::pubkey(recipient),
+                 logging::text(content));
+
+        let message = PrivateMessage::new(
+            &self.keypair,
+            recipient,
+            content,
+            extras,
+            self.version_for(recipient),
+            self.signs_extended_digest(recipient),
+        )?;
+        let serialized = wire::encode_message(&message)?;
+
+        let private_path = self.private_conversation_path(recipient)?;
+        let path = format!("pubky://{}{}{}{}",
+                           self.keypair.public_key(),
+                           private_path,
+                           message.msg_id,
+                           wire::BLOB_EXTENSION);
+
+        tracing::debug!("💾 Storing message at {}", logging::path(&path));
+        tracing::debug!("📦 Message data length: {} bytes", serialized.len());
+
+        let response = self.transport.put(&path, serialized).await?;
+
+        if !response.status().is_success() {
+            tracing::warn!("❌ Storage failed with status: {}", response.status());
+            return Err(anyhow!(MessengerError::HomeserverRejected(format!("Failed to store message: {}", response.status()))));
+        }
+
+        tracing::debug!("✅ Message stored successfully!");
+
+        if !self.is_self(recipient) {
+            let recipient_key = recipient.to_string();
+            let kind = if extras.is_some_and(|extras| extras.mentions.contains(&recipient_key)) {
+                NotificationKind::Mention
+            } else {
+                NotificationKind::Message
+            };
+            self.notify(recipient, &message.msg_id, kind).await;
+        }
+
+        Ok(())
+    }
+
+    // Newest protocol `recipient` is known to understand, so they can still
+    // read what we write for them
+    fn version_for(&self, recipient: &PublicKey) -> u32 {
+        if self.is_self(recipient) {
+            return PROTOCOL_VERSION;
+        }
+        protocol::negotiated_version(self.peer_versions.as_ref().and_then(|peers| peers.get(&recipient.to_string())))
+    }
+
+    // Only peers that have written at EXTENDED_DIGEST_VERSION or later are
+    // known to verify it; negotiated_version can't tell, as it assumes
+    // MIN_WRITE_VERSION for peers we haven't heard from
+    fn signs_extended_digest(&self, recipient: &PublicKey) -> bool {
+        self.is_self(recipient)
+            || self.peer_versions.as_ref()
+                .and_then(|peers| peers.get(&recipient.to_string()))
+                .is_some_and(|version| version >= EXTENDED_DIGEST_VERSION)
+    }
+
+    // Announce a sent message to its recipient, now or as part of a batch,
+    // and wake them if they use push. The message is already delivered
+    // through the conversation listing; these only help the recipient find
+    // it, so failures are just logged.
+    async fn notify(&self, recipient: &PublicKey, msg_id: &str, kind: NotificationKind) {
+        let Some(batcher) = &self.notification_batcher else {
+            if let Err(e) = self.create_notification(recipient, &[msg_id.to_string()], kind).await {
+                tracing::debug!("📭 Failed to notify {}: {}", logging::pubkey(recipient), e);
+            }
+            self.wake(recipient).await;
+            return;
+        };
+        if !batcher.add(&recipient.to_string(), msg_id, kind) {
+            return;
+        }
+
+        // First message of a batch: write it once the window closes
+        let handler = self.clone();
+        let batcher = batcher.clone();
+        let recipient = recipient.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(notification_batch::BATCH_WINDOW).await;
+            let batch = batcher.take(&recipient.to_string());
+            if let Err(e) = handler.create_notification(&recipient, &batch.msg_ids, batch.kind).await {
+                tracing::debug!("📭 Failed to notify {}: {}", logging::pubkey(&recipient), e);
+            }
+            handler.wake(&recipient).await;
+        });
+    }
+
+    // Up to `limit` notifications waiting in our inbox, oldest first. They
+    // stay there until acknowledged, so one whose message never got cached
+    // is seen again on the next pass.
+    pub async fn pending_notifications(&self, limit: usize) -> Result<Vec<PendingNotification>> {
+        let notifications_path = format!("pubky://{}/pub/notifications/", self.keypair.public_key());
+
+        let notification_urls = self.transport.list(&notifications_path).await?;
+        if notification_urls.len() > limit {
+            tracing::warn!("📬 {} notifications waiting, reading {} this pass", notification_urls.len(), limit);
+        }
+        let mut pending = Vec::new();
+
+        for url in notification_urls.into_iter().take(limit) {
+            let body = match self.http_cache.get_bytes_limited(self.transport.as_ref(), &url, limits::MAX_NOTIFICATION_BYTES).await {
+                Err(e) if e.is::<BodyTooLarge>() => {
+                    limits::quarantine(&url, &e);
+                    self.acknowledge_notification(&url).await?;
+                    continue;
+                }
+                result => result?,
+            };
+            let Some(body) = body else {
+                continue;
+            };
+            let decoded = std::str::from_utf8(&body)
+                .map_err(|e| anyhow!("Notification is not text: {}", e))
+                .and_then(decode_notification);
+            match decoded {
+                Ok(Some(notification)) => match PublicKey::try_from(notification.sender.as_str()) {
+                    Ok(sender) if !notification.verify(&sender, &self.keypair.public_key()) => {
+                        // We can't check kinds from newer clients; leave those for one that can
+                        if notification.kind != NotificationKind::Unknown {
+                            tracing::warn!("🗑️  Deleting unauthenticated notification claiming to be from {}", logging::pubkey(&sender));
+                            self.acknowledge_notification(&url).await?;
+                        }
+                    }
+                    Ok(sender) => pending.push(PendingNotification {
+                        url,
+                        sender: sender.to_string(),
+                        msg_ids: if notification.msg_ids.is_empty() { vec![notification.msg_id] } else { notification.msg_ids },
+                        timestamp: notification.timestamp,
+                        kind: notification.kind,
+                    }),
+                    Err(_) => {
+                        tracing::debug!("🗑️  Deleting notification with an invalid sender");
+                        self.acknowledge_notification(&url).await?;
+                    }
+                },
+                // Written by a newer client; leave it for one that understands it
+                Ok(None) => {}
+                // Legacy or unknown format - delete it
+                Err(e) => {
+                    tracing::debug!("🗑️  Deleting unreadable notification: {}", e);
+                    self.acknowledge_notification(&url).await?;
+                }
+            }
+        }
+
+        pending.sort_by_key(|notification| notification.timestamp);
+        Ok(pending)
+    }
+
+    // Delete a notification we're done with
+    pub async fn acknowledge_notification(&self, url: &str) -> Result<()> {
+        self.transport.delete(url).await?;
+        self.http_cache.invalidate(url);
+        Ok(())
+    }
+
+    // Delete notifications from blocked senders without processing them
+    pub async fn delete_notifications_from(&self, blocked: &HashSet<String>) -> Result<usize> {
+        let notifications_path = format!("pubky://{}/pub/notifications/", self.keypair.public_key());
+        let notification_urls = self.transport.list(&notifications_path).await?;
+
+        let mut deleted = 0;
+        for url in notification_urls {
+            let Some(response_text) = self.http_cache.get_text(self.transport.as_ref(), &url).await? else {
+                continue;
+            };
+            let blocked_sender = decode_notification(&response_text)
+                .ok()
+                .flatten()
+                .map(|notification| blocked.contains(&notification.sender))
+                .unwrap_or(false);
+            if blocked_sender {
+                self.transport.delete(&url).await?;
+                self.http_cache.invalidate(&url);
+                deleted += 1;
+            }
+        }
+
+        Ok(deleted)
+    }
+
+    pub(crate) async fn get_messages(&self, other_pubkey: &PublicKey) -> Result<Vec<(PrivateMessage, String, bool)>> {
+        Ok(self.get_messages_excluding(other_pubkey, &HashSet::new()).await?.0)
+    }
+
+    // Record a blob that failed. Logged once, then retried quietly on its
+    // backoff. Returns 1 for a new failure, to count towards a spike.
+    fn quarantine_blob(&self, url: &str, other_pubkey: &PublicKey, reason: &anyhow::Error, now: u64) -> usize {
+        let Some(quarantine) = &self.quarantine else {
+            limits::quarantine(url, reason);
+            return 1;
+        };
+        match quarantine.record_failure(url, &other_pubkey.to_string(), &reason.to_string(), now) {
+            Ok(true) => {
+                limits::quarantine(url, reason);
+                1
+            }
+            Ok(false) => {
+                tracing::debug!("🚫 Still failing {}: {}", logging::path(url), reason);
+                0
+            }
+            Err(e) => {
+                tracing::warn!("⚠️  Failed to quarantine {}: {}", logging::path(url), e);
+                1
+            }
+        }
+    }
+
+    // Fetch and decrypt only the messages whose ids aren't already known
+    // locally. Also returns whether both paths listed and every new blob
+    // could be fetched; if not, some messages only turn up on a later pass.
+    pub(crate) async fn get_messages_excluding(&self, other_pubkey: &PublicKey, known_ids: &HashSet<String>) -> Result<(Vec<(PrivateMessage, String, bool)>, bool)> {
+        let mut all_messages = Vec::new();
+        let private_path = self.private_conversation_path(other_pubkey)?;
+
+        let self_path = format!("pubky://{}{}", self.keypair.public_key(), private_path);
+        let other_path = format!("pubky://{}{}", other_pubkey, private_path);
+
+        tracing::debug!("🔍 Searching for messages in {} and {}", logging::path(&self_path), logging::path(&other_path));
+
+        let mut urls = Vec::new();
+        let mut complete = true;
+
+        // Collect URLs from both paths
+        match self.transport.list(&self_path).await {
+            Ok(self_urls) => urls.extend(self_urls),
+            Err(e) => {
+                tracing::warn!("⚠️  Failed to list {}: {}", logging::path(&self_path), e);
+                complete = false;
+            }
+        }
+
+        // Notes-to-self only ever live on our own homeserver
+        if !self.is_self(other_pubkey) {
+            match self.transport.list(&other_path).await {
+                Ok(other_urls) => urls.extend(other_urls),
+                Err(e) => {
+                    tracing::warn!("⚠️  Failed to list {}: {}", logging::path(&other_path), e);
+                    complete = false;
+                }
+            }
+        }
+
+        // Process each message we haven't cached yet, once even if it shows
+        // up under both paths, leaving out quarantined ones not due a retry
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let quarantined = self.quarantine.as_ref().map(Quarantine::entries).unwrap_or_default();
+        let pending: Vec<&String> = urls.iter()
+            .filter(|url| !known_ids.contains(&msg_id_from_url(url)))
+            .filter(|url| quarantined.get(*url).map_or(true, |entry| entry.retry_at <= now))
+            .collect();
+        let mut seen_ids: HashSet<String> = HashSet::new();
+        let mut failures = 0;
+        let mut released = Vec::new();
+        for (index, url) in pending.iter().enumerate() {
+            self.progress.report(SYNC_PROGRESS_EVENT, &SyncProgress {
+                conversation: other_pubkey.to_string(),
+                fetched: index,
+                total: pending.len(),
+            });
+            let blob = match self.http_cache.get_bytes_limited(self.transport.as_ref(), url, limits::MAX_MESSAGE_BLOB_BYTES).await {
+                Err(e) if e.is::<BodyTooLarge>() => {
+                    failures += self.quarantine_blob(url, other_pubkey, &e, now);
+                    None
+                }
+                // One unreachable blob shouldn't hold up the rest; it's tried again next pass
+                Err(e) => {
+                    tracing::warn!("⚠️  Failed to fetch {}: {}", logging::path(url), e);
+                    complete = false;
+                    None
+                }
+                Ok(blob) => blob,
+            };
+            if let Some(blob) = blob {
+                let decoded = wire::decode_message(&blob);
+                if let Err(e) = &decoded {
+                    failures += self.quarantine_blob(url, other_pubkey, &anyhow!("Malformed message blob: {}", e), now);
+                }
+                if let Ok(mut message) = decoded {
+                    if let Err(e) = message.check_limits(now) {
+                        failures += self.quarantine_blob(url, other_pubkey, &e, now);
+                        continue;
+                    }
+                    if message.msg_id.is_empty() {
+                        message.msg_id = msg_id_from_url(url);
+                        message.legacy_msg_id = true;
+                    }
+                    if known_ids.contains(&message.msg_id) || !seen_ids.insert(message.msg_id.clone()) {
+                        continue;
+                    }
+
+                    // Decrypt content
+                    if let Ok(content) = message.decrypt_content(&self.keypair, other_pubkey) {
+                        // Decrypt sender
+                        if let Ok(sender) = message.decrypt_sender(&self.keypair, other_pubkey) {
+                            // Verify signature using decrypted content, sender and extras
+                            let verified = match message.decrypt_extras_bytes(&self.keypair, other_pubkey) {
+                                Ok(extras) => message.verify_signature(&content, &sender, extras.as_deref()).unwrap_or(false),
+                                Err(_) => false,
+                            };
+
+                            tracing::debug!("     ✅ Decrypted message from {}: {} (verified: {})",
+                                     logging::pubkey(&sender),
+                                     logging::text(&content),
+                                     verified);
+
+                            all_messages.push((message, content, verified));
+                            if quarantined.contains_key(*url) {
+                                released.push(url.to_string());
+                            }
+                        } else {
+                            failures += self.quarantine_blob(url, other_pubkey, &anyhow!("Failed to decrypt sender"), now);
+                        }
+                    } else {
+                        failures += self.quarantine_blob(url, other_pubkey, &anyhow!("Failed to decrypt content"), now);
+                    }
+                }
+            }
+        }
+
+        if !pending.is_empty() {
+            self.progress.report(SYNC_PROGRESS_EVENT, &SyncProgress {
+                conversation: other_pubkey.to_string(),
+                fetched: pending.len(),
+                total: pending.len(),
+            });
+        }
+
+        if let Some(quarantine) = &self.quarantine {
+            if let Err(e) = quarantine.release(&released) {
+                tracing::warn!("⚠️  Failed to update quarantine: {}", e);
+            }
+        }
+        if failures >= quarantine::SPIKE_THRESHOLD {
+            tracing::warn!("🚨 {} new decryption failures in conversation with {}", failures, logging::pubkey(other_pubkey));
+            self.progress.report(quarantine::DECRYPTION_FAILURES_EVENT, &DecryptionFailuresEvent {
+                conversation: other_pubkey.to_string(),
+                failures,
+                detected_at: now,
+            });
+            if let Some(security_log) = &self.security_log {
+                let detail = format!("{} messages failed to decrypt", failures);
+                security_log.record(SecurityEventKind::DecryptionFailures, Some(&other_pubkey.to_string()), Some(&detail));
+            }
+        }
+
+        // Sort by timestamp
+        all_messages.sort_by(|a, b| a.0.timestamp.cmp(&b.0.timestamp));
+        tracing::debug!("🎯 Returning {} messages total", all_messages.len());
+        Ok((all_messages, complete))
+    }
+
+    // Every message blob we've written, across all conversations except
+    // Saved messages
+    pub async fn own_message_blobs(&self) -> Result<Vec<String>> {
+        let saved_messages = format!("pubky://{}{}", self.keypair.public_key(), self.self_conversation_path());
+
+        Ok(self.own_private_blobs().await?
+            .into_iter()
+            .filter(|url| wire::is_message_blob(url) && !url.starts_with(&saved_messages))
+            .collect())
+    }
+
+    // Everything we've written under /pub/private_messages/: messages,
+    // notifications, attachments and records, paging through the listing
+    pub async fn own_private_blobs(&self) -> Result<Vec<String>> {
+        const PAGE_SIZE: u16 = 500;
+
+        let root = format!("pubky://{}/pub/private_messages/", self.keypair.public_key());
+
+        let mut urls = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let page = self.transport.list_page(&root, Some(PAGE_SIZE), cursor.as_deref()).await?;
+            let page_len = page.len();
+            cursor = page.last().cloned();
+            urls.extend(page);
+
+            if page_len < PAGE_SIZE as usize {
+                break;
+            }
+        }
+        Ok(urls)
+    }
+
+    // Timestamp of a stored message blob without decrypting it
+    pub async fn message_blob_timestamp(&self, url: &str) -> Result<Option<u64>> {
+        let Some(blob) = self.http_cache.get_bytes(self.transport.as_ref(), url).await? else {
+            return Ok(None);
+        };
+        Ok(wire::decode_message(&blob).ok().map(|message| message.timestamp))
+    }
+
+    pub async fn delete_blob(&self, url: &str) -> Result<()> {
+        let response = self.transport.delete(url).await?;
+        self.http_cache.invalidate(url);
+        if !response.status().is_success() {
+            return Err(anyhow!(MessengerError::HomeserverRejected(format!("Failed to delete {}: {}", url, response.status()))));
+        }
+        Ok(())
+    }
+
+    // Add this method to PrivateMessageHandler
+    pub async fn get_all_new_messages_from_contacts(&self, contacts: &[PublicKey]) -> Result<Vec<(String, String, bool)>> {
+        let mut all_messages = Vec::new();
+        let mut seen_ids: HashSet<String> = HashSet::new();
+
+        for contact in contacts {
+            let conversation_messages = self.get_messages(contact).await?;
+            for (msg, content, verified) in conversation_messages {
+                if !seen_ids.insert(msg.msg_id.clone()) {
+                    continue;
+                }
+                // Decrypt the sender field using the contact as the other participant
+                match msg.decrypt_sender(&self.keypair, contact) {
+                    Ok(sender) => {
+                        all_messages.push((sender, content, verified));
+                    }
+                    Err(e) => {
+                        tracing::warn!("❌ Failed to decrypt sender for message: {}", e);
+                        // Skip messages we can't decrypt
+                        continue;
+                    }
+                }
+            }
+        }
+
+        // Sort by timestamp to get most recent messages first
+        // Note: We might want to include timestamp in the return type for proper sorting
+        all_messages.sort_by(|a, b| {
+            // For now, sorting by sender string (not ideal, but maintains current API)
+            a.0.cmp(&b.0)
+        });
+
+        Ok(all_messages)
+    }
+
+    // Fetch messages missing from the local cache, in the shape the frontend consumes
+    pub async fn get_new_chat_messages(&self, other_pk: &PublicKey, known_ids: &HashSet<String>) -> Result<Vec<StoredMessage>> {
+        Ok(self.fetch_new_chat_messages(other_pk, known_ids).await?.0)
+    }
+
+    // get_new_chat_messages, plus whether everything on the homeservers was
+    // seen; see get_messages_excluding
+    pub async fn fetch_new_chat_messages(&self, other_pk: &PublicKey, known_ids: &HashSet<String>) -> Result<(Vec<StoredMessage>, bool)> {
+        let current_user = self.keypair.public_key().to_string();
+        let (raw_messages, complete) = self.get_messages_excluding(other_pk, known_ids).await?;
+
+        let mut chat_messages = Vec::new();
+        for (msg, content, verified) in raw_messages {
+            let Ok(sender) = msg.decrypt_sender(&self.keypair, other_pk) else {
+                continue;
+            };
+            let mut extras = msg.decrypt_extras(&self.keypair, other_pk).unwrap_or_default();
+            // Our own attachments were checked when we sent them
+            if let (Some(attachment), Some(limits)) = (&extras.attachment, &self.attachment_limits) {
+                if sender != current_user {
+                    if let Err(e) = limits.check_receive(attachment) {
+                        tracing::debug!("📎 Dropping attachment from {}: {}", msg.msg_id, e);
+                        extras.attachment = None;
+                    }
+                }
+            }
+
+            // Mentions of the current user get routed as high priority
+            let priority = if sender != current_user && extras.mentions.contains(&current_user) {
+                NotificationPriority::High
+            } else {
+                NotificationPriority::Normal
+            };
+
+            chat_messages.push(StoredMessage {
+                cipher_format: msg.cipher_format(),
+                message: ChatMessage {
+                    id: msg.msg_id.clone(),
+                    is_own_message: sender == current_user,
+                    sender,  // Now using decrypted sender
+                    content,
+                    timestamp: msg.timestamp,
+                    verified,
+                    sender_name: None,
+                    contact_card: extras.contact_card,
+                    link_preview: extras.link_preview,
+                    mentions: extras.mentions,
+                    attachment: extras.attachment,
+                    priority,
+                    protocol_version: msg.protocol_version,
+                    supported_protocol: extras.supported_protocol.filter(|_| verified),
+                    received_at: None,
+                    timestamp_suspect: false,
+                },
+            });
+        }
+
+        Ok((chat_messages, complete))
+    }
+
+    // Evaluate crypto health from (format, protocol version, timestamp,
+    // verified) of every message and whether the user verified the contact
+    pub fn conversation_health<I>(messages: I, verification: Option<VerificationState>) -> ConversationHealth
+    where
+        I: IntoIterator<Item = (CipherFormat, u32, u64, bool)>,
+    {
+        let messages: Vec<(CipherFormat, u32, u64, bool)> = messages.into_iter().collect();
+        let inputs = HealthInputs {
+            legacy_messages: messages.iter().filter(|(format, version, _, _)| health::is_legacy_message(*format, *version)).count(),
+            unverified_messages: messages.iter().filter(|(_, _, _, verified)| !verified).count(),
+            verification,
+            oldest_message_time: messages.iter().map(|(_, _, timestamp, _)| *timestamp).min(),
+            // Conversations use static ECDH keys until a ratchet exists
+            forward_secrecy: false,
+        };
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        health::evaluate(&inputs, now)
+    }
+
+    pub async fn get_homeserver(&self, pubky: String) -> Result<String> {
+        let public_key = PublicKey::try_from(pubky.clone())
+            .map_err(|e| MessengerError::InvalidPublicKey(format!("Invalid public key: {}", e)))?;
+        self.transport.homeserver(&public_key).await?
+            .ok_or_else(|| anyhow!(MessengerError::HomeserverNotFound(format!("No homeserver found for public key: {}", pubky))))
+    }
+
+    // Whether the homeserver hosting `public_key` answers at all; any
+    // response short of a server error counts, even a 404
+    pub async fn probe_homeserver(&self, public_key: &PublicKey) -> Result<()> {
+        let url = format!("pubky://{}/pub/", public_key);
+        let response = self.transport.get(&url).await?;
+        if response.status().is_server_error() {
+            return Err(anyhow!(MessengerError::HomeserverRejected(format!("Homeserver returned {}", response.status()))));
+        }
+        Ok(())
+    }
+
+    // Whether our homeserver still has a session for us
+    pub async fn session_valid(&self) -> Result<bool> {
+        let public_key = self.keypair.public_key();
+        self.transport.has_session(&public_key).await
+    }
+
+    pub async fn sign_in(&self) -> Result<()> {
+        self.transport.signin(&self.keypair).await
+    }
+
+    // Get current user's own profile
+    fn own_profile_url(&self) -> String {
+        format!("pubky://{}/pub/pubky.app/profile.json", self.keypair.public_key())
+    }
+
+    // Replace our pubky.app profile; pass one through PubkyProfile::normalized first
+    pub async fn publish_profile(&self, profile: &PubkyProfile) -> Result<()> {
+        let profile_url = self.own_profile_url();
+        let response = self.transport.put(&profile_url, serde_json::to_vec(profile)?).await?;
+        if !response.status().is_success() {
+            return Err(anyhow!(MessengerError::HomeserverRejected(format!("Failed to publish profile: {}", response.status()))));
+        }
+
+        self.http_cache.invalidate(&profile_url);
+        tracing::info!("🪪 Published profile {}", logging::text(&profile.name));
+        Ok(())
+    }
+
+    // Upload a new profile picture and point our profile at it. Needs an
+    // existing profile, since pubky.app profiles can't go without a name.
+    pub async fn set_profile_image(&self, image_bytes: &[u8]) -> Result<PubkyProfile> {
+        let profile = self.get_own_pubky_profile().await?
+            .ok_or_else(|| MessengerError::InvalidInput("Set a profile name before adding a picture".to_string()))?;
+        let jpeg = avatar::prepare(image_bytes)?;
+
+        let owner = self.keypair.public_key();
+        let created_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_micros() as i64)
+            .unwrap_or(0);
+        let blob_url = format!("pubky://{}/pub/pubky.app/blobs/{}", owner, &blake3::hash(&jpeg).to_hex()[..32]);
+        let file_url = format!("pubky://{}/pub/pubky.app/files/{}", owner, created_at);
+        let file = PubkyAppFile {
+            name: "avatar.jpg".to_string(),
+            created_at,
+            src: blob_url.clone(),
+            content_type: avatar::AVATAR_CONTENT_TYPE.to_string(),
+            size: jpeg.len(),
+        };
+
+        for (url, body, what) in [
+            (&blob_url, jpeg, "avatar"),
+            (&file_url, serde_json::to_vec(&file)?, "avatar file record"),
+        ] {
+            let response = self.transport.put(url, body).await?;
+            if !response.status().is_success() {
+                return Err(anyhow!(MessengerError::HomeserverRejected(format!("Failed to upload {}: {}", what, response.status()))));
+            }
+        }
+
+        let profile = PubkyProfile { image: Some(file_url), ..profile };
+        self.publish_profile(&profile).await?;
+        Ok(profile)
+    }
+
+    // Our whole pubky.app profile; None if we never published one or it can't be parsed
+    pub async fn get_own_pubky_profile(&self) -> Result<Option<PubkyProfile>> {
+        let profile_url = self.own_profile_url();
+
+        tracing::debug!("🔍 Fetching own profile from {}", logging::path(&profile_url));
+
+        if let Some(profile_data) = self.http_cache.get_text(self.transport.as_ref(), &profile_url).await? {
+            // Try to parse the profile
+            match serde_json::from_str::<PubkyProfile>(&profile_data) {
+                Ok(profile) => {
+                    tracing::debug!("✅ Found own profile name: {}", logging::text(&profile.name));
+                    Ok(Some(profile))
+                }
+                Err(e) => {
+                    tracing::warn!("⚠️  Failed to parse own profile: {}", e);
+                    Ok(None)
+                }
+            }
+        } else {
+            tracing::debug!("📭 No profile found for current user");
+            Ok(None)
+        }
+    }
+
+    // Publish a new status; an empty one clears it
+    pub async fn set_status(&self, status: &str) -> Result<PubkyProfile> {
+        let profile = self.get_own_pubky_profile().await?
+            .ok_or_else(|| MessengerError::InvalidInput("Set a profile name before adding a status".to_string()))?;
+        // Only the status is new; the rest is republished as it was
+        let profile = PubkyProfile { status: normalized_status(Some(status))?, ..profile };
+        self.publish_profile(&profile).await?;
+        Ok(profile)
+    }
+
+    pub async fn get_own_profile(&self) -> Result<Option<String>> {
+        Ok(self.get_own_pubky_profile().await?.map(|profile| profile.name))
+    }
+
+    pub fn decrypt_recovery_file(&self, recovery_file: &str, passphrase: &str) -> Result<Keypair> {
+        if recovery_file.is_empty() || passphrase.is_empty() {
+            return Err(anyhow!(MessengerError::InvalidInput("Recovery file and passphrase must not be empty".to_string())));
+        }
+
+        let recovery_file_bytes = base64::decode(recovery_file)
+            .map_err(|e| MessengerError::InvalidRecoveryFile(format!("Failed to decode recovery file: {}", e)))?;
+
+        crypto_compat::decrypt_recovery_file(&recovery_file_bytes, passphrase)
+    }
+
+    // Extract pubky from follow URL
+    fn extract_pubky_from_follow_url(url: &str) -> Option<String> {
+        // URL format: pubky://[your_pubky]/pub/pubky.app/follows/[followed_pubky]
+        url.split('/').last().map(|s| s.to_string())
+    }
+
+    // Get list of followed users
+    pub async fn get_followed_users(&self) -> Result<Vec<String>> {
+        let follows_url = format!("pubky://{}/pub/pubky.app/follows/", self.keypair.public_key());
+
+        tracing::debug!("🔍 Fetching follows from {}", logging::path(&follows_url));
+
+        if let Some(follows_response) = self.http_cache.get_text(self.transport.as_ref(), &follows_url).await? {
+            // Split the response by newlines to get individual URLs
+            let follow_urls: Vec<String> = follows_response.lines()
+                .filter(|line| !line.is_empty())
+                .map(|url| url.to_string())
+                .collect();
+
+            tracing::info!("✅ Found {} followed users", follow_urls.len());
+            Ok(follow_urls)
+        } else {
+            tracing::warn!("❌ Failed to fetch follows");
+            Ok(Vec::new()) // Return empty list instead of error
+        }
+    }
+
+    // Write a pubky.app follow, the same record pubky.app itself creates
+    pub async fn follow_user(&self, pubky: &PublicKey) -> Result<()> {
+        let created_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_micros() as i64)
+            .unwrap_or(0);
+        let follow = PubkyAppFollow { created_at };
+
+        let follow_url = self.follow_url(pubky);
+        let response = self.transport.put(&follow_url, serde_json::to_vec(&follow)?).await?;
+        if !response.status().is_success() {
+            return Err(anyhow!(MessengerError::HomeserverRejected(format!("Failed to follow user: {}", response.status()))));
+        }
+
+        self.invalidate_follows();
+        Ok(())
+    }
+
+    pub async fn unfollow_user(&self, pubky: &PublicKey) -> Result<()> {
+        let follow_url = self.follow_url(pubky);
+        let response = self.transport.delete(&follow_url).await?;
+        // Not following them in the first place is fine
+        if !response.status().is_success() && response.status() != reqwest::StatusCode::NOT_FOUND {
+            return Err(anyhow!(MessengerError::HomeserverRejected(format!("Failed to unfollow user: {}", response.status()))));
+        }
+
+        self.invalidate_follows();
+        Ok(())
+    }
+
+    fn follow_url(&self, pubky: &PublicKey) -> String {
+        format!("pubky://{}/pub/pubky.app/follows/{}", self.keypair.public_key(), pubky)
+    }
+
+    // The next follows fetch must see our own change
+    fn invalidate_follows(&self) {
+        self.http_cache.invalidate(&format!("pubky://{}/pub/pubky.app/follows/", self.keypair.public_key()));
+    }
+
+    // Get the profile name for any pubky, if they published one
+    pub async fn get_profile_name(&self, pubky: &str) -> Result<Option<String>> {
+        Ok(self.get_profile(pubky).await?.map(|profile| profile.name))
+    }
+
+    // A user's profile, from the profile cache while it is fresh
+    pub async fn get_profile(&self, pubky: &str) -> Result<Option<PubkyProfile>> {
+        let now = profiles::now_secs();
+        let cached = self.profile_cache.as_ref().and_then(|cache| cache.get(pubky));
+        if let Some(entry) = cached.as_ref().filter(|entry| entry.is_fresh(now)) {
+            return Ok(entry.profile.clone());
+        }
+
+        match self.fetch_profile(pubky).await {
+            Ok(profile) => {
+                self.cache_profiles(now, &[(pubky.to_string(), profile.clone())]);
+                Ok(profile)
+            }
+            // A stale copy still beats nothing
+            Err(e) => cached.map(|entry| entry.profile).ok_or(e),
+        }
+    }
+
+    // Fetch a user's profile from their homeserver, skipping the profile
+    // cache. None if they have no profile or it can't be parsed.
+    async fn fetch_profile(&self, pubky: &str) -> Result<Option<PubkyProfile>> {
+        let profile_url = format!("pubky://{}/pub/pubky.app/profile.json", pubky);
+
+        let Some(profile_data) = self.http_cache.get_text(self.transport.as_ref(), &profile_url).await? else {
+            return Ok(None);
+        };
+        match serde_json::from_str::<PubkyProfile>(&profile_data) {
+            Ok(profile) => Ok(Some(profile)),
+            Err(e) => {
+                tracing::warn!("⚠️  Failed to parse profile for {}: {}", logging::pubkey(pubky), e);
+                Ok(None)
+            }
+        }
+    }
+
+    // The whole profile of any pubky, from the profile cache while it is
+    // fresh. `verify_links` checks which of their links point back at them.
+    pub async fn get_contact_profile(&self, pubky: &str, verify_links: bool) -> Result<ContactProfile> {
+        let pubky = PublicKey::try_from(pubky)
+            .map_err(|e| MessengerError::InvalidPublicKey(format!("Invalid public key: {}", e)))?
+            .to_string();
+        let profile = self.get_profile(&pubky).await?;
+        let mut contact = ContactProfile::from_profile(pubky, profile);
+        if verify_links {
+            link_verification::verify_links(&contact.pubky, &mut contact.links).await;
+        }
+        Ok(contact)
+    }
+
+    // Resolve and probe a pubky's homeserver and fetch their profile, so
+    // the user can vet someone before writing to them. Lookup failures
+    // show up as an unreachable preview rather than an error.
+    pub async fn lookup_user(&self, pubky: &str) -> Result<UserPreview> {
+        let public_key = PublicKey::try_from(pubky)
+            .map_err(|e| MessengerError::InvalidPublicKey(format!("Invalid public key: {}", e)))?;
+        let pubky = public_key.to_string();
+
+        let homeserver = match self.transport.homeserver(&public_key).await {
+            Ok(homeserver) => homeserver,
+            Err(e) => {
+                tracing::debug!("⚠️  Failed to resolve homeserver of {}: {}", logging::pubkey(&pubky), e);
+                None
+            }
+        };
+        let reachable = homeserver.is_some() && self.probe_homeserver(&public_key).await.is_ok();
+
+        let (profile, follows_us) = if reachable {
+            let profile = self.fetch_profile(&pubky).await.unwrap_or(None);
+            self.cache_profiles(profiles::now_secs(), &[(pubky.clone(), profile.clone())]);
+            (profile, self.follows_us(&pubky).await.unwrap_or(false))
+        } else {
+            (None, false)
+        };
+
+        let contact = ContactProfile::from_profile(pubky, profile);
+        Ok(UserPreview {
+            pubky: contact.pubky,
+            homeserver,
+            reachable,
+            name: contact.name,
+            bio: contact.bio,
+            image: contact.image,
+            status: contact.status,
+            follows_us,
+        })
+    }
+
+    // Refetch one contact's profile now instead of waiting for it to go stale
+    pub async fn refresh_contact_profile(&self, pubky: &str) -> Result<Option<PubkyProfile>> {
+        let pubky = PublicKey::try_from(pubky)
+            .map_err(|e| MessengerError::InvalidPublicKey(format!("Invalid public key: {}", e)))?
+            .to_string();
+
+        let profile = self.fetch_profile(&pubky).await?;
+        self.cache_profiles(profiles::now_secs(), &[(pubky, profile.clone())]);
+        Ok(profile)
+    }
+
+    // Refetch the profiles of `pubkys` whose cached copies went stale,
+    // reporting any that changed. Returns how many were fetched.
+    pub async fn refresh_stale_profiles(&self, pubkys: &[String]) -> usize {
+        let Some(cache) = &self.profile_cache else {
+            return 0;
+        };
+        let now = profiles::now_secs();
+        let cached = cache.all();
+        let stale: Vec<&String> = pubkys
+            .iter()
+            .filter(|pubky| !cached.get(*pubky).is_some_and(|entry| entry.is_fresh(now)))
+            .collect();
+
+        let results: Vec<(&String, Result<Option<PubkyProfile>>)> = stream::iter(stale)
+            .map(|pubky| async move { (pubky, self.fetch_profile(pubky).await) })
+            .buffer_unordered(profiles::REFRESH_CONCURRENCY)
+            .collect()
+            .await;
+        let fetched: Vec<(String, Option<PubkyProfile>)> = results
+            .into_iter()
+            .filter_map(|(pubky, result)| match result {
+                Ok(profile) => Some((pubky.clone(), profile)),
+                Err(e) => {
+                    tracing::debug!("⚠️  Failed to refresh profile for {}: {}", logging::pubkey(pubky), e);
+                    None
+                }
+            })
+            .collect();
+        self.cache_profiles(now, &fetched);
+        fetched.len()
+    }
+
+    // Revalidate the avatars of `pubkys` so they are ready when shown
+    pub async fn refresh_avatars(&self, pubkys: &[String]) {
+        stream::iter(pubkys)
+            .for_each_concurrent(profiles::REFRESH_CONCURRENCY, |pubky| async move {
+                if let Err(e) = self.get_avatar(pubky).await {
+                    tracing::debug!("⚠️  Failed to refresh avatar for {}: {}", logging::pubkey(pubky), e);
+                }
+            })
+            .await;
+    }
+
+    // A user's profile picture, if their profile points at a pubky.app file
+    // on their own homeserver
+    pub async fn get_avatar(&self, pubky: &str) -> Result<Option<Avatar>> {
+        let Some(file_url) = self.get_profile(pubky).await?.and_then(|profile| profile.image) else {
+            return Ok(None);
+        };
+        let own_files = format!("pubky://{}/pub/pubky.app/files/", pubky);
+        if !file_url.starts_with(&own_files) {
+            return Ok(None);
+        }
+
+        let Some(record) = self.http_cache.get_bytes_limited(self.transport.as_ref(), &file_url, avatar::MAX_FILE_RECORD_BYTES).await? else {
+            return Ok(None);
+        };
+        let file: PubkyAppFile = serde_json::from_slice(&record)
+            .map_err(|e| anyhow!("Invalid avatar file record: {}", e))?;
+        if !file.src.starts_with(&format!("pubky://{}/pub/pubky.app/blobs/", pubky)) || !file.content_type.starts_with("image/") {
+            return Ok(None);
+        }
+        if file.size > avatar::MAX_SOURCE_BYTES {
+            return Err(anyhow!(MessengerError::InvalidInput("Avatar is too large".to_string())));
+        }
+
+        // The record's size is only what the profile claims, so the download
+        // itself is cut off at the limit too
+        let bytes = match self.http_cache.get_bytes_limited(self.transport.as_ref(), &file.src, avatar::MAX_SOURCE_BYTES).await {
+            Err(e) if e.is::<BodyTooLarge>() => {
+                return Err(anyhow!(MessengerError::InvalidInput("Avatar is too large".to_string())));
+            }
+            result => result?,
+        };
+        Ok(bytes.map(|bytes| Avatar { content_type: file.content_type, bytes }))
+    }
+
+    // Store fetched profiles in the profile cache, reporting changed ones
+    fn cache_profiles(&self, fetched_at: u64, fetched: &[(String, Option<PubkyProfile>)]) {
+        let Some(cache) = &self.profile_cache else {
+            return;
+        };
+        match cache.insert_all(fetched_at, fetched) {
+            Ok(changes) => {
+                for change in changes {
+                    tracing::info!("🪪 Profile of {} changed", logging::pubkey(&change.pubky));
+                    self.progress.report(PROFILE_CHANGED_EVENT, &change);
+                }
+            }
+            Err(e) => tracing::warn!("⚠️  Failed to cache profiles: {}", e),
+        }
+    }
+
+    // Get all followed users with their profiles
+    pub async fn get_followed_users_with_profiles(&self) -> Result<Vec<FollowedUser>> {
+        // First, get the list of follows
+        let follow_urls = self.get_followed_users().await?;
+
+        if follow_urls.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let pubkys: Vec<String> = follow_urls
+            .iter()
+            .filter_map(|url| Self::extract_pubky_from_follow_url(url))
+            .collect();
+
+        // Only stale or never-seen profiles go to the network
+        let now = profiles::now_secs();
+        let mut cached = self.profile_cache.as_ref().map(|cache| cache.all()).unwrap_or_default();
+        let stale: Vec<&String> = pubkys
+            .iter()
+            .filter(|pubky| !cached.get(*pubky).is_some_and(|entry| entry.is_fresh(now)))
+            .collect();
+
+        tracing::debug!("📋 Fetching profiles for {} of {} users...", stale.len(), pubkys.len());
+
+        let results: Vec<(&String, Result<Option<PubkyProfile>>)> = stream::iter(stale)
+            .map(|pubky| async move { (pubky, self.fetch_profile(pubky).await) })
+            .buffer_unordered(profiles::REFRESH_CONCURRENCY)
+            .collect()
+            .await;
+
+        let mut fetched = Vec::new();
+        for (pubky, result) in results {
+            match result {
+                Ok(profile) => fetched.push((pubky.clone(), profile)),
+                // A stale copy still beats nothing
+                Err(e) => tracing::warn!("  ✗ Failed to fetch profile for {}: {}", logging::pubkey(pubky), e),
+            }
+        }
+        self.cache_profiles(now, &fetched);
+        let mut fetched: HashMap<String, Option<PubkyProfile>> = fetched.into_iter().collect();
+
+        // Process results
+        let mut users = Vec::new();
+        let mut success_count = 0;
+        let mut no_profile_count = 0;
+
+        for pubky in pubkys {
+            let profile = match fetched.remove(&pubky) {
+                Some(profile) => profile,
+                None => match cached.remove(&pubky) {
+                    Some(entry) => entry.profile,
+                    // Failed to fetch and never cached
+                    None => continue,
+                },
+            };
+            let user = FollowedUser::from_profile(pubky, profile, false);
+
+            if user.name.is_some() {
+                success_count += 1;
+                tracing::debug!("  ✓ Found profile: {} - {}",
+                    logging::text(user.name.as_deref().unwrap_or_default()),
+                    logging::pubkey(&user.pubky)
+                );
+            } else {
+                no_profile_count += 1;
+                tracing::warn!("  ⚠️  No profile found for: {}",
+                    logging::pubkey(&user.pubky)
+                );
+            }
+            users.push(user);
+        }
+
+        tracing::debug!("📊 Summary: {} profiles found, {} without profiles",
+            success_count, no_profile_count);
+
+        // Everyone here is followed by us, so following back makes it mutual
+        let follow_backs: Vec<(usize, bool)> = stream::iter(users.iter().enumerate())
+            .map(|(index, user)| async move { (index, self.follows_us(&user.pubky).await.unwrap_or(false)) })
+            .buffer_unordered(profiles::REFRESH_CONCURRENCY)
+            .collect()
+            .await;
+        for (index, follows_back) in follow_backs {
+            users[index].mutual = follows_back;
+        }
+
+        Ok(users)
+    }
+
+    // Whether `pubky` has a pubky.app follow record for us
+    pub async fn follows_us(&self, pubky: &str) -> Result<bool> {
+        let follow_url = format!("pubky://{}/pub/pubky.app/follows/{}", pubky, self.keypair.public_key());
+        Ok(self.http_cache.get_bytes(self.transport.as_ref(), &follow_url).await?.is_some())
+    }
+
+    // Which of `candidates` follow us. Homeservers keep no index of
+    // followers, so only people we already know of can be found this way.
+    pub async fn scan_followers(&self, candidates: &[String]) -> Result<Vec<FollowedUser>> {
+        let own_pubkey = self.keypair.public_key().to_string();
+        let candidates: Vec<&String> = candidates.iter().filter(|pubky| **pubky != own_pubkey).collect();
+        let scanned = candidates.len();
+
+        let checks: Vec<(&String, bool)> = stream::iter(candidates)
+            .map(|pubky| async move { (pubky, self.follows_us(pubky).await.unwrap_or(false)) })
+            .buffer_unordered(profiles::REFRESH_CONCURRENCY)
+            .collect()
+            .await;
+        let followers: Vec<&String> = checks.into_iter()
+            .filter(|(_, follows)| *follows)
+            .map(|(pubky, _)| pubky)
+            .collect();
+
+        let following: HashSet<String> = self.get_followed_users()
+            .await?
+            .iter()
+            .filter_map(|url| Self::extract_pubky_from_follow_url(url))
+            .collect();
+
+        let results: Vec<(&String, Result<Option<PubkyProfile>>)> = stream::iter(followers)
+            .map(|pubky| async move { (pubky, self.get_profile(pubky).await) })
+            .buffer_unordered(profiles::REFRESH_CONCURRENCY)
+            .collect()
+            .await;
+
+        let mut users = Vec::new();
+        for (pubky, result) in results {
+            match result {
+                Ok(profile) => users.push(FollowedUser::from_profile(pubky.clone(), profile, following.contains(pubky))),
+                Err(e) => tracing::warn!("  ✗ Failed to process follower: {}", e),
+            }
+        }
+
+        tracing::info!("👥 Found {} followers among {} known users", users.len(), scanned);
+        Ok(users)
+    }
+}
+
+pub struct AppState {
+    pub keypair: Mutex<Option<Keypair>>,
+    pub user_name: Mutex<Option<String>>,
+    // The real pubky client unless a test swapped in another transport
+    pub transport: Mutex<Option<Arc<dyn Transport>>>,
+    pub is_signed_in: Mutex<bool>,
+    pub store: LocalStore,
+    pub conversation_health: Mutex<HashMap<String, ConversationHealth>>,
+    pub outbox_lock: Mutex<()>,
+    pub storage: Mutex<Option<Storage>>,
+    pub http_cache: HttpCache,
+    pub watcher: ListingWatcher,
+    // Wakes the background sync worker when its settings change
+    pub sync_settings_changed: Notify,
+    pub operations: Operations,
+    pub progress: Progress,
+    pub notification_batcher: NotificationBatcher,
+    pub downloads: DownloadManager,
+}
+
+impl AppState {
+    pub fn new(data_dir: PathBuf) -> Self {
+        Self {
+            downloads: DownloadManager::new(data_dir.join("downloads")),
+            keypair: Mutex::new(None),
+            user_name: Mutex::new(None),
+            transport: Mutex::new(None),
+            is_signed_in: Mutex::new(false),
+            store: LocalStore::new(data_dir),
+            conversation_health: Mutex::new(HashMap::new()),
+            outbox_lock: Mutex::new(()),
+            storage: Mutex::new(None),
+            http_cache: HttpCache::new(),
+            watcher: ListingWatcher::new(),
+            sync_settings_changed: Notify::new(),
+            operations: Operations::new(),
+            progress: Progress::new(),
+            notification_batcher: NotificationBatcher::new(),
+        }
+    }
+
+    // Whether the duress PIN opened the decoy account. It never talks to a
+    // homeserver, so workers skip it and handlers can't be made for it.
+    pub async fn is_decoy_session(&self) -> bool {
+        let keypair_guard = self.keypair.lock().await;
+        keypair_guard.as_ref().is_some_and(|keypair| app_lock::is_decoy(&self.store, &keypair.public_key()))
+    }
+
+    // Open the signed-in user's encrypted local cache
+    pub async fn open_storage(&self, keypair: &Keypair) -> MessengerResult<()> {
+        let storage = Storage::open(self.store.dir(), keypair)
+            .err_context("Failed to open local storage")?;
+        *self.storage.lock().await = Some(storage);
+        Ok(())
+    }
+
+    // Run a synchronous operation against the open local cache
+    pub async fn with_storage<R, F>(&self, operation: F) -> MessengerResult<R>
+    where
+        F: FnOnce(&Storage) -> Result<R>,
+    {
+        // Storage is opened on sign-in and closed on sign-out
+        let storage_guard = self.storage.lock().await;
+        let storage = storage_guard.as_ref().ok_or(MessengerError::NotSignedIn)?;
+        operation(storage).err_context("Local storage error")
+    }
+
+    // Helper method to get or create the transport
+    pub async fn get_or_create_transport(&self) -> MessengerResult<Arc<dyn Transport>> {
+        let mut transport_guard = self.transport.lock().await;
+        
+        if let Some(transport) = transport_guard.as_ref() {
+            // Return the existing transport
+            Ok(transport.clone())
+        } else {
+            // Create a new client for the configured network and store it
+            let client: Arc<dyn Transport> = Arc::new(net::build_client(&net::get_settings(&self.store))?);
+            *transport_guard = Some(client.clone());
+            Ok(client)
+        }
+    }
+    
+    // Helper method to create a handler and perform sign_in (for initial authentication)
+    pub async fn create_handler_and_sign_in(&self) -> MessengerResult<Option<PrivateMessageHandler>> {
+        let keypair_guard = self.keypair.lock().await;
+        if let Some(keypair) = keypair_guard.as_ref() {
+            check_not_decoy(&self.store, keypair)?;
+            let transport = self.get_or_create_transport().await?;
+            let handler = self.configure_handler(
+                PrivateMessageHandler::new(transport, keypair.clone(), self.http_cache.clone(), self.watcher.clone()),
+            );
+            
+            // Perform sign_in to establish session with homeserver
+            handler.sign_in().await?;
+            
+            // Mark as signed in
+            let mut signed_in_guard = self.is_signed_in.lock().await;
+            *signed_in_guard = true;
+            
+            Ok(Some(handler))
+        } else {
+            Ok(None)
+        }
+    }
+    
+    // Helper method to create a handler without signing in (when already authenticated)
+    pub async fn create_handler(&self) -> MessengerResult<Option<PrivateMessageHandler>> {
+        let keypair_guard = self.keypair.lock().await;
+        if let Some(keypair) = keypair_guard.as_ref() {
+            check_not_decoy(&self.store, keypair)?;
+            let transport = self.get_or_create_transport().await?;
+            Ok(Some(self.configure_handler(
+                PrivateMessageHandler::new(transport, keypair.clone(), self.http_cache.clone(), self.watcher.clone()),
+            )))
+        } else {
+            Ok(None)
+        }
+    }
+
+    // Everything a handler shares with the app: progress, caches and the
+    // signed-in user's local state
+    fn configure_handler(&self, handler: PrivateMessageHandler) -> PrivateMessageHandler {
+        let owner = handler.keypair.public_key();
+        handler
+            .with_progress(self.progress.clone())
+            .with_profile_cache(ProfileCache::new(self.store.clone(), &owner))
+            .with_notification_batcher(self.notification_batcher.clone())
+            .with_quarantine(Quarantine::new(self.store.clone(), &owner))
+            .with_security_log(SecurityLog::new(&self.store, &owner))
+            .with_peer_versions(PeerVersions::new(&self.store, &owner))
+            .with_attachment_limits(attachment_limits::get_settings(&self.store))
+    }
+}
+
+// The decoy account looks like it is offline rather than refusing, so
+// nothing about it gives the duress PIN away
+fn check_not_decoy(store: &LocalStore, keypair: &Keypair) -> MessengerResult<()> {
+    if app_lock::is_decoy(store, &keypair.public_key()) {
+        return Err(MessengerError::HomeserverUnreachable("Couldn't reach the homeserver".to_string()));
+    }
+    Ok(())
+}
+
+// Data structures for frontend communication
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ChatMessage {
+    #[serde(default)]
+    pub id: String,
+    pub sender: String,
+    pub content: String,
+    pub timestamp: u64,
+    pub verified: bool,
+    pub is_own_message: bool,
+    // Our alias for the sender, else their profile name; filled in when
+    // messages are handed to the frontend, never stored
+    #[serde(default, skip_deserializing)]
+    pub sender_name: Option<String>,
+    #[serde(default)]
+    pub contact_card: Option<ContactCard>,
+    #[serde(default)]
+    pub link_preview: Option<LinkPreview>,
+    #[serde(default)]
+    pub mentions: Vec<String>,
+    #[serde(default)]
+    pub attachment: Option<Attachment>,
+    #[serde(default)]
+    pub priority: NotificationPriority,
+    // Protocol version the sender wrote; 0 for messages cached before it was tracked
+    #[serde(default)]
+    pub protocol_version: u32,
+    // Newest one their client understands, from the signed extras
+    #[serde(default)]
+    pub supported_protocol: Option<u32>,
+    // When we first cached it, by our clock. `timestamp` is whatever the
+    // sender claimed, so conversations are ordered by this first.
+    #[serde(default)]
+    pub received_at: Option<u64>,
+    // The claimed timestamp is far from when the message showed up, so it
+    // was probably backdated or post-dated
+    #[serde(default)]
+    pub timestamp_suspect: bool,
+}
+
+impl ChatMessage {
+    // When the message reached us, for anything that mustn't trust the
+    // sender's clock; messages cached before arrival was recorded fall
+    // back to the claimed time
+    pub fn arrived_at(&self) -> u64 {
+        self.received_at.unwrap_or(self.timestamp)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Contact {
+    pub public_key: String,
+    pub name: Option<String>,
+    pub last_message: Option<String>,
+    pub last_message_time: Option<u64>,
+    #[serde(default)]
+    pub health: Option<ConversationHealth>,
+    // Set for contacts whose keys the user verified
+    #[serde(default)]
+    pub verification: Option<VerificationState>,
+}
+
+impl Contact {
+    // Pinned notes-to-self entry for the conversation list
+    pub fn saved_messages(public_key: String) -> Self {
+        Self {
+            public_key,
+            name: Some(SAVED_MESSAGES_NAME.to_string()),
+            last_message: None,
+            last_message_time: None,
+            health: None,
+            verification: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct UserProfile {
+    pub public_key: String,
+    pub signed_in: bool,
+    pub name: Option<String>,
+}
+
+// pubky.app follow record; `created_at` is in microseconds
+#[derive(Debug, Deserialize, Serialize)]
+struct PubkyAppFollow {
+    created_at: i64,
+}
+
+// Profile struct for parsing Pubky profiles
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct PubkyProfile {
+    pub name: String,
+    pub bio: Option<String>,
+    pub image: Option<String>,
+    pub links: Option<Vec<Link>>,
+    pub status: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Link {
+    pub title: String,
+    pub url: String,
+}
+
+// Limits pubky.app enforces, so a profile we publish shows up there too
+const PROFILE_NAME_CHARS: std::ops::RangeInclusive<usize> = 3..=50;
+const PROFILE_BIO_MAX_CHARS: usize = 160;
+const PROFILE_STATUS_MAX_CHARS: usize = 50;
+const PROFILE_MAX_LINKS: usize = 5;
+const PROFILE_LINK_TITLE_MAX_CHARS: usize = 100;
+
+impl PubkyProfile {
+    // Trim every field, drop empty optional ones and check the limits
+    pub fn normalized(self) -> MessengerResult<Self> {
+        let optional = |value: Option<String>| value.map(|value| value.trim().to_string()).filter(|value| !value.is_empty());
+
+        let name = self.name.trim().to_string();
+        if !PROFILE_NAME_CHARS.contains(&name.chars().count()) {
+            return Err(MessengerError::InvalidInput(format!(
+                "Name must be {} to {} characters",
+                PROFILE_NAME_CHARS.start(),
+                PROFILE_NAME_CHARS.end()
+            )));
+        }
+
+        let bio = optional(self.bio);
+        if bio.as_ref().is_some_and(|bio| bio.chars().count() > PROFILE_BIO_MAX_CHARS) {
+            return Err(MessengerError::InvalidInput(format!("Bio must be at most {} characters", PROFILE_BIO_MAX_CHARS)));
+        }
+
+        let status = normalized_status(self.status.as_deref())?;
+
+        let links: Vec<Link> = self.links.unwrap_or_default()
+            .into_iter()
+            .map(|link| Link { title: link.title.trim().to_string(), url: link.url.trim().to_string() })
+            .filter(|link| !link.url.is_empty())
+            .collect();
+        if links.len() > PROFILE_MAX_LINKS {
+            return Err(MessengerError::InvalidInput(format!("At most {} links are allowed", PROFILE_MAX_LINKS)));
+        }
+        for link in &links {
+            if link.title.chars().count() > PROFILE_LINK_TITLE_MAX_CHARS {
+                return Err(MessengerError::InvalidInput(format!("Link titles must be at most {} characters", PROFILE_LINK_TITLE_MAX_CHARS)));
+            }
+            url::Url::parse(&link.url).map_err(|e| MessengerError::InvalidInput(format!("Invalid link {}: {}", link.url, e)))?;
+        }
+
+        let image = optional(self.image);
+        if let Some(image) = &image {
+            url::Url::parse(image).map_err(|e| MessengerError::InvalidInput(format!("Invalid image URL {}: {}", image, e)))?;
+        }
+
+        Ok(Self {
+            name,
+            bio,
+            image,
+            links: (!links.is_empty()).then_some(links),
+            status,
+        })
+    }
+}
+
+// Trimmed, None when empty, and within the limit
+fn normalized_status(status: Option<&str>) -> MessengerResult<Option<String>> {
+    let status = status.map(str::trim).filter(|status| !status.is_empty());
+    if status.is_some_and(|status| status.chars().count() > PROFILE_STATUS_MAX_CHARS) {
+        return Err(MessengerError::InvalidInput(format!("Status must be at most {} characters", PROFILE_STATUS_MAX_CHARS)));
+    }
+    Ok(status.map(str::to_string))
+}
+
+// Struct to hold name and pubky for a followed user
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FollowedUser {
+    pub name: Option<String>,
+    pub pubky: String,
+    // We follow them and they follow us back
+    #[serde(default)]
+    pub mutual: bool,
+    // Their short availability note, e.g. "on vacation"
+    #[serde(default)]
+    pub status: Option<String>,
+}
+
+// A notification announcing messages `msg_ids` from `sender`
+#[derive(Clone, Debug)]
+pub struct PendingNotification {
+    pub url: String,
+    pub sender: String,
+    pub msg_ids: Vec<String>,
+    pub timestamp: u64,
+    pub kind: NotificationKind,
+}
+
+// What we can learn about a pubky before talking to them
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct UserPreview {
+    pub pubky: String,
+    // Where their pkarr record points; None if they published none
+    pub homeserver: Option<String>,
+    // Their homeserver answered, so messages to them can be delivered
+    pub reachable: bool,
+    pub name: Option<String>,
+    pub bio: Option<String>,
+    pub image: Option<String>,
+    pub status: Option<String>,
+    pub follows_us: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct Avatar {
+    pub content_type: String,
+    pub bytes: Vec<u8>,
+}
+
+// Everything a contact published in their profile, for a detail page.
+// Fields are empty when they have no profile.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ContactProfile {
+    pub pubky: String,
+    pub name: Option<String>,
+    pub bio: Option<String>,
+    // pubky:// URL of their avatar's pubky.app file record
+    pub image: Option<String>,
+    pub links: Vec<ProfileLink>,
+    pub status: Option<String>,
+}
+
+impl ContactProfile {
+    pub fn from_profile(pubky: String, profile: Option<PubkyProfile>) -> Self {
+        match profile {
+            Some(profile) => Self {
+                pubky,
+                name: Some(profile.name),
+                bio: profile.bio,
+                image: profile.image,
+                links: profile.links.unwrap_or_default()
+                    .into_iter()
+                    .map(|link| ProfileLink { title: link.title, url: link.url, verified: None })
+                    .collect(),
+                status: profile.status,
+            },
+            None => Self { pubky, name: None, bio: None, image: None, links: Vec::new(), status: None },
+        }
+    }
+}
+
+impl FollowedUser {
+    pub fn from_profile(pubky: String, profile: Option<PubkyProfile>, mutual: bool) -> Self {
+        let (name, status) = match profile {
+            Some(profile) => (Some(profile.name), profile.status),
+            None => (None, None),
+        };
+        Self { name, pubky, mutual, status }
+    }
+}
\ No newline at end of file
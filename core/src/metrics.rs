@@ -0,0 +1,138 @@
+// Counters for every homeserver call made through `net`, so users and
+// developers can see why sync is slow. Kept in memory since launch (or the
+// last reset); nothing is persisted.
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Latency percentiles are computed over this many recent attempts per endpoint
+const LATENCY_SAMPLES: usize = 256;
+
+#[derive(Default)]
+struct EndpointCounters {
+    requests: u64,
+    failures: u64,
+    timeouts: u64,
+    latencies_ms: VecDeque<u64>,
+}
+
+struct Counters {
+    since: u64,
+    bytes_sent: u64,
+    bytes_received: u64,
+    endpoints: BTreeMap<String, EndpointCounters>,
+}
+
+impl Counters {
+    fn new() -> Self {
+        Self {
+            since: now_secs(),
+            bytes_sent: 0,
+            bytes_received: 0,
+            endpoints: BTreeMap::new(),
+        }
+    }
+}
+
+static COUNTERS: Lazy<Mutex<Counters>> = Lazy::new(|| Mutex::new(Counters::new()));
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct EndpointStats {
+    // Attempts, so a request retried twice counts three times
+    pub requests: u64,
+    pub failures: u64,
+    pub timeouts: u64,
+    pub p50_ms: Option<u64>,
+    pub p90_ms: Option<u64>,
+    pub p99_ms: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct NetworkStats {
+    pub since: u64,
+    pub requests: u64,
+    pub failures: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    // Keyed by operation: GET, PUT, LIST, DELETE, SIGNIN, ...
+    pub endpoints: BTreeMap<String, EndpointStats>,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn lock() -> std::sync::MutexGuard<'static, Counters> {
+    COUNTERS.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+pub fn record_attempt(endpoint: &str, elapsed: Duration, failed: bool, timed_out: bool) {
+    let mut counters = lock();
+    let endpoint = counters.endpoints.entry(endpoint.to_string()).or_default();
+    endpoint.requests += 1;
+    if failed {
+        endpoint.failures += 1;
+    }
+    if timed_out {
+        endpoint.timeouts += 1;
+    }
+    endpoint.latencies_ms.push_back(elapsed.as_millis() as u64);
+    if endpoint.latencies_ms.len() > LATENCY_SAMPLES {
+        endpoint.latencies_ms.pop_front();
+    }
+}
+
+pub fn record_sent(bytes: usize) {
+    lock().bytes_sent += bytes as u64;
+}
+
+pub fn record_received(bytes: usize) {
+    lock().bytes_received += bytes as u64;
+}
+
+// Nearest-rank percentile of already sorted samples
+fn percentile(sorted: &[u64], percent: usize) -> Option<u64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = (sorted.len() * percent).div_ceil(100).max(1);
+    sorted.get(rank - 1).copied()
+}
+
+pub fn snapshot() -> NetworkStats {
+    let counters = lock();
+    let endpoints: BTreeMap<String, EndpointStats> = counters.endpoints
+        .iter()
+        .map(|(name, endpoint)| {
+            let mut sorted: Vec<u64> = endpoint.latencies_ms.iter().copied().collect();
+            sorted.sort_unstable();
+            let stats = EndpointStats {
+                requests: endpoint.requests,
+                failures: endpoint.failures,
+                timeouts: endpoint.timeouts,
+                p50_ms: percentile(&sorted, 50),
+                p90_ms: percentile(&sorted, 90),
+                p99_ms: percentile(&sorted, 99),
+            };
+            (name.clone(), stats)
+        })
+        .collect();
+
+    NetworkStats {
+        since: counters.since,
+        requests: endpoints.values().map(|endpoint| endpoint.requests).sum(),
+        failures: endpoints.values().map(|endpoint| endpoint.failures).sum(),
+        bytes_sent: counters.bytes_sent,
+        bytes_received: counters.bytes_received,
+        endpoints,
+    }
+}
+
+pub fn reset() {
+    *lock() = Counters::new();
+}
@@ -0,0 +1,67 @@
+use crate::documents;
+use crate::local_store::LocalStore;
+use anyhow::Result;
+use pkarr::PublicKey;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct MuteEntry {
+    // None mutes until explicitly unmuted
+    pub until: Option<u64>,
+}
+
+impl MuteEntry {
+    fn is_active(&self, now: u64) -> bool {
+        self.until.map(|until| until > now).unwrap_or(true)
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct MuteDocument {
+    conversations: HashMap<String, MuteEntry>,
+}
+
+// Per-user muted conversations. Muting only silences alerts and events;
+// messages keep syncing as usual.
+pub struct MuteList<'a> {
+    store: &'a LocalStore,
+    document: String,
+}
+
+impl<'a> MuteList<'a> {
+    pub fn new(store: &'a LocalStore, owner: &PublicKey) -> Self {
+        Self {
+            store,
+            document: documents::user_document(documents::MUTES, owner),
+        }
+    }
+
+    pub fn mute(&self, conversation: &str, until: Option<u64>) -> Result<()> {
+        self.store.update(&self.document, |document: &mut MuteDocument| {
+            document.conversations.insert(conversation.to_string(), MuteEntry { until });
+        })
+    }
+
+    pub fn unmute(&self, conversation: &str) -> Result<bool> {
+        self.store.update(&self.document, |document: &mut MuteDocument| {
+            document.conversations.remove(conversation).is_some()
+        })
+    }
+
+    pub fn is_muted(&self, conversation: &str, now: u64) -> bool {
+        self.active(now)
+            .map(|active| active.contains_key(conversation))
+            .unwrap_or(false)
+    }
+
+    // Mutes that haven't expired yet
+    pub fn active(&self, now: u64) -> Result<HashMap<String, MuteEntry>> {
+        let document: MuteDocument = self.store.load(&self.document)?;
+        Ok(document
+            .conversations
+            .into_iter()
+            .filter(|(_, entry)| entry.is_active(now))
+            .collect())
+    }
+}
@@ -0,0 +1,108 @@
+// What we call a pubky, everywhere it is shown: our alias for them, else
+// their profile name, else a shortened pubkey. Messages, contacts and
+// notifications all go through here so the same person never shows up
+// under two names.
+use crate::error::{MessengerError, MessengerResult};
+use crate::messaging::{AppState, ChatMessage, Contact, SAVED_MESSAGES_NAME};
+use crate::profiles::ProfileCache;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NameSource {
+    Alias,
+    Profile,
+    SavedMessages,
+    Pubkey,
+}
+
+// How a pubky's name was picked, with every candidate that was considered
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ResolvedName {
+    pub pubky: String,
+    pub display_name: String,
+    pub source: NameSource,
+    pub alias: Option<String>,
+    pub profile_name: Option<String>,
+}
+
+pub fn short_pubkey(pubkey: &str) -> String {
+    if pubkey.len() > 12 {
+        format!("{}…{}", &pubkey[..6], &pubkey[pubkey.len() - 4..])
+    } else {
+        pubkey.to_string()
+    }
+}
+
+pub struct NameResolver {
+    own_pubkey: String,
+    aliases: HashMap<String, String>,
+    profile_names: HashMap<String, String>,
+}
+
+impl NameResolver {
+    // Snapshot of the signed-in user's aliases and known profile names
+    pub async fn load(state: &AppState) -> MessengerResult<Self> {
+        let owner = state.keypair.lock().await.as_ref()
+            .map(|keypair| keypair.public_key())
+            .ok_or(MessengerError::NotSignedIn)?;
+        let contacts = state.with_storage(|storage| storage.all_contacts()).await?;
+
+        let mut aliases = HashMap::new();
+        let mut profile_names = HashMap::new();
+        for contact in contacts {
+            if let Some(alias) = contact.alias {
+                aliases.insert(contact.public_key.clone(), alias);
+            }
+            if let Some(name) = contact.name {
+                profile_names.insert(contact.public_key, name);
+            }
+        }
+        // The profile cache is refreshed more often than the contacts table
+        for (pubky, cached) in ProfileCache::new(state.store.clone(), &owner).all() {
+            if let Some(profile) = cached.profile {
+                profile_names.insert(pubky, profile.name);
+            }
+        }
+
+        Ok(Self { own_pubkey: owner.to_string(), aliases, profile_names })
+    }
+
+    pub fn resolve(&self, pubky: &str) -> ResolvedName {
+        let alias = self.aliases.get(pubky).cloned();
+        let profile_name = self.profile_names.get(pubky).cloned();
+        let (display_name, source) = if let Some(alias) = &alias {
+            (alias.clone(), NameSource::Alias)
+        } else if pubky == self.own_pubkey {
+            (SAVED_MESSAGES_NAME.to_string(), NameSource::SavedMessages)
+        } else if let Some(name) = &profile_name {
+            (name.clone(), NameSource::Profile)
+        } else {
+            (short_pubkey(pubky), NameSource::Pubkey)
+        };
+        ResolvedName { pubky: pubky.to_string(), display_name, source, alias, profile_name }
+    }
+
+    pub fn display_name(&self, pubky: &str) -> String {
+        self.resolve(pubky).display_name
+    }
+
+    // A real name if we know one; None leaves the caller to show the pubky
+    pub fn label(&self, pubky: &str) -> Option<String> {
+        let resolved = self.resolve(pubky);
+        (resolved.source != NameSource::Pubkey).then_some(resolved.display_name)
+    }
+
+    pub fn label_senders(&self, messages: &mut [ChatMessage]) {
+        for message in messages.iter_mut().filter(|msg| !msg.is_own_message) {
+            message.sender_name = Some(self.display_name(&message.sender));
+        }
+    }
+
+    pub fn label_contacts(&self, contacts: &mut [Contact]) {
+        for contact in contacts.iter_mut() {
+            contact.name = self.label(&contact.public_key);
+        }
+    }
+}
@@ -0,0 +1,376 @@
+// Homeserver I/O with timeouts and retries.
+//
+// Every put/get/list/delete against a homeserver goes through here so a
+// flaky connection is retried with exponential backoff and jitter instead
+// of surfacing as an instant failure, and a hung one times out instead of
+// blocking sync forever. Only transient failures (timeouts, connection
+// errors, 408/429/5xx) are retried, within an attempt limit and an overall
+// time budget.
+use crate::documents;
+use crate::local_store::LocalStore;
+use crate::metrics;
+use anyhow::{anyhow, Result};
+use rand_core::{OsRng, RngCore};
+use reqwest::{Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncReadExt;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+const NETWORK_SETTINGS_DOCUMENT: &str = documents::NETWORK_SETTINGS;
+
+const DEFAULT_TIMEOUT_SECS: u64 = 20;
+const MIN_TIMEOUT_SECS: u64 = 2;
+const MAX_TIMEOUT_SECS: u64 = 5 * 60;
+
+// Streamed uploads slower than this are given up on
+const MIN_UPLOAD_BYTES_PER_SEC: u64 = 64 * 1024;
+const FILE_CHUNK_BYTES: usize = 64 * 1024;
+
+// Applies to each attempt of a request, not to the request with its retries
+static REQUEST_TIMEOUT_SECS: AtomicU64 = AtomicU64::new(DEFAULT_TIMEOUT_SECS);
+static PROXY: RwLock<Option<String>> = RwLock::new(None);
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct NetworkSettings {
+    pub request_timeout_secs: u64,
+    // Use the local testnet (pkarr relay and DHT on localhost) instead of mainnet
+    #[serde(default)]
+    pub testnet: bool,
+    // Pkarr relay URLs replacing the defaults; empty keeps them
+    #[serde(default)]
+    pub relays: Vec<String>,
+    // DHT bootstrap nodes as host:port, replacing the defaults; empty keeps them
+    #[serde(default)]
+    pub bootstrap_nodes: Vec<String>,
+    // HTTP(S) proxy for requests to third-party sites (link previews).
+    // pubky::Client has no proxy option, so homeserver traffic only goes
+    // through one configured system-wide via HTTPS_PROXY.
+    #[serde(default)]
+    pub proxy: Option<String>,
+}
+
+impl Default for NetworkSettings {
+    fn default() -> Self {
+        Self {
+            request_timeout_secs: DEFAULT_TIMEOUT_SECS,
+            testnet: false,
+            relays: Vec::new(),
+            bootstrap_nodes: Vec::new(),
+            proxy: None,
+        }
+    }
+}
+
+impl NetworkSettings {
+    // Whether switching to `other` needs a new client
+    pub fn same_endpoints(&self, other: &NetworkSettings) -> bool {
+        self.testnet == other.testnet && self.relays == other.relays && self.bootstrap_nodes == other.bootstrap_nodes
+    }
+}
+
+fn validate(settings: NetworkSettings) -> Result<NetworkSettings> {
+    for relay in &settings.relays {
+        let url = url::Url::parse(relay).map_err(|e| anyhow!("Invalid relay URL {}: {}", relay, e))?;
+        if url.scheme() != "http" && url.scheme() != "https" {
+            return Err(anyhow!("Relay URL {} must be http or https", relay));
+        }
+    }
+    for node in &settings.bootstrap_nodes {
+        let valid = node
+            .rsplit_once(':')
+            .map(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok())
+            .unwrap_or(false);
+        if !valid {
+            return Err(anyhow!("Bootstrap node {} must be host:port", node));
+        }
+    }
+    let proxy = settings.proxy
+        .as_deref()
+        .map(str::trim)
+        .filter(|proxy| !proxy.is_empty())
+        .map(|proxy| {
+            let url = url::Url::parse(proxy).map_err(|e| anyhow!("Invalid proxy URL {}: {}", proxy, e))?;
+            if url.scheme() != "http" && url.scheme() != "https" {
+                return Err(anyhow!("Proxy URL {} must be http or https", proxy));
+            }
+            Ok(proxy.to_string())
+        })
+        .transpose()?;
+
+    Ok(NetworkSettings {
+        request_timeout_secs: settings.request_timeout_secs.clamp(MIN_TIMEOUT_SECS, MAX_TIMEOUT_SECS),
+        relays: settings.relays.into_iter().map(|relay| relay.trim().to_string()).collect(),
+        bootstrap_nodes: settings.bootstrap_nodes.into_iter().map(|node| node.trim().to_string()).collect(),
+        proxy,
+        ..settings
+    })
+}
+
+// A pubky client pointed at the configured network
+pub fn build_client(settings: &NetworkSettings) -> Result<pubky::Client> {
+    let relays = settings.relays
+        .iter()
+        .map(|relay| url::Url::parse(relay))
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    let mut builder = pubky::Client::builder();
+    if settings.testnet {
+        builder.testnet();
+    }
+    if !relays.is_empty() || !settings.bootstrap_nodes.is_empty() {
+        builder.pkarr(|pkarr| {
+            if !relays.is_empty() {
+                let _ = pkarr.relays(&relays);
+            }
+            if !settings.bootstrap_nodes.is_empty() {
+                pkarr.bootstrap(&settings.bootstrap_nodes);
+            }
+            pkarr
+        });
+    }
+
+    builder.build().map_err(|e| anyhow!("Failed to create client: {}", e))
+}
+
+pub fn get_settings(store: &LocalStore) -> NetworkSettings {
+    store.load(NETWORK_SETTINGS_DOCUMENT).unwrap_or_default()
+}
+
+// Persist the settings and apply the timeout to every request from now on.
+// Endpoint changes only take effect for clients built afterwards.
+pub fn set_settings(store: &LocalStore, settings: NetworkSettings) -> Result<NetworkSettings> {
+    let settings = validate(settings)?;
+    store.save(NETWORK_SETTINGS_DOCUMENT, &settings)?;
+    apply_settings(&settings);
+    Ok(settings)
+}
+
+pub fn apply_settings(settings: &NetworkSettings) {
+    let secs = settings.request_timeout_secs.clamp(MIN_TIMEOUT_SECS, MAX_TIMEOUT_SECS);
+    REQUEST_TIMEOUT_SECS.store(secs, Ordering::Relaxed);
+    *PROXY.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = settings.proxy.clone();
+}
+
+pub fn proxy() -> Option<String> {
+    PROXY.read().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()
+}
+
+pub fn request_timeout() -> Duration {
+    Duration::from_secs(REQUEST_TIMEOUT_SECS.load(Ordering::Relaxed))
+}
+
+// Returned when an attempt takes longer than the request timeout
+#[derive(Debug)]
+pub struct TimedOut {
+    pub label: String,
+    pub after: Duration,
+}
+
+impl std::fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} timed out after {}s", self.label, self.after.as_secs())
+    }
+}
+
+impl std::error::Error for TimedOut {}
+
+fn is_timeout(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| cause.is::<TimedOut>())
+}
+
+async fn timed<T, Fut>(label: &str, operation: Fut) -> Result<T>
+where
+    Fut: Future<Output = Result<T>>,
+{
+    let after = request_timeout();
+    match tokio::time::timeout(after, operation).await {
+        Ok(result) => result,
+        Err(_) => Err(anyhow!(TimedOut { label: label.to_string(), after })),
+    }
+}
+
+// Run one network call under the request timeout, without retries
+pub async fn with_timeout<T, Fut>(label: &str, operation: Fut) -> Result<T>
+where
+    Fut: Future<Output = Result<T>>,
+{
+    let started = Instant::now();
+    let result = timed(label, operation).await;
+    let timed_out = result.as_ref().err().map(is_timeout).unwrap_or(false);
+    metrics::record_attempt(label, started.elapsed(), result.is_err(), timed_out);
+    result
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    // Give up once retrying would take longer than this in total
+    pub budget: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            base_delay: Duration::from_millis(250),
+            max_delay: Duration::from_secs(4),
+            budget: Duration::from_secs(15),
+        }
+    }
+}
+
+impl RetryPolicy {
+    // Full jitter: a random delay up to the capped exponential backoff
+    fn delay(&self, attempt: u32) -> Duration {
+        let exponential = self.base_delay.saturating_mul(1u32 << attempt.min(16));
+        let capped = exponential.min(self.max_delay).as_millis() as u64;
+        Duration::from_millis(OsRng.next_u64() % (capped + 1))
+    }
+}
+
+pub fn is_transient_status(status: StatusCode) -> bool {
+    status == StatusCode::REQUEST_TIMEOUT
+        || status == StatusCode::TOO_MANY_REQUESTS
+        || status.is_server_error()
+}
+
+// Network-level failures are worth retrying; malformed URLs, bad keys and
+// the like are not
+pub fn is_transient_error(error: &anyhow::Error) -> bool {
+    if is_timeout(error) {
+        return true;
+    }
+    if let Some(reqwest_error) = error.chain().find_map(|cause| cause.downcast_ref::<reqwest::Error>()) {
+        return reqwest_error.is_timeout()
+            || reqwest_error.is_connect()
+            || reqwest_error.is_request()
+            || reqwest_error.status().map(is_transient_status).unwrap_or(false);
+    }
+    !error.chain().any(|cause| cause.is::<url::ParseError>())
+}
+
+fn should_retry_response(result: &Result<Response>) -> bool {
+    match result {
+        Ok(response) => is_transient_status(response.status()),
+        Err(e) => is_transient_error(e),
+    }
+}
+
+// Run `operation` until it succeeds, fails permanently or the policy runs
+// out. Each attempt is cut off after the request timeout.
+pub async fn retry<T, F, Fut, R>(policy: &RetryPolicy, label: &str, mut operation: F, should_retry: R) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+    R: Fn(&Result<T>) -> bool,
+{
+    let started = Instant::now();
+    let mut attempt = 0;
+
+    loop {
+        attempt += 1;
+        let attempt_started = Instant::now();
+        let result = timed(label, operation()).await;
+        let retryable = should_retry(&result);
+        let timed_out = result.as_ref().err().map(is_timeout).unwrap_or(false);
+        metrics::record_attempt(label, attempt_started.elapsed(), result.is_err() || retryable, timed_out);
+
+        if attempt >= policy.max_attempts || !retryable {
+            return result;
+        }
+
+        let delay = policy.delay(attempt);
+        if started.elapsed() + delay > policy.budget {
+            return result;
+        }
+
+        tracing::debug!("🔁 {} failed (attempt {}), retrying in {}ms", label, attempt, delay.as_millis());
+        tokio::time::sleep(delay).await;
+    }
+}
+
+// Send a request built fresh for every attempt. A transient status left
+// after the last attempt is returned as the response, not as an error.
+pub async fn send<F, Fut>(label: &str, mut request: F) -> Result<Response>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = reqwest::Result<Response>>,
+{
+    let policy = RetryPolicy::default();
+    retry(&policy, label, || {
+        let pending = request();
+        async move { pending.await.map_err(anyhow::Error::from) }
+    }, should_retry_response).await
+}
+
+pub async fn put(client: &pubky::Client, url: &str, body: Vec<u8>) -> Result<Response> {
+    metrics::record_sent(body.len());
+    send("PUT", || client.put(url).body(body.clone()).send()).await
+}
+
+// Streams the file from disk in one attempt, allowed as long as a slow link
+// needs for its size. Retrying would start the whole upload again.
+pub async fn put_file(client: &pubky::Client, url: &str, path: &Path) -> Result<Response> {
+    let len = tokio::fs::metadata(path).await?.len();
+    metrics::record_sent(len as usize);
+
+    let after = request_timeout() + Duration::from_secs(len / MIN_UPLOAD_BYTES_PER_SEC);
+    let started = Instant::now();
+    let request = client.put(url)
+        .header(reqwest::header::CONTENT_LENGTH, len)
+        .body(file_body(path.to_path_buf()))
+        .send();
+    let result = match tokio::time::timeout(after, request).await {
+        Ok(result) => result.map_err(anyhow::Error::from),
+        Err(_) => Err(anyhow!(TimedOut { label: "PUT".to_string(), after })),
+    };
+    let timed_out = result.as_ref().err().map(is_timeout).unwrap_or(false);
+    metrics::record_attempt("PUT", started.elapsed(), result.is_err(), timed_out);
+    result
+}
+
+fn file_body(path: PathBuf) -> reqwest::Body {
+    let chunks = futures::stream::try_unfold(None, move |file: Option<tokio::fs::File>| {
+        let path = path.clone();
+        async move {
+            let mut file = match file {
+                Some(file) => file,
+                None => tokio::fs::File::open(&path).await?,
+            };
+            let mut chunk = vec![0u8; FILE_CHUNK_BYTES];
+            let read = file.read(&mut chunk).await?;
+            if read == 0 {
+                return Ok::<_, std::io::Error>(None);
+            }
+            chunk.truncate(read);
+            Ok(Some((chunk, Some(file))))
+        }
+    });
+    reqwest::Body::wrap_stream(chunks)
+}
+
+pub async fn delete(client: &pubky::Client, url: &str) -> Result<Response> {
+    send("DELETE", || client.delete(url).send()).await
+}
+
+pub async fn list_page(client: &pubky::Client, url: &str, limit: Option<u16>, cursor: Option<&str>) -> Result<Vec<String>> {
+    let policy = RetryPolicy::default();
+    retry(&policy, "LIST", || async move {
+        let mut list_builder = client.list(url)?;
+        if let Some(limit) = limit {
+            list_builder = list_builder.limit(limit);
+        }
+        if let Some(cursor) = cursor {
+            list_builder = list_builder.cursor(cursor);
+        }
+        let urls = list_builder.send().await?;
+        metrics::record_received(urls.iter().map(|url| url.len()).sum());
+        Ok(urls)
+    }, |result| result.as_ref().err().map(is_transient_error).unwrap_or(false)).await
+}
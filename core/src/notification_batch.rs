@@ -0,0 +1,50 @@
+// Coalesces the notifications for messages sent in quick succession: a
+// burst of messages to one recipient costs one notification record, and
+// one write, instead of one per message.
+use crate::mentions::NotificationKind;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// How long a recipient's first notification waits for more to join it
+pub const BATCH_WINDOW: Duration = Duration::from_secs(2);
+
+// Messages waiting to be announced to one recipient
+#[derive(Clone, Debug, Default)]
+pub struct Batch {
+    pub msg_ids: Vec<String>,
+    // A mention anywhere in the batch makes the whole record one
+    pub kind: NotificationKind,
+}
+
+#[derive(Clone, Default)]
+pub struct NotificationBatcher {
+    // By recipient
+    pending: Arc<Mutex<HashMap<String, Batch>>>,
+}
+
+impl NotificationBatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Batch>> {
+        self.pending.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    // Queue `msg_id` for `recipient`. True when this started a new batch,
+    // which the caller then flushes with `take` after BATCH_WINDOW.
+    pub fn add(&self, recipient: &str, msg_id: &str, kind: NotificationKind) -> bool {
+        let mut pending = self.lock();
+        let batch = pending.entry(recipient.to_string()).or_default();
+        batch.msg_ids.push(msg_id.to_string());
+        if kind == NotificationKind::Mention {
+            batch.kind = kind;
+        }
+        batch.msg_ids.len() == 1
+    }
+
+    pub fn take(&self, recipient: &str) -> Batch {
+        self.lock().remove(recipient).unwrap_or_default()
+    }
+}
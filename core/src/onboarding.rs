@@ -0,0 +1,108 @@
+use crate::documents;
+use crate::local_store::LocalStore;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const ONBOARDING_DOCUMENT: &str = documents::ONBOARDING;
+
+// First-run steps, in the order the UI walks through them
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum OnboardingStep {
+    ImportKey,
+    BackupRecoveryFile,
+    SetAppLock,
+    ScanContacts,
+    SendFirstMessage,
+}
+
+impl OnboardingStep {
+    pub const ALL: [OnboardingStep; 5] = [
+        OnboardingStep::ImportKey,
+        OnboardingStep::BackupRecoveryFile,
+        OnboardingStep::SetAppLock,
+        OnboardingStep::ScanContacts,
+        OnboardingStep::SendFirstMessage,
+    ];
+
+    // Every step after the key import needs an identity to act on
+    fn requires(&self) -> Option<OnboardingStep> {
+        match self {
+            OnboardingStep::ImportKey => None,
+            _ => Some(OnboardingStep::ImportKey),
+        }
+    }
+}
+
+// Persisted form: completion time per finished step
+#[derive(Serialize, Deserialize, Default)]
+struct OnboardingRecord {
+    completed: HashMap<OnboardingStep, u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct OnboardingStepStatus {
+    pub step: OnboardingStep,
+    pub completed: bool,
+    pub completed_at: Option<u64>,
+}
+
+// Data structure returned to the frontend
+#[derive(Serialize, Deserialize)]
+pub struct OnboardingState {
+    pub steps: Vec<OnboardingStepStatus>,
+    pub current_step: Option<OnboardingStep>,
+    pub finished: bool,
+}
+
+impl OnboardingRecord {
+    fn to_state(&self) -> OnboardingState {
+        let steps: Vec<OnboardingStepStatus> = OnboardingStep::ALL
+            .iter()
+            .map(|step| OnboardingStepStatus {
+                step: *step,
+                completed: self.completed.contains_key(step),
+                completed_at: self.completed.get(step).copied(),
+            })
+            .collect();
+
+        let current_step = steps.iter().find(|s| !s.completed).map(|s| s.step);
+
+        OnboardingState {
+            steps,
+            current_step,
+            finished: current_step.is_none(),
+        }
+    }
+}
+
+pub fn get_state(store: &LocalStore) -> Result<OnboardingState> {
+    let record: OnboardingRecord = store.load(ONBOARDING_DOCUMENT)?;
+    Ok(record.to_state())
+}
+
+// Mark a step as done; completing an already finished step is a no-op
+pub fn complete_step(store: &LocalStore, step: OnboardingStep) -> Result<OnboardingState> {
+    let mut record: OnboardingRecord = store.load(ONBOARDING_DOCUMENT)?;
+
+    if let Some(required) = step.requires() {
+        if !record.completed.contains_key(&required) {
+            return Err(anyhow!("Cannot complete {:?} before {:?}", step, required));
+        }
+    }
+
+    if !record.completed.contains_key(&step) {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        record.completed.insert(step, now);
+        store.save(ONBOARDING_DOCUMENT, &record)?;
+    }
+
+    Ok(record.to_state())
+}
+
+pub fn reset(store: &LocalStore) -> Result<OnboardingState> {
+    store.remove(ONBOARDING_DOCUMENT)?;
+    Ok(OnboardingRecord::default().to_state())
+}
@@ -0,0 +1,68 @@
+use futures::future::{AbortHandle, Abortable};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+
+use crate::error::{MessengerError, MessengerResult};
+
+// Long-running commands the frontend can cancel. A command that takes an
+// operation id registers an abort handle under it while it runs, and
+// cancel_operation drops its future at the next await point.
+#[derive(Default)]
+pub struct Operations {
+    running: Mutex<HashMap<String, AbortHandle>>,
+}
+
+impl Operations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, AbortHandle>> {
+        self.running.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    // Run `operation`, cancellable under `id` when one is given
+    pub async fn run<T, Fut>(&self, id: Option<String>, operation: Fut) -> MessengerResult<T>
+    where
+        Fut: Future<Output = MessengerResult<T>>,
+    {
+        let Some(id) = id else {
+            return operation.await;
+        };
+
+        let (handle, registration) = AbortHandle::new_pair();
+        {
+            let mut running = self.lock();
+            if running.contains_key(&id) {
+                return Err(MessengerError::InvalidInput(format!("Operation {} is already running", id)));
+            }
+            running.insert(id.clone(), handle);
+        }
+
+        let result = Abortable::new(operation, registration).await;
+        self.lock().remove(&id);
+
+        match result {
+            Ok(result) => result,
+            Err(_) => Err(MessengerError::Cancelled(format!("Operation {} was cancelled", id))),
+        }
+    }
+
+    // Abort a running operation; false when nothing runs under `id`
+    pub fn cancel(&self, id: &str) -> bool {
+        match self.lock().remove(id) {
+            Some(handle) => {
+                handle.abort();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn cancel_all(&self) {
+        for (_, handle) in self.lock().drain() {
+            handle.abort();
+        }
+    }
+}
@@ -0,0 +1,262 @@
+use crate::blocks::BlockList;
+use crate::documents;
+use crate::events::{self, EventSink};
+use crate::local_store::LocalStore;
+use crate::messaging::{AppState, MessageExtras};
+use anyhow::Result;
+use pkarr::PublicKey;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+pub const OUTBOX_STATUS_EVENT: &str = "outbox-status";
+
+const WORKER_TICK: Duration = Duration::from_secs(5);
+const BASE_BACKOFF_SECS: u64 = 5;
+const MAX_BACKOFF_SECS: u64 = 15 * 60;
+// After this many attempts an entry stays failed until retried by hand
+const MAX_ATTEMPTS: u32 = 20;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OutboxStatus {
+    Pending,
+    Sent,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct OutboxEntry {
+    pub id: String,
+    pub recipient: String,
+    pub content: String,
+    #[serde(default)]
+    pub extras: MessageExtras,
+    pub created_at: u64,
+    pub attempts: u32,
+    pub next_attempt_at: u64,
+    pub last_error: Option<String>,
+    pub status: OutboxStatus,
+}
+
+// Payload of the outbox-status event
+#[derive(Serialize, Deserialize, Clone)]
+pub struct OutboxStatusEvent {
+    pub id: String,
+    pub recipient: String,
+    pub status: OutboxStatus,
+    pub attempts: u32,
+    pub next_attempt_at: u64,
+    pub last_error: Option<String>,
+}
+
+impl From<&OutboxEntry> for OutboxStatusEvent {
+    fn from(entry: &OutboxEntry) -> Self {
+        Self {
+            id: entry.id.clone(),
+            recipient: entry.recipient.clone(),
+            status: entry.status,
+            attempts: entry.attempts,
+            next_attempt_at: entry.next_attempt_at,
+            last_error: entry.last_error.clone(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct OutboxDocument {
+    entries: Vec<OutboxEntry>,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// Exponential backoff with up to 20% jitter so queued sends don't stampede
+fn backoff_secs(attempts: u32) -> u64 {
+    let exponential = BASE_BACKOFF_SECS.saturating_mul(1u64 << attempts.min(16));
+    let capped = exponential.min(MAX_BACKOFF_SECS);
+    let jitter = (Uuid::new_v4().as_u128() % (capped as u128 / 5 + 1)) as u64;
+    capped + jitter
+}
+
+// Per-user durable queue of messages that couldn't be delivered yet
+pub struct Outbox<'a> {
+    store: &'a LocalStore,
+    document: String,
+}
+
+impl<'a> Outbox<'a> {
+    pub fn new(store: &'a LocalStore, owner: &PublicKey) -> Self {
+        Self {
+            store,
+            document: documents::user_document(documents::OUTBOX, owner),
+        }
+    }
+
+    fn load(&self) -> Result<OutboxDocument> {
+        self.store.load(&self.document)
+    }
+
+    fn save(&self, document: &OutboxDocument) -> Result<()> {
+        self.store.save(&self.document, document)
+    }
+
+    pub fn enqueue(&self, recipient: &PublicKey, content: &str, extras: MessageExtras, error: &str) -> Result<OutboxEntry> {
+        let now = now_secs();
+        let entry = OutboxEntry {
+            id: Uuid::new_v4().to_string(),
+            recipient: recipient.to_string(),
+            content: content.to_string(),
+            extras,
+            created_at: now,
+            attempts: 1,
+            next_attempt_at: now + backoff_secs(1),
+            last_error: Some(error.to_string()),
+            status: OutboxStatus::Pending,
+        };
+
+        let mut document = self.load()?;
+        document.entries.push(entry.clone());
+        self.save(&document)?;
+
+        Ok(entry)
+    }
+
+    pub fn entries(&self) -> Result<Vec<OutboxEntry>> {
+        Ok(self.load()?.entries)
+    }
+
+    pub fn due(&self, now: u64) -> Result<Vec<OutboxEntry>> {
+        Ok(self.load()?
+            .entries
+            .into_iter()
+            .filter(|entry| entry.status == OutboxStatus::Pending && entry.next_attempt_at <= now)
+            .collect())
+    }
+
+    // Returns the removed entry so callers can report it
+    pub fn cancel(&self, id: &str) -> Result<Option<OutboxEntry>> {
+        let mut document = self.load()?;
+        let Some(index) = document.entries.iter().position(|entry| entry.id == id) else {
+            return Ok(None);
+        };
+
+        let mut entry = document.entries.remove(index);
+        self.save(&document)?;
+
+        entry.status = OutboxStatus::Cancelled;
+        Ok(Some(entry))
+    }
+
+    // Make every pending or failed entry due right away (e.g. connectivity returned)
+    pub fn retry_all_now(&self) -> Result<usize> {
+        let mut document = self.load()?;
+        let now = now_secs();
+        let mut count = 0;
+
+        for entry in document.entries.iter_mut() {
+            if entry.status != OutboxStatus::Cancelled {
+                entry.status = OutboxStatus::Pending;
+                entry.next_attempt_at = now;
+                count += 1;
+            }
+        }
+
+        self.save(&document)?;
+        Ok(count)
+    }
+
+    pub fn mark_sent(&self, id: &str) -> Result<Option<OutboxEntry>> {
+        let mut document = self.load()?;
+        let Some(index) = document.entries.iter().position(|entry| entry.id == id) else {
+            return Ok(None);
+        };
+
+        let mut entry = document.entries.remove(index);
+        self.save(&document)?;
+
+        entry.status = OutboxStatus::Sent;
+        Ok(Some(entry))
+    }
+
+    pub fn mark_failed_attempt(&self, id: &str, error: &str) -> Result<Option<OutboxEntry>> {
+        let mut document = self.load()?;
+        let Some(entry) = document.entries.iter_mut().find(|entry| entry.id == id) else {
+            return Ok(None);
+        };
+
+        entry.attempts += 1;
+        entry.last_error = Some(error.to_string());
+        entry.next_attempt_at = now_secs() + backoff_secs(entry.attempts);
+        if entry.attempts >= MAX_ATTEMPTS {
+            entry.status = OutboxStatus::Failed;
+        }
+
+        let updated = entry.clone();
+        self.save(&document)?;
+        Ok(Some(updated))
+    }
+}
+
+pub fn emit_status(events: &dyn EventSink, entry: &OutboxEntry) {
+    if let Err(e) = events::emit(events, OUTBOX_STATUS_EVENT, &OutboxStatusEvent::from(entry)) {
+        tracing::warn!("⚠️  Failed to emit outbox event: {}", e);
+    }
+}
+
+// Background task retrying queued messages once they're due
+pub async fn run_outbox_worker(state: &AppState, events: &dyn EventSink) {
+    loop {
+        tokio::time::sleep(WORKER_TICK).await;
+
+        // The decoy account never sends anything
+        if state.is_decoy_session().await {
+            continue;
+        }
+        if let Err(e) = flush_due(state, events).await {
+            tracing::warn!("⚠️  Outbox flush failed: {}", e);
+        }
+    }
+}
+
+// Try every due entry once; hosts without the worker call this directly
+pub async fn flush_due(state: &AppState, events: &dyn EventSink) -> Result<()> {
+    let handler = match state.create_handler().await {
+        Ok(Some(handler)) => handler,
+        // Not signed in - nothing to send as
+        _ => return Ok(()),
+    };
+
+    let _guard = state.outbox_lock.lock().await;
+    let outbox = Outbox::new(&state.store, &handler.keypair.public_key());
+    let blocks = BlockList::new(&state.store, &handler.keypair.public_key());
+
+    for entry in outbox.due(now_secs())? {
+        let Ok(recipient) = PublicKey::try_from(entry.recipient.as_str()) else {
+            continue;
+        };
+        // Held while the recipient is blocked; sent if they're unblocked
+        if blocks.is_blocked(&entry.recipient) {
+            continue;
+        }
+
+        let updated = match handler.send_message_with_extras(&recipient, &entry.content, Some(&entry.extras)).await {
+            Ok(()) => {
+                tracing::info!("📤 Delivered queued message {}", entry.id);
+                outbox.mark_sent(&entry.id)?
+            }
+            Err(e) => outbox.mark_failed_attempt(&entry.id, &e.to_string())?,
+        };
+
+        if let Some(updated) = updated {
+            emit_status(events, &updated);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,44 @@
+// Plaintext padding, so the size of an encrypted message only tells a
+// homeserver which bucket its length falls in. The padded form is a
+// big-endian u32 length, the bytes, then zeros up to the bucket size.
+// Written from protocol version 4.
+use anyhow::{anyhow, Result};
+
+pub const PADDING_VERSION: u32 = 4;
+
+const LENGTH_PREFIX_BYTES: usize = 4;
+const BUCKETS: [usize; 3] = [256, 1024, 4096];
+// Past the largest bucket, sizes round up to a multiple of this
+const LARGE_STEP: usize = 4096;
+
+pub const fn padded_len(len: usize) -> usize {
+    let len = len + LENGTH_PREFIX_BYTES;
+    let mut index = 0;
+    while index < BUCKETS.len() {
+        if len <= BUCKETS[index] {
+            return BUCKETS[index];
+        }
+        index += 1;
+    }
+    len.div_ceil(LARGE_STEP) * LARGE_STEP
+}
+
+pub fn pad(bytes: &[u8]) -> Vec<u8> {
+    let mut padded = Vec::with_capacity(padded_len(bytes.len()));
+    padded.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+    padded.extend_from_slice(bytes);
+    padded.resize(padded_len(bytes.len()), 0);
+    padded
+}
+
+pub fn unpad(padded: &[u8]) -> Result<Vec<u8>> {
+    if padded.len() < LENGTH_PREFIX_BYTES {
+        return Err(anyhow!("Padded plaintext is too short"));
+    }
+    let (prefix, rest) = padded.split_at(LENGTH_PREFIX_BYTES);
+    let len = u32::from_be_bytes(prefix.try_into()?) as usize;
+    if len > rest.len() || rest[len..].iter().any(|byte| *byte != 0) {
+        return Err(anyhow!("Invalid plaintext padding"));
+    }
+    Ok(rest[..len].to_vec())
+}
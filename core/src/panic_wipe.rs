@@ -0,0 +1,111 @@
+// One irreversible wipe of everything this app keeps about the user:
+// the saved session's device secret, the local databases and documents,
+// logs, in-memory caches and, when asked, everything the user wrote under
+// /pub/private_messages/ on their homeserver.
+//
+// A wipe needs a fresh confirmation token, so a stray click or a replayed
+// call can't trigger one.
+use crate::error::{MessengerError, MessengerResult};
+use crate::logging;
+use crate::messaging::{AppState, PrivateMessageHandler};
+use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::sync::Mutex;
+
+const TOKEN_TTL_SECS: u64 = 2 * 60;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct WipeToken {
+    pub token: String,
+    pub expires_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct WipeReport {
+    pub remote_deleted: usize,
+    pub remote_failed: usize,
+    pub local_removed: usize,
+    // e.g. a log file another process still holds open
+    pub local_failed: usize,
+}
+
+// Only the latest token is valid
+static PENDING_TOKEN: Mutex<Option<WipeToken>> = Mutex::new(None);
+
+pub fn request_token(now: u64) -> WipeToken {
+    let mut bytes = [0u8; 16];
+    OsRng.fill_bytes(&mut bytes);
+    let token = WipeToken { token: hex::encode(bytes), expires_at: now + TOKEN_TTL_SECS };
+    *PENDING_TOKEN.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(token.clone());
+    token
+}
+
+// Tokens are single use, whether or not they match
+fn take_token(token: &str, now: u64) -> MessengerResult<()> {
+    let pending = PENDING_TOKEN.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).take();
+    match pending {
+        Some(pending) if pending.token == token && now <= pending.expires_at => Ok(()),
+        _ => Err(MessengerError::InvalidInput("Wipe confirmation expired or didn't match; request a new one".to_string())),
+    }
+}
+
+// `handler` is needed for `delete_remote`. Remote blobs go first, while
+// we still have a session; a blob that fails to delete doesn't stop the
+// local wipe.
+pub async fn wipe(
+    state: &AppState,
+    handler: Option<&PrivateMessageHandler>,
+    token: &str,
+    delete_remote: bool,
+    now: u64,
+) -> MessengerResult<WipeReport> {
+    take_token(token, now)?;
+    let mut report = WipeReport::default();
+
+    if delete_remote {
+        let handler = handler.ok_or(MessengerError::NotSignedIn)?;
+        let urls = handler.own_private_blobs().await.unwrap_or_else(|e| {
+            tracing::warn!("⚠️  Failed to list homeserver blobs to wipe: {}", e);
+            Vec::new()
+        });
+        for url in urls {
+            match handler.delete_blob(&url).await {
+                Ok(()) => report.remote_deleted += 1,
+                Err(e) => {
+                    tracing::warn!("⚠️  Failed to wipe {}: {}", logging::path(&url), e);
+                    report.remote_failed += 1;
+                }
+            }
+        }
+    }
+
+    // Sign out and drop everything held in memory
+    *state.keypair.lock().await = None;
+    *state.user_name.lock().await = None;
+    *state.is_signed_in.lock().await = false;
+    state.operations.cancel_all();
+    *state.storage.lock().await = None;
+    state.http_cache.clear();
+    state.watcher.clear();
+
+    let entries = match fs::read_dir(state.store.dir()) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(report),
+        Err(e) => return Err(MessengerError::Io(format!("Failed to read app data: {}", e))),
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let removed = if path.is_dir() { fs::remove_dir_all(&path) } else { fs::remove_file(&path) };
+        match removed {
+            Ok(()) => report.local_removed += 1,
+            Err(e) => {
+                tracing::warn!("⚠️  Failed to wipe {}: {}", path.display(), e);
+                report.local_failed += 1;
+            }
+        }
+    }
+
+    tracing::warn!("🧨 Wiped {} local items and {} homeserver blobs", report.local_removed, report.remote_deleted);
+    Ok(report)
+}
@@ -0,0 +1,110 @@
+// Opt-in "last active" presence. While enabled, a signed timestamp is
+// written to our homeserver every few minutes, encrypted for each contact
+// or public, and get_contact_presence reads the ones contacts publish.
+use crate::blocks::BlockList;
+use crate::documents;
+use crate::error::{ErrorContext, MessengerResult};
+use crate::local_store::LocalStore;
+use crate::messaging::{AppState, PrivateMessageHandler};
+use crate::storage::MANUAL_CONTACT_SOURCE;
+use anyhow::Result;
+use pkarr::PublicKey;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const PUBLISH_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PresenceVisibility {
+    // Encrypted separately for everyone we have a conversation with
+    #[default]
+    Contacts,
+    // One record anyone can read
+    Public,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default)]
+pub struct PresenceSettings {
+    pub enabled: bool,
+    #[serde(default)]
+    pub visibility: PresenceVisibility,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ContactPresence {
+    pub public_key: String,
+    // None when the contact doesn't share presence with us
+    pub last_active: Option<u64>,
+}
+
+fn settings_document(owner: &PublicKey) -> String {
+    documents::user_document(documents::PRESENCE, owner)
+}
+
+pub fn get_settings(store: &LocalStore, owner: &PublicKey) -> Result<PresenceSettings> {
+    store.load(&settings_document(owner))
+}
+
+pub fn set_settings(store: &LocalStore, owner: &PublicKey, settings: PresenceSettings) -> Result<()> {
+    store.save(&settings_document(owner), &settings)
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// Write our presence now according to `settings`
+pub async fn publish(state: &AppState, handler: &PrivateMessageHandler, settings: PresenceSettings) -> MessengerResult<()> {
+    if !settings.enabled {
+        return Ok(());
+    }
+
+    let result = match settings.visibility {
+        PresenceVisibility::Public => handler.publish_presence(None, now_secs()).await,
+        PresenceVisibility::Contacts => {
+            let blocked = BlockList::new(&state.store, &handler.keypair.public_key())
+                .blocked()
+                .err_context("Failed to load blocked contacts")?;
+            let mut keys = state.with_storage(|storage| {
+                let mut keys = storage.conversation_keys()?;
+                keys.extend(storage.contact_keys(MANUAL_CONTACT_SOURCE)?);
+                Ok(keys)
+            }).await?;
+            keys.sort();
+            keys.dedup();
+
+            let contacts: Vec<PublicKey> = keys.iter()
+                .filter(|key| !blocked.contains_key(*key))
+                .filter_map(|key| PublicKey::try_from(key.as_str()).ok())
+                .collect();
+            handler.publish_presence(Some(&contacts), now_secs()).await
+        }
+    };
+    result.err_context("Failed to publish presence")
+}
+
+// Background task refreshing our presence record while it's enabled
+pub async fn run_presence_worker(state: &AppState) {
+    loop {
+        tokio::time::sleep(PUBLISH_INTERVAL).await;
+
+        // The decoy account is never present
+        if state.is_decoy_session().await {
+            continue;
+        }
+        let handler = match state.create_handler().await {
+            Ok(Some(handler)) => handler,
+            // Not signed in - nobody to be present as
+            _ => continue,
+        };
+
+        let settings = get_settings(&state.store, &handler.keypair.public_key()).unwrap_or_default();
+        if let Err(e) = publish(state, &handler, settings).await {
+            tracing::warn!("⚠️  {}", e);
+        }
+    }
+}
@@ -0,0 +1,201 @@
+// Parsed pubky.app profiles of the people we follow, kept on disk so a
+// contact scan only refetches profile.json for entries that went stale.
+// A background job keeps the cache, and the contacts built from it,
+// fresh without the user having to scan.
+use crate::documents;
+use crate::error::{ErrorContext, MessengerResult};
+use crate::events::{self, EventSink};
+use crate::local_store::LocalStore;
+use crate::messaging::{AppState, FollowedUser, PrivateMessageHandler, PubkyProfile};
+use crate::names::NameResolver;
+use anyhow::Result;
+use pkarr::PublicKey;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub const PROFILE_TTL_SECS: u64 = 6 * 60 * 60;
+
+// How often the background job looks for stale profiles
+const REFRESH_INTERVAL: Duration = Duration::from_secs(30 * 60);
+// Profiles and avatars fetched at once, to stay out of message sync's way
+pub const REFRESH_CONCURRENCY: usize = 4;
+
+// The background job refreshed contacts; the payload is every followed user
+pub const CONTACTS_REFRESHED_EVENT: &str = "contacts-refreshed";
+
+// A contact's name or avatar changed since we last fetched their profile.
+// Usually harmless, but also what an impersonation attempt looks like.
+pub const PROFILE_CHANGED_EVENT: &str = "contact-profile-changed";
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CachedProfile {
+    // None when they have no profile we could parse
+    pub profile: Option<PubkyProfile>,
+    pub fetched_at: u64,
+}
+
+impl CachedProfile {
+    pub fn is_fresh(&self, now: u64) -> bool {
+        now.saturating_sub(self.fetched_at) < PROFILE_TTL_SECS
+    }
+}
+
+// Payload of the contact-profile-changed event
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ProfileChangedEvent {
+    pub pubky: String,
+    pub previous_name: Option<String>,
+    pub name: Option<String>,
+    pub name_changed: bool,
+    pub avatar_changed: bool,
+    pub detected_at: u64,
+}
+
+impl ProfileChangedEvent {
+    // None when nothing we alert on changed
+    fn between(pubky: &str, previous: Option<&PubkyProfile>, current: Option<&PubkyProfile>, detected_at: u64) -> Option<Self> {
+        let previous_name = previous.map(|profile| profile.name.clone());
+        let name = current.map(|profile| profile.name.clone());
+        let name_changed = previous_name != name;
+        let avatar_changed = previous.and_then(|profile| profile.image.as_ref()) != current.and_then(|profile| profile.image.as_ref());
+        (name_changed || avatar_changed).then(|| Self {
+            pubky: pubky.to_string(),
+            previous_name,
+            name,
+            name_changed,
+            avatar_changed,
+            detected_at,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct ProfileDocument {
+    profiles: HashMap<String, CachedProfile>,
+}
+
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// Per-user, since each user's contact scans fill their own cache
+#[derive(Clone)]
+pub struct ProfileCache {
+    store: LocalStore,
+    document: String,
+}
+
+impl ProfileCache {
+    pub fn new(store: LocalStore, owner: &PublicKey) -> Self {
+        Self {
+            store,
+            document: documents::user_document(documents::PROFILES, owner),
+        }
+    }
+
+    pub fn get(&self, pubky: &str) -> Option<CachedProfile> {
+        self.all().remove(pubky)
+    }
+
+    // Every cached entry, stale ones included
+    pub fn all(&self) -> HashMap<String, CachedProfile> {
+        self.store
+            .load::<ProfileDocument>(&self.document)
+            .map(|document| document.profiles)
+            .unwrap_or_default()
+    }
+
+    // Record profiles fetched at `fetched_at`, replacing older entries.
+    // Returns how they differ from what was cached; profiles seen for the
+    // first time aren't changes.
+    pub fn insert_all(&self, fetched_at: u64, profiles: &[(String, Option<PubkyProfile>)]) -> Result<Vec<ProfileChangedEvent>> {
+        if profiles.is_empty() {
+            return Ok(Vec::new());
+        }
+        self.store.update(&self.document, |document: &mut ProfileDocument| {
+            let mut changes = Vec::new();
+            for (pubky, profile) in profiles {
+                let previous = document.profiles.insert(pubky.clone(), CachedProfile { profile: profile.clone(), fetched_at });
+                if let Some(previous) = previous {
+                    changes.extend(ProfileChangedEvent::between(pubky, previous.profile.as_ref(), profile.as_ref(), fetched_at));
+                }
+            }
+            changes
+        })
+    }
+}
+
+// Everyone we follow with their profiles, also cached as contacts and
+// labelled with our names for them
+pub async fn refresh_contacts(state: &AppState, handler: &PrivateMessageHandler) -> MessengerResult<Vec<FollowedUser>> {
+    let mut users = handler.get_followed_users_with_profiles().await
+        .err_context("Failed to get followed users")?;
+
+    let now = now_secs();
+    let cached = state.with_storage(|storage| {
+        for user in &users {
+            storage.upsert_contact(&user.pubky, user.name.as_deref(), "follows", now)?;
+        }
+        Ok(())
+    }).await;
+    if let Err(e) = cached {
+        tracing::warn!("⚠️  Failed to cache contacts: {}", e);
+    }
+
+    // Show our own names for people over their profile names
+    if let Ok(names) = NameResolver::load(state).await {
+        for user in users.iter_mut() {
+            user.name = names.label(&user.pubky);
+        }
+    }
+    Ok(users)
+}
+
+// Background task refreshing the profiles and avatars of everyone we know
+pub async fn run_profile_refresh_worker(state: &AppState, events: &dyn EventSink) {
+    loop {
+        tokio::time::sleep(REFRESH_INTERVAL).await;
+
+        // Not signed in - nobody to refresh. The decoy account has nobody either.
+        if state.storage.lock().await.is_none() || state.is_decoy_session().await {
+            continue;
+        }
+        let handler = match state.create_handler().await {
+            Ok(Some(handler)) => handler,
+            _ => continue,
+        };
+
+        let mut pubkys = match state.with_storage(|storage| storage.all_contacts()).await {
+            Ok(contacts) => contacts.into_iter().map(|contact| contact.public_key).collect::<Vec<_>>(),
+            Err(e) => {
+                tracing::warn!("⚠️  Failed to load contacts: {}", e);
+                continue;
+            }
+        };
+        if let Ok(follow_urls) = handler.get_followed_users().await {
+            pubkys.extend(follow_urls.iter().filter_map(|url| url.split('/').last().map(|s| s.to_string())));
+        }
+        pubkys.sort();
+        pubkys.dedup();
+
+        let refreshed = handler.refresh_stale_profiles(&pubkys).await;
+        handler.refresh_avatars(&pubkys).await;
+        if refreshed == 0 {
+            continue;
+        }
+        tracing::debug!("🪪 Refreshed {} profiles", refreshed);
+
+        match refresh_contacts(state, &handler).await {
+            Ok(users) => {
+                if let Err(e) = events::emit(events, CONTACTS_REFRESHED_EVENT, &users) {
+                    tracing::warn!("⚠️  Failed to emit contacts event: {}", e);
+                }
+            }
+            Err(e) => tracing::warn!("⚠️  Failed to refresh contacts: {}", e),
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use crate::downloads::DownloadState;
+use crate::events::{self, EventSink};
+use serde::Serialize;
+use std::sync::{Arc, RwLock};
+
+// Reported while long-running work is underway, so the UI can show real
+// numbers instead of an indeterminate spinner
+pub const SYNC_PROGRESS_EVENT: &str = "sync-progress";
+pub const ATTACHMENT_UPLOAD_PROGRESS_EVENT: &str = "attachment-upload-progress";
+pub const ATTACHMENT_DOWNLOAD_PROGRESS_EVENT: &str = "attachment-download-progress";
+
+// Message blobs downloaded so far out of those missing from the cache
+#[derive(Serialize, Clone, Debug)]
+pub struct SyncProgress {
+    pub conversation: String,
+    pub fetched: usize,
+    pub total: usize,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct AttachmentUploadProgress {
+    pub name: String,
+    pub sent_bytes: u64,
+    pub total_bytes: u64,
+}
+
+// Also sent when a download changes state
+#[derive(Serialize, Clone, Debug)]
+pub struct AttachmentDownloadProgress {
+    pub id: String,
+    pub name: String,
+    pub state: DownloadState,
+    pub received_bytes: u64,
+    pub total_bytes: Option<u64>,
+}
+
+// Where handlers report progress, and anything else they notice along the
+// way (e.g. changed contact profiles). Shared by every handler made from
+// the same AppState; reports go nowhere until a sink is attached.
+#[derive(Clone, Default)]
+pub struct Progress {
+    sink: Arc<RwLock<Option<Arc<dyn EventSink>>>>,
+}
+
+impl Progress {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_sink(&self, sink: Option<Arc<dyn EventSink>>) {
+        *self.sink.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = sink;
+    }
+
+    pub fn report<T: Serialize>(&self, event: &str, payload: &T) {
+        let sink = self.sink.read().unwrap_or_else(|poisoned| poisoned.into_inner()).clone();
+        if let Some(sink) = sink {
+            if let Err(e) = events::emit(sink.as_ref(), event, payload) {
+                tracing::debug!("⚠️  Failed to report {}: {}", event, e);
+            }
+        }
+    }
+}
@@ -0,0 +1,103 @@
+// Protocol versions of everything we store on homeservers, and the
+// migrations that bring older objects up to date when they are read.
+//
+// Every object we write (message payloads, notifications, presence
+// records) carries the `protocol_version` it was written with. Readers
+// infer a version for objects from before the field existed, then run the
+// registered migrations in order, so the rest of the code only ever sees
+// the current shape. The highest version each peer has written is
+// recorded, so a format change (a ratchet, sealed sender, ...) can wait
+// until a peer is known to understand it.
+//
+// Versions:
+//   1 - bare JSON blobs, byte fields as arrays of numbers
+//   2 - CBOR envelopes with embedded msg_id and signed extras
+//   3 - explicit protocol_version on every object
+//   4 - message content and extras padded to size buckets
+use crate::documents;
+use crate::local_store::LocalStore;
+use anyhow::{anyhow, Result};
+use pkarr::PublicKey;
+use std::collections::HashMap;
+
+pub const PROTOCOL_VERSION: u32 = 4;
+
+// Oldest version we still write. Objects a bump didn't change are written
+// at this, so clients that don't know the newer versions keep reading them.
+pub const MIN_WRITE_VERSION: u32 = 3;
+
+// Version to write for a peer: the newest both of us understand
+pub fn negotiated_version(peer_version: Option<u32>) -> u32 {
+    peer_version.unwrap_or(MIN_WRITE_VERSION).clamp(MIN_WRITE_VERSION, PROTOCOL_VERSION)
+}
+
+// Upgrades an object written at version `from` to version `from + 1`
+pub struct Migration<V> {
+    pub from: u32,
+    pub description: &'static str,
+    pub upgrade: fn(&mut V) -> Result<()>,
+}
+
+// For version bumps that didn't change an object's shape
+pub fn unchanged<V>(_value: &mut V) -> Result<()> {
+    Ok(())
+}
+
+// Run `migrations` on `value`, written at `version`, until it is current.
+// Objects from a newer protocol than ours, or too old to upgrade, are errors.
+pub fn upgrade<V>(kind: &str, migrations: &[Migration<V>], value: &mut V, version: u32) -> Result<()> {
+    if version > PROTOCOL_VERSION {
+        return Err(anyhow!("Unsupported {} protocol version {}", kind, version));
+    }
+
+    for current in version..PROTOCOL_VERSION {
+        let migration = migrations
+            .iter()
+            .find(|migration| migration.from == current)
+            .ok_or_else(|| anyhow!("Can't upgrade {} from protocol version {}", kind, current))?;
+        (migration.upgrade)(value)
+            .map_err(|e| anyhow!("Failed to upgrade {} from protocol version {} ({}): {}", kind, current, migration.description, e))?;
+    }
+    Ok(())
+}
+
+fn peers_document(owner: &PublicKey) -> String {
+    documents::user_document(documents::PROTOCOL_PEERS, owner)
+}
+
+fn load_peers(store: &LocalStore, owner: &PublicKey) -> HashMap<String, u32> {
+    store.load(&peers_document(owner)).unwrap_or_default()
+}
+
+// Highest protocol version seen in anything `peer` wrote, if we've seen anything
+pub fn peer_version(store: &LocalStore, owner: &PublicKey, peer: &str) -> Option<u32> {
+    load_peers(store, owner).get(peer).copied()
+}
+
+// Recorded peer versions of one user, for the handler to pick what to write
+#[derive(Clone)]
+pub struct PeerVersions {
+    store: LocalStore,
+    owner: PublicKey,
+}
+
+impl PeerVersions {
+    pub fn new(store: &LocalStore, owner: &PublicKey) -> Self {
+        Self { store: store.clone(), owner: owner.clone() }
+    }
+
+    pub fn get(&self, peer: &str) -> Option<u32> {
+        peer_version(&self.store, &self.owner, peer)
+    }
+}
+
+// Returns whether this raised the recorded version
+pub fn record_peer_version(store: &LocalStore, owner: &PublicKey, peer: &str, version: u32) -> Result<bool> {
+    let mut peers = load_peers(store, owner);
+    if peers.get(peer).is_some_and(|seen| *seen >= version) {
+        return Ok(false);
+    }
+    peers.insert(peer.to_string(), version);
+    store.save(&peers_document(owner), &peers)?;
+    Ok(true)
+}
@@ -0,0 +1,152 @@
+// Optional push wake-ups for mobile, where polling drains the battery.
+//
+// The app registers with a UnifiedPush distributor (or an APNs/FCM relay)
+// and gets back an endpoint URL, which is published on our homeserver.
+// Whoever sends us a message POSTs the same constant ping to it after
+// writing the message, and the app syncs when it arrives. The relay sees
+// that someone pinged us and nothing else: no sender, message or
+// conversation. Anyone can read the endpoint and wake us, which costs one
+// sync pass.
+use crate::documents;
+use crate::error::MessengerError;
+use crate::local_store::LocalStore;
+use crate::net;
+use anyhow::{anyhow, Result};
+use pkarr::PublicKey;
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+// Body of every ping; it says nothing beyond "check your homeserver"
+const PUSH_PING: &[u8] = b"sync";
+const MAX_ENDPOINT_CHARS: usize = 2048;
+const PING_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Published at PUSH_RECORD_PATH
+#[derive(Serialize, Deserialize)]
+pub struct PushRecord {
+    #[serde(default)]
+    pub protocol_version: u32,
+    pub endpoint: String,
+}
+
+pub const PUSH_RECORD_PATH: &str = "/pub/private_messages/push.json";
+
+fn endpoint_document(owner: &PublicKey) -> String {
+    documents::user_document(documents::PUSH, owner)
+}
+
+// The endpoint we last published, so re-registering on every start (as
+// distributors ask apps to) only writes when it changed
+pub fn registered_endpoint(store: &LocalStore, owner: &PublicKey) -> Option<String> {
+    store.load::<Option<String>>(&endpoint_document(owner)).ok().flatten()
+}
+
+pub fn set_registered_endpoint(store: &LocalStore, owner: &PublicKey, endpoint: Option<&str>) -> Result<()> {
+    match endpoint {
+        Some(endpoint) => store.save(&endpoint_document(owner), &Some(endpoint)),
+        None => store.remove(&endpoint_document(owner)),
+    }
+}
+
+// Anything a contact's endpoint could use to reach our own machine or
+// network: loopback, private, link-local, unspecified and the like
+pub fn is_public_address(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                // Carrier-grade NAT, 100.64.0.0/10
+                || (a == 100 && (64..128).contains(&b)))
+        }
+        IpAddr::V6(ip) => {
+            if let Some(mapped) = ip.to_ipv4_mapped() {
+                return is_public_address(IpAddr::V4(mapped));
+            }
+            let first = ip.segments()[0];
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                // Unique local, fc00::/7
+                || (first & 0xfe00) == 0xfc00
+                // Link-local, fe80::/10
+                || (first & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+fn endpoint_error(message: &str) -> anyhow::Error {
+    anyhow!(MessengerError::InvalidInput(message.to_string()))
+}
+
+// Only https endpoints on public hosts, so the ping can't be pointed at
+// anything local. Hosts given by name are checked again when resolved.
+pub fn check_endpoint(endpoint: &str) -> Result<()> {
+    let url = reqwest::Url::parse(endpoint)
+        .map_err(|e| MessengerError::InvalidInput(format!("Invalid push endpoint: {}", e)))?;
+    if url.scheme() != "https" || endpoint.len() > MAX_ENDPOINT_CHARS {
+        return Err(endpoint_error("Push endpoint must be an https URL"));
+    }
+    match url.host() {
+        None => Err(endpoint_error("Push endpoint must be an https URL")),
+        Some(url::Host::Ipv4(ip)) if !is_public_address(IpAddr::V4(ip)) => Err(endpoint_error("Push endpoint must be a public host")),
+        Some(url::Host::Ipv6(ip)) if !is_public_address(IpAddr::V6(ip)) => Err(endpoint_error("Push endpoint must be a public host")),
+        Some(url::Host::Domain(domain)) if is_local_name(domain) => Err(endpoint_error("Push endpoint must be a public host")),
+        Some(_) => Ok(()),
+    }
+}
+
+fn is_local_name(domain: &str) -> bool {
+    let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+    domain == "localhost" || domain.ends_with(".localhost") || domain.ends_with(".local")
+}
+
+// Where to send the ping: every address the host resolves to has to be
+// public, and the request is pinned to the one checked so a second lookup
+// can't hand back something else
+async fn resolve_endpoint(url: &reqwest::Url) -> Result<SocketAddr> {
+    let host = url.host_str().ok_or_else(|| endpoint_error("Push endpoint must be an https URL"))?;
+    let port = url.port_or_known_default().unwrap_or(443);
+    let addresses: Vec<SocketAddr> = tokio::net::lookup_host((host.trim_start_matches('[').trim_end_matches(']'), port))
+        .await?
+        .collect();
+    if addresses.is_empty() || addresses.iter().any(|address| !is_public_address(address.ip())) {
+        return Err(endpoint_error("Push endpoint must be a public host"));
+    }
+    Ok(addresses[0])
+}
+
+// Never follows redirects, which could lead anywhere the checks above don't
+fn ping_client(host: &str, address: SocketAddr) -> Result<reqwest::Client> {
+    let mut builder = reqwest::Client::builder()
+        .timeout(PING_TIMEOUT)
+        .redirect(reqwest::redirect::Policy::none())
+        .resolve(host, address);
+    if let Some(proxy) = net::proxy() {
+        builder = builder.proxy(reqwest::Proxy::all(proxy.as_str())?);
+    }
+    Ok(builder.build()?)
+}
+
+// Wake whoever published `endpoint`
+pub async fn ping(endpoint: &str) -> Result<()> {
+    check_endpoint(endpoint)?;
+    let url = reqwest::Url::parse(endpoint)?;
+    let address = resolve_endpoint(&url).await?;
+    let response = ping_client(url.host_str().unwrap_or_default(), address)?
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/octet-stream")
+        .body(PUSH_PING)
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        return Err(anyhow!("Push endpoint answered {}", response.status()));
+    }
+    Ok(())
+}
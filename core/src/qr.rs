@@ -0,0 +1,76 @@
+// QR codes for exchanging pubkys in person: we render our own as a PNG and
+// read contacts' codes from a scanned or photographed image.
+use anyhow::{anyhow, Result};
+use image::{GrayImage, ImageFormat, Luma};
+use pkarr::PublicKey;
+use qrcode::{Color, EcLevel, QrCode};
+use std::io::Cursor;
+
+const URI_PREFIX: &str = "pubky://";
+
+// Pixels per QR module, and the quiet zone around the code in modules
+const MODULE_PIXELS: u32 = 8;
+const QUIET_ZONE_MODULES: u32 = 4;
+
+pub fn render_png(public_key: &PublicKey) -> Result<Vec<u8>> {
+    let data = format!("{}{}", URI_PREFIX, public_key);
+    let code = QrCode::with_error_correction_level(data.as_bytes(), EcLevel::M)
+        .map_err(|e| anyhow!("Failed to encode QR code: {}", e))?;
+
+    let modules = code.width() as u32;
+    let colors = code.to_colors();
+    let size = (modules + 2 * QUIET_ZONE_MODULES) * MODULE_PIXELS;
+
+    let image = GrayImage::from_fn(size, size, |x, y| {
+        let column = (x / MODULE_PIXELS).checked_sub(QUIET_ZONE_MODULES);
+        let row = (y / MODULE_PIXELS).checked_sub(QUIET_ZONE_MODULES);
+        let dark = match (column, row) {
+            (Some(column), Some(row)) if column < modules && row < modules => {
+                colors[(row * modules + column) as usize] == Color::Dark
+            }
+            _ => false,
+        };
+        Luma([if dark { 0 } else { 255 }])
+    });
+
+    let mut png = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .map_err(|e| anyhow!("Failed to write QR image: {}", e))?;
+    Ok(png)
+}
+
+// Accepts "pubky://<key>", "pk:<key>" or a bare key
+fn parse_pubky(text: &str) -> Option<PublicKey> {
+    let text = text.trim();
+    let key = text
+        .strip_prefix(URI_PREFIX)
+        .or_else(|| text.strip_prefix("pk:"))
+        .unwrap_or(text)
+        .trim_end_matches('/');
+    PublicKey::try_from(key).ok()
+}
+
+// First QR code in the image that holds a pubky
+pub fn parse_image(image_bytes: &[u8]) -> Result<PublicKey> {
+    let image = image::load_from_memory(image_bytes)
+        .map_err(|e| anyhow!("Unsupported image: {}", e))?
+        .to_luma8();
+
+    let mut prepared = rqrr::PreparedImage::prepare_from_greyscale(
+        image.width() as usize,
+        image.height() as usize,
+        |x, y| image.get_pixel(x as u32, y as u32).0[0],
+    );
+
+    let grids = prepared.detect_grids();
+    if grids.is_empty() {
+        return Err(anyhow!("No QR code found in image"));
+    }
+
+    grids
+        .iter()
+        .filter_map(|grid| grid.decode().ok())
+        .find_map(|(_, content)| parse_pubky(&content))
+        .ok_or_else(|| anyhow!("QR code doesn't contain a pubky"))
+}
@@ -0,0 +1,113 @@
+// Message blobs that broke the size limits or failed to decrypt. Each one
+// is retried with exponential backoff instead of on every poll, listed by
+// get_quarantined_messages, and a burst of new failures raises an event,
+// since it points at a key mismatch or someone flooding a conversation.
+use crate::documents;
+use crate::local_store::LocalStore;
+use anyhow::Result;
+use pkarr::PublicKey;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+pub const DECRYPTION_FAILURES_EVENT: &str = "decryption-failures-spiked";
+
+// New failures in one conversation fetch that count as a spike
+pub const SPIKE_THRESHOLD: usize = 10;
+
+const FIRST_RETRY_SECS: u64 = 60 * 60;
+const MAX_RETRY_SECS: u64 = 7 * 24 * 60 * 60;
+// The oldest failures are forgotten past this
+const MAX_ENTRIES: usize = 1000;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct QuarantinedMessage {
+    pub url: String,
+    pub conversation: String,
+    // Why the latest attempt failed
+    pub reason: String,
+    pub failures: u32,
+    pub first_failed_at: u64,
+    pub last_failed_at: u64,
+    // Not fetched again before this
+    pub retry_at: u64,
+}
+
+// Payload of the decryption-failures-spiked event
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DecryptionFailuresEvent {
+    pub conversation: String,
+    pub failures: usize,
+    pub detected_at: u64,
+}
+
+fn retry_delay(failures: u32) -> u64 {
+    FIRST_RETRY_SECS
+        .saturating_mul(1 << failures.saturating_sub(1).min(20))
+        .min(MAX_RETRY_SECS)
+}
+
+// Per user, by blob URL
+#[derive(Clone)]
+pub struct Quarantine {
+    store: LocalStore,
+    document: String,
+}
+
+impl Quarantine {
+    pub fn new(store: LocalStore, owner: &PublicKey) -> Self {
+        Self {
+            store,
+            document: documents::user_document(documents::QUARANTINE, owner),
+        }
+    }
+
+    pub fn entries(&self) -> HashMap<String, QuarantinedMessage> {
+        self.store.load(&self.document).unwrap_or_default()
+    }
+
+    // Most recent failures first
+    pub fn list(&self) -> Vec<QuarantinedMessage> {
+        let mut entries: Vec<QuarantinedMessage> = self.entries().into_values().collect();
+        entries.sort_by(|a, b| b.last_failed_at.cmp(&a.last_failed_at));
+        entries
+    }
+
+    // Returns whether this is the blob's first failure
+    pub fn record_failure(&self, url: &str, conversation: &str, reason: &str, now: u64) -> Result<bool> {
+        self.store.update(&self.document, |entries: &mut HashMap<String, QuarantinedMessage>| {
+            let entry = entries.entry(url.to_string()).or_insert_with(|| QuarantinedMessage {
+                url: url.to_string(),
+                conversation: conversation.to_string(),
+                reason: String::new(),
+                failures: 0,
+                first_failed_at: now,
+                last_failed_at: now,
+                retry_at: now,
+            });
+            entry.failures += 1;
+            entry.reason = reason.to_string();
+            entry.last_failed_at = now;
+            entry.retry_at = now.saturating_add(retry_delay(entry.failures));
+            let first = entry.failures == 1;
+
+            if entries.len() > MAX_ENTRIES {
+                if let Some(oldest) = entries.values().min_by_key(|entry| entry.last_failed_at).map(|entry| entry.url.clone()) {
+                    entries.remove(&oldest);
+                }
+            }
+            first
+        })
+    }
+
+    // Forget blobs that have since opened fine
+    pub fn release(&self, urls: &[String]) -> Result<()> {
+        if urls.is_empty() {
+            return Ok(());
+        }
+        self.store.update(&self.document, |entries: &mut HashMap<String, QuarantinedMessage>| {
+            for url in urls {
+                entries.remove(url);
+            }
+        })
+    }
+}
@@ -0,0 +1,96 @@
+use crate::documents;
+use crate::local_store::LocalStore;
+use anyhow::Result;
+use pkarr::PublicKey;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+pub const UNREAD_COUNTS_EVENT: &str = "unread-counts-changed";
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+struct ConversationReadState {
+    last_read: u64,
+    unread: usize,
+    latest_incoming: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct ReadStateDocument {
+    conversations: HashMap<String, ConversationReadState>,
+}
+
+// Data structure returned to the frontend
+#[derive(Serialize, Deserialize)]
+pub struct UnreadCounts {
+    pub per_contact: HashMap<String, usize>,
+    pub total: usize,
+}
+
+// Per-user last-read markers, updated whenever a conversation is loaded
+pub struct ReadState<'a> {
+    store: &'a LocalStore,
+    document: String,
+}
+
+impl<'a> ReadState<'a> {
+    pub fn new(store: &'a LocalStore, owner: &PublicKey) -> Self {
+        Self {
+            store,
+            document: documents::user_document(documents::READ_STATE, owner),
+        }
+    }
+
+    // Recount unread messages from when incoming messages arrived. Claimed
+    // timestamps would let a post-dated message stay unread after mark_read.
+    pub fn record_incoming(&self, conversation: &str, incoming_arrivals: &[u64]) -> Result<usize> {
+        self.store.update(&self.document, |document: &mut ReadStateDocument| {
+            let entry = document.conversations.entry(conversation.to_string()).or_default();
+            entry.unread = incoming_arrivals
+                .iter()
+                .filter(|arrived_at| **arrived_at > entry.last_read)
+                .count();
+            entry.latest_incoming = incoming_arrivals.iter().copied().max();
+            entry.unread
+        })
+    }
+
+    // Everything up to `read_until` counts as read
+    pub fn mark_read(&self, conversation: &str, read_until: u64) -> Result<()> {
+        self.store.update(&self.document, |document: &mut ReadStateDocument| {
+            let entry = document.conversations.entry(conversation.to_string()).or_default();
+            entry.last_read = entry.last_read.max(read_until);
+            entry.unread = 0;
+        })
+    }
+
+    pub fn mark_all_read(&self, read_until: u64) -> Result<()> {
+        self.store.update(&self.document, |document: &mut ReadStateDocument| {
+            for entry in document.conversations.values_mut() {
+                entry.last_read = entry.last_read.max(read_until);
+                entry.unread = 0;
+            }
+        })
+    }
+
+    pub fn last_read(&self, conversation: &str) -> Result<u64> {
+        let document: ReadStateDocument = self.store.load(&self.document)?;
+        Ok(document
+            .conversations
+            .get(conversation)
+            .map(|entry| entry.last_read)
+            .unwrap_or(0))
+    }
+
+    pub fn unread_counts(&self) -> Result<UnreadCounts> {
+        let document: ReadStateDocument = self.store.load(&self.document)?;
+        let per_contact: HashMap<String, usize> = document
+            .conversations
+            .into_iter()
+            .filter(|(_, entry)| entry.unread > 0)
+            .map(|(conversation, entry)| (conversation, entry.unread))
+            .collect();
+        let total = per_contact.values().sum();
+
+        Ok(UnreadCounts { per_contact, total })
+    }
+}
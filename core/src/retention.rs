@@ -0,0 +1,100 @@
+use crate::documents;
+use crate::error::{ErrorContext, MessengerResult};
+use crate::local_store::LocalStore;
+use crate::logging;
+use crate::messaging::{msg_id_from_url, AppState, PrivateMessageHandler};
+use anyhow::Result;
+use pkarr::PublicKey;
+use serde::{Deserialize, Serialize};
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+// How long our own message blobs stay on the homeserver; None keeps them forever
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default)]
+pub struct RetentionPolicy {
+    pub max_age_days: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CleanupCandidate {
+    pub url: String,
+    pub timestamp: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct CleanupReport {
+    pub dry_run: bool,
+    pub max_age_days: u32,
+    pub scanned: usize,
+    // Blobs old enough to remove (removed unless this was a dry run)
+    pub candidates: Vec<CleanupCandidate>,
+    pub deleted: usize,
+    pub failed: Vec<String>,
+}
+
+fn policy_document(owner: &PublicKey) -> String {
+    documents::user_document(documents::RETENTION, owner)
+}
+
+pub fn get_policy(store: &LocalStore, owner: &PublicKey) -> Result<RetentionPolicy> {
+    store.load(&policy_document(owner))
+}
+
+pub fn set_policy(store: &LocalStore, owner: &PublicKey, policy: RetentionPolicy) -> Result<()> {
+    store.save(&policy_document(owner), &policy)
+}
+
+// Find (and unless `dry_run`, delete) our message blobs older than
+// `max_age_days`. Saved messages are never touched, and the local cache
+// keeps its copy of everything removed remotely.
+pub async fn run_cleanup(
+    state: &AppState,
+    handler: &PrivateMessageHandler,
+    max_age_days: u32,
+    dry_run: bool,
+    now: u64,
+) -> MessengerResult<CleanupReport> {
+    let cutoff = now.saturating_sub(max_age_days as u64 * SECS_PER_DAY);
+
+    let urls = handler.own_message_blobs()
+        .await
+        .err_context("Failed to list message blobs")?;
+
+    let mut report = CleanupReport {
+        dry_run,
+        max_age_days,
+        scanned: urls.len(),
+        ..Default::default()
+    };
+
+    for url in urls {
+        // Prefer the cached timestamp, only fetch blobs we never synced
+        let msg_id = msg_id_from_url(&url);
+        let cached = state.with_storage(|storage| storage.message_timestamp(&msg_id)).await?;
+        let timestamp = match cached {
+            Some(timestamp) => Some(timestamp),
+            None => handler.message_blob_timestamp(&url).await.unwrap_or(None),
+        };
+
+        if let Some(timestamp) = timestamp.filter(|timestamp| *timestamp < cutoff) {
+            report.candidates.push(CleanupCandidate { url, timestamp });
+        }
+    }
+
+    if dry_run {
+        return Ok(report);
+    }
+
+    for candidate in &report.candidates {
+        match handler.delete_blob(&candidate.url).await {
+            Ok(()) => report.deleted += 1,
+            Err(e) => {
+                tracing::warn!("⚠️  Cleanup failed for {}: {}", logging::path(&candidate.url), e);
+                report.failed.push(candidate.url.clone());
+            }
+        }
+    }
+
+    tracing::info!("🧹 Removed {} of {} old message blobs", report.deleted, report.candidates.len());
+    Ok(report)
+}
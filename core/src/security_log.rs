@@ -0,0 +1,80 @@
+// Local audit trail of security-relevant events on the account: sign-ins,
+// restored sessions, key changes, verifications and decryption failures.
+// Entries are only ever appended; the oldest fall off past MAX_EVENTS so
+// the log can't grow without bound.
+use crate::documents;
+use crate::local_store::LocalStore;
+use anyhow::Result;
+use pkarr::PublicKey;
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const MAX_EVENTS: usize = 5000;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SecurityEventKind {
+    SignIn,
+    SessionRestored,
+    // This device's session was ended and its saved copy discarded
+    SessionRevoked,
+    KeyChanged,
+    ContactVerified,
+    VerificationRemoved,
+    DecryptionFailures,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SecurityEvent {
+    pub kind: SecurityEventKind,
+    pub timestamp: u64,
+    // The contact it concerns, if any
+    #[serde(default)]
+    pub contact: Option<String>,
+    #[serde(default)]
+    pub detail: Option<String>,
+}
+
+// Per user
+#[derive(Clone)]
+pub struct SecurityLog {
+    store: LocalStore,
+    document: String,
+}
+
+impl SecurityLog {
+    pub fn new(store: &LocalStore, owner: &PublicKey) -> Self {
+        Self {
+            store: store.clone(),
+            document: documents::user_document(documents::SECURITY_LOG, owner),
+        }
+    }
+
+    pub fn append(&self, kind: SecurityEventKind, contact: Option<&str>, detail: Option<&str>) -> Result<()> {
+        let event = SecurityEvent {
+            kind,
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+            contact: contact.map(str::to_string),
+            detail: detail.map(str::to_string),
+        };
+        self.store.update(&self.document, |events: &mut Vec<SecurityEvent>| {
+            events.push(event);
+            let excess = events.len().saturating_sub(MAX_EVENTS);
+            events.drain(..excess);
+        })
+    }
+
+    // For callers that shouldn't fail because the log couldn't be written
+    pub fn record(&self, kind: SecurityEventKind, contact: Option<&str>, detail: Option<&str>) {
+        if let Err(e) = self.append(kind, contact, detail) {
+            tracing::warn!("⚠️  Failed to record security event: {}", e);
+        }
+    }
+
+    // Most recent first
+    pub fn events(&self) -> Vec<SecurityEvent> {
+        let mut events: Vec<SecurityEvent> = self.store.load(&self.document).unwrap_or_default();
+        events.reverse();
+        events
+    }
+}
@@ -0,0 +1,236 @@
+// Every user-facing setting behind one get/update pair.
+//
+// Sections owned by another module (sync, network, retention, link
+// previews, webhook, logging, attachment cache and limits) are still
+// validated and persisted by that module; this one stores the sections
+// that have no other home and assembles the whole picture.
+use crate::attachment_cache::{self, AttachmentCacheSettings};
+use crate::attachment_limits::{self, AttachmentLimits};
+use crate::documents;
+use crate::error::MessengerError;
+use crate::image_pipeline::ImageSettings;
+use crate::link_preview;
+use crate::local_store::LocalStore;
+use crate::logging::{self, LogSettings};
+use crate::net::{self, NetworkSettings};
+use crate::retention::{self, RetentionPolicy};
+use crate::sync::{self, SyncSettings};
+use crate::webhook::{self, WebhookSettings};
+use anyhow::{anyhow, Result};
+use chrono::Timelike;
+use pkarr::PublicKey;
+use serde::{Deserialize, Serialize};
+
+const SETTINGS_DOCUMENT: &str = documents::SETTINGS;
+
+const MINUTES_PER_DAY: u16 = 24 * 60;
+
+const MAX_SOUND_NAME_CHARS: usize = 64;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct NotificationSettings {
+    pub enabled: bool,
+    // Show message text in notifications, not just the sender. Off keeps
+    // it off locked and shared screens.
+    pub show_preview: bool,
+    pub sound: bool,
+    // Platform sound to play, e.g. "Glass" on macOS; None for the default
+    #[serde(default)]
+    pub sound_name: Option<String>,
+    // Only notify for messages that mention us
+    pub mentions_only: bool,
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            show_preview: true,
+            sound: true,
+            sound_name: None,
+            mentions_only: false,
+        }
+    }
+}
+
+// Do not disturb, in local time. Messages still sync and unread counts
+// still update; only OS notifications and their sounds are held back.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QuietHoursSettings {
+    pub enabled: bool,
+    // Minutes after midnight; a start later than the end spans midnight
+    pub start_minute: u16,
+    pub end_minute: u16,
+    // Still notify for contacts whose keys the user verified
+    pub allow_verified: bool,
+}
+
+impl Default for QuietHoursSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            start_minute: 22 * 60,
+            end_minute: 7 * 60,
+            allow_verified: true,
+        }
+    }
+}
+
+impl QuietHoursSettings {
+    pub fn is_quiet_at(&self, minute_of_day: u16) -> bool {
+        if !self.enabled || self.start_minute == self.end_minute {
+            return false;
+        }
+        if self.start_minute < self.end_minute {
+            (self.start_minute..self.end_minute).contains(&minute_of_day)
+        } else {
+            minute_of_day >= self.start_minute || minute_of_day < self.end_minute
+        }
+    }
+
+    pub fn is_quiet_now(&self) -> bool {
+        let now = chrono::Local::now();
+        self.is_quiet_at((now.hour() * 60 + now.minute()) as u16)
+    }
+}
+
+// How the desktop app starts; the sync service runs either way
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StartupSettings {
+    pub launch_at_login: bool,
+    // Start in the tray without showing the window
+    pub start_hidden: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+struct LocalSettings {
+    #[serde(default)]
+    notifications: NotificationSettings,
+    #[serde(default)]
+    startup: StartupSettings,
+    #[serde(default)]
+    quiet_hours: QuietHoursSettings,
+    #[serde(default)]
+    images: ImageSettings,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Settings {
+    pub sync: SyncSettings,
+    pub network: NetworkSettings,
+    // Per user, so None while signed out
+    pub retention: Option<RetentionPolicy>,
+    pub link_previews: bool,
+    pub notifications: NotificationSettings,
+    pub startup: StartupSettings,
+    pub quiet_hours: QuietHoursSettings,
+    pub images: ImageSettings,
+    pub webhook: WebhookSettings,
+    pub logging: LogSettings,
+    pub attachment_cache: AttachmentCacheSettings,
+    pub attachment_limits: AttachmentLimits,
+}
+
+// Sections to change; the rest are left as they are
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct SettingsUpdate {
+    #[serde(default)]
+    pub sync: Option<SyncSettings>,
+    #[serde(default)]
+    pub network: Option<NetworkSettings>,
+    #[serde(default)]
+    pub retention: Option<RetentionPolicy>,
+    #[serde(default)]
+    pub link_previews: Option<bool>,
+    #[serde(default)]
+    pub notifications: Option<NotificationSettings>,
+    #[serde(default)]
+    pub startup: Option<StartupSettings>,
+    #[serde(default)]
+    pub quiet_hours: Option<QuietHoursSettings>,
+    #[serde(default)]
+    pub images: Option<ImageSettings>,
+    #[serde(default)]
+    pub webhook: Option<WebhookSettings>,
+    #[serde(default)]
+    pub logging: Option<LogSettings>,
+    #[serde(default)]
+    pub attachment_cache: Option<AttachmentCacheSettings>,
+    #[serde(default)]
+    pub attachment_limits: Option<AttachmentLimits>,
+}
+
+fn load_local(store: &LocalStore) -> LocalSettings {
+    store.load(SETTINGS_DOCUMENT).unwrap_or_default()
+}
+
+pub fn get(store: &LocalStore, owner: Option<&PublicKey>) -> Settings {
+    let local = load_local(store);
+    Settings {
+        sync: sync::get_settings(store),
+        network: net::get_settings(store),
+        retention: owner.map(|owner| retention::get_policy(store, owner).unwrap_or_default()),
+        link_previews: link_preview::is_enabled(store),
+        notifications: local.notifications,
+        startup: local.startup,
+        quiet_hours: local.quiet_hours,
+        images: local.images,
+        webhook: webhook::get_settings(store),
+        logging: logging::get_settings(store),
+        attachment_cache: attachment_cache::get_settings(store),
+        attachment_limits: attachment_limits::get_settings(store),
+    }
+}
+
+pub fn notification_settings(store: &LocalStore) -> NotificationSettings {
+    load_local(store).notifications
+}
+
+// A blank sound name means the default. Names are passed to the OS as they
+// are, so anything that looks like a path is refused.
+pub fn set_notification_settings(store: &LocalStore, notifications: NotificationSettings) -> Result<NotificationSettings> {
+    let sound_name = notifications.sound_name
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty());
+    if let Some(name) = &sound_name {
+        if name.chars().count() > MAX_SOUND_NAME_CHARS || name.contains(['/', '\\']) {
+            return Err(anyhow!(MessengerError::InvalidInput(format!("Invalid notification sound: {}", name))));
+        }
+    }
+    let notifications = NotificationSettings { sound_name, ..notifications };
+
+    store.update(SETTINGS_DOCUMENT, |local: &mut LocalSettings| local.notifications = notifications.clone())?;
+    Ok(notifications)
+}
+
+pub fn startup_settings(store: &LocalStore) -> StartupSettings {
+    load_local(store).startup
+}
+
+pub fn set_startup_settings(store: &LocalStore, startup: StartupSettings) -> Result<StartupSettings> {
+    store.update(SETTINGS_DOCUMENT, |local: &mut LocalSettings| local.startup = startup)?;
+    Ok(startup)
+}
+
+pub fn quiet_hours_settings(store: &LocalStore) -> QuietHoursSettings {
+    load_local(store).quiet_hours
+}
+
+pub fn set_quiet_hours_settings(store: &LocalStore, quiet_hours: QuietHoursSettings) -> Result<QuietHoursSettings> {
+    if quiet_hours.start_minute >= MINUTES_PER_DAY || quiet_hours.end_minute >= MINUTES_PER_DAY {
+        return Err(anyhow!(MessengerError::InvalidInput("Quiet hours must be times of day".to_string())));
+    }
+    store.update(SETTINGS_DOCUMENT, |local: &mut LocalSettings| local.quiet_hours = quiet_hours)?;
+    Ok(quiet_hours)
+}
+
+pub fn image_settings(store: &LocalStore) -> ImageSettings {
+    load_local(store).images
+}
+
+// Sizes and quality are clamped to what we allow
+pub fn set_image_settings(store: &LocalStore, images: ImageSettings) -> Result<ImageSettings> {
+    let images = images.clamped();
+    store.update(SETTINGS_DOCUMENT, |local: &mut LocalSettings| local.images = images)?;
+    Ok(images)
+}
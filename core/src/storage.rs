@@ -0,0 +1,643 @@
+use crate::crypto_compat::{CipherFormat, CURRENT_CIPHER_FORMAT};
+use crate::media_gallery::{self, MediaKind};
+use crate::messaging::{ChatMessage, Contact};
+use crate::verification::VerificationState;
+use anyhow::{anyhow, Result};
+use pkarr::Keypair;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+// Domain separation for the database key, derived from the user's secret key
+const STORAGE_KEY_CONTEXT: &str = "pubky-private-messenger 2025 local storage key v1";
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS messages (
+        id TEXT PRIMARY KEY,
+        conversation TEXT NOT NULL,
+        sender TEXT NOT NULL,
+        timestamp INTEGER NOT NULL,
+        verified INTEGER NOT NULL,
+        is_own INTEGER NOT NULL,
+        cipher_format TEXT NOT NULL,
+        payload TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS messages_by_conversation ON messages (conversation, timestamp);
+
+    CREATE TABLE IF NOT EXISTS contacts (
+        public_key TEXT PRIMARY KEY,
+        name TEXT,
+        source TEXT NOT NULL,
+        updated_at INTEGER NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS sync_cursors (
+        conversation TEXT PRIMARY KEY,
+        last_synced_at INTEGER NOT NULL,
+        remote_count INTEGER NOT NULL
+    );
+";
+
+// Applied in order on top of SCHEMA; PRAGMA user_version counts how many ran
+const MIGRATIONS: &[&str] = &[
+    // Our own name for a contact, set when adding them by hand
+    "ALTER TABLE contacts ADD COLUMN alias TEXT;",
+    // What a contact's keys looked like when we verified them
+    "ALTER TABLE contacts ADD COLUMN verified_fingerprint TEXT;
+     ALTER TABLE contacts ADD COLUMN verified_homeserver TEXT;
+     ALTER TABLE contacts ADD COLUMN verified_at INTEGER;
+     ALTER TABLE contacts ADD COLUMN verification_broken_at INTEGER;",
+    // When each message reached us; older rows only have the sender's claim
+    "ALTER TABLE messages ADD COLUMN received_at INTEGER;
+     UPDATE messages SET received_at = timestamp;
+     CREATE INDEX IF NOT EXISTS messages_by_arrival ON messages (conversation, received_at, timestamp);",
+    // What each message shares, for the media gallery. Older rows are
+    // tagged from their payload; new ones by media_gallery::media_kind.
+    "ALTER TABLE messages ADD COLUMN media_kind TEXT;
+     UPDATE messages SET media_kind = CASE
+         WHEN json_extract(payload, '$.attachment.mime') LIKE 'image/%'
+           OR json_extract(payload, '$.attachment.mime') LIKE 'video/%' THEN 'media'
+         WHEN json_extract(payload, '$.attachment') IS NOT NULL THEN 'file'
+         WHEN json_extract(payload, '$.link_preview') IS NOT NULL
+           OR json_extract(payload, '$.content') LIKE '%http://%'
+           OR json_extract(payload, '$.content') LIKE '%https://%' THEN 'link'
+     END;
+     CREATE INDEX IF NOT EXISTS messages_by_media ON messages (conversation, media_kind, timestamp);",
+];
+
+// How far a claimed timestamp may be from when the message could have been
+// written before it is flagged
+const TIMESTAMP_TOLERANCE_SECS: u64 = 10 * 60;
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// A message can't have been written after we received it, nor long before
+// our previous sync of its conversation, since that sync would have seen it
+fn timestamp_suspect(claimed: u64, received_at: u64, last_synced_at: Option<u64>) -> bool {
+    let post_dated = claimed > received_at.saturating_add(TIMESTAMP_TOLERANCE_SECS);
+    let backdated = last_synced_at.is_some_and(|synced| claimed.saturating_add(TIMESTAMP_TOLERANCE_SECS) < synced);
+    post_dated || backdated
+}
+
+// Where a conversation page ends: arrival, claimed time and id, the order
+// conversation_messages and conversation_page share
+pub fn page_cursor(message: &ChatMessage) -> String {
+    format!("{}:{}:{}", message.arrived_at(), message.timestamp, message.id)
+}
+
+pub fn parse_page_cursor(cursor: &str) -> Result<(u64, u64, &str)> {
+    let mut parts = cursor.splitn(3, ':');
+    let (Some(arrived_at), Some(timestamp), Some(id)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err(anyhow!("Invalid page cursor"));
+    };
+    let arrived_at = arrived_at.parse().map_err(|_| anyhow!("Invalid page cursor"))?;
+    let timestamp = timestamp.parse().map_err(|_| anyhow!("Invalid page cursor"))?;
+    Ok((arrived_at, timestamp, id))
+}
+
+// Contacts added by hand rather than discovered through follows or cards
+pub const MANUAL_CONTACT_SOURCE: &str = "manual";
+
+// A decrypted message as kept in the local cache
+#[derive(Clone)]
+pub struct StoredMessage {
+    pub message: ChatMessage,
+    pub cipher_format: CipherFormat,
+}
+
+// Last message and counts of a cached conversation
+pub struct StoredConversation {
+    pub conversation: String,
+    pub last_message: ChatMessage,
+    pub message_count: usize,
+    pub unverified_count: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct SyncCursor {
+    pub last_synced_at: u64,
+    pub remote_count: usize,
+}
+
+// A contacts row, used by backups
+#[derive(Serialize, Deserialize, Clone)]
+pub struct StoredContact {
+    pub public_key: String,
+    pub name: Option<String>,
+    #[serde(default)]
+    pub alias: Option<String>,
+    pub source: String,
+    pub updated_at: u64,
+    #[serde(default)]
+    pub verification: Option<ContactVerification>,
+}
+
+// Recorded when the user verified a contact's keys
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ContactVerification {
+    pub fingerprint: String,
+    pub homeserver: String,
+    pub verified_at: u64,
+    // Set once the keys no longer match what was verified
+    pub broken_at: Option<u64>,
+}
+
+fn verification_from_row(row: &rusqlite::Row, first: usize) -> rusqlite::Result<Option<ContactVerification>> {
+    let fingerprint: Option<String> = row.get(first)?;
+    let homeserver: Option<String> = row.get(first + 1)?;
+    let verified_at: Option<i64> = row.get(first + 2)?;
+    let broken_at: Option<i64> = row.get(first + 3)?;
+    Ok(match (fingerprint, homeserver, verified_at) {
+        (Some(fingerprint), Some(homeserver), Some(verified_at)) => Some(ContactVerification {
+            fingerprint,
+            homeserver,
+            verified_at: verified_at as u64,
+            broken_at: broken_at.map(|broken_at| broken_at as u64),
+        }),
+        _ => None,
+    })
+}
+
+// Encrypted (SQLCipher) per-user cache of decrypted messages, known contacts
+// and sync progress, so conversations open instantly and stay readable offline
+pub struct Storage {
+    connection: Connection,
+}
+
+impl Storage {
+    pub fn open(dir: &Path, keypair: &Keypair) -> Result<Self> {
+        fs::create_dir_all(dir)
+            .map_err(|e| anyhow!("Failed to create {}: {}", dir.display(), e))?;
+
+        let path = dir.join(format!("storage_{}.db", keypair.public_key()));
+        let connection = Connection::open(&path)
+            .map_err(|e| anyhow!("Failed to open {}: {}", path.display(), e))?;
+
+        // Raw key so SQLCipher skips its own passphrase KDF
+        let key = blake3::derive_key(STORAGE_KEY_CONTEXT, &keypair.secret_key());
+        connection.execute_batch(&format!("PRAGMA key = \"x'{}'\";", hex::encode(key)))?;
+        connection
+            .execute_batch(SCHEMA)
+            .map_err(|e| anyhow!("Failed to initialize local storage (wrong key?): {}", e))?;
+        migrate(&connection)?;
+
+        Ok(Self { connection })
+    }
+
+    pub fn message_ids(&self, conversation: &str) -> Result<HashSet<String>> {
+        let mut statement = self.connection.prepare("SELECT id FROM messages WHERE conversation = ?1")?;
+        let ids = statement
+            .query_map(params![conversation], |row| row.get(0))?
+            .collect::<rusqlite::Result<HashSet<String>>>()?;
+        Ok(ids)
+    }
+
+    // Returns the messages that weren't cached before, stamped with when
+    // they arrived. Restored messages keep the arrival time they had.
+    pub fn insert_messages(&self, conversation: &str, messages: &[StoredMessage]) -> Result<Vec<ChatMessage>> {
+        let now = now_secs();
+        let last_synced_at = self.sync_cursor(conversation)?.map(|cursor| cursor.last_synced_at);
+
+        let transaction = self.connection.unchecked_transaction()?;
+        let mut inserted = Vec::new();
+        {
+            let mut statement = transaction.prepare(
+                "INSERT OR IGNORE INTO messages
+                    (id, conversation, sender, timestamp, verified, is_own, cipher_format, payload, received_at, media_kind)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            )?;
+
+            for stored in messages {
+                let mut message = stored.message.clone();
+                if message.received_at.is_none() {
+                    message.received_at = Some(now);
+                    message.timestamp_suspect = !message.is_own_message
+                        && timestamp_suspect(message.timestamp, now, last_synced_at);
+                }
+                let changed = statement.execute(params![
+                    message.id,
+                    conversation,
+                    message.sender,
+                    message.timestamp as i64,
+                    message.verified,
+                    message.is_own_message,
+                    stored.cipher_format.as_str(),
+                    serde_json::to_string(&message)?,
+                    message.received_at.map(|received_at| received_at as i64),
+                    media_gallery::media_kind(&message).map(|kind| kind.as_str()),
+                ])?;
+                if changed > 0 {
+                    inserted.push(message);
+                }
+            }
+        }
+        transaction.commit()?;
+        Ok(inserted)
+    }
+
+    // In arrival order, then by claimed timestamp among messages that
+    // arrived together, so a sender can't move a message back in time
+    pub fn conversation_messages(&self, conversation: &str) -> Result<Vec<StoredMessage>> {
+        let mut statement = self.connection.prepare(
+            "SELECT cipher_format, payload FROM messages WHERE conversation = ?1
+             ORDER BY COALESCE(received_at, timestamp) ASC, timestamp ASC, id ASC",
+        )?;
+        let rows = statement
+            .query_map(params![conversation], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        rows.into_iter().map(|(format, payload)| decode_message(&format, &payload)).collect()
+    }
+
+    pub fn message_timestamp(&self, id: &str) -> Result<Option<u64>> {
+        let timestamp = self
+            .connection
+            .query_row("SELECT timestamp FROM messages WHERE id = ?1", params![id], |row| row.get::<_, i64>(0))
+            .optional()?;
+        Ok(timestamp.map(|timestamp| timestamp as u64))
+    }
+
+    // Every cached message with its conversation, for backups
+    pub fn all_messages(&self) -> Result<Vec<(String, StoredMessage)>> {
+        let mut statement = self.connection.prepare(
+            "SELECT conversation, cipher_format, payload FROM messages ORDER BY conversation, timestamp ASC",
+        )?;
+        let rows = statement
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        rows.into_iter()
+            .map(|(conversation, format, payload)| Ok((conversation, decode_message(&format, &payload)?)))
+            .collect()
+    }
+
+    // Up to `limit` messages before `before` (a page_cursor; newest when
+    // None), oldest first, plus whether anything older remains. Pages follow
+    // the order of conversation_messages, so scrolling never skips or
+    // repeats a message whatever its claimed timestamp.
+    pub fn conversation_page(&self, conversation: &str, limit: usize, before: Option<(u64, u64, &str)>) -> Result<(Vec<StoredMessage>, bool)> {
+        let (before_arrival, before_timestamp, before_id) = before
+            .map(|(arrived_at, timestamp, id)| (arrived_at as i64, timestamp as i64, id))
+            .unwrap_or((i64::MAX, i64::MAX, ""));
+        let mut statement = self.connection.prepare(
+            "SELECT cipher_format, payload FROM messages
+             WHERE conversation = ?1
+               AND (COALESCE(received_at, timestamp), timestamp, id) < (?2, ?3, ?4)
+             ORDER BY COALESCE(received_at, timestamp) DESC, timestamp DESC, id DESC LIMIT ?5",
+        )?;
+        let mut rows = statement
+            .query_map(params![conversation, before_arrival, before_timestamp, before_id, limit as i64 + 1], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let has_more = rows.len() > limit;
+        rows.truncate(limit);
+        rows.reverse();
+
+        let messages = rows.into_iter()
+            .map(|(format, payload)| decode_message(&format, &payload))
+            .collect::<Result<Vec<_>>>()?;
+        Ok((messages, has_more))
+    }
+
+    // Up to `limit` messages sharing `kind`, newest first, older than
+    // `before` (a claimed timestamp and id), plus whether more remain
+    pub fn media_page(&self, conversation: &str, kind: MediaKind, limit: usize, before: Option<(u64, &str)>) -> Result<(Vec<StoredMessage>, bool)> {
+        let (before_timestamp, before_id) = before
+            .map(|(timestamp, id)| (timestamp as i64, id))
+            .unwrap_or((i64::MAX, ""));
+        let mut statement = self.connection.prepare(
+            "SELECT cipher_format, payload FROM messages
+             WHERE conversation = ?1 AND media_kind = ?2
+               AND (timestamp < ?3 OR (timestamp = ?3 AND id < ?4))
+             ORDER BY timestamp DESC, id DESC LIMIT ?5",
+        )?;
+        let mut rows = statement
+            .query_map(params![conversation, kind.as_str(), before_timestamp, before_id, limit as i64 + 1], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let has_more = rows.len() > limit;
+        rows.truncate(limit);
+        let messages = rows.into_iter()
+            .map(|(format, payload)| decode_message(&format, &payload))
+            .collect::<Result<Vec<_>>>()?;
+        Ok((messages, has_more))
+    }
+
+    pub fn conversation_summaries(&self) -> Result<Vec<StoredConversation>> {
+        let mut statement = self.connection.prepare(
+            "SELECT m.conversation, m.payload, counts.total, counts.unverified
+             FROM messages m
+             JOIN (
+                SELECT conversation, COUNT(*) AS total, SUM(verified = 0) AS unverified,
+                       MAX(COALESCE(received_at, timestamp)) AS newest
+                FROM messages GROUP BY conversation
+             ) counts ON counts.conversation = m.conversation AND counts.newest = COALESCE(m.received_at, m.timestamp)
+             GROUP BY m.conversation
+             ORDER BY counts.newest DESC",
+        )?;
+        let rows = statement
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, i64>(2)?,
+                    row.get::<_, i64>(3)?,
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        rows.into_iter()
+            .map(|(conversation, payload, total, unverified)| {
+                Ok(StoredConversation {
+                    conversation,
+                    last_message: serde_json::from_str(&payload)?,
+                    message_count: total as usize,
+                    unverified_count: unverified as usize,
+                })
+            })
+            .collect()
+    }
+
+    pub fn conversation_keys(&self) -> Result<Vec<String>> {
+        let mut statement = self.connection.prepare("SELECT DISTINCT conversation FROM messages")?;
+        let keys = statement
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        Ok(keys)
+    }
+
+    // `source` records where we learned about the contact (follows, shared card, ...)
+    pub fn upsert_contact(&self, public_key: &str, name: Option<&str>, source: &str, now: u64) -> Result<()> {
+        self.connection.execute(
+            "INSERT INTO contacts (public_key, name, source, updated_at) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(public_key) DO UPDATE SET
+                name = COALESCE(excluded.name, contacts.name),
+                updated_at = excluded.updated_at",
+            params![public_key, name, source, now as i64],
+        )?;
+        Ok(())
+    }
+
+    // Add (or re-add) a contact by hand; unlike discovered ones these show up
+    // in the conversation list before any message was exchanged
+    pub fn add_contact(&self, public_key: &str, name: Option<&str>, alias: Option<&str>, now: u64) -> Result<()> {
+        self.connection.execute(
+            "INSERT INTO contacts (public_key, name, alias, source, updated_at) VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT(public_key) DO UPDATE SET
+                name = COALESCE(excluded.name, contacts.name),
+                alias = COALESCE(excluded.alias, contacts.alias),
+                source = excluded.source,
+                updated_at = excluded.updated_at",
+            params![public_key, name, alias, MANUAL_CONTACT_SOURCE, now as i64],
+        )?;
+        Ok(())
+    }
+
+    // Set or (with None) clear our own name for a contact, adding them if
+    // we didn't know them yet
+    pub fn set_contact_alias(&self, public_key: &str, alias: Option<&str>, now: u64) -> Result<()> {
+        self.connection.execute(
+            "INSERT INTO contacts (public_key, alias, source, updated_at) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(public_key) DO UPDATE SET
+                alias = excluded.alias,
+                updated_at = excluded.updated_at",
+            params![public_key, alias, MANUAL_CONTACT_SOURCE, now as i64],
+        )?;
+        Ok(())
+    }
+
+    pub fn contact_keys(&self, source: &str) -> Result<Vec<String>> {
+        let mut statement = self.connection.prepare("SELECT public_key FROM contacts WHERE source = ?1")?;
+        let keys = statement
+            .query_map(params![source], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        Ok(keys)
+    }
+
+    // Contacts labelled with our alias where we set one, else their profile name
+    pub fn contacts(&self) -> Result<Vec<Contact>> {
+        let mut statement = self.connection.prepare(
+            "SELECT public_key, COALESCE(alias, name),
+                    verified_fingerprint, verified_homeserver, verified_at, verification_broken_at
+             FROM contacts ORDER BY updated_at DESC",
+        )?;
+        let contacts = statement
+            .query_map([], |row| {
+                Ok(Contact {
+                    public_key: row.get(0)?,
+                    name: row.get(1)?,
+                    last_message: None,
+                    last_message_time: None,
+                    health: None,
+                    verification: verification_from_row(row, 2)?.as_ref().map(VerificationState::of),
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<Contact>>>()?;
+        Ok(contacts)
+    }
+
+    pub fn all_contacts(&self) -> Result<Vec<StoredContact>> {
+        let mut statement = self.connection.prepare(
+            "SELECT public_key, name, alias, source, updated_at,
+                    verified_fingerprint, verified_homeserver, verified_at, verification_broken_at
+             FROM contacts",
+        )?;
+        let contacts = statement
+            .query_map([], |row| {
+                Ok(StoredContact {
+                    public_key: row.get(0)?,
+                    name: row.get(1)?,
+                    alias: row.get(2)?,
+                    source: row.get(3)?,
+                    updated_at: row.get::<_, i64>(4)? as u64,
+                    verification: verification_from_row(row, 5)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<StoredContact>>>()?;
+        Ok(contacts)
+    }
+
+    // Keeps whichever copy of a contact was updated last
+    pub fn restore_contact(&self, contact: &StoredContact) -> Result<()> {
+        let verification = contact.verification.as_ref();
+        self.connection.execute(
+            "INSERT INTO contacts
+                (public_key, name, alias, source, updated_at,
+                 verified_fingerprint, verified_homeserver, verified_at, verification_broken_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+             ON CONFLICT(public_key) DO UPDATE SET
+                name = excluded.name,
+                alias = excluded.alias,
+                source = excluded.source,
+                updated_at = excluded.updated_at,
+                verified_fingerprint = excluded.verified_fingerprint,
+                verified_homeserver = excluded.verified_homeserver,
+                verified_at = excluded.verified_at,
+                verification_broken_at = excluded.verification_broken_at
+             WHERE excluded.updated_at > contacts.updated_at",
+            params![
+                contact.public_key,
+                contact.name,
+                contact.alias,
+                contact.source,
+                contact.updated_at as i64,
+                verification.map(|v| v.fingerprint.clone()),
+                verification.map(|v| v.homeserver.clone()),
+                verification.map(|v| v.verified_at as i64),
+                verification.and_then(|v| v.broken_at).map(|broken_at| broken_at as i64),
+            ],
+        )?;
+        Ok(())
+    }
+
+    // Record (or with None, forget) that the user verified a contact's
+    // keys; adds the contact if we didn't know them yet
+    pub fn set_contact_verification(&self, public_key: &str, verification: Option<&ContactVerification>, now: u64) -> Result<()> {
+        self.connection.execute(
+            "INSERT INTO contacts
+                (public_key, source, updated_at, verified_fingerprint, verified_homeserver, verified_at, verification_broken_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+             ON CONFLICT(public_key) DO UPDATE SET
+                updated_at = excluded.updated_at,
+                verified_fingerprint = excluded.verified_fingerprint,
+                verified_homeserver = excluded.verified_homeserver,
+                verified_at = excluded.verified_at,
+                verification_broken_at = excluded.verification_broken_at",
+            params![
+                public_key,
+                MANUAL_CONTACT_SOURCE,
+                now as i64,
+                verification.map(|v| v.fingerprint.clone()),
+                verification.map(|v| v.homeserver.clone()),
+                verification.map(|v| v.verified_at as i64),
+                verification.and_then(|v| v.broken_at).map(|broken_at| broken_at as i64),
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn mark_verification_broken(&self, public_key: &str, now: u64) -> Result<()> {
+        self.connection.execute(
+            "UPDATE contacts SET verification_broken_at = ?2
+             WHERE public_key = ?1 AND verified_at IS NOT NULL AND verification_broken_at IS NULL",
+            params![public_key, now as i64],
+        )?;
+        Ok(())
+    }
+
+    pub fn contact_verification(&self, public_key: &str) -> Result<Option<ContactVerification>> {
+        let verification = self
+            .connection
+            .query_row(
+                "SELECT verified_fingerprint, verified_homeserver, verified_at, verification_broken_at
+                 FROM contacts WHERE public_key = ?1",
+                params![public_key],
+                |row| verification_from_row(row, 0),
+            )
+            .optional()?;
+        Ok(verification.flatten())
+    }
+
+    // Every contact the user verified, broken or not
+    pub fn contact_verifications(&self) -> Result<HashMap<String, ContactVerification>> {
+        let mut statement = self.connection.prepare(
+            "SELECT public_key, verified_fingerprint, verified_homeserver, verified_at, verification_broken_at
+             FROM contacts WHERE verified_at IS NOT NULL",
+        )?;
+        let verifications = statement
+            .query_map([], |row| Ok((row.get::<_, String>(0)?, verification_from_row(row, 1)?)))?
+            .filter_map(|row| row.map(|(public_key, verification)| verification.map(|v| (public_key, v))).transpose())
+            .collect::<rusqlite::Result<HashMap<String, ContactVerification>>>()?;
+        Ok(verifications)
+    }
+
+    pub fn contact_name(&self, public_key: &str) -> Result<Option<String>> {
+        let name = self
+            .connection
+            .query_row("SELECT COALESCE(alias, name) FROM contacts WHERE public_key = ?1", params![public_key], |row| row.get(0))
+            .optional()?;
+        Ok(name.flatten())
+    }
+
+    pub fn set_sync_cursor(&self, conversation: &str, cursor: SyncCursor) -> Result<()> {
+        self.connection.execute(
+            "INSERT OR REPLACE INTO sync_cursors (conversation, last_synced_at, remote_count) VALUES (?1, ?2, ?3)",
+            params![conversation, cursor.last_synced_at as i64, cursor.remote_count as i64],
+        )?;
+        Ok(())
+    }
+
+    pub fn sync_cursor(&self, conversation: &str) -> Result<Option<SyncCursor>> {
+        let cursor = self
+            .connection
+            .query_row(
+                "SELECT last_synced_at, remote_count FROM sync_cursors WHERE conversation = ?1",
+                params![conversation],
+                |row| {
+                    Ok(SyncCursor {
+                        last_synced_at: row.get::<_, i64>(0)? as u64,
+                        remote_count: row.get::<_, i64>(1)? as usize,
+                    })
+                },
+            )
+            .optional()?;
+        Ok(cursor)
+    }
+
+    pub fn all_sync_cursors(&self) -> Result<HashMap<String, SyncCursor>> {
+        let mut statement = self.connection.prepare("SELECT conversation, last_synced_at, remote_count FROM sync_cursors")?;
+        let cursors = statement
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    SyncCursor {
+                        last_synced_at: row.get::<_, i64>(1)? as u64,
+                        remote_count: row.get::<_, i64>(2)? as usize,
+                    },
+                ))
+            })?
+            .collect::<rusqlite::Result<HashMap<String, SyncCursor>>>()?;
+        Ok(cursors)
+    }
+
+    // When any conversation last synced successfully
+    pub fn last_synced_at(&self) -> Result<Option<u64>> {
+        let latest: Option<i64> = self
+            .connection
+            .query_row("SELECT MAX(last_synced_at) FROM sync_cursors", [], |row| row.get(0))?;
+        Ok(latest.map(|timestamp| timestamp as u64))
+    }
+}
+
+fn decode_message(format: &str, payload: &str) -> Result<StoredMessage> {
+    Ok(StoredMessage {
+        message: serde_json::from_str(payload)?,
+        cipher_format: CipherFormat::parse(format).unwrap_or(CURRENT_CIPHER_FORMAT),
+    })
+}
+
+fn migrate(connection: &Connection) -> Result<()> {
+    let applied: usize = connection.query_row("PRAGMA user_version", [], |row| row.get::<_, i64>(0))? as usize;
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(applied) {
+        let transaction = connection.unchecked_transaction()?;
+        transaction
+            .execute_batch(migration)
+            .map_err(|e| anyhow!("Failed to migrate local storage to version {}: {}", index + 1, e))?;
+        transaction.execute_batch(&format!("PRAGMA user_version = {};", index + 1))?;
+        transaction.commit()?;
+    }
+    Ok(())
+}
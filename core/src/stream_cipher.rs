@@ -0,0 +1,240 @@
+// Chunked encryption for attachments too big to hold in memory.
+//
+// A stream is a header (magic, chunk size, random 15-byte nonce prefix)
+// followed by frames: a u32 BE length, its top bit set on the final frame,
+// then one chunk sealed with XSalsa20-Poly1305. Chunk `i` is sealed under
+// the prefix, the final flag and `i`, so chunks can't be reordered,
+// dropped, or cut off at a chunk boundary without failing to open.
+use crate::error::MessengerError;
+use anyhow::{anyhow, Result};
+use crypto_secretbox::aead::generic_array::GenericArray;
+use crypto_secretbox::aead::{Aead, KeyInit};
+use crypto_secretbox::XSalsa20Poly1305;
+use rand_core::{OsRng, RngCore};
+use std::path::Path;
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncWriteExt, BufWriter};
+
+const MAGIC: &[u8; 4] = b"PMS1";
+pub const CHUNK_BYTES: usize = 64 * 1024;
+// Largest chunk size we accept from someone else's stream
+const MAX_CHUNK_BYTES: usize = 16 * 1024 * 1024;
+
+const PREFIX_LEN: usize = 15;
+const HEADER_LEN: usize = MAGIC.len() + 4 + PREFIX_LEN;
+const TAG_LEN: usize = 16;
+const FINAL_FLAG: u32 = 1 << 31;
+
+// Size of a sealed stream of `plain_len` bytes. Every chunk but the last is
+// full; the last may be empty.
+pub const fn sealed_len(plain_len: u64) -> u64 {
+    let chunks = plain_len / CHUNK_BYTES as u64 + 1;
+    HEADER_LEN as u64 + plain_len + chunks * (4 + TAG_LEN) as u64
+}
+
+fn nonce(prefix: &[u8; PREFIX_LEN], index: u64, last: bool) -> [u8; 24] {
+    let mut nonce = [0u8; 24];
+    nonce[..PREFIX_LEN].copy_from_slice(prefix);
+    nonce[PREFIX_LEN] = last as u8;
+    nonce[PREFIX_LEN + 1..].copy_from_slice(&index.to_be_bytes());
+    nonce
+}
+
+pub struct Sealer {
+    cipher: XSalsa20Poly1305,
+    prefix: [u8; PREFIX_LEN],
+    index: u64,
+    finished: bool,
+}
+
+impl Sealer {
+    // The sealer and the header to write before its frames
+    pub fn new(key: &[u8; 32]) -> (Self, Vec<u8>) {
+        let mut prefix = [0u8; PREFIX_LEN];
+        OsRng.fill_bytes(&mut prefix);
+
+        let mut header = Vec::with_capacity(HEADER_LEN);
+        header.extend_from_slice(MAGIC);
+        header.extend_from_slice(&(CHUNK_BYTES as u32).to_be_bytes());
+        header.extend_from_slice(&prefix);
+
+        let sealer = Self { cipher: XSalsa20Poly1305::new(key.into()), prefix, index: 0, finished: false };
+        (sealer, header)
+    }
+
+    // One framed chunk of at most CHUNK_BYTES
+    pub fn seal(&mut self, chunk: &[u8], last: bool) -> Result<Vec<u8>> {
+        if self.finished || chunk.len() > CHUNK_BYTES {
+            return Err(anyhow!(MessengerError::Crypto("Invalid chunk for stream".to_string())));
+        }
+        let nonce = nonce(&self.prefix, self.index, last);
+        let sealed = self.cipher
+            .encrypt(GenericArray::from_slice(&nonce), chunk)
+            .map_err(|_| anyhow!(MessengerError::Crypto("Stream encryption failed".to_string())))?;
+        self.index += 1;
+        self.finished = last;
+
+        let length = (sealed.len() as u32) | (if last { FINAL_FLAG } else { 0 });
+        let mut frame = Vec::with_capacity(4 + sealed.len());
+        frame.extend_from_slice(&length.to_be_bytes());
+        frame.extend_from_slice(&sealed);
+        Ok(frame)
+    }
+}
+
+// Opens a stream fed in pieces of any size
+pub struct Opener {
+    cipher: XSalsa20Poly1305,
+    // From the header, once it has arrived
+    header: Option<([u8; PREFIX_LEN], usize)>,
+    buffer: Vec<u8>,
+    index: u64,
+    finished: bool,
+}
+
+impl Opener {
+    pub fn new(key: &[u8; 32]) -> Self {
+        Self { cipher: XSalsa20Poly1305::new(key.into()), header: None, buffer: Vec::new(), index: 0, finished: false }
+    }
+
+    // Whatever plaintext `bytes` completes
+    pub fn push(&mut self, bytes: &[u8]) -> Result<Vec<u8>> {
+        self.buffer.extend_from_slice(bytes);
+        let mut plain = Vec::new();
+        let mut read = 0;
+
+        if self.header.is_none() {
+            if self.buffer.len() < HEADER_LEN {
+                return Ok(plain);
+            }
+            let (magic, rest) = self.buffer.split_at(MAGIC.len());
+            let (chunk_bytes, prefix) = rest.split_at(4);
+            let chunk_bytes = u32::from_be_bytes(chunk_bytes.try_into()?) as usize;
+            if magic != MAGIC || chunk_bytes == 0 || chunk_bytes > MAX_CHUNK_BYTES {
+                return Err(anyhow!(MessengerError::Crypto("Not a supported encrypted stream".to_string())));
+            }
+            self.header = Some((prefix[..PREFIX_LEN].try_into()?, chunk_bytes));
+            read = HEADER_LEN;
+        }
+        let Some((prefix, chunk_bytes)) = self.header else {
+            return Ok(plain);
+        };
+
+        while self.buffer.len() - read >= 4 {
+            let length = u32::from_be_bytes(self.buffer[read..read + 4].try_into()?);
+            let last = length & FINAL_FLAG != 0;
+            let length = (length & !FINAL_FLAG) as usize;
+            if self.finished || !(TAG_LEN..=chunk_bytes + TAG_LEN).contains(&length) {
+                return Err(anyhow!(MessengerError::Crypto("Encrypted stream is corrupt".to_string())));
+            }
+            if self.buffer.len() - read - 4 < length {
+                break;
+            }
+
+            let nonce = nonce(&prefix, self.index, last);
+            let sealed = &self.buffer[read + 4..read + 4 + length];
+            let chunk = self.cipher
+                .decrypt(GenericArray::from_slice(&nonce), sealed)
+                .map_err(|_| anyhow!(MessengerError::Crypto("Stream decryption failed".to_string())))?;
+            plain.extend_from_slice(&chunk);
+            self.index += 1;
+            self.finished = last;
+            read += 4 + length;
+        }
+
+        self.buffer.drain(..read);
+        Ok(plain)
+    }
+
+    // Fails unless the final chunk arrived with nothing after it
+    pub fn finish(self) -> Result<()> {
+        if !self.finished || !self.buffer.is_empty() {
+            return Err(anyhow!(MessengerError::Crypto("Encrypted stream is truncated".to_string())));
+        }
+        Ok(())
+    }
+}
+
+// Open a whole stream that's already in memory
+pub fn open(bytes: &[u8], key: &[u8; 32]) -> Result<Vec<u8>> {
+    let mut opener = Opener::new(key);
+    let plain = opener.push(bytes)?;
+    opener.finish()?;
+    Ok(plain)
+}
+
+// Encrypt `src` into `dst` a chunk at a time. Returns the plaintext's size
+// and hex blake3 hash.
+pub async fn seal_file(src: &Path, dst: &Path, key: &[u8; 32]) -> Result<(u64, String)> {
+    let mut input = File::open(src).await?;
+    let mut output = BufWriter::new(File::create(dst).await?);
+    let mut hasher = blake3::Hasher::new();
+    let mut size = 0u64;
+
+    let (mut sealer, header) = Sealer::new(key);
+    output.write_all(&header).await?;
+
+    // A file that ends on a chunk boundary gets an empty final chunk
+    let mut chunk = vec![0u8; CHUNK_BYTES];
+    loop {
+        let len = read_chunk(&mut input, &mut chunk).await?;
+        hasher.update(&chunk[..len]);
+        size += len as u64;
+
+        let last = len < CHUNK_BYTES;
+        output.write_all(&sealer.seal(&chunk[..len], last)?).await?;
+        if last {
+            break;
+        }
+    }
+
+    output.flush().await?;
+    Ok((size, hasher.finalize().to_hex().to_string()))
+}
+
+// Fill `buffer` as far as the file allows, returning how much was read
+async fn read_chunk(input: &mut File, buffer: &mut [u8]) -> Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        let read = input.read(&mut buffer[filled..]).await?;
+        if read == 0 {
+            break;
+        }
+        filled += read;
+    }
+    Ok(filled)
+}
+
+// Decrypt `src` into `dst` a piece at a time. Returns the plaintext's size
+// and hex blake3 hash; `dst` is deleted if the stream doesn't open.
+pub async fn open_file(src: &Path, dst: &Path, key: &[u8; 32]) -> Result<(u64, String)> {
+    let opened = open_file_into(src, dst, key).await;
+    if opened.is_err() {
+        let _ = tokio::fs::remove_file(dst).await;
+    }
+    opened
+}
+
+async fn open_file_into(src: &Path, dst: &Path, key: &[u8; 32]) -> Result<(u64, String)> {
+    let mut input = File::open(src).await?;
+    let mut output = BufWriter::new(File::create(dst).await?);
+    let mut hasher = blake3::Hasher::new();
+    let mut size = 0u64;
+
+    let mut opener = Opener::new(key);
+    let mut buffer = vec![0u8; CHUNK_BYTES];
+    loop {
+        let read = input.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        let plain = opener.push(&buffer[..read])?;
+        hasher.update(&plain);
+        size += plain.len() as u64;
+        output.write_all(&plain).await?;
+    }
+    opener.finish()?;
+
+    output.flush().await?;
+    Ok((size, hasher.finalize().to_hex().to_string()))
+}
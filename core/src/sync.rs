@@ -0,0 +1,465 @@
+use crate::blocks::BlockList;
+use crate::documents;
+use crate::error::{ErrorContext, MessengerError, MessengerResult};
+use crate::events::{self, EventSink};
+use crate::health::ConversationHealth;
+use crate::inbox;
+use crate::inbox_feed::{InboxFeed, InboxItem, InboxItemKind};
+use crate::local_store::LocalStore;
+use crate::logging;
+use crate::mentions::{self, MentionEvent, NotificationPriority, MENTION_RECEIVED_EVENT};
+use crate::messaging::{AppState, ChatMessage, PrivateMessageHandler};
+use crate::mutes::MuteList;
+use crate::names::NameResolver;
+use crate::protocol;
+use crate::read_state::ReadState;
+use crate::storage::{Storage, StoredMessage, SyncCursor, MANUAL_CONTACT_SOURCE};
+use crate::verification::{self, VerificationState};
+use crate::webhook;
+use pkarr::{Keypair, PublicKey};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub const MESSAGE_RECEIVED_EVENT: &str = "message-received";
+pub const CONVERSATION_UPDATED_EVENT: &str = "conversation-updated";
+
+const SYNC_SETTINGS_DOCUMENT: &str = documents::SYNC_SETTINGS;
+
+const DEFAULT_INTERVAL_SECS: u64 = 30;
+const MIN_INTERVAL_SECS: u64 = 10;
+const MAX_INTERVAL_SECS: u64 = 24 * 60 * 60;
+
+// How often the background worker syncs; in manual mode it never does and
+// syncing only happens when the frontend asks (sync_now, refresh)
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct SyncSettings {
+    pub interval_secs: u64,
+    pub manual_only: bool,
+}
+
+impl Default for SyncSettings {
+    fn default() -> Self {
+        Self {
+            interval_secs: DEFAULT_INTERVAL_SECS,
+            manual_only: false,
+        }
+    }
+}
+
+pub fn get_settings(store: &LocalStore) -> SyncSettings {
+    store.load(SYNC_SETTINGS_DOCUMENT).unwrap_or_default()
+}
+
+pub fn set_settings(store: &LocalStore, settings: SyncSettings) -> anyhow::Result<SyncSettings> {
+    let settings = SyncSettings {
+        interval_secs: settings.interval_secs.clamp(MIN_INTERVAL_SECS, MAX_INTERVAL_SECS),
+        ..settings
+    };
+    store.save(SYNC_SETTINGS_DOCUMENT, &settings)?;
+    Ok(settings)
+}
+
+// Payload of the message-received event
+#[derive(Serialize, Deserialize, Clone)]
+pub struct MessageReceivedEvent {
+    pub conversation: String,
+    pub message: ChatMessage,
+    // When this device found the message, as opposed to when it was sent
+    pub received_at: u64,
+}
+
+// Payload of the conversation-updated event
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ConversationUpdatedEvent {
+    pub conversation: String,
+    pub new_messages: usize,
+    pub last_message_time: Option<u64>,
+}
+
+// A conversation after syncing it into the local cache
+pub struct SyncedConversation {
+    // Everything cached, oldest first
+    pub messages: Vec<ChatMessage>,
+    // Messages this sync added to the cache
+    pub new_messages: Vec<ChatMessage>,
+    pub health: ConversationHealth,
+    // Set when the homeserver couldn't be reached and only the cache was read
+    pub fetch_error: Option<String>,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// Pull messages missing from the local cache, store them and return the
+// full cached conversation
+pub async fn sync_conversation(
+    state: &AppState,
+    handler: &PrivateMessageHandler,
+    conversation_key: &str,
+) -> MessengerResult<SyncedConversation> {
+    let other_pk = PublicKey::try_from(conversation_key)
+        .map_err(|e| MessengerError::InvalidPublicKey(format!("Invalid public key: {}", e)))?;
+
+    let known_ids = state.with_storage(|storage| storage.message_ids(conversation_key)).await?;
+
+    let (new_messages, fetch_error) = match handler.fetch_new_chat_messages(&other_pk, &known_ids).await {
+        Ok((fetched, complete)) => {
+            record_peer_version(state, handler, conversation_key, &fetched);
+            let remote_count = known_ids.len() + fetched.len();
+            let inserted = state.with_storage(|storage| {
+                let inserted = storage.insert_messages(conversation_key, &fetched)?;
+                // Messages missed this time would look backdated next time if
+                // the cursor moved on without them
+                if complete {
+                    storage.set_sync_cursor(conversation_key, SyncCursor { last_synced_at: now_secs(), remote_count })?;
+                }
+                Ok(inserted)
+            }).await?;
+            (inserted, None)
+        }
+        Err(e) => (Vec::new(), Some(e.to_string())),
+    };
+
+    let own_pubkey = handler.keypair.public_key().to_string();
+    let (stored, verification) = state.with_storage(|storage| Ok((
+        storage.conversation_messages(conversation_key)?,
+        contact_verification(storage, &own_pubkey, conversation_key)?,
+    ))).await?;
+    let health = cached_health(&stored, verification);
+
+    Ok(SyncedConversation {
+        messages: stored.into_iter().map(|msg| msg.message).collect(),
+        new_messages,
+        health,
+        fetch_error,
+    })
+}
+
+// Remember the newest protocol the peer has written with
+fn record_peer_version(state: &AppState, handler: &PrivateMessageHandler, conversation_key: &str, fetched: &[StoredMessage]) {
+    let Some(version) = fetched.iter()
+        .filter(|stored| !stored.message.is_own_message)
+        .map(|stored| stored.message.protocol_version.max(stored.message.supported_protocol.unwrap_or(0)))
+        .max()
+    else {
+        return;
+    };
+    if let Err(e) = protocol::record_peer_version(&state.store, &handler.keypair.public_key(), conversation_key, version) {
+        tracing::warn!("⚠️  Failed to record peer protocol version: {}", e);
+    }
+}
+
+pub fn cached_health(stored: &[StoredMessage], verification: Option<VerificationState>) -> ConversationHealth {
+    PrivateMessageHandler::conversation_health(
+        stored.iter().map(|msg| (msg.cipher_format, msg.message.protocol_version, msg.message.timestamp, msg.message.verified)),
+        verification,
+    )
+}
+
+// Saved Messages is our own key, so there's nothing to verify
+fn contact_verification(storage: &Storage, own_pubkey: &str, conversation_key: &str) -> anyhow::Result<Option<VerificationState>> {
+    if conversation_key == own_pubkey {
+        return Ok(Some(VerificationState::Verified));
+    }
+    verification::get_state(storage, conversation_key)
+}
+
+// Health of a conversation from the local cache. Results are reused until
+// they go stale or something they depend on changes, so lists can show
+// every contact's health without touching the network.
+pub async fn conversation_health(state: &AppState, own_pubkey: &str, conversation_key: &str) -> MessengerResult<ConversationHealth> {
+    let now = now_secs();
+    if let Some(health) = state.conversation_health.lock().await.get(conversation_key) {
+        if !health.is_stale(now) {
+            return Ok(health.clone());
+        }
+    }
+
+    let (stored, verification) = state.with_storage(|storage| Ok((
+        storage.conversation_messages(conversation_key)?,
+        contact_verification(storage, own_pubkey, conversation_key)?,
+    ))).await?;
+    let health = cached_health(&stored, verification);
+    state.conversation_health.lock().await.insert(conversation_key.to_string(), health.clone());
+    Ok(health)
+}
+
+// Bookkeeping shared by every path that loads a full conversation:
+// health cache, unread counts and mention events. Muted conversations
+// don't gain unread messages; they're counted again once unmuted.
+pub async fn record_loaded_conversation(
+    events: &dyn EventSink,
+    state: &AppState,
+    keypair: &Keypair,
+    conversation_key: &str,
+    chat_messages: &[ChatMessage],
+    health: ConversationHealth,
+    muted: bool,
+) {
+    let current_user = keypair.public_key().to_string();
+
+    state.conversation_health.lock().await.insert(conversation_key.to_string(), health);
+
+    // Keep unread counts in step with what the conversation now contains
+    if !muted {
+        let incoming: Vec<u64> = chat_messages.iter()
+            .filter(|msg| !msg.is_own_message)
+            .map(|msg| msg.arrived_at())
+            .collect();
+        if let Err(e) = ReadState::new(&state.store, &keypair.public_key()).record_incoming(conversation_key, &incoming) {
+            tracing::warn!("⚠️  Failed to update unread count: {}", e);
+        }
+    }
+
+    let mention_candidates = chat_messages.iter()
+        .filter(|msg| msg.priority == NotificationPriority::High)
+        .map(|msg| MentionEvent {
+            conversation: conversation_key.to_string(),
+            sender: msg.sender.clone(),
+            timestamp: msg.timestamp,
+            received_at: msg.arrived_at(),
+            priority: msg.priority,
+        })
+        .collect();
+
+    // Mentions come through even in muted conversations
+    match mentions::take_new_mentions(&state.store, &current_user, conversation_key, mention_candidates) {
+        Ok(new_mentions) => {
+            let mut items = Vec::new();
+            for mention in new_mentions {
+                if let Err(e) = events::emit(events, MENTION_RECEIVED_EVENT, &mention) {
+                    tracing::warn!("⚠️  Failed to emit mention event: {}", e);
+                }
+                let message = chat_messages.iter()
+                    .find(|msg| msg.timestamp == mention.timestamp && msg.sender == mention.sender);
+                items.extend(message.map(|msg| InboxItem::message(InboxItemKind::Mention, conversation_key, msg)));
+            }
+            if let Err(e) = InboxFeed::new(&state.store, &keypair.public_key()).add(items) {
+                tracing::warn!("⚠️  Failed to add mentions to inbox: {}", e);
+            }
+        }
+        Err(e) => tracing::warn!("⚠️  Failed to track mentions: {}", e),
+    }
+}
+
+// Label incoming messages with the sender's resolved display name
+pub async fn label_senders(state: &AppState, messages: &mut [ChatMessage]) {
+    match NameResolver::load(state).await {
+        Ok(names) => names.label_senders(messages),
+        Err(e) => tracing::warn!("⚠️  Failed to load contact names: {}", e),
+    }
+}
+
+// Every conversation worth syncing: cached ones, added contacts, follows
+// and Saved messages
+pub async fn known_conversations(state: &AppState, handler: &PrivateMessageHandler) -> MessengerResult<Vec<String>> {
+    let mut candidates = state.with_storage(|storage| {
+        let mut keys = storage.conversation_keys()?;
+        keys.extend(storage.contact_keys(MANUAL_CONTACT_SOURCE)?);
+        Ok(keys)
+    }).await?;
+    if let Ok(follow_urls) = handler.get_followed_users().await {
+        candidates.extend(follow_urls.iter().filter_map(|url| url.split('/').last().map(|s| s.to_string())));
+    }
+    candidates.push(handler.keypair.public_key().to_string());
+    candidates.sort();
+    candidates.dedup();
+
+    // Blocked contacts are never synced
+    let blocked = BlockList::new(&state.store, &handler.keypair.public_key())
+        .blocked()
+        .err_context("Failed to load blocked contacts")?;
+    candidates.retain(|pubky| !blocked.contains_key(pubky));
+    Ok(candidates)
+}
+
+// Known conversations whose homeserver listings changed since the last
+// sync, plus any we've never synced. A changed notifications directory
+// means someone wrote to us, so everything gets synced.
+async fn changed_conversations(state: &AppState, handler: &PrivateMessageHandler, candidates: Vec<String>) -> MessengerResult<Vec<String>> {
+    if handler.notifications_changed().await.unwrap_or(true) {
+        return Ok(candidates);
+    }
+
+    let checks = candidates.into_iter().map(|pubky| async move {
+        let never_synced = state.with_storage(|storage| storage.sync_cursor(&pubky)).await?.is_none();
+        let changed = match PublicKey::try_from(pubky.as_str()) {
+            Ok(other_pk) => handler.conversation_changed(&other_pk).await.unwrap_or(true),
+            Err(_) => false,
+        };
+        Ok::<_, MessengerError>((pubky, never_synced || changed))
+    });
+
+    let mut changed = Vec::new();
+    for result in futures::future::join_all(checks).await {
+        let (pubky, is_changed) = result?;
+        if is_changed {
+            changed.push(pubky);
+        }
+    }
+    Ok(changed)
+}
+
+// Sync every known conversation that changed concurrently, emitting events
+// for whatever arrived, and return the new messages
+pub async fn sync_all(events: &dyn EventSink, state: &AppState) -> MessengerResult<Vec<ChatMessage>> {
+    let keypair = {
+        let keypair_guard = state.keypair.lock().await;
+        keypair_guard.clone().ok_or(MessengerError::NotSignedIn)?
+    };
+    let handler = state.create_handler().await?
+        .ok_or(MessengerError::NotSignedIn)?;
+
+    drop_blocked_notifications(state, &handler).await;
+
+    let known = known_conversations(state, &handler).await?;
+    let mut candidates = changed_conversations(state, &handler, known.clone()).await?;
+
+    // Notifications announce messages from people we may not know yet
+    let notifications = match handler.pending_notifications(inbox::MAX_NOTIFICATIONS_PER_PASS).await {
+        Ok(notifications) => notifications,
+        Err(e) => {
+            tracing::debug!("⚠️  Failed to read notifications: {}", e);
+            Vec::new()
+        }
+    };
+    candidates.extend(inbox::new_senders(state, &keypair, &notifications, &candidates));
+
+    let loads = candidates.iter().map(|pubky| {
+        let handler = &handler;
+        async move { (pubky.clone(), sync_conversation(state, handler, pubky).await) }
+    });
+
+    let results = futures::future::join_all(loads).await;
+
+    // A changed key on a verified contact is worth knowing about every pass
+    verification::check_all(events, state, &handler).await;
+    // Profiles only go stale every few hours, so this is usually a no-op
+    handler.refresh_stale_profiles(&known).await;
+
+    // Muted conversations still sync into the cache, they just stay quiet
+    let muted = MuteList::new(&state.store, &keypair.public_key())
+        .active(now_secs())
+        .unwrap_or_default();
+
+    let mut received = Vec::new();
+    for (pubky, result) in results {
+        let mut synced = match result {
+            Ok(synced) => synced,
+            Err(e) => {
+                tracing::warn!("⚠️  Failed to sync conversation {}: {}", logging::pubkey(&pubky), e);
+                forget_listing(&handler, &pubky);
+                continue;
+            }
+        };
+        if let Some(e) = &synced.fetch_error {
+            tracing::warn!("⚠️  Failed to sync conversation {}: {}", logging::pubkey(&pubky), e);
+            forget_listing(&handler, &pubky);
+        }
+
+        let is_muted = muted.contains_key(&pubky);
+        record_loaded_conversation(events, state, &keypair, &pubky, &synced.messages, synced.health, is_muted).await;
+
+        if synced.new_messages.is_empty() {
+            continue;
+        }
+        label_senders(state, &mut synced.new_messages).await;
+
+        // Mentions come through even in muted conversations
+        let alerting = synced.new_messages.iter()
+            .filter(|message| !is_muted || message.priority == NotificationPriority::High);
+        for message in alerting {
+            let event = MessageReceivedEvent { conversation: pubky.clone(), message: message.clone(), received_at: now_secs() };
+            if let Err(e) = events::emit(events, MESSAGE_RECEIVED_EVENT, &event) {
+                tracing::warn!("⚠️  Failed to emit message event: {}", e);
+            }
+        }
+
+        // Conversations we didn't know before this pass were started by strangers
+        let kind = if known.contains(&pubky) { InboxItemKind::Message } else { InboxItemKind::MessageRequest };
+        let items = synced.new_messages.iter()
+            .filter(|msg| !msg.is_own_message)
+            .map(|msg| InboxItem::message(kind, &pubky, msg))
+            .collect();
+        if let Err(e) = InboxFeed::new(&state.store, &keypair.public_key()).add(items) {
+            tracing::warn!("⚠️  Failed to add messages to inbox: {}", e);
+        }
+        webhook::deliver(&state.store, &pubky, &synced.new_messages);
+
+        if !is_muted {
+            let event = ConversationUpdatedEvent {
+                conversation: pubky.clone(),
+                new_messages: synced.new_messages.len(),
+                last_message_time: synced.messages.last().map(|msg| msg.timestamp),
+            };
+            if let Err(e) = events::emit(events, CONVERSATION_UPDATED_EVENT, &event) {
+                tracing::warn!("⚠️  Failed to emit conversation event: {}", e);
+            }
+        }
+
+        received.extend(synced.new_messages);
+    }
+
+    inbox::clean_up(state, &handler, &notifications, now_secs()).await;
+
+    Ok(received)
+}
+
+async fn drop_blocked_notifications(state: &AppState, handler: &PrivateMessageHandler) {
+    let blocked: HashSet<String> = match BlockList::new(&state.store, &handler.keypair.public_key()).blocked() {
+        Ok(blocked) => blocked.into_keys().collect(),
+        Err(e) => {
+            tracing::warn!("⚠️  Failed to load blocked contacts: {}", e);
+            return;
+        }
+    };
+    if blocked.is_empty() {
+        return;
+    }
+
+    match handler.delete_notifications_from(&blocked).await {
+        Ok(0) => {}
+        Ok(deleted) => tracing::info!("🚫 Deleted {} notifications from blocked contacts", deleted),
+        Err(e) => tracing::warn!("⚠️  Failed to delete blocked notifications: {}", e),
+    }
+}
+
+// Retry a conversation on the next pass even if its listing stays the same
+fn forget_listing(handler: &PrivateMessageHandler, pubky: &str) {
+    if let Ok(other_pk) = PublicKey::try_from(pubky) {
+        handler.forget_conversation(&other_pk);
+    }
+}
+
+// Background task keeping the local cache in step with the homeservers
+pub async fn run_sync_worker(state: &AppState, events: &dyn EventSink) {
+    loop {
+        let settings = get_settings(&state.store);
+
+        // Settings changes wake us so a new interval applies right away
+        if settings.manual_only {
+            state.sync_settings_changed.notified().await;
+            continue;
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_secs(settings.interval_secs)) => {}
+            _ = state.sync_settings_changed.notified() => continue,
+        }
+
+        // Not signed in - nothing to sync. The decoy account never syncs.
+        if state.storage.lock().await.is_none() || state.is_decoy_session().await {
+            continue;
+        }
+
+        match sync_all(events, state).await {
+            Ok(received) if !received.is_empty() => tracing::info!("🔄 Background sync received {} messages", received.len()),
+            Ok(_) => {}
+            Err(e) => tracing::warn!("⚠️  Background sync failed: {}", e),
+        }
+    }
+}
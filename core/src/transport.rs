@@ -0,0 +1,249 @@
+// Everything the messenger asks of a homeserver.
+//
+// PrivateMessageHandler talks to homeservers only through this trait, so
+// the real pubky::Client can be swapped for MemoryTransport and the path,
+// encryption and sync logic exercised without a live homeserver.
+use crate::net;
+use anyhow::{anyhow, Result};
+use futures::future::BoxFuture;
+use pkarr::{Keypair, PublicKey};
+use reqwest::header::{HeaderMap, ETAG, IF_NONE_MATCH};
+use reqwest::{Response, StatusCode};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+pub trait Transport: Send + Sync {
+    // GET with extra request headers, e.g. cache validators
+    fn get_with_headers<'a>(&'a self, url: &'a str, headers: HeaderMap) -> BoxFuture<'a, Result<Response>>;
+
+    fn put<'a>(&'a self, url: &'a str, body: Vec<u8>) -> BoxFuture<'a, Result<Response>>;
+
+    // PUT the file at `path` as the body. Implementations that can stream it
+    // should; this one reads it into memory.
+    fn put_file<'a>(&'a self, url: &'a str, path: &'a Path) -> BoxFuture<'a, Result<Response>> {
+        Box::pin(async move { self.put(url, tokio::fs::read(path).await?).await })
+    }
+
+    fn delete<'a>(&'a self, url: &'a str) -> BoxFuture<'a, Result<Response>>;
+
+    // URLs under the directory `url`, in order, starting after `cursor`
+    fn list_page<'a>(&'a self, url: &'a str, limit: Option<u16>, cursor: Option<&'a str>) -> BoxFuture<'a, Result<Vec<String>>>;
+
+    fn signin<'a>(&'a self, keypair: &'a Keypair) -> BoxFuture<'a, Result<()>>;
+
+    // Whether the homeserver of `public_key` has a session for it
+    fn has_session<'a>(&'a self, public_key: &'a PublicKey) -> BoxFuture<'a, Result<bool>>;
+
+    fn homeserver<'a>(&'a self, public_key: &'a PublicKey) -> BoxFuture<'a, Result<Option<String>>>;
+
+    fn get<'a>(&'a self, url: &'a str) -> BoxFuture<'a, Result<Response>> {
+        self.get_with_headers(url, HeaderMap::new())
+    }
+
+    fn list<'a>(&'a self, url: &'a str) -> BoxFuture<'a, Result<Vec<String>>> {
+        self.list_page(url, None, None)
+    }
+}
+
+// The real thing, with the timeouts and retries from `net`
+impl Transport for pubky::Client {
+    fn get_with_headers<'a>(&'a self, url: &'a str, headers: HeaderMap) -> BoxFuture<'a, Result<Response>> {
+        Box::pin(net::send("GET", move || self.get(url).headers(headers.clone()).send()))
+    }
+
+    fn put<'a>(&'a self, url: &'a str, body: Vec<u8>) -> BoxFuture<'a, Result<Response>> {
+        Box::pin(net::put(self, url, body))
+    }
+
+    fn put_file<'a>(&'a self, url: &'a str, path: &'a Path) -> BoxFuture<'a, Result<Response>> {
+        Box::pin(net::put_file(self, url, path))
+    }
+
+    fn delete<'a>(&'a self, url: &'a str) -> BoxFuture<'a, Result<Response>> {
+        Box::pin(net::delete(self, url))
+    }
+
+    fn list_page<'a>(&'a self, url: &'a str, limit: Option<u16>, cursor: Option<&'a str>) -> BoxFuture<'a, Result<Vec<String>>> {
+        Box::pin(net::list_page(self, url, limit, cursor))
+    }
+
+    fn signin<'a>(&'a self, keypair: &'a Keypair) -> BoxFuture<'a, Result<()>> {
+        Box::pin(net::with_timeout("SIGNIN", async move {
+            pubky::Client::signin(self, keypair).await
+                .map(|_| ())
+                .map_err(|e| anyhow::Error::new(e).context("Failed to sign in"))
+        }))
+    }
+
+    fn has_session<'a>(&'a self, public_key: &'a PublicKey) -> BoxFuture<'a, Result<bool>> {
+        Box::pin(net::with_timeout("SESSION", async move {
+            self.session(public_key).await
+                .map(|session| session.is_some())
+                .map_err(|e| anyhow::Error::new(e).context("Failed to check session"))
+        }))
+    }
+
+    fn homeserver<'a>(&'a self, public_key: &'a PublicKey) -> BoxFuture<'a, Result<Option<String>>> {
+        Box::pin(net::with_timeout("HOMESERVER", async move { Ok(self.get_homeserver(public_key).await) }))
+    }
+}
+
+#[derive(Default)]
+struct MemoryState {
+    // Full pubky:// URL to body; ordered so listings come out sorted
+    files: BTreeMap<String, Vec<u8>>,
+    sessions: HashSet<String>,
+    homeservers: HashMap<String, String>,
+}
+
+// An in-memory stand-in for every homeserver at once. Clones share state,
+// so handlers for two users can exchange messages through one instance.
+#[derive(Clone, Default)]
+pub struct MemoryTransport {
+    state: Arc<Mutex<MemoryState>>,
+}
+
+impl MemoryTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, MemoryState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    // Make `public_key` resolve to a homeserver; signing in does this too
+    pub fn register(&self, public_key: &PublicKey, homeserver: &str) {
+        self.lock().homeservers.insert(public_key.to_string(), homeserver.to_string());
+    }
+
+    // Drop a session, as a homeserver restart or expiry would
+    pub fn expire_session(&self, public_key: &PublicKey) {
+        self.lock().sessions.remove(&public_key.to_string());
+    }
+
+    pub fn file(&self, url: &str) -> Option<Vec<u8>> {
+        self.lock().files.get(url).cloned()
+    }
+
+    pub fn urls(&self) -> Vec<String> {
+        self.lock().files.keys().cloned().collect()
+    }
+
+    // Only the owner may write, and only with a session
+    fn authorize_write(&self, url: &str) -> Result<Option<Response>> {
+        let owner = owner_of(url)?;
+        if !self.lock().sessions.contains(owner) {
+            return Ok(Some(response(StatusCode::UNAUTHORIZED, HeaderMap::new(), Vec::new())));
+        }
+        Ok(None)
+    }
+}
+
+// The public key a pubky:// URL belongs to
+fn owner_of(url: &str) -> Result<&str> {
+    url.strip_prefix("pubky://")
+        .and_then(|rest| rest.split('/').next())
+        .filter(|owner| !owner.is_empty())
+        .ok_or_else(|| anyhow!("Not a pubky URL: {}", url))
+}
+
+fn response(status: StatusCode, headers: HeaderMap, body: Vec<u8>) -> Response {
+    let mut builder = http::Response::builder().status(status);
+    if let Some(response_headers) = builder.headers_mut() {
+        response_headers.extend(headers);
+    }
+    Response::from(builder.body(body).expect("static response parts are valid"))
+}
+
+fn etag(body: &[u8]) -> String {
+    format!("\"{}\"", blake3::hash(body).to_hex())
+}
+
+impl Transport for MemoryTransport {
+    fn get_with_headers<'a>(&'a self, url: &'a str, headers: HeaderMap) -> BoxFuture<'a, Result<Response>> {
+        Box::pin(async move {
+            let Some(body) = self.file(url) else {
+                return Ok(response(StatusCode::NOT_FOUND, HeaderMap::new(), Vec::new()));
+            };
+            let tag = etag(&body);
+            let mut response_headers = HeaderMap::new();
+            response_headers.insert(ETAG, tag.parse()?);
+
+            let unchanged = headers.get(IF_NONE_MATCH)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value == tag)
+                .unwrap_or(false);
+            if unchanged {
+                return Ok(response(StatusCode::NOT_MODIFIED, response_headers, Vec::new()));
+            }
+            Ok(response(StatusCode::OK, response_headers, body))
+        })
+    }
+
+    fn put<'a>(&'a self, url: &'a str, body: Vec<u8>) -> BoxFuture<'a, Result<Response>> {
+        Box::pin(async move {
+            if let Some(denied) = self.authorize_write(url)? {
+                return Ok(denied);
+            }
+            self.lock().files.insert(url.to_string(), body);
+            Ok(response(StatusCode::CREATED, HeaderMap::new(), Vec::new()))
+        })
+    }
+
+    fn delete<'a>(&'a self, url: &'a str) -> BoxFuture<'a, Result<Response>> {
+        Box::pin(async move {
+            if let Some(denied) = self.authorize_write(url)? {
+                return Ok(denied);
+            }
+            let status = match self.lock().files.remove(url) {
+                Some(_) => StatusCode::NO_CONTENT,
+                None => StatusCode::NOT_FOUND,
+            };
+            Ok(response(status, HeaderMap::new(), Vec::new()))
+        })
+    }
+
+    fn list_page<'a>(&'a self, url: &'a str, limit: Option<u16>, cursor: Option<&'a str>) -> BoxFuture<'a, Result<Vec<String>>> {
+        Box::pin(async move {
+            if !url.ends_with('/') {
+                return Err(anyhow!("Can only list directories: {}", url));
+            }
+            // Cursors are either full URLs or paths relative to the directory
+            let cursor = cursor.map(|cursor| {
+                if cursor.starts_with("pubky://") {
+                    cursor.to_string()
+                } else {
+                    format!("{}{}", url, cursor)
+                }
+            });
+
+            let state = self.lock();
+            Ok(state.files.keys()
+                .filter(|file| file.starts_with(url))
+                .filter(|file| cursor.as_ref().map(|cursor| file.as_str() > cursor.as_str()).unwrap_or(true))
+                .take(limit.map(usize::from).unwrap_or(usize::MAX))
+                .cloned()
+                .collect())
+        })
+    }
+
+    fn signin<'a>(&'a self, keypair: &'a Keypair) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let public_key = keypair.public_key().to_string();
+            let mut state = self.lock();
+            state.homeservers.entry(public_key.clone()).or_insert_with(|| "memory".to_string());
+            state.sessions.insert(public_key);
+            Ok(())
+        })
+    }
+
+    fn has_session<'a>(&'a self, public_key: &'a PublicKey) -> BoxFuture<'a, Result<bool>> {
+        Box::pin(async move { Ok(self.lock().sessions.contains(&public_key.to_string())) })
+    }
+
+    fn homeserver<'a>(&'a self, public_key: &'a PublicKey) -> BoxFuture<'a, Result<Option<String>>> {
+        Box::pin(async move { Ok(self.lock().homeservers.get(&public_key.to_string()).cloned()) })
+    }
+}
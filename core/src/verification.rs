@@ -0,0 +1,195 @@
+// Contact key verification with key-change detection, like Signal's safety
+// numbers. Verifying a contact records a fingerprint of their identity key
+// and the homeserver their pkarr record points to; if either changes later
+// the contact is flagged as broken and a security event is raised.
+use crate::error::{ErrorContext, MessengerResult};
+use crate::events::{self, EventSink};
+use crate::inbox_feed::{InboxFeed, InboxItem};
+use crate::logging;
+use crate::messaging::{AppState, PrivateMessageHandler};
+use crate::security_log::{SecurityEventKind, SecurityLog};
+use crate::storage::{ContactVerification, Storage};
+use pkarr::PublicKey;
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const KEY_CHANGED_EVENT: &str = "contact-key-changed";
+
+const FINGERPRINT_CONTEXT: &str = "pubky-private-messenger 2025 contact fingerprint v1";
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum VerificationState {
+    Verified,
+    // The keys changed since the user verified them
+    Broken,
+}
+
+impl VerificationState {
+    pub fn of(verification: &ContactVerification) -> Self {
+        if verification.broken_at.is_some() {
+            VerificationState::Broken
+        } else {
+            VerificationState::Verified
+        }
+    }
+}
+
+// Whether the user verified the contact; None if they never did
+pub fn get_state(storage: &Storage, public_key: &str) -> anyhow::Result<Option<VerificationState>> {
+    Ok(storage.contact_verification(public_key)?.as_ref().map(VerificationState::of))
+}
+
+// Payload of the contact-key-changed event
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct KeyChangedEvent {
+    pub public_key: String,
+    pub verified_fingerprint: String,
+    pub current_fingerprint: String,
+    pub verified_homeserver: String,
+    pub current_homeserver: String,
+    pub detected_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct VerificationStatus {
+    pub public_key: String,
+    // Compare this with the one the contact sees for themselves
+    pub current_fingerprint: Option<String>,
+    pub current_homeserver: Option<String>,
+    pub state: Option<VerificationState>,
+    pub verification: Option<ContactVerification>,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// Short, human comparable digest of a contact's identity key and homeserver
+pub fn fingerprint(public_key: &PublicKey, homeserver: &str) -> String {
+    let mut material = public_key.as_bytes().to_vec();
+    material.extend_from_slice(homeserver.as_bytes());
+    let digest = blake3::derive_key(FINGERPRINT_CONTEXT, &material);
+
+    hex::encode(&digest[..16])
+        .as_bytes()
+        .chunks(4)
+        .map(|group| String::from_utf8_lossy(group).into_owned())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+async fn current_keys(handler: &PrivateMessageHandler, public_key: &PublicKey) -> MessengerResult<(String, String)> {
+    let homeserver = handler.get_homeserver(public_key.to_string())
+        .await
+        .err_context("Failed to resolve contact")?;
+    Ok((fingerprint(public_key, &homeserver), homeserver))
+}
+
+// Record the contact's current keys as verified
+pub async fn verify(state: &AppState, handler: &PrivateMessageHandler, public_key: &PublicKey) -> MessengerResult<VerificationStatus> {
+    let (current_fingerprint, current_homeserver) = current_keys(handler, public_key).await?;
+    let verification = ContactVerification {
+        fingerprint: current_fingerprint.clone(),
+        homeserver: current_homeserver.clone(),
+        verified_at: now_secs(),
+        broken_at: None,
+    };
+
+    let key = public_key.to_string();
+    state.with_storage(|storage| storage.set_contact_verification(&key, Some(&verification), now_secs())).await?;
+    // Health is evaluated against the verification state
+    state.conversation_health.lock().await.remove(&key);
+    SecurityLog::new(&state.store, &handler.keypair.public_key())
+        .record(SecurityEventKind::ContactVerified, Some(&key), Some(&current_fingerprint));
+
+    Ok(VerificationStatus {
+        public_key: key,
+        current_fingerprint: Some(current_fingerprint),
+        current_homeserver: Some(current_homeserver),
+        state: Some(VerificationState::Verified),
+        verification: Some(verification),
+    })
+}
+
+// Compare a verified contact's current keys with the verified ones,
+// flagging the contact and emitting an event the first time they differ
+pub async fn check(
+    events: &dyn EventSink,
+    state: &AppState,
+    handler: &PrivateMessageHandler,
+    public_key: &PublicKey,
+) -> MessengerResult<VerificationStatus> {
+    let key = public_key.to_string();
+    let stored = state.with_storage(|storage| storage.contact_verification(&key)).await?;
+    let current = current_keys(handler, public_key).await;
+
+    let mut status = VerificationStatus {
+        public_key: key.clone(),
+        current_fingerprint: current.as_ref().ok().map(|(fingerprint, _)| fingerprint.clone()),
+        current_homeserver: current.as_ref().ok().map(|(_, homeserver)| homeserver.clone()),
+        state: stored.as_ref().map(VerificationState::of),
+        verification: stored.clone(),
+    };
+
+    // Nothing verified, already flagged, or unresolvable right now
+    let (Some(verification), Ok((current_fingerprint, current_homeserver))) = (stored, current) else {
+        return Ok(status);
+    };
+    if verification.broken_at.is_some() || verification.fingerprint == current_fingerprint {
+        return Ok(status);
+    }
+
+    let detected_at = now_secs();
+    state.with_storage(|storage| storage.mark_verification_broken(&key, detected_at)).await?;
+    state.conversation_health.lock().await.remove(&key);
+    tracing::warn!("🚨 Keys of verified contact {} changed", logging::pubkey(&key));
+
+    let event = KeyChangedEvent {
+        public_key: key,
+        verified_fingerprint: verification.fingerprint.clone(),
+        current_fingerprint,
+        verified_homeserver: verification.homeserver.clone(),
+        current_homeserver,
+        detected_at,
+    };
+    if let Err(e) = events::emit(events, KEY_CHANGED_EVENT, &event) {
+        tracing::warn!("⚠️  Failed to emit key change event: {}", e);
+    }
+    if let Err(e) = InboxFeed::new(&state.store, &handler.keypair.public_key()).add(vec![InboxItem::key_changed(&event)]) {
+        tracing::warn!("⚠️  Failed to add key change to inbox: {}", e);
+    }
+    let detail = format!("{} is now {}", event.verified_fingerprint, event.current_fingerprint);
+    SecurityLog::new(&state.store, &handler.keypair.public_key())
+        .record(SecurityEventKind::KeyChanged, Some(&event.public_key), Some(&detail));
+
+    status.state = Some(VerificationState::Broken);
+    status.verification = Some(ContactVerification { broken_at: Some(detected_at), ..verification });
+    Ok(status)
+}
+
+// Check every verified contact that isn't flagged yet; run on each sync
+pub async fn check_all(events: &dyn EventSink, state: &AppState, handler: &PrivateMessageHandler) {
+    let verified = match state.with_storage(|storage| storage.contact_verifications()).await {
+        Ok(verified) => verified,
+        Err(e) => {
+            tracing::warn!("⚠️  Failed to load contact verifications: {}", e);
+            return;
+        }
+    };
+
+    for (key, verification) in verified {
+        if verification.broken_at.is_some() {
+            continue;
+        }
+        let Ok(public_key) = PublicKey::try_from(key.as_str()) else {
+            continue;
+        };
+        if let Err(e) = check(events, state, handler, &public_key).await {
+            tracing::warn!("⚠️  Failed to check verification of {}: {}", logging::pubkey(&key), e);
+        }
+    }
+}
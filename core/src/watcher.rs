@@ -0,0 +1,48 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+// Remembers a fingerprint of every homeserver listing we've watched, so
+// the sync service only downloads conversations whose listing changed
+#[derive(Clone, Default)]
+pub struct ListingWatcher {
+    fingerprints: Arc<Mutex<HashMap<String, blake3::Hash>>>,
+}
+
+fn fingerprint(urls: &[String]) -> blake3::Hash {
+    let mut sorted: Vec<&String> = urls.iter().collect();
+    sorted.sort();
+
+    let mut hasher = blake3::Hasher::new();
+    for url in sorted {
+        hasher.update(url.as_bytes());
+        hasher.update(b"\n");
+    }
+    hasher.finalize()
+}
+
+impl ListingWatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, blake3::Hash>> {
+        self.fingerprints.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    // Record the current listing of `path`; true when it differs from the
+    // last one seen (or it's the first time we look)
+    pub fn observe(&self, path: &str, urls: &[String]) -> bool {
+        let current = fingerprint(urls);
+        self.lock().insert(path.to_string(), current) != Some(current)
+    }
+
+    // Make the next observation of `path` count as a change, e.g. when a
+    // sync triggered by it failed
+    pub fn forget(&self, path: &str) {
+        self.lock().remove(path);
+    }
+
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+}
@@ -0,0 +1,124 @@
+// Opt-in local webhook for bots and home automation: each received message
+// is POSTed as JSON to a URL on this machine. Only loopback URLs are
+// accepted, so messages never leave the device this way.
+use crate::documents;
+use crate::error::MessengerError;
+use crate::local_store::LocalStore;
+use crate::logging;
+use crate::messaging::ChatMessage;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::time::Duration;
+use url::{Host, Url};
+
+const WEBHOOK_DOCUMENT: &str = documents::WEBHOOK;
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct WebhookSettings {
+    pub enabled: bool,
+    pub url: Option<String>,
+    // Off sends who wrote and when, but not what
+    #[serde(default)]
+    pub include_content: bool,
+}
+
+// What the webhook receives for each message
+#[derive(Serialize, Clone, Debug)]
+pub struct WebhookPayload {
+    pub event: &'static str,
+    pub conversation: String,
+    pub sender: String,
+    pub sender_name: Option<String>,
+    pub message_id: String,
+    pub timestamp: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+}
+
+pub fn get_settings(store: &LocalStore) -> WebhookSettings {
+    store.load(WEBHOOK_DOCUMENT).unwrap_or_default()
+}
+
+pub fn set_settings(store: &LocalStore, settings: WebhookSettings) -> Result<WebhookSettings> {
+    let url = settings.url
+        .map(|url| url.trim().to_string())
+        .filter(|url| !url.is_empty());
+    if let Some(url) = &url {
+        check_url(url)?;
+    }
+    if settings.enabled && url.is_none() {
+        return Err(anyhow!(MessengerError::InvalidInput("Webhook needs a URL".to_string())));
+    }
+
+    let settings = WebhookSettings { url, ..settings };
+    store.save(WEBHOOK_DOCUMENT, &settings)?;
+    Ok(settings)
+}
+
+// http(s) on localhost or a loopback address
+fn check_url(url: &str) -> Result<()> {
+    let parsed = Url::parse(url)
+        .map_err(|e| MessengerError::InvalidInput(format!("Invalid webhook URL: {}", e)))?;
+    let loopback = match parsed.host() {
+        Some(Host::Domain(domain)) => domain.eq_ignore_ascii_case("localhost"),
+        Some(Host::Ipv4(ip)) => IpAddr::V4(ip).is_loopback(),
+        Some(Host::Ipv6(ip)) => IpAddr::V6(ip).is_loopback(),
+        None => false,
+    };
+    if !matches!(parsed.scheme(), "http" | "https") || !loopback {
+        return Err(anyhow!(MessengerError::InvalidInput("Webhook URL must be on localhost".to_string())));
+    }
+    Ok(())
+}
+
+// POST the messages others sent in `conversation` to the webhook, if one
+// is set up. Runs in the background so a slow hook can't hold up sync.
+pub fn deliver(store: &LocalStore, conversation: &str, messages: &[ChatMessage]) {
+    let settings = get_settings(store);
+    let Some(url) = settings.url.filter(|_| settings.enabled) else {
+        return;
+    };
+    let payloads: Vec<WebhookPayload> = messages.iter()
+        .filter(|msg| !msg.is_own_message)
+        .map(|msg| WebhookPayload {
+            event: "message_received",
+            conversation: conversation.to_string(),
+            sender: msg.sender.clone(),
+            sender_name: msg.sender_name.clone(),
+            message_id: msg.id.clone(),
+            timestamp: msg.timestamp,
+            content: settings.include_content.then(|| msg.content.clone()),
+        })
+        .collect();
+    if payloads.is_empty() {
+        return;
+    }
+
+    tokio::spawn(async move {
+        if let Err(e) = post_all(&url, &payloads).await {
+            tracing::warn!("⚠️  Failed to deliver webhook for {}: {}", logging::pubkey(&payloads[0].conversation), e);
+        }
+    });
+}
+
+async fn post_all(url: &str, payloads: &[WebhookPayload]) -> Result<()> {
+    // Never through the configured proxy, which couldn't reach our localhost
+    let client = reqwest::Client::builder()
+        .timeout(DELIVERY_TIMEOUT)
+        .redirect(reqwest::redirect::Policy::none())
+        .no_proxy()
+        .build()?;
+    for payload in payloads {
+        let response = client.post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(serde_json::to_vec(payload)?)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(anyhow!("Webhook answered {}", response.status()));
+        }
+    }
+    Ok(())
+}
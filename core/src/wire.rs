@@ -0,0 +1,135 @@
+// Binary wire format for everything stored under /pub/private_messages/.
+//
+// Blobs are a CBOR envelope `{ version, type, payload }`, so new message
+// types can be added without old clients misreading them: a client skips
+// envelopes whose version or type it doesn't know. Blobs written before
+// the envelope existed are bare JSON and still decode. Payloads are
+// upgraded to the current protocol version on read (see protocol.rs).
+use crate::messaging::PrivateMessage;
+use crate::protocol::{self, Migration};
+use anyhow::{anyhow, Result};
+use ciborium::Value;
+use serde::{Deserialize, Serialize};
+
+// Highest envelope version this build understands. This only covers the
+// envelope itself; the payload carries its own protocol_version.
+pub const WIRE_VERSION: u8 = 1;
+
+// Byte fields of a message payload; legacy JSON has them as number arrays
+const MESSAGE_BYTE_FIELDS: [&str; 4] = ["encrypted_sender", "encrypted_content", "signature_bytes", "encrypted_extras"];
+
+const MESSAGE_MIGRATIONS: &[Migration<Value>] = &[
+    Migration { from: 1, description: "byte arrays to byte strings", upgrade: byte_arrays_to_byte_strings },
+    Migration { from: 2, description: "explicit protocol version", upgrade: protocol::unchanged },
+    // Padding is inside the ciphertext; PrivateMessage strips it on decrypt
+    Migration { from: 3, description: "padded plaintext", upgrade: protocol::unchanged },
+];
+
+fn byte_arrays_to_byte_strings(payload: &mut Value) -> Result<()> {
+    let entries = payload.as_map_mut().ok_or_else(|| anyhow!("Message payload is not a map"))?;
+    for (key, value) in entries.iter_mut() {
+        if !key.as_text().is_some_and(|key| MESSAGE_BYTE_FIELDS.contains(&key)) {
+            continue;
+        }
+        let Some(items) = value.as_array() else {
+            continue;
+        };
+        let bytes = items
+            .iter()
+            .map(|item| item.as_integer().and_then(|byte| u8::try_from(byte).ok()))
+            .collect::<Option<Vec<u8>>>()
+            .ok_or_else(|| anyhow!("Invalid byte array in message payload"))?;
+        *value = Value::Bytes(bytes);
+    }
+    Ok(())
+}
+
+// Payloads from before protocol_version existed are version 2
+fn payload_version(payload: &Value) -> Result<u32> {
+    let version = payload
+        .as_map()
+        .and_then(|entries| entries.iter().find(|(key, _)| key.as_text() == Some("protocol_version")))
+        .map(|(_, version)| version.as_integer().and_then(|version| u32::try_from(version).ok()));
+    match version {
+        Some(Some(version)) => Ok(version),
+        Some(None) => Err(anyhow!("Invalid protocol version in message payload")),
+        None => Ok(2),
+    }
+}
+
+// File extension of enveloped blobs; legacy JSON blobs end in .json
+pub const BLOB_EXTENSION: &str = ".cbor";
+pub const LEGACY_BLOB_EXTENSION: &str = ".json";
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EnvelopeType {
+    PrivateMessage,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Envelope {
+    version: u8,
+    #[serde(rename = "type")]
+    kind: EnvelopeType,
+    payload: Value,
+}
+
+// Header of an envelope, readable even when the type is unknown to us
+#[derive(Deserialize)]
+struct EnvelopeHeader {
+    version: u8,
+    #[serde(rename = "type")]
+    kind: Value,
+}
+
+pub(crate) fn encode_message(message: &PrivateMessage) -> Result<Vec<u8>> {
+    let envelope = Envelope {
+        version: WIRE_VERSION,
+        kind: EnvelopeType::PrivateMessage,
+        payload: Value::serialized(message).map_err(|e| anyhow!("Failed to encode message: {}", e))?,
+    };
+
+    let mut bytes = Vec::new();
+    ciborium::into_writer(&envelope, &mut bytes).map_err(|e| anyhow!("Failed to encode envelope: {}", e))?;
+    Ok(bytes)
+}
+
+pub(crate) fn decode_message(bytes: &[u8]) -> Result<PrivateMessage> {
+    let (mut payload, version) = decode_payload(bytes)?;
+    protocol::upgrade("message", MESSAGE_MIGRATIONS, &mut payload, version)?;
+
+    let mut message: PrivateMessage = payload
+        .deserialized()
+        .map_err(|e| anyhow!("Invalid message payload: {}", e))?;
+    // Keep the version the sender wrote, not the one we upgraded to
+    message.protocol_version = version;
+    Ok(message)
+}
+
+// The raw payload and the protocol version it was written with
+fn decode_payload(bytes: &[u8]) -> Result<(Value, u32)> {
+    // Legacy blobs are a bare JSON object
+    if bytes.first() == Some(&b'{') {
+        let json: serde_json::Value = serde_json::from_slice(bytes).map_err(|e| anyhow!("Invalid legacy message: {}", e))?;
+        let payload = Value::serialized(&json).map_err(|e| anyhow!("Invalid legacy message: {}", e))?;
+        return Ok((payload, 1));
+    }
+
+    let header: EnvelopeHeader = ciborium::from_reader(bytes).map_err(|e| anyhow!("Invalid envelope: {}", e))?;
+    if header.version > WIRE_VERSION {
+        return Err(anyhow!("Unsupported envelope version {}", header.version));
+    }
+    if header.kind != Value::Text("private_message".to_string()) {
+        return Err(anyhow!("Unsupported envelope type {:?}", header.kind));
+    }
+
+    let envelope: Envelope = ciborium::from_reader(bytes).map_err(|e| anyhow!("Invalid envelope: {}", e))?;
+    let version = payload_version(&envelope.payload)?;
+    Ok((envelope.payload, version))
+}
+
+// Whether a listed URL is a message blob in any format we know
+pub fn is_message_blob(url: &str) -> bool {
+    url.ends_with(BLOB_EXTENSION) || url.ends_with(LEGACY_BLOB_EXTENSION)
+}
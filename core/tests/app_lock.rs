@@ -0,0 +1,37 @@
+// The duress PIN's decoy account must stay off the network: no handler is
+// ever built for it, so neither commands nor workers can reach a homeserver.
+use pkarr::Keypair;
+use pubky_messenger_core::app_lock::{self, Unlock};
+use pubky_messenger_core::messaging::AppState;
+
+fn state() -> AppState {
+    let dir = std::env::temp_dir().join(format!("pubky-messenger-test-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).expect("data dir should be created");
+    AppState::new(dir)
+}
+
+#[tokio::test]
+async fn decoy_session_never_gets_a_handler() {
+    let state = state();
+    app_lock::set(&state.store, None, "1234", Some("9876"), None).expect("lock should be set");
+    let decoy = match app_lock::unlock(&state.store, "9876").expect("PIN should check") {
+        Unlock::Decoy(keypair) => keypair,
+        _ => panic!("duress PIN should open the decoy account"),
+    };
+    *state.keypair.lock().await = Some(decoy);
+
+    assert!(state.is_decoy_session().await);
+    let error = state.create_handler().await.err().expect("decoy should get no handler");
+    assert_eq!(error.code(), "homeserver_unreachable");
+    let error = state.create_handler_and_sign_in().await.err().expect("decoy should not sign in");
+    assert_eq!(error.code(), "homeserver_unreachable");
+}
+
+#[tokio::test]
+async fn real_session_is_not_a_decoy() {
+    let state = state();
+    app_lock::set(&state.store, None, "1234", Some("9876"), None).expect("lock should be set");
+    *state.keypair.lock().await = Some(Keypair::random());
+
+    assert!(!state.is_decoy_session().await);
+}
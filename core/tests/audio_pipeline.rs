@@ -0,0 +1,64 @@
+// Voice notes go out as Ogg Opus whatever they were recorded as, and
+// anything that isn't audio goes out untouched.
+use pubky_messenger_core::attachment_preview::{self, AttachmentPreview};
+use pubky_messenger_core::attachments::AttachmentFile;
+use pubky_messenger_core::audio_pipeline;
+
+// `secs` of a quiet 440 Hz tone as 16-bit stereo PCM
+fn wav(sample_rate: u32, secs: u32) -> Vec<u8> {
+    let frames = sample_rate * secs;
+    let mut data = Vec::new();
+    for frame in 0..frames {
+        let sample = ((frame as f32 * 440.0 * std::f32::consts::TAU / sample_rate as f32).sin() * 3_000.0) as i16;
+        data.extend_from_slice(&sample.to_le_bytes());
+        data.extend_from_slice(&sample.to_le_bytes());
+    }
+
+    let mut wav = b"RIFF".to_vec();
+    wav.extend_from_slice(&(36 + data.len() as u32).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&2u16.to_le_bytes());
+    wav.extend_from_slice(&sample_rate.to_le_bytes());
+    wav.extend_from_slice(&(sample_rate * 4).to_le_bytes());
+    wav.extend_from_slice(&4u16.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    wav.extend_from_slice(&data);
+    wav
+}
+
+fn duration_ms(file: &AttachmentFile) -> u64 {
+    match attachment_preview::generate(&file.mime, &file.bytes) {
+        Some(AttachmentPreview::Audio { duration_ms, .. }) => duration_ms,
+        _ => panic!("{} should have an audio preview", file.name),
+    }
+}
+
+#[test]
+fn recordings_become_smaller_ogg_opus() {
+    let recording = AttachmentFile::new("recording.wav", Some("audio/wav"), wav(44_100, 2)).unwrap();
+    let recorded_bytes = recording.bytes.len();
+
+    let voice_note = audio_pipeline::prepare(recording);
+    assert_eq!(voice_note.name, "recording.ogg");
+    assert_eq!(voice_note.mime, audio_pipeline::MIME);
+    assert!(voice_note.bytes.starts_with(b"OggS"));
+    assert!(voice_note.bytes.len() < recorded_bytes / 10);
+    assert!((1_990..=2_010).contains(&duration_ms(&voice_note)), "duration was {} ms", duration_ms(&voice_note));
+
+    // Already Opus, and still the same length after another pass
+    let again = audio_pipeline::prepare(voice_note);
+    assert!((1_990..=2_010).contains(&duration_ms(&again)), "duration was {} ms", duration_ms(&again));
+}
+
+#[test]
+fn undecodable_recordings_are_sent_as_they_are() {
+    let file = AttachmentFile::new("recording.webm", Some("audio/webm"), b"not audio".to_vec()).unwrap();
+    let sent = audio_pipeline::prepare(file);
+    assert_eq!(sent.name, "recording.webm");
+    assert_eq!(sent.mime, "audio/webm");
+    assert_eq!(sent.bytes, b"not audio");
+}
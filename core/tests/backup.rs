@@ -0,0 +1,48 @@
+// Backups carry every document the registry lists, and nothing bound to
+// the device.
+use pkarr::Keypair;
+use pubky_messenger_core::backup;
+use pubky_messenger_core::documents;
+use pubky_messenger_core::local_store::LocalStore;
+use pubky_messenger_core::storage::Storage;
+
+const PASSPHRASE: &str = "correct horse battery staple";
+
+fn temp_dir() -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("pubky-messenger-test-{}", uuid::Uuid::new_v4()))
+}
+
+#[test]
+fn every_registered_document_round_trips() {
+    let keypair = Keypair::random();
+    let owner = keypair.public_key();
+
+    let dir = temp_dir();
+    let store = LocalStore::new(dir.clone());
+    let storage = Storage::open(&dir, &keypair).expect("storage should open");
+    let names = backup::document_names(&owner);
+    assert_eq!(names.len(), documents::USER_DOCUMENTS.len() + documents::SETTINGS_DOCUMENTS.len());
+    for name in &names {
+        store.save(name, &serde_json::json!({ "document": name })).unwrap();
+    }
+    for name in documents::DEVICE_DOCUMENTS {
+        store.save(name, &serde_json::json!({ "document": name })).unwrap();
+    }
+    let archive = backup::create(&storage, &store, &owner, PASSPHRASE, 1_000).expect("backup should be created");
+
+    let restored_dir = temp_dir();
+    let restored_store = LocalStore::new(restored_dir.clone());
+    let restored_storage = Storage::open(&restored_dir, &keypair).expect("storage should open");
+    let summary = backup::restore(&archive, PASSPHRASE, &restored_storage, &restored_store, &owner)
+        .expect("backup should restore");
+
+    assert_eq!(summary.documents, names.len());
+    for name in &names {
+        let document: serde_json::Value = restored_store.load(name).unwrap();
+        assert_eq!(document, serde_json::json!({ "document": name }), "{} should be restored", name);
+    }
+    for name in documents::DEVICE_DOCUMENTS {
+        let document: serde_json::Value = restored_store.load(name).unwrap();
+        assert!(document.is_null(), "{} should stay on the device", name);
+    }
+}
@@ -0,0 +1,64 @@
+// Shared setup for the integration tests: a local pubky testnet (DHT,
+// pkarr relay and homeserver) with users signed up on its homeserver.
+use pkarr::Keypair;
+use pubky_messenger_core::http_cache::HttpCache;
+use pubky_messenger_core::messaging::PrivateMessageHandler;
+use pubky_messenger_core::watcher::ListingWatcher;
+use pubky_testnet::EphemeralTestnet;
+use std::sync::Arc;
+
+pub struct TestUser {
+    pub keypair: Keypair,
+    // Raw access for tests that write blobs the way other clients would
+    pub client: pubky::Client,
+    pub handler: PrivateMessageHandler,
+}
+
+pub struct Harness {
+    // Keeps the testnet running for as long as the test holds the harness
+    pub testnet: EphemeralTestnet,
+}
+
+impl Harness {
+    pub async fn start() -> Self {
+        let testnet = EphemeralTestnet::start().await.expect("testnet should start");
+        Self { testnet }
+    }
+
+    // A fresh identity signed up on the testnet homeserver, with its own
+    // client as if on a separate device
+    pub async fn user(&self) -> TestUser {
+        let keypair = Keypair::random();
+        let client = self.testnet.pubky_client_builder().build().expect("client should build");
+        client
+            .signup(&keypair, &self.testnet.homeserver_suite().public_key(), None)
+            .await
+            .expect("signup should succeed");
+
+        let handler = PrivateMessageHandler::new(
+            Arc::new(client.clone()),
+            keypair.clone(),
+            HttpCache::new(),
+            ListingWatcher::new(),
+        );
+        TestUser { keypair, client, handler }
+    }
+}
+
+impl TestUser {
+    pub async fn get_bytes(&self, url: &str) -> Vec<u8> {
+        let response = self.client.get(url).send().await.expect("GET should succeed");
+        assert!(response.status().is_success(), "GET {} returned {}", url, response.status());
+        response.bytes().await.expect("body should read").to_vec()
+    }
+
+    pub async fn put_bytes(&self, url: &str, body: Vec<u8>) {
+        let response = self.client.put(url).body(body).send().await.expect("PUT should succeed");
+        assert!(response.status().is_success(), "PUT {} returned {}", url, response.status());
+    }
+
+    pub async fn delete(&self, url: &str) {
+        let response = self.client.delete(url).send().await.expect("DELETE should succeed");
+        assert!(response.status().is_success(), "DELETE {} returned {}", url, response.status());
+    }
+}
@@ -0,0 +1,49 @@
+// Conversation health is evaluated from what's cached locally plus whether
+// the user verified the contact.
+use pubky_messenger_core::crypto_compat::CURRENT_CIPHER_FORMAT;
+use pubky_messenger_core::health::{self, HealthInputs, HealthSeverity, HealthWarning};
+use pubky_messenger_core::protocol;
+use pubky_messenger_core::verification::VerificationState;
+
+const NOW: u64 = 1_800_000_000;
+
+fn severity_of(inputs: &HealthInputs, warning: HealthWarning) -> Option<HealthSeverity> {
+    health::evaluate(inputs, NOW).issues.into_iter()
+        .find(|issue| issue.warning == warning)
+        .map(|issue| issue.severity)
+}
+
+#[test]
+fn older_wire_formats_are_legacy() {
+    assert!(health::is_legacy_message(CURRENT_CIPHER_FORMAT, 1));
+    assert!(health::is_legacy_message(CURRENT_CIPHER_FORMAT, protocol::MIN_WRITE_VERSION - 1));
+    assert!(!health::is_legacy_message(CURRENT_CIPHER_FORMAT, protocol::MIN_WRITE_VERSION));
+    assert!(!health::is_legacy_message(CURRENT_CIPHER_FORMAT, protocol::PROTOCOL_VERSION));
+    // Cached before versions were recorded
+    assert!(!health::is_legacy_message(CURRENT_CIPHER_FORMAT, 0));
+
+    let inputs = HealthInputs { legacy_messages: 2, ..Default::default() };
+    assert_eq!(severity_of(&inputs, HealthWarning::LegacyFormat), Some(HealthSeverity::Warning));
+}
+
+#[test]
+fn unverified_contact_follows_verification_state() {
+    let never = HealthInputs::default();
+    assert_eq!(severity_of(&never, HealthWarning::UnverifiedContact), Some(HealthSeverity::Warning));
+
+    let verified = HealthInputs { verification: Some(VerificationState::Verified), ..Default::default() };
+    assert_eq!(severity_of(&verified, HealthWarning::UnverifiedContact), None);
+
+    let broken = HealthInputs { verification: Some(VerificationState::Broken), ..Default::default() };
+    assert_eq!(severity_of(&broken, HealthWarning::UnverifiedContact), Some(HealthSeverity::Critical));
+
+    let forged = HealthInputs { verification: Some(VerificationState::Verified), unverified_messages: 1, ..Default::default() };
+    assert_eq!(severity_of(&forged, HealthWarning::UnverifiedContact), Some(HealthSeverity::Critical));
+}
+
+#[test]
+fn results_go_stale() {
+    let evaluated = health::evaluate(&HealthInputs::default(), NOW);
+    assert!(!evaluated.is_stale(NOW + 60));
+    assert!(evaluated.is_stale(NOW + health::REEVALUATE_AFTER_SECS));
+}
@@ -0,0 +1,321 @@
+// End-to-end message flow against a local homeserver: send, list,
+// decrypt and verify, including blobs in the formats older clients wrote.
+mod common;
+
+use ciborium::Value;
+use common::{Harness, TestUser};
+use pubky_messenger_core::local_store::LocalStore;
+use pubky_messenger_core::protocol::{self, PeerVersions, PROTOCOL_VERSION};
+use pubky_messenger_core::quarantine::Quarantine;
+use pubky_messenger_core::storage::StoredMessage;
+use pubky_messenger_core::wire;
+use std::collections::HashSet;
+
+async fn conversation(reader: &TestUser, other: &TestUser) -> Vec<StoredMessage> {
+    reader.handler
+        .get_new_chat_messages(&other.keypair.public_key(), &HashSet::new())
+        .await
+        .expect("conversation should load")
+}
+
+// The only message blob `user` has written
+async fn only_blob(user: &TestUser) -> String {
+    let blobs = user.handler.own_message_blobs().await.expect("blobs should list");
+    assert_eq!(blobs.len(), 1, "expected exactly one blob, got {:?}", blobs);
+    blobs.into_iter().next().unwrap()
+}
+
+// The envelope payload as the bare JSON object pre-envelope clients stored
+fn payload_as_legacy_json(blob: &[u8]) -> serde_json::Map<String, serde_json::Value> {
+    let envelope: Value = ciborium::from_reader(blob).expect("blob should be CBOR");
+    let payload = envelope
+        .as_map()
+        .and_then(|entries| entries.iter().find(|(key, _)| key.as_text() == Some("payload")))
+        .map(|(_, payload)| payload.clone())
+        .expect("envelope should have a payload");
+    match cbor_to_json(payload) {
+        serde_json::Value::Object(object) => object,
+        other => panic!("payload should be a map, got {}", other),
+    }
+}
+
+// Byte strings become arrays of numbers, as serde_json wrote Vec<u8>
+fn cbor_to_json(value: Value) -> serde_json::Value {
+    match value {
+        Value::Integer(integer) => serde_json::json!(i128::from(integer) as i64),
+        Value::Bytes(bytes) => serde_json::json!(bytes),
+        Value::Text(text) => serde_json::Value::String(text),
+        Value::Bool(flag) => serde_json::Value::Bool(flag),
+        Value::Null => serde_json::Value::Null,
+        Value::Array(items) => serde_json::Value::Array(items.into_iter().map(cbor_to_json).collect()),
+        Value::Map(entries) => serde_json::Value::Object(
+            entries
+                .into_iter()
+                .map(|(key, value)| (key.into_text().expect("keys should be text"), cbor_to_json(value)))
+                .collect(),
+        ),
+        other => panic!("unexpected CBOR value {:?}", other),
+    }
+}
+
+// Replace a blob with `body` under a new file name in the same directory
+async fn rewrite_blob(user: &TestUser, url: &str, file_name: &str, body: Vec<u8>) -> String {
+    let directory = &url[..url.rfind('/').unwrap() + 1];
+    let new_url = format!("{}{}", directory, file_name);
+    user.delete(url).await;
+    user.put_bytes(&new_url, body).await;
+    new_url
+}
+
+#[tokio::test]
+async fn message_round_trip_between_two_users() {
+    let harness = Harness::start().await;
+    let alice = harness.user().await;
+    let bob = harness.user().await;
+
+    alice.handler
+        .send_message(&bob.keypair.public_key(), "hello bob")
+        .await
+        .expect("send should succeed");
+
+    let received = conversation(&bob, &alice).await;
+    assert_eq!(received.len(), 1);
+    let message = &received[0].message;
+    assert_eq!(message.content, "hello bob");
+    assert_eq!(message.sender, alice.keypair.public_key().to_string());
+    assert!(message.verified, "signature should verify");
+    assert!(!message.is_own_message);
+
+    // The sender reads the same message back from their own homeserver
+    let sent = conversation(&alice, &bob).await;
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].message.id, message.id);
+    assert!(sent[0].message.is_own_message);
+    assert!(sent[0].message.verified);
+
+    let blob = only_blob(&alice).await;
+    assert!(blob.ends_with(wire::BLOB_EXTENSION));
+    assert!(!String::from_utf8_lossy(&alice.get_bytes(&blob).await).contains("hello bob"));
+}
+
+#[tokio::test]
+async fn replies_merge_into_one_conversation() {
+    let harness = Harness::start().await;
+    let alice = harness.user().await;
+    let bob = harness.user().await;
+
+    alice.handler.send_message(&bob.keypair.public_key(), "ping").await.unwrap();
+    bob.handler.send_message(&alice.keypair.public_key(), "pong").await.unwrap();
+
+    for (reader, other) in [(&alice, &bob), (&bob, &alice)] {
+        let messages = conversation(reader, other).await;
+        let contents: HashSet<_> = messages.iter().map(|stored| stored.message.content.as_str()).collect();
+        assert_eq!(contents, HashSet::from(["ping", "pong"]));
+        assert!(messages.iter().all(|stored| stored.message.verified));
+    }
+}
+
+#[tokio::test]
+async fn third_party_cannot_read_conversation() {
+    let harness = Harness::start().await;
+    let alice = harness.user().await;
+    let bob = harness.user().await;
+    let eve = harness.user().await;
+
+    alice.handler.send_message(&bob.keypair.public_key(), "for bob only").await.unwrap();
+
+    assert!(conversation(&eve, &alice).await.is_empty());
+}
+
+#[tokio::test]
+async fn contact_card_extras_are_signed() {
+    let harness = Harness::start().await;
+    let alice = harness.user().await;
+    let bob = harness.user().await;
+    let carol = harness.user().await;
+
+    alice.handler
+        .share_contact(&bob.keypair.public_key(), &carol.keypair.public_key())
+        .await
+        .expect("share should succeed");
+
+    let received = conversation(&bob, &alice).await;
+    assert_eq!(received.len(), 1);
+    let card = received[0].message.contact_card.as_ref().expect("card should decrypt");
+    assert_eq!(card.pubky, carol.keypair.public_key().to_string());
+    assert!(received[0].message.verified);
+}
+
+#[tokio::test]
+async fn legacy_json_blob_with_embedded_id_still_verifies() {
+    let harness = Harness::start().await;
+    let alice = harness.user().await;
+    let bob = harness.user().await;
+
+    alice.handler.send_message(&bob.keypair.public_key(), "from an older client").await.unwrap();
+    let blob = only_blob(&alice).await;
+    let id = conversation(&bob, &alice).await[0].message.id.clone();
+
+    let legacy = payload_as_legacy_json(&alice.get_bytes(&blob).await);
+    let file_name = format!("{}{}", id, wire::LEGACY_BLOB_EXTENSION);
+    rewrite_blob(&alice, &blob, &file_name, serde_json::to_vec(&legacy).unwrap()).await;
+
+    let received = conversation(&bob, &alice).await;
+    assert_eq!(received.len(), 1);
+    assert_eq!(received[0].message.id, id);
+    assert_eq!(received[0].message.content, "from an older client");
+    assert!(received[0].message.verified);
+}
+
+#[tokio::test]
+async fn legacy_json_blob_takes_id_from_file_name() {
+    let harness = Harness::start().await;
+    let alice = harness.user().await;
+    let bob = harness.user().await;
+
+    let content = "before message ids were embedded";
+    alice.handler.send_message(&bob.keypair.public_key(), content).await.unwrap();
+    let blob = only_blob(&alice).await;
+
+    // The oldest clients neither embedded the id nor signed it
+    let mut legacy = payload_as_legacy_json(&alice.get_bytes(&blob).await);
+    legacy.remove("msg_id");
+    let timestamp = legacy["timestamp"].as_u64().expect("timestamp should be a number");
+    let mut digest = blake3::Hasher::new();
+    digest.update(content.as_bytes());
+    digest.update(alice.keypair.public_key().as_bytes());
+    digest.update(&timestamp.to_be_bytes());
+    let signature = alice.keypair.sign(digest.finalize().as_bytes());
+    legacy.insert("signature_bytes".to_string(), serde_json::json!(signature.to_bytes().to_vec()));
+
+    let file_name = format!("legacy-id{}", wire::LEGACY_BLOB_EXTENSION);
+    rewrite_blob(&alice, &blob, &file_name, serde_json::to_vec(&legacy).unwrap()).await;
+
+    let received = conversation(&bob, &alice).await;
+    assert_eq!(received.len(), 1);
+    assert_eq!(received[0].message.id, "legacy-id");
+    assert_eq!(received[0].message.content, content);
+    assert!(received[0].message.verified);
+}
+
+// Signature over content, sender and timestamp only, as the oldest clients check it
+fn signs_legacy_digest(sender: &TestUser, blob: &[u8], content: &str) -> bool {
+    let payload = payload_as_legacy_json(blob);
+    let timestamp = payload["timestamp"].as_u64().expect("timestamp should be a number");
+    let mut digest = blake3::Hasher::new();
+    digest.update(content.as_bytes());
+    digest.update(sender.keypair.public_key().as_bytes());
+    digest.update(&timestamp.to_be_bytes());
+    payload["signature_bytes"] == serde_json::json!(sender.keypair.sign(digest.finalize().as_bytes()).to_bytes().to_vec())
+}
+
+#[tokio::test]
+async fn extended_digest_waits_for_peer_version() {
+    let harness = Harness::start().await;
+    let alice = harness.user().await;
+    let bob = harness.user().await;
+
+    // Nothing heard from bob yet, so he might be an old client
+    alice.handler.send_message(&bob.keypair.public_key(), "hello").await.unwrap();
+    let blob = only_blob(&alice).await;
+    assert!(signs_legacy_digest(&alice, &alice.get_bytes(&blob).await, "hello"));
+    assert!(conversation(&bob, &alice).await[0].message.verified);
+    alice.delete(&blob).await;
+
+    let dir = std::env::temp_dir().join(format!("pubky-messenger-test-{}", uuid::Uuid::new_v4()));
+    let store = LocalStore::new(dir);
+    protocol::record_peer_version(&store, &alice.keypair.public_key(), &bob.keypair.public_key().to_string(), PROTOCOL_VERSION).unwrap();
+    let handler = alice.handler.clone().with_peer_versions(PeerVersions::new(&store, &alice.keypair.public_key()));
+
+    handler.send_message(&bob.keypair.public_key(), "hello").await.unwrap();
+    let blob = only_blob(&alice).await;
+    assert!(!signs_legacy_digest(&alice, &alice.get_bytes(&blob).await, "hello"));
+    assert!(conversation(&bob, &alice).await[0].message.verified);
+}
+
+#[tokio::test]
+async fn tampered_blob_fails_verification() {
+    let harness = Harness::start().await;
+    let alice = harness.user().await;
+    let bob = harness.user().await;
+
+    alice.handler.send_message(&bob.keypair.public_key(), "original").await.unwrap();
+    let blob = only_blob(&alice).await;
+
+    let mut legacy = payload_as_legacy_json(&alice.get_bytes(&blob).await);
+    let timestamp = legacy["timestamp"].as_u64().unwrap();
+    legacy.insert("timestamp".to_string(), serde_json::json!(timestamp + 60));
+    let file_name = format!("tampered{}", wire::LEGACY_BLOB_EXTENSION);
+    rewrite_blob(&alice, &blob, &file_name, serde_json::to_vec(&legacy).unwrap()).await;
+
+    let received = conversation(&bob, &alice).await;
+    assert_eq!(received.len(), 1);
+    assert_eq!(received[0].message.content, "original");
+    assert!(!received[0].message.verified);
+}
+
+#[tokio::test]
+async fn envelopes_from_newer_versions_are_skipped() {
+    let harness = Harness::start().await;
+    let alice = harness.user().await;
+    let bob = harness.user().await;
+
+    alice.handler.send_message(&bob.keypair.public_key(), "current").await.unwrap();
+    let blob = only_blob(&alice).await;
+
+    let mut envelope: Value = ciborium::from_reader(alice.get_bytes(&blob).await.as_slice()).unwrap();
+    for (key, value) in envelope.as_map_mut().unwrap() {
+        if key.as_text() == Some("version") {
+            *value = Value::from(wire::WIRE_VERSION + 1);
+        }
+    }
+    let mut future_blob = Vec::new();
+    ciborium::into_writer(&envelope, &mut future_blob).unwrap();
+    alice.put_bytes(&blob.replace(wire::BLOB_EXTENSION, &format!("-next{}", wire::BLOB_EXTENSION)), future_blob).await;
+
+    let received = conversation(&bob, &alice).await;
+    assert_eq!(received.len(), 1);
+    assert_eq!(received[0].message.content, "current");
+}
+
+#[tokio::test]
+async fn malformed_blobs_are_quarantined_not_retried() {
+    let harness = Harness::start().await;
+    let alice = harness.user().await;
+    let bob = harness.user().await;
+
+    alice.handler.send_message(&bob.keypair.public_key(), "still readable").await.unwrap();
+    let blob = only_blob(&alice).await;
+    let garbage_url = blob.replace(wire::BLOB_EXTENSION, &format!("-garbage{}", wire::BLOB_EXTENSION));
+    alice.put_bytes(&garbage_url, b"not a message".to_vec()).await;
+
+    let dir = std::env::temp_dir().join(format!("pubky-messenger-test-{}", uuid::Uuid::new_v4()));
+    let quarantine = Quarantine::new(LocalStore::new(dir), &bob.keypair.public_key());
+    let handler = bob.handler.clone().with_quarantine(quarantine.clone());
+
+    let received = handler
+        .get_new_chat_messages(&alice.keypair.public_key(), &HashSet::new())
+        .await
+        .expect("conversation should load");
+    assert_eq!(received.len(), 1);
+    assert_eq!(received[0].message.content, "still readable");
+    assert!(quarantine.entries().contains_key(&garbage_url), "garbage blob should be quarantined");
+}
+
+#[tokio::test]
+async fn status_leaves_the_rest_of_the_profile_alone() {
+    let harness = Harness::start().await;
+    let alice = harness.user().await;
+
+    // Written by another client, with a bio longer than we'd publish
+    let bio = "b".repeat(500);
+    let profile_url = format!("pubky://{}/pub/pubky.app/profile.json", alice.keypair.public_key());
+    alice.put_bytes(&profile_url, serde_json::to_vec(&serde_json::json!({ "name": " Alice ", "bio": bio })).unwrap()).await;
+
+    let profile = alice.handler.set_status("  away  ").await.expect("status should publish");
+    assert_eq!(profile.status.as_deref(), Some("away"));
+    assert_eq!(profile.name, " Alice ");
+    assert_eq!(profile.bio.as_deref(), Some(bio.as_str()));
+
+    assert!(alice.handler.set_status(&"s".repeat(500)).await.is_err());
+}
@@ -0,0 +1,52 @@
+// Push endpoints come from contacts, so nothing they publish may point the
+// ping at our own machine or network.
+use pubky_messenger_core::push;
+use std::net::IpAddr;
+
+#[test]
+fn rejects_local_endpoints() {
+    for endpoint in [
+        "http://push.example.com/up",
+        "https://127.0.0.1/up",
+        "https://127.1.2.3:8443/up",
+        "https://0.0.0.0/up",
+        "https://10.0.0.1/up",
+        "https://172.16.5.4/up",
+        "https://192.168.1.1/up",
+        "https://169.254.169.254/latest/meta-data",
+        "https://100.64.0.1/up",
+        "https://[::1]/up",
+        "https://[::]/up",
+        "https://[fe80::1]/up",
+        "https://[fd00::1]/up",
+        "https://[::ffff:127.0.0.1]/up",
+        "https://localhost/up",
+        "https://printer.local/up",
+    ] {
+        assert!(push::check_endpoint(endpoint).is_err(), "{} should be rejected", endpoint);
+    }
+}
+
+#[test]
+fn accepts_public_endpoints() {
+    for endpoint in ["https://push.example.com/up/abc", "https://93.184.216.34/up", "https://[2606:4700::1111]/up"] {
+        assert!(push::check_endpoint(endpoint).is_ok(), "{} should be accepted", endpoint);
+    }
+}
+
+#[test]
+fn classifies_addresses() {
+    for ip in ["127.0.0.1", "10.1.2.3", "192.168.0.10", "169.254.1.1", "0.0.0.0", "::1", "fe80::abcd", "fc00::1"] {
+        assert!(!push::is_public_address(ip.parse::<IpAddr>().unwrap()), "{} should not be public", ip);
+    }
+    for ip in ["1.1.1.1", "8.8.8.8", "2001:4860:4860::8888"] {
+        assert!(push::is_public_address(ip.parse::<IpAddr>().unwrap()), "{} should be public", ip);
+    }
+}
+
+// A name that resolves to loopback is refused before anything is sent
+#[tokio::test]
+async fn ping_refuses_names_resolving_locally() {
+    assert!(push::ping("https://127.0.0.1.nip.io/up").await.is_err());
+    assert!(push::ping("https://localhost/up").await.is_err());
+}
@@ -0,0 +1,100 @@
+// The local cache orders conversations by when messages reached us, since
+// the timestamp a sender claims can be anything.
+use pkarr::Keypair;
+use pubky_messenger_core::crypto_compat::CURRENT_CIPHER_FORMAT;
+use pubky_messenger_core::local_store::LocalStore;
+use pubky_messenger_core::mentions::{self, MentionEvent, NotificationPriority};
+use pubky_messenger_core::messaging::ChatMessage;
+use pubky_messenger_core::read_state::ReadState;
+use pubky_messenger_core::storage::{self, Storage, StoredMessage};
+
+const CONVERSATION: &str = "conversation";
+
+fn temp_dir() -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("pubky-messenger-test-{}", uuid::Uuid::new_v4()))
+}
+
+fn message(id: &str, timestamp: u64, received_at: u64) -> StoredMessage {
+    let mut message: ChatMessage = serde_json::from_value(serde_json::json!({
+        "id": id,
+        "sender": "sender",
+        "content": id,
+        "timestamp": timestamp,
+        "verified": true,
+        "is_own_message": false,
+    }))
+    .expect("message should deserialize");
+    message.received_at = Some(received_at);
+    StoredMessage { message, cipher_format: CURRENT_CIPHER_FORMAT }
+}
+
+fn ids(messages: &[StoredMessage]) -> Vec<&str> {
+    messages.iter().map(|stored| stored.message.id.as_str()).collect()
+}
+
+// One message claims to be from long ago, another from tomorrow
+fn storage_with_suspect_messages() -> Storage {
+    let storage = Storage::open(&temp_dir(), &Keypair::random()).expect("storage should open");
+    storage
+        .insert_messages(CONVERSATION, &[
+            message("a", 1_000, 1_000),
+            message("b", 2_000, 2_000),
+            message("backdated", 10, 3_000),
+            message("c", 4_000, 4_000),
+            message("post-dated", 90_000, 5_000),
+            message("d", 6_000, 6_000),
+        ])
+        .expect("messages should insert");
+    storage
+}
+
+#[test]
+fn pages_follow_arrival_order() {
+    let storage = storage_with_suspect_messages();
+    let all = storage.conversation_messages(CONVERSATION).unwrap();
+    assert_eq!(ids(&all), ["a", "b", "backdated", "c", "post-dated", "d"]);
+
+    let mut paged = Vec::new();
+    let mut cursor: Option<String> = None;
+    loop {
+        let before = cursor.as_deref().map(|cursor| storage::parse_page_cursor(cursor).unwrap());
+        let (page, has_more) = storage.conversation_page(CONVERSATION, 2, before).unwrap();
+        cursor = page.first().map(|stored| storage::page_cursor(&stored.message));
+        paged.splice(0..0, page);
+        if !has_more {
+            break;
+        }
+    }
+    assert_eq!(ids(&paged), ids(&all));
+}
+
+#[test]
+fn summaries_use_the_latest_arrival() {
+    let storage = storage_with_suspect_messages();
+    let summaries = storage.conversation_summaries().unwrap();
+    assert_eq!(summaries[0].last_message.id, "d");
+}
+
+#[test]
+fn read_state_and_mentions_use_arrival() {
+    let store = LocalStore::new(temp_dir());
+    let owner = Keypair::random().public_key();
+    let read_state = ReadState::new(&store, &owner);
+
+    // Read at 5_000: a message claiming 90_000 that arrived before is read
+    read_state.mark_read(CONVERSATION, 5_000).unwrap();
+    assert_eq!(read_state.record_incoming(CONVERSATION, &[1_000, 5_000, 6_000]).unwrap(), 1);
+
+    let mention = |timestamp, received_at| MentionEvent {
+        conversation: CONVERSATION.to_string(),
+        sender: "sender".to_string(),
+        timestamp,
+        received_at,
+        priority: NotificationPriority::High,
+    };
+    let own = owner.to_string();
+    assert_eq!(mentions::take_new_mentions(&store, &own, CONVERSATION, vec![mention(4_000, 4_000)]).unwrap().len(), 1);
+    // Backdated, but it only just arrived
+    assert_eq!(mentions::take_new_mentions(&store, &own, CONVERSATION, vec![mention(10, 4_500)]).unwrap().len(), 1);
+    assert!(mentions::take_new_mentions(&store, &own, CONVERSATION, vec![mention(10, 4_500)]).unwrap().is_empty());
+}
@@ -0,0 +1,360 @@
+// UniFFI bindings for native iOS and Android apps.
+//
+// Wraps the same AppState, PrivateMessageHandler and sync code the
+// desktop app drives, so Swift and Kotlin get the encryption, path
+// derivation and sync logic without reimplementing the protocol. Only
+// plain records cross the boundary; everything else stays in Rust.
+use pkarr::{Keypair, PublicKey};
+use pubky_messenger_core::blocks::BlockList;
+use pubky_messenger_core::crypto_compat;
+use pubky_messenger_core::error::{ErrorContext, MessengerError, MessengerResult};
+use pubky_messenger_core::events::EventSink;
+use pubky_messenger_core::link_preview;
+use pubky_messenger_core::mentions;
+use pubky_messenger_core::messaging::{AppState, ChatMessage, FollowedUser, MessageExtras, PrivateMessageHandler};
+use pubky_messenger_core::mutes::MuteList;
+use pubky_messenger_core::outbox::{self, Outbox};
+use pubky_messenger_core::panic_wipe::{self, WipeReport};
+use pubky_messenger_core::push;
+use pubky_messenger_core::security_log::{SecurityEventKind, SecurityLog};
+use pubky_messenger_core::sync;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+uniffi::setup_scaffolding!();
+
+// Every core error, with the same stable codes the desktop frontend sees
+#[derive(Debug, thiserror::Error, uniffi::Error)]
+pub enum FfiError {
+    #[error("{message}")]
+    Messenger { code: String, message: String },
+}
+
+impl From<MessengerError> for FfiError {
+    fn from(error: MessengerError) -> Self {
+        FfiError::Messenger { code: error.code().to_string(), message: error.to_string() }
+    }
+}
+
+type FfiResult<T> = Result<T, FfiError>;
+
+// Receives the events the desktop app gets as Tauri events, e.g.
+// "message-received", with the payload as JSON
+#[uniffi::export(with_foreign)]
+pub trait EventListener: Send + Sync {
+    fn on_event(&self, name: String, payload_json: String);
+}
+
+struct ListenerEvents(Option<Arc<dyn EventListener>>);
+
+impl EventSink for ListenerEvents {
+    fn emit_json(&self, event: &str, payload: serde_json::Value) -> anyhow::Result<()> {
+        if let Some(listener) = &self.0 {
+            listener.on_event(event.to_string(), payload.to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(uniffi::Record)]
+pub struct Profile {
+    pub public_key: String,
+    pub name: Option<String>,
+}
+
+#[derive(uniffi::Record)]
+pub struct Message {
+    pub id: String,
+    pub sender: String,
+    pub sender_name: Option<String>,
+    pub content: String,
+    pub timestamp: u64,
+    pub verified: bool,
+    pub is_own_message: bool,
+}
+
+impl From<ChatMessage> for Message {
+    fn from(message: ChatMessage) -> Self {
+        Self {
+            id: message.id,
+            sender: message.sender,
+            sender_name: message.sender_name,
+            content: message.content,
+            timestamp: message.timestamp,
+            verified: message.verified,
+            is_own_message: message.is_own_message,
+        }
+    }
+}
+
+#[derive(uniffi::Record)]
+pub struct Contact {
+    pub pubky: String,
+    pub name: Option<String>,
+    pub mutual: bool,
+    pub status: Option<String>,
+}
+
+impl From<FollowedUser> for Contact {
+    fn from(user: FollowedUser) -> Self {
+        Self { pubky: user.pubky, name: user.name, mutual: user.mutual, status: user.status }
+    }
+}
+
+#[derive(uniffi::Enum)]
+pub enum SendOutcome {
+    Sent,
+    // Kept in the outbox; sent by retry_outbox
+    Queued { outbox_id: String },
+}
+
+#[derive(uniffi::Record)]
+pub struct WipeSummary {
+    pub remote_deleted: u64,
+    pub remote_failed: u64,
+    pub local_removed: u64,
+    pub local_failed: u64,
+}
+
+impl From<WipeReport> for WipeSummary {
+    fn from(report: WipeReport) -> Self {
+        Self {
+            remote_deleted: report.remote_deleted as u64,
+            remote_failed: report.remote_failed as u64,
+            local_removed: report.local_removed as u64,
+            local_failed: report.local_failed as u64,
+        }
+    }
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn parse_pubkey(pubkey: &str) -> MessengerResult<PublicKey> {
+    PublicKey::try_from(pubkey)
+        .map_err(|e| MessengerError::InvalidPublicKey(format!("Invalid public key: {}", e)))
+}
+
+#[derive(uniffi::Object)]
+pub struct Messenger {
+    state: AppState,
+    listener: Mutex<Option<Arc<dyn EventListener>>>,
+}
+
+impl Messenger {
+    fn events(&self) -> ListenerEvents {
+        ListenerEvents(self.listener.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clone())
+    }
+
+    async fn keypair(&self) -> MessengerResult<Keypair> {
+        self.state.keypair.lock().await.clone().ok_or(MessengerError::NotSignedIn)
+    }
+
+    async fn handler(&self) -> MessengerResult<PrivateMessageHandler> {
+        self.state.create_handler().await?.ok_or(MessengerError::NotSignedIn)
+    }
+
+    async fn sign_in(&self, keypair: Keypair) -> MessengerResult<Profile> {
+        *self.state.keypair.lock().await = Some(keypair.clone());
+
+        let handler = self.state.create_handler_and_sign_in().await?
+            .ok_or(MessengerError::NotSignedIn)?;
+        let name = handler.get_own_profile().await.err_context("Failed to get profile")?;
+        *self.state.user_name.lock().await = name.clone();
+
+        self.state.open_storage(&keypair).await?;
+        SecurityLog::new(&self.state.store, &keypair.public_key()).record(SecurityEventKind::SignIn, None, None);
+
+        Ok(Profile { public_key: keypair.public_key().to_string(), name })
+    }
+}
+
+#[uniffi::export(async_runtime = "tokio")]
+impl Messenger {
+    // `data_dir` holds the encrypted local cache and settings, e.g. the
+    // app's Application Support or files directory
+    #[uniffi::constructor]
+    pub fn new(data_dir: String) -> Arc<Self> {
+        Arc::new(Self {
+            state: AppState::new(PathBuf::from(data_dir)),
+            listener: Mutex::new(None),
+        })
+    }
+
+    pub fn set_event_listener(&self, listener: Option<Arc<dyn EventListener>>) {
+        let progress = listener.clone().map(|listener| Arc::new(ListenerEvents(Some(listener))) as Arc<dyn EventSink>);
+        self.state.progress.set_sink(progress);
+        *self.listener.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = listener;
+    }
+
+    pub async fn sign_in_with_recovery_file(&self, recovery_file: Vec<u8>, passphrase: String) -> FfiResult<Profile> {
+        // Argon2 key stretching is slow; keep it off the async workers
+        let keypair = tokio::task::spawn_blocking(move || {
+            crypto_compat::decrypt_recovery_file(&recovery_file, &passphrase)
+        }).await.err_context("Task failed")?.map_err(MessengerError::from)?;
+        Ok(self.sign_in(keypair).await?)
+    }
+
+    // Sign in again with a key the app kept in the Keychain or Keystore
+    pub async fn sign_in_with_secret_key(&self, secret_key: Vec<u8>) -> FfiResult<Profile> {
+        let secret_key: [u8; 32] = secret_key.try_into()
+            .map_err(|_| MessengerError::InvalidInput("Secret key must be 32 bytes".to_string()))?;
+        Ok(self.sign_in(Keypair::from_secret_key(&secret_key)).await?)
+    }
+
+    // For the app to store in platform secure storage; never persisted here
+    pub async fn secret_key(&self) -> FfiResult<Vec<u8>> {
+        Ok(self.keypair().await?.secret_key().to_vec())
+    }
+
+    pub async fn public_key(&self) -> Option<String> {
+        self.state.keypair.lock().await.as_ref().map(|keypair| keypair.public_key().to_string())
+    }
+
+    pub async fn sign_out(&self) {
+        if let Some(keypair) = self.state.keypair.lock().await.take() {
+            SecurityLog::new(&self.state.store, &keypair.public_key())
+                .record(SecurityEventKind::SessionRevoked, None, Some("Signed out"));
+        }
+        *self.state.user_name.lock().await = None;
+        *self.state.is_signed_in.lock().await = false;
+        self.state.operations.cancel_all();
+        *self.state.storage.lock().await = None;
+        self.state.http_cache.clear();
+        self.state.watcher.clear();
+    }
+
+    pub async fn send_message(&self, recipient: String, content: String) -> FfiResult<SendOutcome> {
+        let keypair = self.keypair().await?;
+        let handler = self.handler().await?;
+        let recipient = parse_pubkey(&recipient)?;
+        if BlockList::new(&self.state.store, &keypair.public_key()).is_blocked(&recipient.to_string()) {
+            return Err(MessengerError::ContactBlocked.into());
+        }
+
+        let preview = if link_preview::is_enabled(&self.state.store) {
+            link_preview::preview_for_message(&content).await
+        } else {
+            None
+        };
+        let extras = MessageExtras {
+            link_preview: preview,
+            mentions: mentions::parse_mentions(&content),
+            ..Default::default()
+        };
+
+        let send_result = handler.send_message_with_extras(&recipient, &content, Some(&extras)).await;
+
+        let _guard = self.state.outbox_lock.lock().await;
+        let outbox = Outbox::new(&self.state.store, &keypair.public_key());
+        if let Err(e) = send_result {
+            tracing::warn!("📥 Send failed, queueing message for retry: {}", e);
+            let entry = outbox.enqueue(&recipient, &content, extras, &e.to_string())
+                .map_err(|queue_err| MessengerError::from(queue_err).context(&format!("Failed to send message: {} (and failed to queue it)", e)))?;
+            return Ok(SendOutcome::Queued { outbox_id: entry.id });
+        }
+
+        if let Err(e) = outbox.retry_all_now() {
+            tracing::warn!("⚠️  Failed to reschedule outbox: {}", e);
+        }
+        Ok(SendOutcome::Sent)
+    }
+
+    // Sync one conversation into the local cache and return all of it,
+    // oldest first. Offline, the cached messages are still returned.
+    pub async fn get_conversation(&self, other: String) -> FfiResult<Vec<Message>> {
+        let keypair = self.keypair().await?;
+        let handler = self.handler().await?;
+
+        let synced = sync::sync_conversation(&self.state, &handler, &other).await?;
+        if let Some(e) = &synced.fetch_error {
+            tracing::warn!("📴 Failed to sync conversation, showing cached messages: {}", e);
+        }
+
+        let mut messages = synced.messages;
+        let muted = MuteList::new(&self.state.store, &keypair.public_key()).is_muted(&other, now_secs());
+        sync::record_loaded_conversation(&self.events(), &self.state, &keypair, &other, &messages, synced.health, muted).await;
+        sync::label_senders(&self.state, &mut messages).await;
+        Ok(messages.into_iter().map(Message::from).collect())
+    }
+
+    // Sync every known conversation; new messages are returned and also
+    // delivered to the event listener
+    pub async fn sync_all(&self) -> FfiResult<Vec<Message>> {
+        let messages = sync::sync_all(&self.events(), &self.state).await?;
+        Ok(messages.into_iter().map(Message::from).collect())
+    }
+
+    // Everyone the user follows on pubky.app, with profile names
+    pub async fn get_contacts(&self) -> FfiResult<Vec<Contact>> {
+        let handler = self.handler().await?;
+        let users = handler.get_followed_users_with_profiles().await
+            .err_context("Failed to get followed users")?;
+        Ok(users.into_iter().map(Contact::from).collect())
+    }
+
+    // Publish the endpoint a UnifiedPush distributor (or APNs/FCM relay)
+    // handed the app, so senders wake us instead of us polling. Cheap to
+    // call on every start; it only writes when the endpoint changed.
+    pub async fn register_push_endpoint(&self, endpoint: String) -> FfiResult<()> {
+        let keypair = self.keypair().await?;
+        let owner = keypair.public_key();
+        if push::registered_endpoint(&self.state.store, &owner).as_deref() == Some(endpoint.as_str()) {
+            return Ok(());
+        }
+        self.handler().await?.publish_push_endpoint(Some(&endpoint)).await
+            .err_context("Failed to publish push endpoint")?;
+        push::set_registered_endpoint(&self.state.store, &owner, Some(&endpoint))
+            .err_context("Failed to save push endpoint")?;
+        Ok(())
+    }
+
+    // Go back to polling, e.g. when the distributor unregisters the app
+    pub async fn unregister_push(&self) -> FfiResult<()> {
+        let keypair = self.keypair().await?;
+        self.handler().await?.publish_push_endpoint(None).await
+            .err_context("Failed to remove push endpoint")?;
+        push::set_registered_endpoint(&self.state.store, &keypair.public_key(), None)
+            .err_context("Failed to save push endpoint")?;
+        Ok(())
+    }
+
+    // For the app to call when a push arrives. The ping carries nothing,
+    // so this is a full sync pass.
+    pub async fn handle_push(&self) -> FfiResult<Vec<Message>> {
+        self.sync_all().await
+    }
+
+    // Token for panic_wipe, valid for two minutes
+    pub fn request_panic_wipe_token(&self) -> String {
+        panic_wipe::request_token(now_secs()).token
+    }
+
+    // Irreversibly delete everything under `data_dir`, and with
+    // `delete_remote` our blobs on the homeserver. The app still has to
+    // drop the secret key it keeps in the Keychain or Keystore.
+    pub async fn panic_wipe(&self, token: String, delete_remote: bool) -> FfiResult<WipeSummary> {
+        let handler = if delete_remote { Some(self.handler().await?) } else { None };
+        let report = panic_wipe::wipe(&self.state, handler.as_ref(), &token, delete_remote, now_secs()).await?;
+        Ok(report.into())
+    }
+
+    // Send queued messages now instead of waiting for their backoff, e.g.
+    // from a background fetch. Returns how many were queued.
+    pub async fn retry_outbox(&self) -> FfiResult<u32> {
+        let keypair = self.keypair().await?;
+        let queued = {
+            let _guard = self.state.outbox_lock.lock().await;
+            Outbox::new(&self.state.store, &keypair.public_key())
+                .retry_all_now()
+                .err_context("Failed to reschedule outbox")?
+        };
+        outbox::flush_due(&self.state, &self.events()).await
+            .err_context("Failed to send queued messages")?;
+        Ok(queued as u32)
+    }
+}
@@ -0,0 +1,35 @@
+use tauri::{AppHandle, Manager};
+
+// Unread count on the macOS dock icon (and Linux launchers that support
+// it). Windows taskbar buttons can't show a number, so they get a dot.
+pub fn set_badge_count(app: &AppHandle, count: usize) {
+    let Some(window) = app.get_webview_window("main") else {
+        return;
+    };
+
+    #[cfg(target_os = "windows")]
+    let result = window.set_overlay_icon((count > 0).then(unread_dot));
+    #[cfg(not(target_os = "windows"))]
+    let result = window.set_badge_count((count > 0).then_some(count as i64));
+
+    if let Err(e) = result {
+        tracing::debug!("⚠️  Failed to update unread badge: {}", e);
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn unread_dot() -> tauri::image::Image<'static> {
+    const SIZE: u32 = 16;
+    let center = (SIZE as f32 - 1.0) / 2.0;
+    let radius = SIZE as f32 / 2.0;
+
+    let mut rgba = Vec::with_capacity((SIZE * SIZE * 4) as usize);
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            let distance = ((x as f32 - center).powi(2) + (y as f32 - center).powi(2)).sqrt();
+            let alpha = if distance <= radius { 255 } else { 0 };
+            rgba.extend_from_slice(&[220, 53, 69, alpha]);
+        }
+    }
+    tauri::image::Image::new_owned(rgba, SIZE, SIZE)
+}
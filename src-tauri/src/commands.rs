@@ -1,5 +1,49 @@
-use crate::messaging::{AppState, ChatMessage, PrivateMessageHandler, UserProfile};
-use anyhow::Result;
+use crate::app_lock::{self, AppLockStatus, Unlock};
+use crate::attachment_cache::{self, AttachmentCache, CacheUsage};
+use crate::attachment_expiry::{self, ExpiringUpload};
+use crate::attachment_limits;
+use crate::attachments::{self, Attachment, AttachmentFile};
+use crate::audio_pipeline;
+use crate::backup::{self, RestoreSummary};
+use crate::blocks::{BlockEntry, BlockList};
+use crate::connection::{self, ConnectionStatus};
+use crate::crypto_compat;
+use crate::error::{ErrorContext, MessengerError, MessengerResult};
+use crate::events::TauriEvents;
+use crate::export::{self, ExportFormat};
+use crate::health::ConversationHealth;
+use crate::image_pipeline;
+use crate::inbox_feed::{self, InboxFeed, InboxPage};
+use crate::link_preview;
+use crate::logging::{self, LogEntry, LogLevel, LogSettings};
+use crate::conversations::ConversationSummary;
+use crate::device_secret;
+use crate::downloads::Download;
+use crate::media_gallery::{self, MediaKind, MediaPage};
+use crate::mentions;
+use crate::mutes::{MuteEntry, MuteList};
+use crate::names::{NameResolver, ResolvedName};
+use crate::net::{self, NetworkSettings};
+use crate::messaging::{AppState, ChatMessage, Contact, ContactCard, Link, MessageExtras, PrivateMessageHandler, PubkyProfile, UserProfile};
+use crate::metrics::{self, NetworkStats};
+use crate::onboarding::{self, OnboardingState, OnboardingStep};
+use crate::outbox::{self, Outbox, OutboxEntry};
+use crate::panic_wipe::{self, WipeReport, WipeToken};
+use crate::presence::{self, ContactPresence, PresenceSettings};
+use crate::profiles;
+use crate::qr;
+use crate::quarantine::{Quarantine, QuarantinedMessage};
+use crate::read_state::{ReadState, UnreadCounts};
+use crate::retention::{self, CleanupReport, RetentionPolicy};
+use crate::security_log::{SecurityEvent, SecurityEventKind, SecurityLog};
+use crate::settings::{self, Settings, SettingsUpdate};
+use crate::startup;
+use crate::storage::{self, MANUAL_CONTACT_SOURCE};
+use crate::subscriptions::MessageSubscriptions;
+use crate::sync::{self, MessageReceivedEvent, SyncSettings};
+use crate::tray;
+use crate::verification::{self, VerificationStatus};
+use crate::webhook;
 use base64;
 use chacha20poly1305::{
     aead::{Aead, AeadCore, KeyInit, OsRng as ChaChaOsRng},
@@ -7,11 +51,14 @@ use chacha20poly1305::{
 };
 use hkdf::Hkdf;
 use pkarr::{Keypair, PublicKey};
-use pubky_common::recovery_file;
 use rand_core::{OsRng, RngCore};
 use serde::{Deserialize, Serialize};
 use sha2::Sha256;
-use tauri::{command, State};
+use std::path::{Path, PathBuf};
+use tauri::ipc::Channel;
+use tauri::{command, AppHandle, State};
+use tauri_plugin_clipboard_manager::ClipboardExt;
+use tauri_plugin_dialog::DialogExt;
 use tokio::task;
 
 // Session-related structures
@@ -26,46 +73,72 @@ struct EncryptedSession {
     ciphertext: Vec<u8>,
     nonce: Vec<u8>,
     salt: Vec<u8>,
+    #[serde(default)]
+    key_source: SessionKeySource,
 }
 
-// Secure key derivation using HKDF
-fn derive_encryption_key(salt: &[u8]) -> Result<[u8; 32], String> {
-    // Collect device-specific entropy
-    let mut device_info = Vec::new();
-
-    // Add hostname if available
-    if let Ok(hostname) = std::env::var("COMPUTERNAME").or_else(|_| std::env::var("HOSTNAME")) {
-        device_info.extend_from_slice(hostname.as_bytes());
-    }
-
-    // Add username if available (additional entropy)
-    if let Ok(username) = std::env::var("USERNAME").or_else(|_| std::env::var("USER")) {
-        device_info.extend_from_slice(username.as_bytes());
-    }
+// Where a saved session's key material came from
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum SessionKeySource {
+    // Hostname and username from the environment. Only read, for sessions
+    // saved before the device secret; they are re-encrypted once opened.
+    #[default]
+    Environment,
+    DeviceSecret,
+}
 
-    // Add application identifier
-    device_info.extend_from_slice(b"pubky_private_messenger_v1");
+const APP_KEY_INFO: &[u8] = b"pubky_private_messenger_v1";
 
-    // Use HKDF to derive a proper encryption key
-    let hk = Hkdf::<Sha256>::new(Some(salt), &device_info);
+// Secure key derivation using HKDF
+fn derive_encryption_key(salt: &[u8], key_material: &[u8]) -> MessengerResult<[u8; 32]> {
+    let hk = Hkdf::<Sha256>::new(Some(salt), key_material);
     let mut key = [0u8; 32];
     hk.expand(b"session_encryption_key", &mut key)
-        .map_err(|e| format!("HKDF expansion failed: {}", e))?;
+        .map_err(|e| MessengerError::Crypto(format!("HKDF expansion failed: {}", e)))?;
 
     Ok(key)
 }
 
-fn encrypt_keypair(keypair: &Keypair) -> Result<String, String> {
+fn device_key_material(secret: &[u8; 32]) -> Vec<u8> {
+    [secret.as_slice(), APP_KEY_INFO].concat()
+}
+
+// What older builds fed HKDF. GUI launches often lack HOSTNAME or USER
+// where a terminal launch had them, so each variable that is set now is
+// also tried as if it were missing.
+fn environment_key_materials() -> Vec<Vec<u8>> {
+    let hostname = std::env::var("COMPUTERNAME").or_else(|_| std::env::var("HOSTNAME")).ok();
+    let username = std::env::var("USERNAME").or_else(|_| std::env::var("USER")).ok();
+
+    let mut materials: Vec<Vec<u8>> = Vec::new();
+    for hostname in [hostname.as_deref(), None] {
+        for username in [username.as_deref(), None] {
+            let material = [
+                hostname.unwrap_or_default().as_bytes(),
+                username.unwrap_or_default().as_bytes(),
+                APP_KEY_INFO,
+            ].concat();
+            if !materials.contains(&material) {
+                materials.push(material);
+            }
+        }
+    }
+    materials
+}
+
+fn encrypt_keypair(keypair: &Keypair, data_dir: &Path) -> MessengerResult<String> {
     // Generate random salt for key derivation
     let mut salt = [0u8; 32];
     OsRng.fill_bytes(&mut salt);
 
     // Derive encryption key using HKDF
-    let key = derive_encryption_key(&salt)?;
+    let secret = device_secret::load_or_create(data_dir)?;
+    let key = derive_encryption_key(&salt, &device_key_material(&secret))?;
 
     // Create cipher instance
     let cipher = ChaCha20Poly1305::new_from_slice(&key)
-        .map_err(|e| format!("Failed to create cipher: {}", e))?;
+        .map_err(|e| MessengerError::Crypto(format!("Failed to create cipher: {}", e)))?;
 
     // Generate random nonce
     let nonce = ChaCha20Poly1305::generate_nonce(&mut ChaChaOsRng);
@@ -75,40 +148,68 @@ fn encrypt_keypair(keypair: &Keypair) -> Result<String, String> {
 
     // Encrypt with authenticated encryption
     let ciphertext = cipher.encrypt(&nonce, keypair_bytes.as_ref())
-        .map_err(|e| format!("Encryption failed: {}", e))?;
+        .map_err(|e| MessengerError::Crypto(format!("Encryption failed: {}", e)))?;
 
     // Package everything together
     let encrypted_session = EncryptedSession {
         ciphertext,
         nonce: nonce.to_vec(),
         salt: salt.to_vec(),
+        key_source: SessionKeySource::DeviceSecret,
     };
 
     // Serialize and encode
     let serialized = serde_json::to_vec(&encrypted_session)
-        .map_err(|e| format!("Serialization failed: {}", e))?;
+        .map_err(|e| MessengerError::Internal(format!("Serialization failed: {}", e)))?;
 
     Ok(base64::encode(serialized))
 }
 
-fn decrypt_keypair(encrypted_data: &str) -> Result<Keypair, String> {
+// The keypair, and whether the session should be re-encrypted because it
+// predates the device secret
+fn decrypt_keypair(encrypted_data: &str, data_dir: &Path) -> MessengerResult<(Keypair, bool)> {
     // Decode and deserialize
     let serialized = base64::decode(encrypted_data)
-        .map_err(|e| format!("Base64 decode failed: {}", e))?;
+        .map_err(|e| MessengerError::InvalidSession(format!("Base64 decode failed: {}", e)))?;
 
     let encrypted_session: EncryptedSession = serde_json::from_slice(&serialized)
-        .map_err(|e| format!("Deserialization failed: {}", e))?;
-
-    // Derive the same encryption key using stored salt
-    let key = derive_encryption_key(&encrypted_session.salt)?;
+        .map_err(|e| MessengerError::InvalidSession(format!("Deserialization failed: {}", e)))?;
+
+    match encrypted_session.key_source {
+        SessionKeySource::DeviceSecret => {
+            let secret = device_secret::load(data_dir)?.ok_or_else(|| MessengerError::InvalidSession(
+                "This device's secret is gone, so the saved session can't be opened. Sign in again with your recovery file.".to_string(),
+            ))?;
+            let key = derive_encryption_key(&encrypted_session.salt, &device_key_material(&secret))?;
+            Ok((decrypt_session(&encrypted_session, &key)?, false))
+        }
+        SessionKeySource::Environment => {
+            if device_secret::was_wiped(data_dir) {
+                return Err(MessengerError::InvalidSession(
+                    "The saved session was wiped. Sign in again with your recovery file.".to_string(),
+                ));
+            }
+            for material in environment_key_materials() {
+                let key = derive_encryption_key(&encrypted_session.salt, &material)?;
+                if let Ok(keypair) = decrypt_session(&encrypted_session, &key) {
+                    return Ok((keypair, true));
+                }
+            }
+            Err(MessengerError::InvalidSession(
+                "The computer name or user account changed since this session was saved. Sign in again with your recovery file.".to_string(),
+            ))
+        }
+    }
+}
 
+fn decrypt_session(encrypted_session: &EncryptedSession, key: &[u8; 32]) -> MessengerResult<Keypair> {
     // Create cipher instance
-    let cipher = ChaCha20Poly1305::new_from_slice(&key)
-        .map_err(|e| format!("Failed to create cipher: {}", e))?;
+    let cipher = ChaCha20Poly1305::new_from_slice(key)
+        .map_err(|e| MessengerError::Crypto(format!("Failed to create cipher: {}", e)))?;
 
     // Reconstruct nonce
     if encrypted_session.nonce.len() != 12 {
-        return Err("Invalid nonce length".to_string());
+        return Err(MessengerError::InvalidSession("Invalid nonce length".to_string()));
     }
     let mut nonce_array = [0u8; 12];
     nonce_array.copy_from_slice(&encrypted_session.nonce);
@@ -116,11 +217,11 @@ fn decrypt_keypair(encrypted_data: &str) -> Result<Keypair, String> {
 
     // Decrypt and authenticate
     let decrypted = cipher.decrypt(&nonce, encrypted_session.ciphertext.as_ref())
-        .map_err(|e| format!("Decryption failed (invalid data or key): {}", e))?;
+        .map_err(|e| MessengerError::InvalidSession(format!("Decryption failed (invalid data or key): {}", e)))?;
 
     // Ensure we have exactly 32 bytes for the secret key
     if decrypted.len() != 32 {
-        return Err(format!("Invalid decrypted data length: expected 32, got {}", decrypted.len()));
+        return Err(MessengerError::InvalidSession(format!("Invalid decrypted data length: expected 32, got {}", decrypted.len())));
     }
 
     let mut secret_key = [0u8; 32];
@@ -130,29 +231,54 @@ fn decrypt_keypair(encrypted_data: &str) -> Result<Keypair, String> {
     Ok(Keypair::from_secret_key(&secret_key))
 }
 
+// Pass `settings` to point the client at a testnet, custom relays or other
+// DHT bootstrap nodes; they are saved and used from then on
 #[command]
-pub async fn init_client(state: State<'_, AppState>) -> Result<String, String> {
+pub async fn init_client(settings: Option<NetworkSettings>, state: State<'_, AppState>) -> MessengerResult<String> {
+    if let Some(settings) = settings {
+        apply_network_settings(&state, settings).await?;
+    }
+
     // Initialize the shared client in AppState
-    state.get_or_create_client().await?;
+    state.get_or_create_transport().await?;
     Ok("Client initialized successfully".to_string())
 }
 
+// Save network settings, rebuilding the client when its endpoints change
+async fn apply_network_settings(state: &AppState, settings: NetworkSettings) -> MessengerResult<NetworkSettings> {
+    let previous = net::get_settings(&state.store);
+    let endpoints_changed = !previous.same_endpoints(&settings);
+    if endpoints_changed && *state.is_signed_in.lock().await {
+        return Err(MessengerError::InvalidInput("Sign out before switching networks".to_string()));
+    }
+
+    let settings = net::set_settings(&state.store, settings)
+        .err_context("Failed to save network settings")?;
+    if endpoints_changed {
+        *state.transport.lock().await = None;
+        state.http_cache.clear();
+        state.watcher.clear();
+    }
+    Ok(settings)
+}
+
 #[command]
 pub async fn sign_in_with_recovery(
     recovery_file_b64: String,
     passphrase: String,
+    app: AppHandle,
     state: State<'_, AppState>,
-) -> Result<SignInResult, String> {
-    let result = task::spawn_blocking(move || -> Result<Keypair, String> {
+) -> MessengerResult<SignInResult> {
+    // Argon2 key stretching is CPU-bound, so it's the one step kept off the async runtime
+    let result = task::spawn_blocking(move || -> MessengerResult<Keypair> {
         // Decode and decrypt recovery file
         let recovery_file_bytes = base64::decode(&recovery_file_b64)
-            .map_err(|e| format!("Failed to decode recovery file: {}", e))?;
+            .map_err(|e| MessengerError::InvalidRecoveryFile(format!("Failed to decode recovery file: {}", e)))?;
 
-        let keypair = recovery_file::decrypt_recovery_file(&recovery_file_bytes, &passphrase)
-            .map_err(|_| "Failed to decrypt recovery file - check your passphrase".to_string())?;
+        let keypair = crypto_compat::decrypt_recovery_file(&recovery_file_bytes, &passphrase)?;
 
         Ok(keypair)
-    }).await.map_err(|e| format!("Task failed: {}", e))??;
+    }).await.err_context("Task failed")??;
 
 // Store keypair in state first
     let mut keypair_guard = state.keypair.lock().await;
@@ -161,24 +287,28 @@ pub async fn sign_in_with_recovery(
 
     // Create handler and sign in to get profile name
     let handler = state.create_handler_and_sign_in().await?
-        .ok_or("Failed to create handler")?;
-
-    let profile_name = task::spawn_blocking(move || -> Result<Option<String>, String> {
-        let rt = tokio::runtime::Handle::current();
+        .ok_or(MessengerError::NotSignedIn)?;
 
-        // Get own profile name
-        let name = rt.block_on(handler.get_own_profile())
-            .map_err(|e| format!("Failed to get profile: {}", e))?;
-
-        Ok(name)
-    }).await.map_err(|e| format!("Task failed: {}", e))??;
+    // Get own profile name
+    let profile_name = handler.get_own_profile().await
+        .err_context("Failed to get profile")?;
 
     // Store user name in state
     let mut name_guard = state.user_name.lock().await;
     *name_guard = profile_name.clone();
 
+    state.open_storage(&result).await?;
+    tray::refresh(&app).await;
+
+    // Importing the key is the first onboarding step
+    if let Err(e) = onboarding::complete_step(&state.store, OnboardingStep::ImportKey) {
+        tracing::warn!("⚠️  Failed to record onboarding progress: {}", e);
+    }
+
     // Encrypt keypair for storage using secure AEAD
-    let encrypted_keypair = encrypt_keypair(&result)?;
+    let encrypted_keypair = encrypt_keypair(&result, state.store.dir())?;
+    SecurityLog::new(&state.store, &result.public_key())
+        .record(SecurityEventKind::SignIn, None, Some("Recovery file"));
 
     Ok(SignInResult {
         profile: UserProfile {
@@ -190,13 +320,137 @@ pub async fn sign_in_with_recovery(
     })
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct RestoredSession {
+    pub profile: UserProfile,
+    // Set when the session was re-encrypted; save it in place of the old one
+    pub encrypted_keypair: Option<String>,
+}
+
+// Sessions behind an app lock PIN go through unlock_session instead
 #[command]
 pub async fn restore_session(
     encrypted_keypair: String,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> MessengerResult<RestoredSession> {
+    if app_lock::is_enabled(&state.store) {
+        return Err(MessengerError::SessionLocked);
+    }
+    open_saved_session(&encrypted_keypair, &app, &state).await
+}
+
+// Restore a locked session. The duress PIN opens the decoy account, and
+// too many wrong PINs wipe the saved session if the user asked for that.
+#[command]
+pub async fn unlock_session(
+    pin: String,
+    encrypted_keypair: String,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> MessengerResult<RestoredSession> {
+    let store = state.store.clone();
+    let unlock = task::spawn_blocking(move || app_lock::unlock(&store, &pin))
+        .await.err_context("Task failed")?
+        .err_context("Failed to check PIN")?;
+
+    match unlock {
+        Unlock::Real => open_saved_session(&encrypted_keypair, &app, &state).await,
+        Unlock::Decoy(keypair) => open_decoy_session(keypair, &app, &state).await,
+        Unlock::Wrong => Err(MessengerError::BadPin),
+        Unlock::Wipe => Err(wipe_saved_session(&state)),
+    }
+}
+
+// The saved session blob lives in the frontend's storage, so it is wiped
+// by making it unopenable: the device secret goes, and sessions saved
+// before there was one are refused from now on
+fn wipe_saved_session(state: &AppState) -> MessengerError {
+    tracing::warn!("🧨 Too many wrong PINs, wiping the saved session");
+    if let Err(e) = device_secret::wipe(state.store.dir()) {
+        tracing::warn!("⚠️  Failed to wipe saved session: {}", e);
+    }
+    MessengerError::InvalidSession("Too many wrong PINs. The saved session was wiped; sign in again with your recovery file.".to_string())
+}
+
+// Readable while signed out, so the frontend knows to ask for the PIN
+#[command]
+pub async fn get_app_lock(state: State<'_, AppState>) -> MessengerResult<AppLockStatus> {
+    let signed_in = state.keypair.lock().await.as_ref().map(|keypair| keypair.public_key());
+    Ok(app_lock::status(&state.store, signed_in.as_ref()))
+}
+
+// Turn the lock on or change it; `current_pin` is needed once one is set
+#[command]
+pub async fn set_app_lock(
+    current_pin: Option<String>,
+    pin: String,
+    duress_pin: Option<String>,
+    wipe_after: Option<u32>,
     state: State<'_, AppState>,
-) -> Result<UserProfile, String> {
+) -> MessengerResult<AppLockStatus> {
+    let keypair = {
+        let keypair_guard = state.keypair.lock().await;
+        keypair_guard.clone().ok_or(MessengerError::NotSignedIn)?
+    };
+    let store = state.store.clone();
+    let wipe = task::spawn_blocking(move || {
+        app_lock::set(&store, current_pin.as_deref(), &pin, duress_pin.as_deref(), wipe_after)
+    }).await.err_context("Task failed")?.err_context("Failed to set app lock")?;
+    if wipe {
+        return Err(wipe_saved_session(&state));
+    }
+    Ok(app_lock::status(&state.store, Some(&keypair.public_key())))
+}
+
+#[command]
+pub async fn remove_app_lock(current_pin: String, state: State<'_, AppState>) -> MessengerResult<AppLockStatus> {
+    if state.keypair.lock().await.is_none() {
+        return Err(MessengerError::NotSignedIn);
+    }
+    let store = state.store.clone();
+    let wipe = task::spawn_blocking(move || app_lock::remove(&store, &current_pin))
+        .await.err_context("Task failed")?
+        .err_context("Failed to remove app lock")?;
+    if wipe {
+        return Err(wipe_saved_session(&state));
+    }
+    Ok(app_lock::status(&state.store, None))
+}
+
+// Signed in as far as the UI can tell, but with an empty cache and never
+// talking to a homeserver
+async fn open_decoy_session(keypair: Keypair, app: &AppHandle, state: &AppState) -> MessengerResult<RestoredSession> {
+    *state.keypair.lock().await = Some(keypair.clone());
+    *state.user_name.lock().await = None;
+    state.open_storage(&keypair).await?;
+    tray::refresh(app).await;
+    SecurityLog::new(&state.store, &keypair.public_key())
+        .record(SecurityEventKind::SessionRestored, None, None);
+
+    Ok(RestoredSession {
+        profile: UserProfile {
+            public_key: keypair.public_key().to_string(),
+            signed_in: true,
+            name: None,
+        },
+        encrypted_keypair: None,
+    })
+}
+
+async fn open_saved_session(
+    encrypted_keypair: &str,
+    app: &AppHandle,
+    state: &AppState,
+) -> MessengerResult<RestoredSession> {
     // Decrypt the keypair using secure AEAD
-    let keypair = decrypt_keypair(&encrypted_keypair)?;
+    let (keypair, outdated) = decrypt_keypair(encrypted_keypair, state.store.dir())?;
+    let encrypted_keypair = if outdated {
+        tracing::info!("🔐 Moving saved session to the device secret");
+        Some(encrypt_keypair(&keypair, state.store.dir())?)
+    } else {
+        None
+    };
 
     // Store keypair in state first
     let mut keypair_guard = state.keypair.lock().await;
@@ -205,26 +459,34 @@ pub async fn restore_session(
 
     // Create handler and sign in to get profile name
     let handler = state.create_handler_and_sign_in().await?
-        .ok_or("Failed to create handler")?;
-
-    let profile_name = task::spawn_blocking(move || -> Result<Option<String>, String> {
-        let rt = tokio::runtime::Handle::current();
+        .ok_or(MessengerError::NotSignedIn)?;
 
-        // Get own profile name
-        let name = rt.block_on(handler.get_own_profile())
-            .map_err(|e| format!("Failed to get profile: {}", e))?;
-
-        Ok(name)
-    }).await.map_err(|e| format!("Task failed: {}", e))??;
+    // Get own profile name
+    let profile_name = handler.get_own_profile().await
+        .err_context("Failed to get profile")?;
 
     // Store user name in state
     let mut name_guard = state.user_name.lock().await;
     *name_guard = profile_name.clone();
 
-    Ok(UserProfile {
-        public_key: keypair.public_key().to_string(),
-        signed_in: true,
-        name: profile_name,
+    state.open_storage(&keypair).await?;
+    tray::refresh(app).await;
+
+    // Sessions created before onboarding existed still imported a key
+    if let Err(e) = onboarding::complete_step(&state.store, OnboardingStep::ImportKey) {
+        tracing::warn!("⚠️  Failed to record onboarding progress: {}", e);
+    }
+    let detail = encrypted_keypair.as_ref().map(|_| "Saved session moved to the device secret");
+    SecurityLog::new(&state.store, &keypair.public_key())
+        .record(SecurityEventKind::SessionRestored, None, detail);
+
+    Ok(RestoredSession {
+        profile: UserProfile {
+            public_key: keypair.public_key().to_string(),
+            signed_in: true,
+            name: profile_name,
+        },
+        encrypted_keypair,
     })
 }
 
@@ -232,151 +494,1740 @@ pub async fn restore_session(
 pub async fn send_message(
     recipient_pubkey: String,
     content: String,
+    app: AppHandle,
     state: State<'_, AppState>,
-) -> Result<String, String> {
+) -> MessengerResult<String> {
     let keypair = {
         let keypair_guard = state.keypair.lock().await;
-        keypair_guard.clone().ok_or("Not signed in")?
+        keypair_guard.clone().ok_or(MessengerError::NotSignedIn)?
     };
 
-    println!("📤 send_message command called");
-    println!("   Sender: {}", keypair.public_key().to_string().chars().take(8).collect::<String>());
-    println!("   Recipient: {}", recipient_pubkey.chars().take(8).collect::<String>());
+    tracing::debug!("📤 send_message from {} to {}", logging::pubkey(&keypair.public_key()), logging::pubkey(&recipient_pubkey));
 
     // Get handler (without signing in since we should already be authenticated)
     let handler = state.create_handler().await?
-        .ok_or("Not signed in")?;
+        .ok_or(MessengerError::NotSignedIn)?;
 
     let recipient = PublicKey::try_from(recipient_pubkey.as_str())
-        .map_err(|e| format!("Invalid recipient public key: {}", e))?;
+        .map_err(|e| MessengerError::InvalidPublicKey(format!("Invalid recipient public key: {}", e)))?;
+    ensure_not_blocked(&state, &keypair, &recipient)?;
 
-    // Send the message
-    println!("📤 Attempting to send message...");
-    handler.send_message(&recipient, &content)
-        .await
-        .map_err(|e| format!("Failed to send message: {}", e))?;
+    // Generate a link preview on our side so the recipient never fetches the link
+    let preview = if link_preview::is_enabled(&state.store) {
+        link_preview::preview_for_message(&content).await
+    } else {
+        None
+    };
+
+    let extras = MessageExtras {
+        link_preview: preview,
+        mentions: mentions::parse_mentions(&content),
+        ..Default::default()
+    };
+
+    send_or_queue(&app, &state, &keypair, &handler, &recipient, &content, extras).await
+}
+
+// Send a message, or keep it in the outbox for the worker to retry
+async fn send_or_queue(
+    app: &AppHandle,
+    state: &AppState,
+    keypair: &Keypair,
+    handler: &PrivateMessageHandler,
+    recipient: &PublicKey,
+    content: &str,
+    extras: MessageExtras,
+) -> MessengerResult<String> {
+    tracing::debug!("📤 Attempting to send message...");
+    let send_result = handler.send_message_with_extras(recipient, content, Some(&extras)).await;
+
+    let _guard = state.outbox_lock.lock().await;
+    let outbox = Outbox::new(&state.store, &keypair.public_key());
+
+    if let Err(e) = send_result {
+        // Keep the message in the outbox and let the worker retry it
+        tracing::warn!("📥 Send failed, queueing message for retry: {}", e);
+        let entry = outbox.enqueue(recipient, content, extras, &e.to_string())
+            .map_err(|queue_err| MessengerError::from(queue_err).context(&format!("Failed to send message: {} (and failed to queue it)", e)))?;
+        outbox::emit_status(&TauriEvents(app.clone()), &entry);
+        return Ok(format!("Message queued for retry ({})", entry.id));
+    }
+
+    // The homeserver is reachable again, so don't keep queued messages waiting
+    if let Err(e) = outbox.retry_all_now() {
+        tracing::warn!("⚠️  Failed to reschedule outbox: {}", e);
+    }
+
+    if let Err(e) = onboarding::complete_step(&state.store, OnboardingStep::SendFirstMessage) {
+        tracing::warn!("⚠️  Failed to record onboarding progress: {}", e);
+    }
 
     Ok("Message sent successfully".to_string())
 }
 
+// Encrypt and upload a local file, then send it as a message. Used by the
+// file picker and by files dropped onto a conversation. With
+// `expires_in_secs` the file is deleted from our homeserver after that long.
+#[command]
+pub async fn send_file(
+    recipient_pubkey: String,
+    path: String,
+    expires_in_secs: Option<u64>,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> MessengerResult<String> {
+    let file = read_file(PathBuf::from(path)).await?;
+    send_attachment(&recipient_pubkey, file, expires_in_secs, &app, &state).await
+}
+
+// Let the user pick a file and send it. Returns None if the dialog was cancelled.
+#[command]
+pub async fn pick_and_send_file(
+    recipient_pubkey: String,
+    expires_in_secs: Option<u64>,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> MessengerResult<Option<String>> {
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    app.dialog()
+        .file()
+        .pick_file(move |path| {
+            let _ = tx.send(path);
+        });
+
+    let Some(path) = rx.await.err_context("File dialog failed")? else {
+        return Ok(None);
+    };
+    let path = path.into_path()
+        .map_err(|e| MessengerError::InvalidInput(format!("Invalid file path: {}", e)))?;
+
+    let file = read_file(path).await?;
+    send_attachment(&recipient_pubkey, file, expires_in_secs, &app, &state).await.map(Some)
+}
+
+// Send the image on the system clipboard as a PNG, e.g. a pasted screenshot
+#[command]
+pub async fn send_clipboard_image(
+    recipient_pubkey: String,
+    expires_in_secs: Option<u64>,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> MessengerResult<String> {
+    let image = app.clipboard().read_image()
+        .map_err(|e| MessengerError::InvalidInput(format!("No image on the clipboard: {}", e)))?;
+    let (width, height, rgba) = (image.width(), image.height(), image.rgba().to_vec());
+
+    let name = format!("pasted-{}.png", now_secs());
+    let file = task::spawn_blocking(move || AttachmentFile::png_from_rgba(&name, width, height, rgba))
+        .await
+        .err_context("Task failed")?
+        .err_context("Failed to read clipboard image")?;
+    send_attachment(&recipient_pubkey, PendingFile::Buffered(file), expires_in_secs, &app, &state).await
+}
+
+// Send a voice note, transcoded to Opus first. `audio_b64` is the
+// recording as the webview's recorder produced it, of type `mime`.
+#[command]
+pub async fn send_voice_note(
+    recipient_pubkey: String,
+    audio_b64: String,
+    mime: String,
+    expires_in_secs: Option<u64>,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> MessengerResult<String> {
+    let bytes = base64::decode(&audio_b64)
+        .map_err(|e| MessengerError::InvalidInput(format!("Invalid voice note: {}", e)))?;
+
+    let name = format!("voice-note-{}", now_secs());
+    let file = task::spawn_blocking(move || AttachmentFile::new(&name, Some(&mime), bytes).map(audio_pipeline::prepare))
+        .await
+        .err_context("Task failed")?
+        .err_context("Failed to read voice note")?;
+    send_attachment(&recipient_pubkey, PendingFile::Buffered(file), expires_in_secs, &app, &state).await
+}
+
+// A file to send: read into memory, or too big for that and encrypted and
+// uploaded a chunk at a time
+enum PendingFile {
+    Buffered(AttachmentFile),
+    Streamed(PathBuf),
+}
+
+async fn read_file(path: PathBuf) -> MessengerResult<PendingFile> {
+    let size = tokio::fs::metadata(&path).await
+        .err_context("Failed to read file")?
+        .len();
+    if attachments::needs_streaming(size) {
+        return Ok(PendingFile::Streamed(path));
+    }
+    let file = task::spawn_blocking(move || AttachmentFile::read(&path))
+        .await
+        .err_context("Task failed")?
+        .err_context("Failed to read file")?;
+    Ok(PendingFile::Buffered(file))
+}
+
+async fn send_attachment(
+    recipient_pubkey: &str,
+    file: PendingFile,
+    expires_in_secs: Option<u64>,
+    app: &AppHandle,
+    state: &AppState,
+) -> MessengerResult<String> {
+    let keypair = {
+        let keypair_guard = state.keypair.lock().await;
+        keypair_guard.clone().ok_or(MessengerError::NotSignedIn)?
+    };
+    let handler = state.create_handler().await?
+        .ok_or(MessengerError::NotSignedIn)?;
+
+    let recipient = PublicKey::try_from(recipient_pubkey)
+        .map_err(|e| MessengerError::InvalidPublicKey(format!("Invalid recipient public key: {}", e)))?;
+    ensure_not_blocked(state, &keypair, &recipient)?;
+
+    // An upload that fails isn't queued; there'd be nothing to retry from
+    let mut attachment = match file {
+        PendingFile::Buffered(file) => {
+            // Scale down and strip location metadata before it's sealed
+            let image_settings = settings::image_settings(&state.store);
+            let file = task::spawn_blocking(move || image_pipeline::prepare(file, &image_settings))
+                .await
+                .err_context("Task failed")?;
+            handler.upload_attachment(&file).await
+        }
+        PendingFile::Streamed(path) => handler.upload_attachment_file(&path).await,
+    }
+    .err_context("Failed to upload attachment")?;
+    tracing::info!("📎 Uploaded {} attachment for {}", attachments::format_size(attachment.size), logging::pubkey(&recipient));
+
+    // Recorded before it's sent, so the file is deleted even if sending fails
+    attachment.expires_at = attachment_expiry::expires_at(expires_in_secs, now_secs());
+    if let Err(e) = attachment_expiry::record(&state.store, &keypair.public_key(), &attachment) {
+        tracing::warn!("⚠️  Failed to record attachment expiry: {}", e);
+    }
+
+    let content = attachments::fallback_text(&attachment);
+    let extras = MessageExtras {
+        attachment: Some(attachment),
+        ..Default::default()
+    };
+    send_or_queue(app, state, &keypair, &handler, &recipient, &content, extras).await
+}
+
+// Download, decrypt and verify a received attachment into a path the user
+// picks. Returns the saved path, or None if the dialog was cancelled.
 #[command]
-pub async fn get_new_messages(
+pub async fn save_attachment(
+    attachment: Attachment,
+    app: AppHandle,
     state: State<'_, AppState>,
-) -> Result<Vec<ChatMessage>, String> {
-    let _keypair = {
+) -> MessengerResult<Option<String>> {
+    let handler = state.create_handler().await?
+        .ok_or(MessengerError::NotSignedIn)?;
+    attachment_limits::get_settings(&state.store).check_receive(&attachment)?;
+    attachments::check_not_expired(&attachment, now_secs())?;
+
+    // Pick the destination first, so a big file can be decrypted straight into it
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    app.dialog()
+        .file()
+        .set_file_name(attachments::safe_file_name(&attachment.name))
+        .save_file(move |path| {
+            let _ = tx.send(path);
+        });
+
+    let Some(path) = rx.await.err_context("Save dialog failed")? else {
+        return Ok(None);
+    };
+    let path = path.into_path()
+        .map_err(|e| MessengerError::InvalidInput(format!("Invalid attachment path: {}", e)))?;
+
+    let cache = AttachmentCache::new(&state.store);
+    if let Some(bytes) = cache.get(&attachment, profiles::now_secs()) {
+        std::fs::write(&path, bytes)
+            .err_context(&format!("Failed to write {}", path.display()))?;
+        return Ok(Some(path.display().to_string()));
+    }
+
+    // Through the download manager, so progress shows and an interrupted
+    // save picks up where it stopped
+    let download = state.downloads.enqueue(&attachment).await
+        .err_context("Failed to download attachment")?;
+    state.downloads.run(&handler, &state.progress, &download.id).await
+        .err_context("Failed to download attachment")?;
+    state.downloads.save(&download.id, &path, &cache).await
+        .err_context(&format!("Failed to save {}", path.display()))?;
+
+    Ok(Some(path.display().to_string()))
+}
+
+#[command]
+pub async fn get_cache_usage(state: State<'_, AppState>) -> MessengerResult<CacheUsage> {
+    Ok(AttachmentCache::new(&state.store).usage())
+}
+
+// Returns what was freed
+#[command]
+pub async fn clear_attachment_cache(state: State<'_, AppState>) -> MessengerResult<CacheUsage> {
+    AttachmentCache::new(&state.store).clear()
+        .err_context("Failed to clear attachment cache")
+}
+
+// Files we sent with an expiry that haven't been deleted yet, soonest first
+#[command]
+pub async fn get_expiring_attachments(state: State<'_, AppState>) -> MessengerResult<Vec<ExpiringUpload>> {
+    let keypair = {
         let keypair_guard = state.keypair.lock().await;
-        keypair_guard.clone().ok_or("Not signed in")?
+        keypair_guard.clone().ok_or(MessengerError::NotSignedIn)?
     };
+    Ok(attachment_expiry::pending(&state.store, &keypair.public_key()))
+}
+
+#[command]
+pub async fn get_downloads(state: State<'_, AppState>) -> MessengerResult<Vec<Download>> {
+    Ok(state.downloads.list())
+}
+
+// Queue an attachment and download it in the background; progress arrives
+// as attachment-download-progress events
+#[command]
+pub async fn start_download(attachment: Attachment, state: State<'_, AppState>) -> MessengerResult<Download> {
+    attachment_limits::get_settings(&state.store).check_receive(&attachment)?;
+    attachments::check_not_expired(&attachment, now_secs())?;
+    let download = state.downloads.enqueue(&attachment).await
+        .err_context("Failed to queue download")?;
+    spawn_download(&state, &download.id).await?;
+    Ok(download)
+}
+
+#[command]
+pub async fn pause_download(id: String, state: State<'_, AppState>) -> MessengerResult<Download> {
+    state.downloads.pause(&id).err_context("Failed to pause download")
+}
+
+// Continue a paused or failed download from the bytes already saved
+#[command]
+pub async fn resume_download(id: String, state: State<'_, AppState>) -> MessengerResult<()> {
+    spawn_download(&state, &id).await
+}
+
+// Stop a download and delete what it saved
+#[command]
+pub async fn cancel_download(id: String, state: State<'_, AppState>) -> MessengerResult<()> {
+    state.downloads.cancel(&id).await.err_context("Failed to cancel download")
+}
+
+async fn spawn_download(state: &AppState, id: &str) -> MessengerResult<()> {
+    let handler = state.create_handler().await?
+        .ok_or(MessengerError::NotSignedIn)?;
+    let downloads = state.downloads.clone();
+    let progress = state.progress.clone();
+    let id = id.to_string();
+    tauri::async_runtime::spawn(async move {
+        // Failures also reach the frontend as a failed progress event
+        if let Err(e) = downloads.run(&handler, &progress, &id).await {
+            tracing::warn!("⚠️  Download failed: {}", e);
+        }
+    });
+    Ok(())
+}
+
+// Push every message sync discovers, from the background worker or
+// sync_now, to `on_message`. Returns an id for unsubscribe_messages.
+#[command]
+pub async fn subscribe_messages(
+    on_message: Channel<MessageReceivedEvent>,
+    subscriptions: State<'_, MessageSubscriptions>,
+) -> MessengerResult<u64> {
+    Ok(subscriptions.subscribe(on_message))
+}
+
+#[command]
+pub async fn unsubscribe_messages(
+    subscription_id: u64,
+    subscriptions: State<'_, MessageSubscriptions>,
+) -> MessengerResult<bool> {
+    Ok(subscriptions.unsubscribe(subscription_id))
+}
+
+// Sync everything now instead of waiting for the background worker. New
+// messages arrive through message subscriptions; returns how many came in.
+// Pass an operation id to make it cancellable with cancel_operation.
+#[command]
+pub async fn sync_now(
+    operation_id: Option<String>,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> MessengerResult<usize> {
+    let received = state.operations.run(operation_id, sync::sync_all(&TauriEvents(app), &state)).await?;
+    Ok(received.iter().filter(|msg| !msg.is_own_message).count())
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// Messages per page when the frontend doesn't ask for a size
+const DEFAULT_PAGE_SIZE: usize = 50;
 
-    // Temporarily return empty array since notifications are disabled
-    // Individual conversations still work via get_conversation
-    println!("📭 New message polling disabled (notifications system disabled)");
-    Ok(vec![])
+// Log lines returned when the frontend doesn't ask for a count
+const DEFAULT_LOG_LIMIT: usize = 200;
+
+#[derive(Serialize, Deserialize)]
+pub struct ConversationPage {
+    pub messages: Vec<ChatMessage>,
+    pub has_more: bool,
+    // Pass back as `cursor` for the page before this one
+    pub next_cursor: Option<String>,
 }
 
+// Sync the conversation, then return all of it or just the page that was asked for
 #[command]
 pub async fn get_conversation(
     other_pubkey: String,
+    limit: Option<usize>,
+    cursor: Option<String>,
+    operation_id: Option<String>,
+    app: AppHandle,
     state: State<'_, AppState>,
-) -> Result<Vec<ChatMessage>, String> {
+) -> MessengerResult<Vec<ChatMessage>> {
     let keypair = {
         let keypair_guard = state.keypair.lock().await;
-        keypair_guard.clone().ok_or("Not signed in")?
+        keypair_guard.clone().ok_or(MessengerError::NotSignedIn)?
     };
 
-    let current_user = keypair.public_key().to_string();
-
     let handler = state.create_handler().await?
-        .ok_or("Not signed in")?;
-    
-    let messages = task::spawn_blocking(move || -> Result<Vec<(crate::messaging::PrivateMessage, String, String, bool)>, String> {
-        let other_pk = PublicKey::try_from(other_pubkey.as_str())
-            .map_err(|e| format!("Invalid public key: {}", e))?;
+        .ok_or(MessengerError::NotSignedIn)?;
 
-        let rt = tokio::runtime::Handle::current();
+    let synced = state.operations
+        .run(operation_id, sync::sync_conversation(&state, &handler, &other_pubkey))
+        .await?;
 
-        // Get conversation with decrypted senders
-        let raw_messages = rt.block_on(handler.get_messages(&other_pk))
-            .map_err(|e| format!("Failed to get conversation: {}", e))?;
+    // Offline: whatever is cached is still readable
+    if let Some(e) = &synced.fetch_error {
+        tracing::warn!("📴 Failed to sync conversation, showing cached messages: {}", e);
+    }
 
-        // Transform to include decrypted sender info
-        let mut processed_messages = Vec::new();
-        for (msg, content, verified) in raw_messages {
-            if let Ok(sender) = msg.decrypt_sender(&handler.keypair, &other_pk) {
-                processed_messages.push((msg, content, sender, verified));
-            }
-        }
+    let mut chat_messages = synced.messages;
+    let muted = MuteList::new(&state.store, &keypair.public_key()).is_muted(&other_pubkey, now_secs());
+    sync::record_loaded_conversation(&TauriEvents(app), &state, &keypair, &other_pubkey, &chat_messages, synced.health, muted).await;
 
-        Ok(processed_messages)
-    }).await.map_err(|e| format!("Task failed: {}", e))??;
+    if limit.is_none() && cursor.is_none() {
+        sync::label_senders(&state, &mut chat_messages).await;
+        return Ok(chat_messages);
+    }
 
-    let chat_messages = messages.into_iter().map(|(msg, content, sender, verified)| {
-        ChatMessage {
-            sender: sender.clone(),  // Now using decrypted sender
-            content,
-            timestamp: msg.timestamp,
-            verified,
-            is_own_message: sender == current_user,
-        }
-    }).collect();
+    let page = load_page(&state, &other_pubkey, limit, cursor.as_deref()).await?;
+    Ok(page.messages)
+}
 
-    Ok(chat_messages)
+async fn load_page(
+    state: &AppState,
+    conversation_key: &str,
+    limit: Option<usize>,
+    cursor: Option<&str>,
+) -> MessengerResult<ConversationPage> {
+    let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    let before = cursor.map(storage::parse_page_cursor).transpose()
+        .map_err(|e| MessengerError::InvalidInput(e.to_string()))?;
+    let (stored, has_more) = state
+        .with_storage(|storage| storage.conversation_page(conversation_key, limit, before))
+        .await?;
+
+    let mut messages: Vec<ChatMessage> = stored.into_iter().map(|msg| msg.message).collect();
+    sync::label_senders(state, &mut messages).await;
+    let next_cursor = messages.first().filter(|_| has_more).map(storage::page_cursor);
+    Ok(ConversationPage { messages, has_more, next_cursor })
 }
 
+// Write a conversation transcript to a path the user picks. Returns the
+// saved path, or None if the dialog was cancelled.
 #[command]
-pub async fn get_user_profile(
+pub async fn export_conversation(
+    pubkey: String,
+    format: ExportFormat,
+    include_attachments: Option<bool>,
+    operation_id: Option<String>,
+    app: AppHandle,
     state: State<'_, AppState>,
-) -> Result<Option<UserProfile>, String> {
-    let keypair_guard = state.keypair.lock().await;
-    let name_guard = state.user_name.lock().await;
+) -> MessengerResult<Option<String>> {
+    let keypair = {
+        let keypair_guard = state.keypair.lock().await;
+        keypair_guard.clone().ok_or(MessengerError::NotSignedIn)?
+    };
+    let own_pubkey = keypair.public_key().to_string();
 
-    if let Some(keypair) = keypair_guard.as_ref() {
-        Ok(Some(UserProfile {
-            public_key: keypair.public_key().to_string(),
-            signed_in: true,
-            name: name_guard.clone(),
-        }))
+    // Export the freshest history we can get, the cache is fine when offline
+    let handler = state.create_handler().await?
+        .ok_or(MessengerError::NotSignedIn)?;
+    let messages = state.operations
+        .run(operation_id, sync::sync_conversation(&state, &handler, &pubkey))
+        .await?
+        .messages;
+
+    let contact_name = if pubkey == own_pubkey {
+        Some(crate::messaging::SAVED_MESSAGES_NAME.to_string())
     } else {
-        Ok(None)
-    }
+        state.with_storage(|storage| storage.contact_name(&pubkey)).await?
+    };
+
+    let transcript = export::render(
+        &pubkey,
+        contact_name.as_deref(),
+        &own_pubkey,
+        &messages,
+        format,
+        include_attachments.unwrap_or(false),
+        now_secs(),
+    ).err_context("Failed to export conversation")?;
+
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    app.dialog()
+        .file()
+        .set_file_name(export::default_file_name(&pubkey, contact_name.as_deref(), format))
+        .add_filter("Transcript", &[format.extension()])
+        .save_file(move |path| {
+            let _ = tx.send(path);
+        });
+
+    let Some(path) = rx.await.err_context("Save dialog failed")? else {
+        return Ok(None);
+    };
+    let path = path.into_path()
+        .map_err(|e| MessengerError::InvalidInput(format!("Invalid export path: {}", e)))?;
+
+    std::fs::write(&path, transcript)
+        .err_context(&format!("Failed to write {}", path.display()))?;
+
+    tracing::info!("📤 Exported {} messages to {}", messages.len(), logging::path(&path.display().to_string()));
+    Ok(Some(path.display().to_string()))
 }
 
+// Encrypt the local database and settings into a single archive at a path
+// the user picks. Returns the saved path, or None if the dialog was cancelled.
 #[command]
-pub async fn sign_out(state: State<'_, AppState>) -> Result<String, String> {
-    let mut keypair_guard = state.keypair.lock().await;
-    *keypair_guard = None;
+pub async fn create_backup(
+    passphrase: String,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> MessengerResult<Option<String>> {
+    let keypair = {
+        let keypair_guard = state.keypair.lock().await;
+        keypair_guard.clone().ok_or(MessengerError::NotSignedIn)?
+    };
 
-    let mut name_guard = state.user_name.lock().await;
-    *name_guard = None;
+    let archive = {
+        let _guard = state.outbox_lock.lock().await;
+        state.with_storage(|storage| backup::create(storage, &state.store, &keypair.public_key(), &passphrase, now_secs())).await?
+    };
 
-    let mut signed_in_guard = state.is_signed_in.lock().await;
-    *signed_in_guard = false;
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    app.dialog()
+        .file()
+        .set_file_name(format!("pubky-messenger-backup-{}.pmbackup", keypair.public_key().to_string().chars().take(8).collect::<String>()))
+        .add_filter("Messenger backup", &["pmbackup"])
+        .save_file(move |path| {
+            let _ = tx.send(path);
+        });
+
+    let Some(path) = rx.await.err_context("Save dialog failed")? else {
+        return Ok(None);
+    };
+    let path = path.into_path()
+        .map_err(|e| MessengerError::InvalidInput(format!("Invalid backup path: {}", e)))?;
 
-    Ok("Signed out successfully".to_string())
+    std::fs::write(&path, archive)
+        .err_context(&format!("Failed to write {}", path.display()))?;
+
+    Ok(Some(path.display().to_string()))
 }
 
 #[command]
-pub async fn scan_followed_users(state: State<'_, AppState>) -> Result<Vec<crate::messaging::FollowedUser>, String> {
+pub async fn restore_backup(
+    path: String,
+    passphrase: String,
+    state: State<'_, AppState>,
+) -> MessengerResult<RestoreSummary> {
     let keypair = {
         let keypair_guard = state.keypair.lock().await;
-        keypair_guard.clone().ok_or("Not signed in")?
+        keypair_guard.clone().ok_or(MessengerError::NotSignedIn)?
     };
 
-    println!("🔍 Scanning for followed users...");
+    let bytes = std::fs::read(&path)
+        .err_context(&format!("Failed to read {}", path))?;
 
-    let handler = state.create_handler().await?
-        .ok_or("Not signed in")?;
-    
-    let users = task::spawn_blocking(move || -> Result<Vec<crate::messaging::FollowedUser>, String> {
-        let rt = tokio::runtime::Handle::current();
+    // Hold the outbox lock so the worker doesn't overwrite the restored queue
+    let _guard = state.outbox_lock.lock().await;
+    let summary = state.with_storage(|storage| backup::restore(&bytes, &passphrase, storage, &state.store, &keypair.public_key())).await?;
 
-        // Get followed users with profiles
-        let users = rt.block_on(handler.get_followed_users_with_profiles())
-            .map_err(|e| format!("Failed to get followed users: {}", e))?;
+    // Cached health was computed from the pre-restore history
+    state.conversation_health.lock().await.clear();
 
-        Ok(users)
-    }).await.map_err(|e| format!("Task failed: {}", e))??;
+    tracing::info!("♻️  Restored backup: {:?}", summary);
+    Ok(summary)
+}
 
-    println!("✅ Found {} followed users", users.len());
-    Ok(users)
-}
\ No newline at end of file
+// Older history for infinite scroll, served from the local cache
+#[command]
+pub async fn get_conversation_page(
+    other_pubkey: String,
+    limit: Option<usize>,
+    cursor: Option<String>,
+    state: State<'_, AppState>,
+) -> MessengerResult<ConversationPage> {
+    load_page(&state, &other_pubkey, limit, cursor.as_deref()).await
+}
+
+// The images and videos, files or links shared in a conversation, newest
+// first, from the local cache. Pass the returned cursor to get older ones.
+#[command]
+pub async fn get_conversation_media(
+    pubkey: String,
+    kind: MediaKind,
+    cursor: Option<String>,
+    limit: Option<usize>,
+    state: State<'_, AppState>,
+) -> MessengerResult<MediaPage> {
+    let limit = limit.unwrap_or(media_gallery::DEFAULT_PAGE_SIZE).clamp(1, media_gallery::MAX_PAGE_SIZE);
+    let before = cursor.as_deref().map(media_gallery::parse_cursor).transpose()
+        .map_err(|e| MessengerError::InvalidInput(e.to_string()))?;
+    let (stored, has_more) = state
+        .with_storage(|storage| storage.media_page(&pubkey, kind, limit, before))
+        .await?;
+
+    let mut messages: Vec<ChatMessage> = stored.into_iter().map(|msg| msg.message).collect();
+    sync::label_senders(&state, &mut messages).await;
+    let next_cursor = messages.last().filter(|_| has_more).map(media_gallery::cursor_of);
+    Ok(MediaPage { messages, next_cursor })
+}
+
+// Cached messages only, for rendering a conversation before the homeserver answers
+#[command]
+pub async fn get_cached_conversation(
+    other_pubkey: String,
+    state: State<'_, AppState>,
+) -> MessengerResult<Vec<ChatMessage>> {
+    let stored = state.with_storage(|storage| storage.conversation_messages(&other_pubkey)).await?;
+    let mut messages: Vec<ChatMessage> = stored.into_iter().map(|msg| msg.message).collect();
+    sync::label_senders(&state, &mut messages).await;
+    Ok(messages)
+}
+
+#[command]
+pub async fn get_user_profile(
+    state: State<'_, AppState>,
+) -> MessengerResult<Option<UserProfile>> {
+    let keypair_guard = state.keypair.lock().await;
+    let name_guard = state.user_name.lock().await;
+
+    if let Some(keypair) = keypair_guard.as_ref() {
+        Ok(Some(UserProfile {
+            public_key: keypair.public_key().to_string(),
+            signed_in: true,
+            name: name_guard.clone(),
+        }))
+    } else {
+        Ok(None)
+    }
+}
+
+// Publish the user's pubky.app profile, replacing the existing one
+#[command]
+pub async fn update_profile(
+    name: String,
+    bio: Option<String>,
+    image: Option<String>,
+    links: Option<Vec<Link>>,
+    status: Option<String>,
+    state: State<'_, AppState>,
+) -> MessengerResult<UserProfile> {
+    let keypair = {
+        let keypair_guard = state.keypair.lock().await;
+        keypair_guard.clone().ok_or(MessengerError::NotSignedIn)?
+    };
+    let handler = state.create_handler().await?
+        .ok_or(MessengerError::NotSignedIn)?;
+
+    let profile = PubkyProfile { name, bio, image, links, status }.normalized()?;
+    handler.publish_profile(&profile).await
+        .err_context("Failed to publish profile")?;
+
+    *state.user_name.lock().await = Some(profile.name.clone());
+    Ok(UserProfile {
+        public_key: keypair.public_key().to_string(),
+        signed_in: true,
+        name: Some(profile.name),
+    })
+}
+
+// Resize and upload a new profile picture, then point the profile at it
+#[command]
+pub async fn set_profile_image(
+    image_bytes: Vec<u8>,
+    state: State<'_, AppState>,
+) -> MessengerResult<PubkyProfile> {
+    let handler = state.create_handler().await?
+        .ok_or(MessengerError::NotSignedIn)?;
+    handler.set_profile_image(&image_bytes).await
+        .err_context("Failed to set profile picture")
+}
+
+// Publish a short availability note; an empty one clears it
+#[command]
+pub async fn set_status(
+    status: String,
+    state: State<'_, AppState>,
+) -> MessengerResult<PubkyProfile> {
+    let handler = state.create_handler().await?
+        .ok_or(MessengerError::NotSignedIn)?;
+    handler.set_status(&status).await
+        .err_context("Failed to set status")
+}
+
+// Name, bio, links, status and avatar of any pubky, for the contact page.
+// With verify_links, each link is fetched to see if it links back (rel="me").
+#[command]
+pub async fn get_contact_profile(
+    pubky: String,
+    verify_links: Option<bool>,
+    state: State<'_, AppState>,
+) -> MessengerResult<crate::messaging::ContactProfile> {
+    let handler = state.create_handler().await?
+        .ok_or(MessengerError::NotSignedIn)?;
+    handler.get_contact_profile(pubky.trim(), verify_links.unwrap_or(false)).await
+        .err_context("Failed to get profile")
+}
+
+// A contact's profile picture as a data: URL for an <img>, if they have one
+#[command]
+pub async fn get_contact_avatar(
+    pubky: String,
+    state: State<'_, AppState>,
+) -> MessengerResult<Option<String>> {
+    let handler = state.create_handler().await?
+        .ok_or(MessengerError::NotSignedIn)?;
+    let avatar = handler.get_avatar(pubky.trim()).await
+        .err_context("Failed to get avatar")?;
+    Ok(avatar.map(|avatar| format!("data:{};base64,{}", avatar.content_type, base64::encode(&avatar.bytes))))
+}
+
+// Preview any pubky (name, bio, reachability) before starting a conversation
+#[command]
+pub async fn lookup_user(
+    pubky: String,
+    state: State<'_, AppState>,
+) -> MessengerResult<crate::messaging::UserPreview> {
+    let handler = state.create_handler().await?
+        .ok_or(MessengerError::NotSignedIn)?;
+    handler.lookup_user(pubky.trim()).await
+        .err_context("Failed to look up user")
+}
+
+// Refetch a contact's profile now, ignoring the profile cache's TTL
+#[command]
+pub async fn refresh_contact_profile(
+    pubky: String,
+    state: State<'_, AppState>,
+) -> MessengerResult<Option<PubkyProfile>> {
+    let handler = state.create_handler().await?
+        .ok_or(MessengerError::NotSignedIn)?;
+    handler.refresh_contact_profile(&pubky).await
+        .err_context("Failed to refresh profile")
+}
+
+#[command]
+pub async fn sign_out(app: AppHandle, state: State<'_, AppState>) -> MessengerResult<String> {
+    let mut keypair_guard = state.keypair.lock().await;
+    if let Some(keypair) = keypair_guard.take() {
+        SecurityLog::new(&state.store, &keypair.public_key())
+            .record(SecurityEventKind::SessionRevoked, None, Some("Signed out"));
+    }
+
+    let mut name_guard = state.user_name.lock().await;
+    *name_guard = None;
+
+    let mut signed_in_guard = state.is_signed_in.lock().await;
+    *signed_in_guard = false;
+
+    state.operations.cancel_all();
+    *state.storage.lock().await = None;
+    state.http_cache.clear();
+    state.watcher.clear();
+    tray::show_unread(&app, None);
+
+    Ok("Signed out successfully".to_string())
+}
+
+// First half of panic_wipe: the token to pass it, valid for two minutes
+#[command]
+pub async fn request_panic_wipe_token() -> MessengerResult<WipeToken> {
+    Ok(panic_wipe::request_token(now_secs()))
+}
+
+// Irreversibly delete the saved session, every local database, document
+// and log, and with `delete_remote` our blobs on the homeserver too
+#[command]
+pub async fn panic_wipe(
+    token: String,
+    delete_remote: bool,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> MessengerResult<WipeReport> {
+    let handler = if delete_remote {
+        state.create_handler().await?
+    } else {
+        None
+    };
+    let report = panic_wipe::wipe(&state, handler.as_ref(), &token, delete_remote, now_secs()).await?;
+    tray::show_unread(&app, None);
+    Ok(report)
+}
+
+// Pass an operation id to make the scan cancellable with cancel_operation
+#[command]
+pub async fn scan_followed_users(
+    operation_id: Option<String>,
+    state: State<'_, AppState>,
+) -> MessengerResult<Vec<crate::messaging::FollowedUser>> {
+    let keypair = {
+        let keypair_guard = state.keypair.lock().await;
+        keypair_guard.clone().ok_or(MessengerError::NotSignedIn)?
+    };
+
+    tracing::debug!("🔍 Scanning for followed users...");
+
+    let handler = state.create_handler().await?
+        .ok_or(MessengerError::NotSignedIn)?;
+    
+    // Get followed users with profiles
+    let users = state.operations.run(operation_id, profiles::refresh_contacts(&state, &handler)).await?;
+
+    if let Err(e) = onboarding::complete_step(&state.store, OnboardingStep::ScanContacts) {
+        tracing::warn!("⚠️  Failed to record onboarding progress: {}", e);
+    }
+
+    tracing::info!("✅ Found {} followed users", users.len());
+    Ok(users)
+}
+
+#[command]
+pub async fn get_saved_messages_contact(state: State<'_, AppState>) -> MessengerResult<Contact> {
+    let keypair_guard = state.keypair.lock().await;
+    let keypair = keypair_guard.as_ref().ok_or(MessengerError::NotSignedIn)?;
+
+    Ok(Contact::saved_messages(keypair.public_key().to_string()))
+}
+
+#[command]
+pub async fn get_onboarding_state(state: State<'_, AppState>) -> MessengerResult<OnboardingState> {
+    onboarding::get_state(&state.store)
+        .err_context("Failed to load onboarding state")
+}
+
+#[command]
+pub async fn complete_onboarding_step(
+    step: OnboardingStep,
+    state: State<'_, AppState>,
+) -> MessengerResult<OnboardingState> {
+    onboarding::complete_step(&state.store, step)
+        .err_context("Failed to complete onboarding step")
+}
+
+#[command]
+pub async fn reset_onboarding(state: State<'_, AppState>) -> MessengerResult<OnboardingState> {
+    onboarding::reset(&state.store)
+        .err_context("Failed to reset onboarding")
+}
+
+#[command]
+pub async fn get_conversation_health(
+    other_pubkey: String,
+    state: State<'_, AppState>,
+) -> MessengerResult<ConversationHealth> {
+    let keypair = {
+        let keypair_guard = state.keypair.lock().await;
+        keypair_guard.clone().ok_or(MessengerError::NotSignedIn)?
+    };
+    sync::conversation_health(&state, &keypair.public_key().to_string(), &other_pubkey).await
+}
+
+#[command]
+pub async fn share_contact(
+    target_conversation: String,
+    pubky: String,
+    state: State<'_, AppState>,
+) -> MessengerResult<ContactCard> {
+    let handler = state.create_handler().await?
+        .ok_or(MessengerError::NotSignedIn)?;
+
+    let recipient = PublicKey::try_from(target_conversation.as_str())
+        .map_err(|e| MessengerError::InvalidPublicKey(format!("Invalid recipient public key: {}", e)))?;
+    ensure_not_blocked(&state, &handler.keypair, &recipient)?;
+    let contact = PublicKey::try_from(pubky.as_str())
+        .map_err(|e| MessengerError::InvalidPublicKey(format!("Invalid contact public key: {}", e)))?;
+
+    handler.share_contact(&recipient, &contact)
+        .await
+        .err_context("Failed to share contact")
+}
+
+#[command]
+pub async fn add_shared_contact(
+    card: ContactCard,
+    state: State<'_, AppState>,
+) -> MessengerResult<Contact> {
+    let handler = state.create_handler().await?
+        .ok_or(MessengerError::NotSignedIn)?;
+
+    let contact_pk = PublicKey::try_from(card.pubky.as_str())
+        .map_err(|e| MessengerError::InvalidPublicKey(format!("Invalid contact public key: {}", e)))?;
+    ensure_not_blocked(&state, &handler.keypair, &contact_pk)?;
+
+    // Prefer the contact's current profile name over the one in the card
+    let name = handler.get_profile_name(&contact_pk.to_string())
+        .await
+        .unwrap_or(None)
+        .or(card.name);
+
+    // Saved like a contact added by hand, so it's listed before any message
+    let public_key = contact_pk.to_string();
+    let label = state.with_storage(|storage| {
+        storage.add_contact(&public_key, name.as_deref(), None, now_secs())?;
+        storage.contact_name(&public_key)
+    }).await?;
+    tracing::info!("✅ Added shared contact {}", logging::pubkey(&public_key));
+
+    Ok(Contact {
+        public_key,
+        name: label.or(name),
+        last_message: None,
+        last_message_time: None,
+        health: None,
+        verification: None,
+    })
+}
+
+// Add someone by public key, without following them on pubky.app. Their
+// homeserver must resolve; `alias` is our own name for them.
+#[command]
+pub async fn add_contact(
+    pubky: String,
+    alias: Option<String>,
+    state: State<'_, AppState>,
+) -> MessengerResult<Contact> {
+    let handler = state.create_handler().await?
+        .ok_or(MessengerError::NotSignedIn)?;
+
+    let contact_pk = PublicKey::try_from(pubky.trim())
+        .map_err(|e| MessengerError::InvalidPublicKey(format!("Invalid contact public key: {}", e)))?;
+    let public_key = contact_pk.to_string();
+
+    handler.get_homeserver(public_key.clone())
+        .await
+        .err_context("Failed to resolve contact")?;
+    let name = handler.get_profile_name(&public_key)
+        .await
+        .unwrap_or(None);
+    let alias = alias.map(|alias| alias.trim().to_string()).filter(|alias| !alias.is_empty());
+
+    state.with_storage(|storage| storage.add_contact(&public_key, name.as_deref(), alias.as_deref(), now_secs())).await?;
+    tracing::info!("✅ Added contact {}", logging::pubkey(&public_key));
+
+    Ok(Contact {
+        public_key,
+        name: alias.or(name),
+        last_message: None,
+        last_message_time: None,
+        health: None,
+        verification: None,
+    })
+}
+
+// Our own name for a contact, shown instead of their (unverified, mutable)
+// profile name. None or an empty alias clears it.
+#[command]
+pub async fn set_contact_alias(
+    pubky: String,
+    alias: Option<String>,
+    state: State<'_, AppState>,
+) -> MessengerResult<Option<String>> {
+    let public_key = PublicKey::try_from(pubky.trim())
+        .map_err(|e| MessengerError::InvalidPublicKey(format!("Invalid contact public key: {}", e)))?
+        .to_string();
+    let alias = alias.map(|alias| alias.trim().to_string()).filter(|alias| !alias.is_empty());
+
+    state.with_storage(|storage| {
+        storage.set_contact_alias(&public_key, alias.as_deref(), now_secs())?;
+        storage.contact_name(&public_key)
+    }).await
+}
+
+// Follow someone on pubky.app; they also become a cached contact
+#[command]
+pub async fn follow_user(
+    pubky: String,
+    state: State<'_, AppState>,
+) -> MessengerResult<crate::messaging::FollowedUser> {
+    let handler = state.create_handler().await?
+        .ok_or(MessengerError::NotSignedIn)?;
+
+    let user_pk = PublicKey::try_from(pubky.trim())
+        .map_err(|e| MessengerError::InvalidPublicKey(format!("Invalid public key: {}", e)))?;
+    if user_pk == handler.keypair.public_key() {
+        return Err(MessengerError::InvalidInput("You can't follow yourself".to_string()));
+    }
+
+    handler.follow_user(&user_pk)
+        .await
+        .err_context("Failed to follow user")?;
+
+    let public_key = user_pk.to_string();
+    let profile = handler.get_profile(&public_key).await.unwrap_or(None);
+    let name = profile.as_ref().map(|profile| profile.name.clone());
+    state.with_storage(|storage| storage.upsert_contact(&public_key, name.as_deref(), "follows", now_secs())).await?;
+
+    tracing::info!("➕ Followed {}", logging::pubkey(&public_key));
+    let mutual = handler.follows_us(&public_key).await.unwrap_or(false);
+    Ok(crate::messaging::FollowedUser::from_profile(public_key, profile, mutual))
+}
+
+// Remove a pubky.app follow. The cached contact and conversation stay.
+#[command]
+pub async fn unfollow_user(
+    pubky: String,
+    state: State<'_, AppState>,
+) -> MessengerResult<String> {
+    let handler = state.create_handler().await?
+        .ok_or(MessengerError::NotSignedIn)?;
+
+    let user_pk = PublicKey::try_from(pubky.trim())
+        .map_err(|e| MessengerError::InvalidPublicKey(format!("Invalid public key: {}", e)))?;
+
+    handler.unfollow_user(&user_pk)
+        .await
+        .err_context("Failed to unfollow user")?;
+
+    tracing::info!("➖ Unfollowed {}", logging::pubkey(&user_pk));
+    Ok("Unfollowed successfully".to_string())
+}
+
+// Record the contact's current keys as verified, after comparing the
+// fingerprint with them in person or over another channel
+#[command]
+pub async fn verify_contact(
+    pubky: String,
+    state: State<'_, AppState>,
+) -> MessengerResult<VerificationStatus> {
+    let handler = state.create_handler().await?
+        .ok_or(MessengerError::NotSignedIn)?;
+    let public_key = PublicKey::try_from(pubky.trim())
+        .map_err(|e| MessengerError::InvalidPublicKey(format!("Invalid contact public key: {}", e)))?;
+
+    verification::verify(&state, &handler, &public_key).await
+}
+
+#[command]
+pub async fn unverify_contact(
+    pubky: String,
+    state: State<'_, AppState>,
+) -> MessengerResult<()> {
+    let public_key = PublicKey::try_from(pubky.trim())
+        .map_err(|e| MessengerError::InvalidPublicKey(format!("Invalid contact public key: {}", e)))?
+        .to_string();
+
+    state.with_storage(|storage| storage.set_contact_verification(&public_key, None, now_secs())).await?;
+    if let Some(keypair) = state.keypair.lock().await.as_ref() {
+        SecurityLog::new(&state.store, &keypair.public_key())
+            .record(SecurityEventKind::VerificationRemoved, Some(&public_key), None);
+    }
+    Ok(())
+}
+
+// Current fingerprint and verification state of a contact, also flagging
+// the contact (and raising contact-key-changed) if their keys changed
+#[command]
+pub async fn get_contact_verification(
+    pubky: String,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> MessengerResult<VerificationStatus> {
+    let handler = state.create_handler().await?
+        .ok_or(MessengerError::NotSignedIn)?;
+    let public_key = PublicKey::try_from(pubky.trim())
+        .map_err(|e| MessengerError::InvalidPublicKey(format!("Invalid contact public key: {}", e)))?;
+
+    verification::check(&TauriEvents(app), &state, &handler, &public_key).await
+}
+
+// Known people (cached contacts, conversations and follows) who follow us,
+// flagged as mutual when we follow them too. Pass an operation id to make
+// the scan cancellable with cancel_operation.
+#[command]
+pub async fn scan_followers(
+    operation_id: Option<String>,
+    state: State<'_, AppState>,
+) -> MessengerResult<Vec<crate::messaging::FollowedUser>> {
+    let handler = state.create_handler().await?
+        .ok_or(MessengerError::NotSignedIn)?;
+
+    tracing::debug!("🔍 Scanning for followers...");
+
+    let mut candidates = state.with_storage(|storage| {
+        let mut keys: Vec<String> = storage.all_contacts()?.into_iter().map(|contact| contact.public_key).collect();
+        keys.extend(storage.conversation_keys()?);
+        Ok(keys)
+    }).await?;
+    candidates.sort();
+    candidates.dedup();
+
+    let scan = async {
+        handler.scan_followers(&candidates)
+            .await
+            .err_context("Failed to scan followers")
+    };
+    let followers = state.operations.run(operation_id, scan).await?;
+
+    let now = now_secs();
+    let cached = state.with_storage(|storage| {
+        for user in &followers {
+            storage.upsert_contact(&user.pubky, user.name.as_deref(), "followers", now)?;
+        }
+        Ok(())
+    }).await;
+    if let Err(e) = cached {
+        tracing::warn!("⚠️  Failed to cache contacts: {}", e);
+    }
+
+    Ok(followers)
+}
+
+// The home screen feed: messages, message requests, mentions and key
+// changes, newest first. Pass the returned cursor to get older items.
+#[command]
+pub async fn get_inbox(
+    limit: Option<usize>,
+    cursor: Option<String>,
+    state: State<'_, AppState>,
+) -> MessengerResult<InboxPage> {
+    let keypair = {
+        let keypair_guard = state.keypair.lock().await;
+        keypair_guard.clone().ok_or(MessengerError::NotSignedIn)?
+    };
+    InboxFeed::new(&state.store, &keypair.public_key())
+        .page(limit.unwrap_or(inbox_feed::DEFAULT_PAGE_SIZE), cursor.as_deref())
+        .err_context("Failed to load inbox")
+}
+
+// What happened on the account on this device, most recent first
+#[command]
+pub async fn get_security_events(state: State<'_, AppState>) -> MessengerResult<Vec<SecurityEvent>> {
+    let keypair = {
+        let keypair_guard = state.keypair.lock().await;
+        keypair_guard.clone().ok_or(MessengerError::NotSignedIn)?
+    };
+    Ok(SecurityLog::new(&state.store, &keypair.public_key()).events())
+}
+
+// Messages that were too large or failed to decrypt, most recent first.
+// Each is retried with backoff and drops off the list once it opens.
+#[command]
+pub async fn get_quarantined_messages(state: State<'_, AppState>) -> MessengerResult<Vec<QuarantinedMessage>> {
+    let keypair = {
+        let keypair_guard = state.keypair.lock().await;
+        keypair_guard.clone().ok_or(MessengerError::NotSignedIn)?
+    };
+    Ok(Quarantine::new(state.store.clone(), &keypair.public_key()).list())
+}
+
+#[command]
+pub async fn get_cached_contacts(state: State<'_, AppState>) -> MessengerResult<Vec<Contact>> {
+    let keypair = {
+        let keypair_guard = state.keypair.lock().await;
+        keypair_guard.clone().ok_or(MessengerError::NotSignedIn)?
+    };
+    let own_pubkey = keypair.public_key().to_string();
+
+    let mut contacts = state.with_storage(|storage| storage.contacts()).await?;
+    NameResolver::load(&state).await?.label_contacts(&mut contacts);
+    for contact in contacts.iter_mut() {
+        contact.health = Some(sync::conversation_health(&state, &own_pubkey, &contact.public_key).await?);
+    }
+    Ok(contacts)
+}
+
+// How a contact's display name is chosen: alias, profile name or pubky.
+// set_contact_alias overrides it.
+#[command]
+pub async fn get_name_resolution(
+    pubky: String,
+    state: State<'_, AppState>,
+) -> MessengerResult<ResolvedName> {
+    let public_key = PublicKey::try_from(pubky.trim())
+        .map_err(|e| MessengerError::InvalidPublicKey(format!("Invalid contact public key: {}", e)))?
+        .to_string();
+    Ok(NameResolver::load(&state).await?.resolve(&public_key))
+}
+
+#[command]
+pub async fn set_link_previews_enabled(
+    enabled: bool,
+    state: State<'_, AppState>,
+) -> MessengerResult<bool> {
+    link_preview::set_enabled(&state.store, enabled)
+        .err_context("Failed to save link preview setting")?;
+    Ok(enabled)
+}
+
+#[command]
+pub async fn get_outbox(state: State<'_, AppState>) -> MessengerResult<Vec<OutboxEntry>> {
+    let keypair = {
+        let keypair_guard = state.keypair.lock().await;
+        keypair_guard.clone().ok_or(MessengerError::NotSignedIn)?
+    };
+
+    let _guard = state.outbox_lock.lock().await;
+    Outbox::new(&state.store, &keypair.public_key())
+        .entries()
+        .err_context("Failed to load outbox")
+}
+
+#[command]
+pub async fn cancel_outbox_entry(
+    id: String,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> MessengerResult<bool> {
+    let keypair = {
+        let keypair_guard = state.keypair.lock().await;
+        keypair_guard.clone().ok_or(MessengerError::NotSignedIn)?
+    };
+
+    let _guard = state.outbox_lock.lock().await;
+    let cancelled = Outbox::new(&state.store, &keypair.public_key())
+        .cancel(&id)
+        .err_context("Failed to cancel queued message")?;
+
+    if let Some(entry) = &cancelled {
+        outbox::emit_status(&TauriEvents(app), entry);
+    }
+    Ok(cancelled.is_some())
+}
+
+#[command]
+pub async fn retry_outbox_now(state: State<'_, AppState>) -> MessengerResult<usize> {
+    let keypair = {
+        let keypair_guard = state.keypair.lock().await;
+        keypair_guard.clone().ok_or(MessengerError::NotSignedIn)?
+    };
+
+    let _guard = state.outbox_lock.lock().await;
+    Outbox::new(&state.store, &keypair.public_key())
+        .retry_all_now()
+        .err_context("Failed to reschedule outbox")
+}
+
+#[command]
+pub async fn get_unread_counts(state: State<'_, AppState>) -> MessengerResult<UnreadCounts> {
+    let keypair = {
+        let keypair_guard = state.keypair.lock().await;
+        keypair_guard.clone().ok_or(MessengerError::NotSignedIn)?
+    };
+
+    ReadState::new(&state.store, &keypair.public_key())
+        .unread_counts()
+        .err_context("Failed to load unread counts")
+}
+
+#[command]
+pub async fn mark_conversation_read(
+    pubkey: String,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> MessengerResult<UnreadCounts> {
+    let keypair = {
+        let keypair_guard = state.keypair.lock().await;
+        keypair_guard.clone().ok_or(MessengerError::NotSignedIn)?
+    };
+
+    let read_state = ReadState::new(&state.store, &keypair.public_key());
+    read_state.mark_read(&pubkey, now_secs())
+        .err_context("Failed to mark conversation read")?;
+
+    let unread = read_state.unread_counts()
+        .err_context("Failed to load unread counts")?;
+    tray::show_unread(&app, Some(&unread));
+    Ok(unread)
+}
+
+#[command]
+pub async fn get_conversations(
+    refresh: Option<bool>,
+    operation_id: Option<String>,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> MessengerResult<Vec<ConversationSummary>> {
+    let keypair = {
+        let keypair_guard = state.keypair.lock().await;
+        keypair_guard.clone().ok_or(MessengerError::NotSignedIn)?
+    };
+
+    // Reload every known conversation (cached ones plus follows) first
+    if refresh.unwrap_or(false) {
+        state.operations.run(operation_id, sync::sync_all(&TauriEvents(app), &state)).await?;
+    }
+
+    let unread = ReadState::new(&state.store, &keypair.public_key())
+        .unread_counts()
+        .err_context("Failed to load unread counts")?;
+
+    let (stored, mut contacts, added) = state.with_storage(|storage| Ok((
+        storage.conversation_summaries()?,
+        storage.contacts()?,
+        storage.contact_keys(MANUAL_CONTACT_SOURCE)?,
+    ))).await?;
+    NameResolver::load(&state).await?.label_contacts(&mut contacts);
+    let contacts: std::collections::HashMap<String, Contact> = contacts.into_iter()
+        .map(|contact| (contact.public_key.clone(), contact))
+        .collect();
+
+    let mut summaries: Vec<ConversationSummary> = stored.into_iter()
+        .map(|conversation| ConversationSummary::from_stored(conversation, &unread.per_contact))
+        .collect();
+
+    // Contacts added by hand are listed before the first message too
+    for public_key in added {
+        if !summaries.iter().any(|summary| summary.contact.public_key == public_key) {
+            summaries.push(ConversationSummary::empty(public_key));
+        }
+    }
+
+    let muted = MuteList::new(&state.store, &keypair.public_key())
+        .active(now_secs())
+        .err_context("Failed to load muted conversations")?;
+
+    let own_pubkey = keypair.public_key().to_string();
+    for summary in summaries.iter_mut() {
+        summary.contact.health = Some(sync::conversation_health(&state, &own_pubkey, &summary.contact.public_key).await?);
+        if let Some(contact) = contacts.get(&summary.contact.public_key) {
+            summary.contact.name = contact.name.clone();
+            summary.contact.verification = contact.verification;
+        }
+        summary.muted = muted.contains_key(&summary.contact.public_key);
+        summary.muted_until = muted.get(&summary.contact.public_key).and_then(|entry| entry.until);
+        if summary.contact.public_key == own_pubkey {
+            summary.contact.name = Some(crate::messaging::SAVED_MESSAGES_NAME.to_string());
+        }
+    }
+
+    Ok(summaries)
+}
+
+#[command]
+pub async fn mute_conversation(
+    pubkey: String,
+    until: Option<u64>,
+    state: State<'_, AppState>,
+) -> MessengerResult<String> {
+    let keypair = {
+        let keypair_guard = state.keypair.lock().await;
+        keypair_guard.clone().ok_or(MessengerError::NotSignedIn)?
+    };
+
+    PublicKey::try_from(pubkey.as_str())
+        .map_err(|e| MessengerError::InvalidPublicKey(format!("Invalid public key: {}", e)))?;
+
+    MuteList::new(&state.store, &keypair.public_key())
+        .mute(&pubkey, until)
+        .err_context("Failed to mute conversation")?;
+
+    Ok("Conversation muted".to_string())
+}
+
+#[command]
+pub async fn unmute_conversation(
+    pubkey: String,
+    state: State<'_, AppState>,
+) -> MessengerResult<bool> {
+    let keypair = {
+        let keypair_guard = state.keypair.lock().await;
+        keypair_guard.clone().ok_or(MessengerError::NotSignedIn)?
+    };
+
+    MuteList::new(&state.store, &keypair.public_key())
+        .unmute(&pubkey)
+        .err_context("Failed to unmute conversation")
+}
+
+#[command]
+pub async fn get_muted_conversations(
+    state: State<'_, AppState>,
+) -> MessengerResult<std::collections::HashMap<String, MuteEntry>> {
+    let keypair = {
+        let keypair_guard = state.keypair.lock().await;
+        keypair_guard.clone().ok_or(MessengerError::NotSignedIn)?
+    };
+
+    MuteList::new(&state.store, &keypair.public_key())
+        .active(now_secs())
+        .err_context("Failed to load muted conversations")
+}
+
+fn ensure_not_blocked(state: &AppState, keypair: &Keypair, recipient: &PublicKey) -> MessengerResult<()> {
+    if BlockList::new(&state.store, &keypair.public_key()).is_blocked(&recipient.to_string()) {
+        return Err(MessengerError::ContactBlocked);
+    }
+    Ok(())
+}
+
+// Blocked contacts aren't synced, their notifications are deleted unread
+// and messages to them are refused
+#[command]
+pub async fn block_contact(
+    pubkey: String,
+    state: State<'_, AppState>,
+) -> MessengerResult<String> {
+    let keypair = {
+        let keypair_guard = state.keypair.lock().await;
+        keypair_guard.clone().ok_or(MessengerError::NotSignedIn)?
+    };
+
+    let contact = PublicKey::try_from(pubkey.as_str())
+        .map_err(|e| MessengerError::InvalidPublicKey(format!("Invalid public key: {}", e)))?;
+    if contact == keypair.public_key() {
+        return Err(MessengerError::InvalidInput("You can't block yourself".to_string()));
+    }
+
+    BlockList::new(&state.store, &keypair.public_key())
+        .block(&contact.to_string(), now_secs())
+        .err_context("Failed to block contact")?;
+
+    Ok("Contact blocked".to_string())
+}
+
+#[command]
+pub async fn unblock_contact(
+    pubkey: String,
+    state: State<'_, AppState>,
+) -> MessengerResult<bool> {
+    let keypair = {
+        let keypair_guard = state.keypair.lock().await;
+        keypair_guard.clone().ok_or(MessengerError::NotSignedIn)?
+    };
+
+    BlockList::new(&state.store, &keypair.public_key())
+        .unblock(&pubkey)
+        .err_context("Failed to unblock contact")
+}
+
+#[command]
+pub async fn get_blocked(
+    state: State<'_, AppState>,
+) -> MessengerResult<std::collections::HashMap<String, BlockEntry>> {
+    let keypair = {
+        let keypair_guard = state.keypair.lock().await;
+        keypair_guard.clone().ok_or(MessengerError::NotSignedIn)?
+    };
+
+    BlockList::new(&state.store, &keypair.public_key())
+        .blocked()
+        .err_context("Failed to load blocked contacts")
+}
+
+#[command]
+pub async fn get_retention_policy(state: State<'_, AppState>) -> MessengerResult<RetentionPolicy> {
+    let keypair = {
+        let keypair_guard = state.keypair.lock().await;
+        keypair_guard.clone().ok_or(MessengerError::NotSignedIn)?
+    };
+
+    retention::get_policy(&state.store, &keypair.public_key())
+        .err_context("Failed to load retention policy")
+}
+
+#[command]
+pub async fn set_retention_policy(
+    max_age_days: Option<u32>,
+    state: State<'_, AppState>,
+) -> MessengerResult<RetentionPolicy> {
+    let keypair = {
+        let keypair_guard = state.keypair.lock().await;
+        keypair_guard.clone().ok_or(MessengerError::NotSignedIn)?
+    };
+
+    let policy = RetentionPolicy { max_age_days };
+    retention::set_policy(&state.store, &keypair.public_key(), policy)
+        .err_context("Failed to save retention policy")?;
+    Ok(policy)
+}
+
+// Delete our own message blobs older than `older_than_days` (or the saved
+// policy). With `dry_run` only report what would be removed.
+#[command]
+pub async fn run_cleanup(
+    older_than_days: Option<u32>,
+    dry_run: Option<bool>,
+    operation_id: Option<String>,
+    state: State<'_, AppState>,
+) -> MessengerResult<CleanupReport> {
+    let keypair = {
+        let keypair_guard = state.keypair.lock().await;
+        keypair_guard.clone().ok_or(MessengerError::NotSignedIn)?
+    };
+
+    let max_age_days = match older_than_days {
+        Some(days) => days,
+        None => retention::get_policy(&state.store, &keypair.public_key())
+            .err_context("Failed to load retention policy")?
+            .max_age_days
+            .ok_or_else(|| MessengerError::InvalidInput("No retention period given and no retention policy set".to_string()))?,
+    };
+
+    let handler = state.create_handler().await?
+        .ok_or(MessengerError::NotSignedIn)?;
+
+    let cleanup = retention::run_cleanup(&state, &handler, max_age_days, dry_run.unwrap_or(false), now_secs());
+    state.operations.run(operation_id, cleanup).await
+}
+
+#[command]
+pub async fn get_sync_settings(state: State<'_, AppState>) -> MessengerResult<SyncSettings> {
+    Ok(sync::get_settings(&state.store))
+}
+
+// Returns the settings as stored, with the interval clamped to what we allow
+#[command]
+pub async fn set_sync_settings(
+    settings: SyncSettings,
+    state: State<'_, AppState>,
+) -> MessengerResult<SyncSettings> {
+    let settings = sync::set_settings(&state.store, settings)
+        .err_context("Failed to save sync settings")?;
+    state.sync_settings_changed.notify_one();
+    Ok(settings)
+}
+
+#[command]
+pub async fn get_network_settings(state: State<'_, AppState>) -> MessengerResult<NetworkSettings> {
+    Ok(net::get_settings(&state.store))
+}
+
+// Returns the settings as stored, with the timeout clamped to what we allow.
+// Switching networks is only allowed while signed out.
+#[command]
+pub async fn set_network_settings(
+    settings: NetworkSettings,
+    state: State<'_, AppState>,
+) -> MessengerResult<NetworkSettings> {
+    apply_network_settings(&state, settings).await
+}
+
+// Cancel a running sync_now, get_conversation(s), scan, export or run_cleanup
+// started with this operation id; false when it already finished
+#[command]
+pub async fn cancel_operation(operation_id: String, state: State<'_, AppState>) -> MessengerResult<bool> {
+    Ok(state.operations.cancel(&operation_id))
+}
+
+// Reachability of our homeserver (and optionally a contact's), session
+// validity and when we last synced
+#[command]
+pub async fn get_connection_status(
+    contact_pubkey: Option<String>,
+    state: State<'_, AppState>,
+) -> MessengerResult<ConnectionStatus> {
+    let contact = contact_pubkey
+        .map(|pubkey| PublicKey::try_from(pubkey.as_str()).map_err(|e| MessengerError::InvalidPublicKey(format!("Invalid public key: {}", e))))
+        .transpose()?;
+
+    let handler = state.create_handler().await?
+        .ok_or(MessengerError::NotSignedIn)?;
+
+    connection::check(&state, &handler, contact).await
+}
+
+// Request counts, failures, latency percentiles and bytes transferred per
+// homeserver operation since launch or the last reset
+#[command]
+pub async fn get_network_stats() -> MessengerResult<NetworkStats> {
+    Ok(metrics::snapshot())
+}
+
+#[command]
+pub async fn reset_network_stats() -> MessengerResult<()> {
+    metrics::reset();
+    Ok(())
+}
+
+// PNG of a QR code holding our pubky, for in-person contact exchange
+#[command]
+pub async fn get_my_pubky_qr(state: State<'_, AppState>) -> MessengerResult<Vec<u8>> {
+    let keypair = {
+        let keypair_guard = state.keypair.lock().await;
+        keypair_guard.clone().ok_or(MessengerError::NotSignedIn)?
+    };
+
+    qr::render_png(&keypair.public_key())
+        .err_context("Failed to create QR code")
+}
+
+// Read a pubky from a scanned QR code image (PNG or JPEG); pass the result
+// to add_contact to save it
+#[command]
+pub async fn parse_pubky_qr(image_bytes: Vec<u8>) -> MessengerResult<String> {
+    let public_key = task::spawn_blocking(move || qr::parse_image(&image_bytes))
+        .await
+        .err_context("Task failed")?
+        .err_context("Failed to read QR code")?;
+    Ok(public_key.to_string())
+}
+
+#[command]
+pub async fn get_presence_settings(state: State<'_, AppState>) -> MessengerResult<PresenceSettings> {
+    let keypair = {
+        let keypair_guard = state.keypair.lock().await;
+        keypair_guard.clone().ok_or(MessengerError::NotSignedIn)?
+    };
+
+    presence::get_settings(&state.store, &keypair.public_key())
+        .err_context("Failed to load presence settings")
+}
+
+// Presence is off unless turned on here. Turning it off deletes what we
+// published; switching visibility replaces it right away.
+#[command]
+pub async fn set_presence_settings(
+    settings: PresenceSettings,
+    state: State<'_, AppState>,
+) -> MessengerResult<PresenceSettings> {
+    let handler = state.create_handler().await?
+        .ok_or(MessengerError::NotSignedIn)?;
+    let owner = handler.keypair.public_key();
+
+    let previous = presence::get_settings(&state.store, &owner).unwrap_or_default();
+    presence::set_settings(&state.store, &owner, settings)
+        .err_context("Failed to save presence settings")?;
+
+    if previous.enabled && (!settings.enabled || previous.visibility != settings.visibility) {
+        handler.clear_presence()
+            .await
+            .err_context("Failed to remove presence")?;
+    }
+    presence::publish(&state, &handler, settings).await?;
+
+    Ok(settings)
+}
+
+#[command]
+pub async fn get_contact_presence(
+    pubky: String,
+    state: State<'_, AppState>,
+) -> MessengerResult<ContactPresence> {
+    let handler = state.create_handler().await?
+        .ok_or(MessengerError::NotSignedIn)?;
+    let contact = PublicKey::try_from(pubky.trim())
+        .map_err(|e| MessengerError::InvalidPublicKey(format!("Invalid contact public key: {}", e)))?;
+
+    let last_active = handler.fetch_presence(&contact)
+        .await
+        .err_context("Failed to fetch presence")?;
+
+    Ok(ContactPresence {
+        public_key: contact.to_string(),
+        last_active,
+    })
+}
+
+#[command]
+pub async fn get_log_settings(state: State<'_, AppState>) -> MessengerResult<LogSettings> {
+    Ok(logging::get_settings(&state.store))
+}
+
+// Takes effect right away; turning `redact` off puts pubkeys, paths and
+// message snippets in the logs
+#[command]
+pub async fn set_log_settings(
+    settings: LogSettings,
+    state: State<'_, AppState>,
+) -> MessengerResult<LogSettings> {
+    logging::set_settings(&state.store, settings)
+        .err_context("Failed to save log settings")
+}
+
+// Most recent log lines at `level` (default warn) or more severe, oldest first
+#[command]
+pub async fn get_recent_logs(level: Option<LogLevel>, limit: Option<usize>) -> MessengerResult<Vec<LogEntry>> {
+    Ok(logging::recent(level.unwrap_or(LogLevel::Warn), limit.unwrap_or(DEFAULT_LOG_LIMIT)))
+}
+
+#[command]
+pub async fn get_settings(state: State<'_, AppState>) -> MessengerResult<Settings> {
+    let owner = state.keypair.lock().await.as_ref().map(|keypair| keypair.public_key());
+    Ok(settings::get(&state.store, owner.as_ref()))
+}
+
+// Each section goes through the same path as its own setter, so validation
+// and side effects (rebuilding the client, waking the sync worker) match.
+// Returns the settings as stored.
+#[command]
+pub async fn update_settings(update: SettingsUpdate, app: AppHandle, state: State<'_, AppState>) -> MessengerResult<Settings> {
+    let owner = state.keypair.lock().await.as_ref().map(|keypair| keypair.public_key());
+    if update.retention.is_some() && owner.is_none() {
+        return Err(MessengerError::NotSignedIn);
+    }
+
+    if let Some(network) = update.network {
+        apply_network_settings(&state, network).await?;
+    }
+    if let Some(sync_settings) = update.sync {
+        sync::set_settings(&state.store, sync_settings)
+            .err_context("Failed to save sync settings")?;
+        state.sync_settings_changed.notify_one();
+    }
+    if let (Some(policy), Some(owner)) = (update.retention, owner.as_ref()) {
+        retention::set_policy(&state.store, owner, policy)
+            .err_context("Failed to save retention policy")?;
+    }
+    if let Some(enabled) = update.link_previews {
+        link_preview::set_enabled(&state.store, enabled)
+            .err_context("Failed to save link preview setting")?;
+    }
+    if let Some(notifications) = update.notifications {
+        settings::set_notification_settings(&state.store, notifications)
+            .err_context("Failed to save notification settings")?;
+    }
+    if let Some(startup) = update.startup {
+        let startup = settings::set_startup_settings(&state.store, startup)
+            .err_context("Failed to save startup settings")?;
+        startup::apply_launch_at_login(&app, startup)?;
+    }
+    if let Some(quiet_hours) = update.quiet_hours {
+        settings::set_quiet_hours_settings(&state.store, quiet_hours)
+            .err_context("Failed to save quiet hours")?;
+    }
+    if let Some(images) = update.images {
+        settings::set_image_settings(&state.store, images)
+            .err_context("Failed to save image settings")?;
+    }
+    if let Some(webhook_settings) = update.webhook {
+        webhook::set_settings(&state.store, webhook_settings)
+            .err_context("Failed to save webhook settings")?;
+    }
+    if let Some(log_settings) = update.logging {
+        logging::set_settings(&state.store, log_settings)
+            .err_context("Failed to save log settings")?;
+    }
+    if let Some(cache_settings) = update.attachment_cache {
+        attachment_cache::set_settings(&state.store, cache_settings)
+            .err_context("Failed to save attachment cache settings")?;
+    }
+    if let Some(limits) = update.attachment_limits {
+        attachment_limits::set_settings(&state.store, limits)
+            .err_context("Failed to save attachment limits")?;
+    }
+
+    Ok(settings::get(&state.store, owner.as_ref()))
+}
@@ -0,0 +1,71 @@
+// Random per-install secret that saved sessions are encrypted under, so a
+// session blob only opens in the install that wrote it. Kept next to the
+// rest of the app data, readable only by the user.
+use crate::error::{ErrorContext, MessengerError, MessengerResult};
+use rand_core::{OsRng, RngCore};
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::Path;
+
+const DEVICE_SECRET_FILE: &str = "device_secret";
+// Left behind by a wipe. Sessions from before the device secret aren't
+// keyed to it, so this is what stops them opening afterwards.
+const WIPED_FILE: &str = "session_wiped";
+
+// None until the first session is saved
+pub fn load(dir: &Path) -> MessengerResult<Option<[u8; 32]>> {
+    match fs::read(dir.join(DEVICE_SECRET_FILE)) {
+        Ok(bytes) => <[u8; 32]>::try_from(bytes)
+            .map(Some)
+            .map_err(|_| MessengerError::InvalidSession("Device secret is corrupt".to_string())),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).err_context("Failed to read device secret"),
+    }
+}
+
+pub fn load_or_create(dir: &Path) -> MessengerResult<[u8; 32]> {
+    if let Some(secret) = load(dir)? {
+        return Ok(secret);
+    }
+
+    fs::create_dir_all(dir).err_context("Failed to create app data directory")?;
+    let mut secret = [0u8; 32];
+    OsRng.fill_bytes(&mut secret);
+
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    // Elsewhere the per-user app data directory's own permissions apply
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    match options.open(dir.join(DEVICE_SECRET_FILE)) {
+        Ok(mut file) => {
+            file.write_all(&secret).err_context("Failed to write device secret")?;
+            file.sync_all().err_context("Failed to write device secret")?;
+            Ok(secret)
+        }
+        // Another window got there first
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+            load(dir)?.ok_or_else(|| MessengerError::Internal("Device secret disappeared".to_string()))
+        }
+        Err(e) => Err(e).err_context("Failed to create device secret"),
+    }
+}
+
+// Makes every saved session unreadable, whatever it was encrypted under
+pub fn wipe(dir: &Path) -> MessengerResult<()> {
+    fs::create_dir_all(dir).err_context("Failed to create app data directory")?;
+    fs::write(dir.join(WIPED_FILE), []).err_context("Failed to record session wipe")?;
+    match fs::remove_file(dir.join(DEVICE_SECRET_FILE)) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e).err_context("Failed to remove device secret"),
+    }
+}
+
+// Whether sessions saved before the device secret were wiped
+pub fn was_wiped(dir: &Path) -> bool {
+    dir.join(WIPED_FILE).exists()
+}
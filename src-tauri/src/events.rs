@@ -0,0 +1,34 @@
+use crate::subscriptions::MessageSubscriptions;
+use crate::{notifications, tray};
+use pubky_messenger_core::events::EventSink;
+use pubky_messenger_core::read_state::{UnreadCounts, UNREAD_COUNTS_EVENT};
+use pubky_messenger_core::sync::{MessageReceivedEvent, CONVERSATION_UPDATED_EVENT, MESSAGE_RECEIVED_EVENT};
+use tauri::{AppHandle, Emitter, Manager};
+
+// Forwards core events to the webview as Tauri events, pushes new messages
+// to subscribed channels, raises OS notifications for them and keeps the
+// tray's unread count current
+pub struct TauriEvents(pub AppHandle);
+
+impl EventSink for TauriEvents {
+    fn emit_json(&self, event: &str, payload: serde_json::Value) -> anyhow::Result<()> {
+        if event == MESSAGE_RECEIVED_EVENT {
+            if let Ok(received) = serde_json::from_value::<MessageReceivedEvent>(payload.clone()) {
+                if let Some(subscriptions) = self.0.try_state::<MessageSubscriptions>() {
+                    subscriptions.push(&received);
+                }
+                let handle = self.0.clone();
+                tauri::async_runtime::spawn(async move { notifications::notify_message(&handle, received).await });
+            }
+        } else if event == CONVERSATION_UPDATED_EVENT {
+            let handle = self.0.clone();
+            tauri::async_runtime::spawn(async move { tray::refresh(&handle).await });
+        } else if event == UNREAD_COUNTS_EVENT {
+            if let Ok(unread) = serde_json::from_value::<UnreadCounts>(payload.clone()) {
+                tray::show_unread(&self.0, Some(&unread));
+            }
+        }
+        self.0.emit(event, payload)?;
+        Ok(())
+    }
+}
@@ -1,27 +1,195 @@
+pub mod badge;
 pub mod commands;
-pub mod messaging;
+pub mod device_secret;
+pub mod events;
+pub mod notifications;
+pub mod startup;
+pub mod subscriptions;
+pub mod tray;
+
+pub use pubky_messenger_core::{
+    app_lock, attachment_cache, attachment_expiry, attachment_limits, attachment_preview, attachments, audio_pipeline, avatar, backup, blocks, connection, conversations, crypto_compat, downloads, error, export, health, http_cache, image_pipeline, inbox, inbox_feed, limits, link_preview, link_verification,
+    local_store, logging, media_gallery, mentions, messaging, metrics, mutes, names, net, notification_batch, onboarding, operations, outbox, padding, panic_wipe, presence, profiles, progress, protocol, push, qr, quarantine, read_state,
+    retention, security_log, settings, storage, stream_cipher, sync, transport, verification, watcher, webhook, wire,
+};
 
 pub use commands::*;
 pub use messaging::*;
 
+use events::TauriEvents;
+use std::sync::Arc;
+use tauri_plugin_autostart::MacosLauncher;
+use tauri::Manager;
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    // Create the app state
-    let app_state = AppState::new();
-
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
-        .manage(app_state)
+        .plugin(tauri_plugin_dialog::init())
+        .plugin(tauri_plugin_notification::init())
+        .plugin(tauri_plugin_clipboard_manager::init())
+        .plugin(tauri_plugin_autostart::init(MacosLauncher::LaunchAgent, None))
+        .setup(|app| {
+            // Create the app state, persisting local data in the app data dir
+            let data_dir = app.path().app_data_dir()?;
+            let state = AppState::new(data_dir.clone());
+            if let Err(e) = logging::init(&data_dir.join("logs"), &logging::get_settings(&state.store)) {
+                eprintln!("Failed to set up logging: {}", e);
+            }
+
+            // Make sure upstream crypto still matches the formats we write
+            crypto_compat::check_upstream_compat();
+
+            net::apply_settings(&net::get_settings(&state.store));
+            state.progress.set_sink(Some(Arc::new(TauriEvents(app.handle().clone()))));
+            let startup_settings = settings::startup_settings(&state.store);
+            app.manage(state);
+            app.manage(subscriptions::MessageSubscriptions::default());
+
+            // Unread count and quick actions while the window is hidden
+            tray::create(app.handle())?;
+            startup::show_initial_window(app.handle(), startup_settings);
+            if let Err(e) = startup::apply_launch_at_login(app.handle(), startup_settings) {
+                tracing::warn!("⚠️  Failed to update launch at login: {}", e);
+            }
+
+            // Retry queued messages in the background
+            let handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                let state = handle.state::<AppState>();
+                outbox::run_outbox_worker(&state, &TauriEvents(handle.clone())).await
+            });
+
+            // Keep the local cache synced and raise events for new messages
+            let handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                let state = handle.state::<AppState>();
+                sync::run_sync_worker(&state, &TauriEvents(handle.clone())).await
+            });
+
+            // Keep contact profiles and avatars fresh without user scans
+            let handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                let state = handle.state::<AppState>();
+                profiles::run_profile_refresh_worker(&state, &TauriEvents(handle.clone())).await
+            });
+
+            // Refresh our last-active record while presence is enabled
+            let handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move { presence::run_presence_worker(&handle.state::<AppState>()).await });
+
+            // Delete our expired attachments and drop expired files from the cache
+            let handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move { attachment_expiry::run_expiry_worker(&handle.state::<AppState>()).await });
+            Ok(())
+        })
+        .on_window_event(|window, event| {
+            // Closing the main window keeps the app running in the tray
+            if let tauri::WindowEvent::CloseRequested { api, .. } = event {
+                if window.label() == "main" {
+                    api.prevent_close();
+                    let _ = window.hide();
+                }
+            }
+        })
         .invoke_handler(tauri::generate_handler![
             init_client,
             sign_in_with_recovery,
             restore_session,
             send_message,
-            get_new_messages,
+            sync_now,
+            subscribe_messages,
+            unsubscribe_messages,
             get_conversation,
+            get_cached_conversation,
+            get_conversation_page,
+            export_conversation,
+            create_backup,
+            restore_backup,
             get_user_profile,
             sign_out,
-            scan_followed_users
+            scan_followed_users,
+            get_saved_messages_contact,
+            get_onboarding_state,
+            complete_onboarding_step,
+            reset_onboarding,
+            get_conversation_health,
+            share_contact,
+            add_shared_contact,
+            get_cached_contacts,
+            set_link_previews_enabled,
+            get_outbox,
+            cancel_outbox_entry,
+            retry_outbox_now,
+            get_unread_counts,
+            mark_conversation_read,
+            get_conversations,
+            mute_conversation,
+            unmute_conversation,
+            get_muted_conversations,
+            get_retention_policy,
+            set_retention_policy,
+            run_cleanup,
+            get_sync_settings,
+            set_sync_settings,
+            get_network_settings,
+            set_network_settings,
+            cancel_operation,
+            get_connection_status,
+            get_network_stats,
+            reset_network_stats,
+            add_contact,
+            set_contact_alias,
+            block_contact,
+            unblock_contact,
+            get_blocked,
+            follow_user,
+            unfollow_user,
+            get_my_pubky_qr,
+            parse_pubky_qr,
+            verify_contact,
+            unverify_contact,
+            get_contact_verification,
+            get_presence_settings,
+            set_presence_settings,
+            get_contact_presence,
+            scan_followers,
+            get_log_settings,
+            set_log_settings,
+            get_recent_logs,
+            get_settings,
+            update_settings,
+            send_file,
+            pick_and_send_file,
+            send_clipboard_image,
+            send_voice_note,
+            save_attachment,
+            update_profile,
+            set_profile_image,
+            refresh_contact_profile,
+            set_status,
+            get_contact_profile,
+            get_name_resolution,
+            get_contact_avatar,
+            lookup_user,
+            get_inbox,
+            get_quarantined_messages,
+            get_security_events,
+            unlock_session,
+            get_app_lock,
+            set_app_lock,
+            remove_app_lock,
+            request_panic_wipe_token,
+            panic_wipe,
+            get_downloads,
+            start_download,
+            pause_download,
+            resume_download,
+            cancel_download,
+            get_cache_usage,
+            clear_attachment_cache,
+            get_conversation_media,
+            get_expiring_attachments
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
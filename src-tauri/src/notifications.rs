@@ -0,0 +1,90 @@
+use crate::logging;
+use crate::mentions::NotificationPriority;
+use crate::messaging::AppState;
+use crate::mutes::MuteList;
+use crate::names;
+use crate::settings;
+use crate::sync::MessageReceivedEvent;
+use crate::verification::VerificationState;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_notification::NotificationExt;
+
+// Body shown when the user hides message content from notifications
+const HIDDEN_PREVIEW: &str = "New message";
+const DEFAULT_SOUND: &str = "default";
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+// Nothing to raise while the user is looking at the app
+fn window_in_view(app: &AppHandle) -> bool {
+    app.get_webview_window("main").is_some_and(|window| {
+        window.is_focused().unwrap_or(false)
+            && window.is_visible().unwrap_or(true)
+            && !window.is_minimized().unwrap_or(false)
+    })
+}
+
+// Verified and not since broken
+async fn is_verified(state: &AppState, pubky: &str) -> bool {
+    let verification = state.with_storage(|storage| storage.contact_verification(pubky)).await;
+    matches!(verification, Ok(Some(verification)) if VerificationState::of(&verification) == VerificationState::Verified)
+}
+
+// Raise an OS notification for a received message, following the
+// notification settings, quiet hours and the conversation's mute. Mentions
+// are shown even in muted conversations, and say so in the title.
+pub async fn notify_message(app: &AppHandle, event: MessageReceivedEvent) {
+    let message = &event.message;
+    if message.is_own_message || window_in_view(app) {
+        return;
+    }
+
+    let state = app.state::<AppState>();
+    let notifications = settings::notification_settings(&state.store);
+    if !notifications.enabled {
+        return;
+    }
+    let mention = message.priority == NotificationPriority::High;
+    if notifications.mentions_only && !mention {
+        return;
+    }
+
+    let Some(owner) = state.keypair.lock().await.as_ref().map(|keypair| keypair.public_key()) else {
+        return;
+    };
+    if !mention && MuteList::new(&state.store, &owner).is_muted(&event.conversation, now_secs()) {
+        return;
+    }
+    let quiet_hours = settings::quiet_hours_settings(&state.store);
+    if quiet_hours.is_quiet_now() && !(quiet_hours.allow_verified && is_verified(&state, &message.sender).await) {
+        return;
+    }
+
+    // Senders are labelled during sync; resolve here for anything that wasn't
+    let sender = match &message.sender_name {
+        Some(name) => name.clone(),
+        None => match names::NameResolver::load(&state).await {
+            Ok(names) => names.display_name(&message.sender),
+            Err(_) => names::short_pubkey(&message.sender),
+        },
+    };
+    let title = if mention { format!("{} mentioned you", sender) } else { sender };
+    let body = if notifications.show_preview && !message.content.trim().is_empty() {
+        message.content.as_str()
+    } else {
+        HIDDEN_PREVIEW
+    };
+
+    let mut builder = app.notification().builder().title(title).body(body);
+    if notifications.sound {
+        builder = builder.sound(notifications.sound_name.as_deref().unwrap_or(DEFAULT_SOUND));
+    }
+    if let Err(e) = builder.show() {
+        tracing::warn!("⚠️  Failed to show notification for {}: {}", logging::pubkey(&event.conversation), e);
+    }
+}
@@ -0,0 +1,26 @@
+use crate::error::{ErrorContext, MessengerResult};
+use crate::settings::StartupSettings;
+use crate::tray;
+use tauri::AppHandle;
+use tauri_plugin_autostart::ManagerExt;
+
+// Register or remove the login item to match the setting
+pub fn apply_launch_at_login(app: &AppHandle, startup: StartupSettings) -> MessengerResult<()> {
+    let autolaunch = app.autolaunch();
+    let registered = autolaunch.is_enabled().err_context("Failed to check launch at login")?;
+    match (startup.launch_at_login, registered) {
+        (true, false) => autolaunch.enable().err_context("Failed to enable launch at login"),
+        (false, true) => autolaunch.disable().err_context("Failed to disable launch at login"),
+        _ => Ok(()),
+    }
+}
+
+// The window starts hidden (see tauri.conf.json) and is only shown here,
+// so starting in the tray never flashes it on screen
+pub fn show_initial_window(app: &AppHandle, startup: StartupSettings) {
+    if startup.start_hidden {
+        tracing::info!("🫥 Starting in the tray");
+    } else {
+        tray::show_window(app);
+    }
+}
@@ -0,0 +1,40 @@
+use crate::sync::MessageReceivedEvent;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use tauri::ipc::Channel;
+
+// Frontend channels that get every message sync discovers, pushed as soon
+// as it is decrypted. A channel whose webview went away is dropped on the
+// next send.
+#[derive(Default)]
+pub struct MessageSubscriptions {
+    next_id: AtomicU64,
+    channels: Mutex<HashMap<u64, Channel<MessageReceivedEvent>>>,
+}
+
+impl MessageSubscriptions {
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<u64, Channel<MessageReceivedEvent>>> {
+        self.channels.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn subscribe(&self, channel: Channel<MessageReceivedEvent>) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.lock().insert(id, channel);
+        id
+    }
+
+    pub fn unsubscribe(&self, id: u64) -> bool {
+        self.lock().remove(&id).is_some()
+    }
+
+    pub fn push(&self, event: &MessageReceivedEvent) {
+        self.lock().retain(|id, channel| match channel.send(event.clone()) {
+            Ok(()) => true,
+            Err(e) => {
+                tracing::debug!("📪 Dropping message subscription {}: {}", id, e);
+                false
+            }
+        });
+    }
+}
@@ -0,0 +1,146 @@
+use crate::badge;
+use crate::events::TauriEvents;
+use crate::messaging::AppState;
+use crate::read_state::{ReadState, UnreadCounts, UNREAD_COUNTS_EVENT};
+use pubky_messenger_core::events;
+use tauri::menu::{Menu, MenuEvent, MenuItem, PredefinedMenuItem};
+use tauri::tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent};
+use tauri::{AppHandle, Manager, Wry};
+
+const TRAY_ID: &str = "main";
+
+const UNREAD_ITEM: &str = "unread";
+const TOGGLE_ITEM: &str = "toggle-window";
+const MARK_ALL_READ_ITEM: &str = "mark-all-read";
+const QUIT_ITEM: &str = "quit";
+
+// Menu entries whose text changes with the unread count
+struct TrayMenu {
+    unread: MenuItem<Wry>,
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn unread_label(total: usize) -> String {
+    match total {
+        0 => "No unread messages".to_string(),
+        1 => "1 unread message".to_string(),
+        n => format!("{} unread messages", n),
+    }
+}
+
+// Called from `run()`'s setup; the tray lives as long as the app
+pub fn create(app: &AppHandle) -> tauri::Result<()> {
+    let unread = MenuItem::with_id(app, UNREAD_ITEM, unread_label(0), false, None::<&str>)?;
+    let toggle = MenuItem::with_id(app, TOGGLE_ITEM, "Show/Hide Window", true, None::<&str>)?;
+    let mark_all_read = MenuItem::with_id(app, MARK_ALL_READ_ITEM, "Mark All Read", true, None::<&str>)?;
+    let quit = MenuItem::with_id(app, QUIT_ITEM, "Quit", true, None::<&str>)?;
+    let menu = Menu::with_items(app, &[
+        &unread,
+        &PredefinedMenuItem::separator(app)?,
+        &toggle,
+        &mark_all_read,
+        &PredefinedMenuItem::separator(app)?,
+        &quit,
+    ])?;
+
+    let mut builder = TrayIconBuilder::with_id(TRAY_ID)
+        .tooltip("Pubky Private Messenger")
+        .menu(&menu)
+        .show_menu_on_left_click(false)
+        .on_menu_event(on_menu_event)
+        .on_tray_icon_event(|tray, event| {
+            if let TrayIconEvent::Click { button: MouseButton::Left, button_state: MouseButtonState::Up, .. } = event {
+                toggle_window(tray.app_handle());
+            }
+        });
+    if let Some(icon) = app.default_window_icon() {
+        builder = builder.icon(icon.clone());
+    }
+    builder.build(app)?;
+
+    app.manage(TrayMenu { unread });
+    Ok(())
+}
+
+fn on_menu_event(app: &AppHandle, event: MenuEvent) {
+    match event.id.as_ref() {
+        TOGGLE_ITEM => toggle_window(app),
+        MARK_ALL_READ_ITEM => {
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move { mark_all_read(&app).await });
+        }
+        QUIT_ITEM => app.exit(0),
+        _ => {}
+    }
+}
+
+pub fn show_window(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.unminimize();
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+}
+
+fn toggle_window(app: &AppHandle) {
+    let Some(window) = app.get_webview_window("main") else {
+        return;
+    };
+    if window.is_visible().unwrap_or(false) && !window.is_minimized().unwrap_or(false) {
+        let _ = window.hide();
+    } else {
+        show_window(app);
+    }
+}
+
+async fn mark_all_read(app: &AppHandle) {
+    let state = app.state::<AppState>();
+    let Some(owner) = state.keypair.lock().await.as_ref().map(|keypair| keypair.public_key()) else {
+        return;
+    };
+
+    let read_state = ReadState::new(&state.store, &owner);
+    if let Err(e) = read_state.mark_all_read(now_secs()) {
+        tracing::warn!("⚠️  Failed to mark all conversations read: {}", e);
+        return;
+    }
+    match read_state.unread_counts() {
+        Ok(unread) => {
+            if let Err(e) = events::emit(&TauriEvents(app.clone()), UNREAD_COUNTS_EVENT, &unread) {
+                tracing::warn!("⚠️  Failed to emit unread counts: {}", e);
+            }
+        }
+        Err(e) => tracing::warn!("⚠️  Failed to load unread counts: {}", e),
+    }
+}
+
+// Show the current total unread count on the tray icon and the dock or
+// taskbar badge
+pub fn show_unread(app: &AppHandle, unread: Option<&UnreadCounts>) {
+    let total = unread.map(|unread| unread.total).unwrap_or(0);
+    let label = unread_label(total);
+    badge::set_badge_count(app, total);
+
+    if let Some(menu) = app.try_state::<TrayMenu>() {
+        let _ = menu.unread.set_text(&label);
+    }
+    if let Some(tray) = app.tray_by_id(TRAY_ID) {
+        let _ = tray.set_tooltip(Some(format!("Pubky Private Messenger - {}", label)));
+        // Shown next to the icon on macOS, ignored elsewhere
+        let _ = tray.set_title((total > 0).then(|| total.to_string()));
+    }
+}
+
+// Recompute the unread count for whoever is signed in
+pub async fn refresh(app: &AppHandle) {
+    let state = app.state::<AppState>();
+    let owner = state.keypair.lock().await.as_ref().map(|keypair| keypair.public_key());
+    let unread = owner.and_then(|owner| ReadState::new(&state.store, &owner).unread_counts().ok());
+    show_unread(app, unread.as_ref());
+}
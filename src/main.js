@@ -31,12 +31,18 @@ const settingsBtn = document.getElementById('settings-btn');
 const closeSettingsBtn = document.getElementById('close-settings-btn');
 const userPubkeySpan = document.getElementById('user-pubkey');
 const loginError = document.getElementById('login-error');
+const lockScreen = document.getElementById('lock-screen');
+const unlockPinInput = document.getElementById('unlock-pin');
+const unlockBtn = document.getElementById('unlock-btn');
+const lockError = document.getElementById('lock-error');
 const newContactInput = document.getElementById('new-contact');
 const addContactBtn = document.getElementById('add-contact-btn');
 const contactsList = document.getElementById('contacts-list');
 const messagesContainer = document.getElementById('messages-container');
 const messageInput = document.getElementById('message-input');
 const sendBtn = document.getElementById('send-btn');
+const attachBtn = document.getElementById('attach-btn');
+const attachmentExpirySelect = document.getElementById('attachment-expiry');
 const conversationTitle = document.getElementById('conversation-title');
 const searchContactInput = document.getElementById('search-contact');
 
@@ -56,20 +62,28 @@ async function init() {
 
     // Check for existing session first
     const savedSession = getSavedSession();
+    if (savedSession && (await invoke('get_app_lock')).enabled) {
+      showLockScreen();
+      return;
+    }
     if (savedSession) {
       console.log('🔄 Found saved session, attempting auto-login...');
       try {
-        const profile = await invoke('restore_session', {
+        const restored = await invoke('restore_session', {
           encryptedKeypair: savedSession
         });
-        if (profile) {
+        if (restored) {
           console.log('✅ Auto-login successful');
-          showChatScreen(profile);
+          if (restored.encrypted_keypair) {
+            saveSession(restored.encrypted_keypair);
+          }
+          showChatScreen(restored.profile);
           return;
         }
       } catch (error) {
         console.log('❌ Auto-login failed, clearing saved session:', error);
         clearSavedSession();
+        showError(errorMessage(error));
       }
     }
 
@@ -131,6 +145,32 @@ function showSettings() {
 
   // Show settings panel
   settingsPanel.classList.remove('hidden');
+  showCacheUsage();
+}
+
+function formatBytes(bytes) {
+  if (bytes < 1024 * 1024) return `${Math.ceil(bytes / 1024)} KB`;
+  return `${(bytes / (1024 * 1024)).toFixed(1)} MB`;
+}
+
+async function showCacheUsage() {
+  try {
+    const usage = await invoke('get_cache_usage');
+    document.getElementById('cache-usage').textContent =
+      `${usage.files} downloaded files use ${formatBytes(usage.bytes)} of ${formatBytes(usage.max_bytes)}.`;
+  } catch (error) {
+    console.error('Failed to load cache usage:', error);
+  }
+}
+
+async function clearAttachmentCache() {
+  try {
+    const freed = await invoke('clear_attachment_cache');
+    console.log(`🧹 Freed ${formatBytes(freed.bytes)} of cached attachments`);
+    await showCacheUsage();
+  } catch (error) {
+    showError(errorMessage(error));
+  }
 }
 
 function closeSettings() {
@@ -212,6 +252,44 @@ function clearSavedSession() {
   }
 }
 
+// The saved session is behind a PIN; the duress PIN looks just the same
+function showLockScreen() {
+  loginScreen.classList.add('hidden');
+  lockScreen.classList.remove('hidden');
+  lockError.style.display = 'none';
+  unlockPinInput.value = '';
+  unlockPinInput.focus();
+}
+
+async function unlock() {
+  const pin = unlockPinInput.value;
+  if (!pin) return;
+
+  try {
+    const restored = await invoke('unlock_session', {
+      pin,
+      encryptedKeypair: getSavedSession()
+    });
+    if (restored.encrypted_keypair) {
+      saveSession(restored.encrypted_keypair);
+    }
+    lockScreen.classList.add('hidden');
+    showChatScreen(restored.profile);
+  } catch (error) {
+    unlockPinInput.value = '';
+    if (error?.code === 'bad_pin') {
+      lockError.textContent = errorMessage(error);
+      lockError.style.display = 'block';
+      return;
+    }
+    // Wiped after too many wrong PINs, or the session no longer opens
+    clearSavedSession();
+    lockScreen.classList.add('hidden');
+    loginScreen.classList.remove('hidden');
+    showError(errorMessage(error));
+  }
+}
+
 // Convert file to base64
 function fileToBase64(file) {
   return new Promise((resolve, reject) => {
@@ -257,7 +335,7 @@ async function signIn() {
     clearError();
   } catch (error) {
     console.error('Sign in error:', error);
-    showError(error.toString());
+    showError(errorMessage(error));
   }
 }
 
@@ -285,12 +363,74 @@ function showChatScreen(profile) {
   // Scan for followed users from Pubky
   scanForFollowedUsers();
 
+  // Messages found by the background sync are pushed to us as they arrive
+  subscribeToMessages();
+
   // Start polling for new messages (if enabled in settings)
   if (userSettings.pollingEnabled) {
     startMessagePolling();
   }
 }
 
+let messageSubscriptionId = null;
+
+async function subscribeToMessages() {
+  if (messageSubscriptionId !== null) return;
+
+  const channel = new window.__TAURI__.core.Channel();
+  channel.onmessage = onMessageReceived;
+  try {
+    messageSubscriptionId = await invoke('subscribe_messages', { onMessage: channel });
+  } catch (error) {
+    console.error('Failed to subscribe to new messages:', error);
+  }
+}
+
+async function unsubscribeFromMessages() {
+  if (messageSubscriptionId === null) return;
+
+  const id = messageSubscriptionId;
+  messageSubscriptionId = null;
+  try {
+    await invoke('unsubscribe_messages', { subscriptionId: id });
+  } catch (error) {
+    console.error('Failed to unsubscribe from new messages:', error);
+  }
+}
+
+// { conversation, message, received_at } from subscribe_messages
+function onMessageReceived(event) {
+  const { conversation, message } = event;
+  if (message.is_own_message) return;
+
+  // Polling may have cached it already
+  const cached = loadMessagesCache(conversation);
+  if (cached && cached.some(msg => msg.id && msg.id === message.id)) return;
+
+  addMessageToCache(conversation, message);
+
+  const contact = contacts.get(conversation);
+  if (contact) {
+    contact.last_message = message.content;
+    contact.last_message_time = message.received_at ?? message.timestamp;
+    if (currentContact !== conversation) {
+      contact.unread_count = (contact.unread_count || 0) + 1;
+    }
+    saveContacts();
+    if (!isEditingContactName) {
+      renderContacts();
+    }
+  }
+
+  if (currentContact === conversation) {
+    const cachedMessages = loadMessagesCache(conversation);
+    if (cachedMessages) {
+      renderMessages(cachedMessages);
+    }
+    markContactAsRead(conversation);
+  }
+}
+
 async function updateUserProfileName() {
   try {
     const profile = await invoke('get_user_profile');
@@ -312,6 +452,7 @@ async function updateUserProfileName() {
 // Sign out
 async function signOut() {
   try {
+    await unsubscribeFromMessages();
     await invoke('sign_out');
 
     // Clear saved session
@@ -337,6 +478,8 @@ async function signOut() {
     conversationTitle.textContent = 'Select a contact to start chatting';
     messageInput.disabled = true;
     sendBtn.disabled = true;
+    attachBtn.disabled = true;
+    attachmentExpirySelect.disabled = true;
 
     stopMessagePolling();
     stopActiveConversationPolling();
@@ -346,6 +489,25 @@ async function signOut() {
   }
 }
 
+// Irreversible; the backend also wants a fresh token so nothing else can set it off
+async function panicWipe() {
+  if (!confirm('Wipe this device? The saved session, all accounts\' local data and caches will be deleted. This cannot be undone.')) return;
+  const deleteRemote = confirm('Also delete everything you wrote to your homeserver, including sent messages? Cancel to keep them.');
+
+  try {
+    await unsubscribeFromMessages();
+    const { token } = await invoke('request_panic_wipe_token');
+    const report = await invoke('panic_wipe', { token, deleteRemote });
+    console.warn('🧨 Wiped:', report);
+  } catch (error) {
+    console.error('Panic wipe error:', error);
+    alert(`Wipe failed: ${errorMessage(error)}`);
+    return;
+  }
+  localStorage.clear();
+  window.location.reload();
+}
+
 // Add contact
 function addContact() {
   const pubkey = newContactInput.value.trim();
@@ -391,6 +553,8 @@ function removeContact(pubkey, event) {
     messagesContainer.innerHTML = '';
     messageInput.disabled = true;
     sendBtn.disabled = true;
+    attachBtn.disabled = true;
+    attachmentExpirySelect.disabled = true;
     stopActiveConversationPolling();
   }
 
@@ -516,10 +680,35 @@ function loadContacts() {
 
   renderContacts();
 
+  // Make sure the notes-to-self conversation is always listed
+  ensureSavedMessagesContact();
+
   // Update last messages and unread counts for all contacts
   updateAllContactsData();
 }
 
+// Pin the "Saved messages" (notes-to-self) conversation in the contact list
+async function ensureSavedMessagesContact() {
+  try {
+    const saved = await invoke('get_saved_messages_contact');
+    const existing = contacts.get(saved.public_key);
+
+    contacts.set(saved.public_key, {
+      ...saved,
+      last_message: existing?.last_message || null,
+      last_message_time: existing?.last_message_time || null,
+      last_read_time: existing?.last_read_time || 0,
+      unread_count: 0,
+      is_saved_messages: true
+    });
+
+    saveContacts();
+    renderContacts();
+  } catch (error) {
+    console.log('Failed to add saved messages contact:', error);
+  }
+}
+
 async function scanForFollowedUsers() {
   try {
     // Check if sync is enabled
@@ -538,59 +727,68 @@ async function scanForFollowedUsers() {
     }
 
     console.log(`📋 Found ${followedUsers.length} followed users`);
+    mergeFollowedUsers(followedUsers);
 
-    let newContactsAdded = 0;
-    let namesUpdated = 0;
-
-    for (const user of followedUsers) {
-      // Check if contact already exists
-      if (contacts.has(user.pubky)) {
-        // Contact exists - check if we should update the name
-        const existingContact = contacts.get(user.pubky);
-
-        // Only update name if:
-        // 1. The user has a name in their profile (user.name is not null)
-        // 2. AND the existing contact doesn't have a custom name set
-        if (user.name && !existingContact.name) {
-          existingContact.name = user.name;
-          namesUpdated++;
-          console.log(`📝 Updated name for ${user.pubky.substring(0, 8)}: ${user.name}`);
-        }
-      } else {
-        // New contact - add it
-        contacts.set(user.pubky, {
-          public_key: user.pubky,
-          name: user.name || null,  // Use profile name if available
-          last_message: null,
-          last_message_time: null,
-          last_read_time: 0,
-          unread_count: 0
-        });
-        newContactsAdded++;
-        console.log(`➕ Added new contact ${user.pubky.substring(0, 8)}${user.name ? ` (${user.name})` : ''}`);
+  } catch (error) {
+    console.error('Failed to scan followed users:', error);
+    // Don't show error to user - this is a non-critical feature
+  }
+}
+
+// Add followed users as contacts and pick up their profile names and statuses
+function mergeFollowedUsers(followedUsers) {
+  let newContactsAdded = 0;
+  let namesUpdated = 0;
+
+  for (const user of followedUsers) {
+    // Check if contact already exists
+    if (contacts.has(user.pubky)) {
+      // Contact exists - check if we should update the name
+      const existingContact = contacts.get(user.pubky);
+
+      // Only update name if:
+      // 1. The user has a name in their profile (user.name is not null)
+      // 2. AND the existing contact doesn't have a custom name set
+      if (user.name && !existingContact.name) {
+        existingContact.name = user.name;
+        namesUpdated++;
+        console.log(`📝 Updated name for ${user.pubky.substring(0, 8)}: ${user.name}`);
+      }
+      if ((existingContact.status || null) !== (user.status || null)) {
+        existingContact.status = user.status || null;
+        namesUpdated++;
       }
+    } else {
+      // New contact - add it
+      contacts.set(user.pubky, {
+        public_key: user.pubky,
+        name: user.name || null,  // Use profile name if available
+        status: user.status || null,
+        last_message: null,
+        last_message_time: null,
+        last_read_time: 0,
+        unread_count: 0
+      });
+      newContactsAdded++;
+      console.log(`➕ Added new contact ${user.pubky.substring(0, 8)}${user.name ? ` (${user.name})` : ''}`);
     }
+  }
 
-    if (newContactsAdded > 0 || namesUpdated > 0) {
-      saveContacts();
-      renderContacts();
+  if (newContactsAdded > 0 || namesUpdated > 0) {
+    saveContacts();
+    renderContacts();
 
-      console.log(`✅ Scan complete: ${newContactsAdded} new contacts, ${namesUpdated} names updated`);
+    console.log(`✅ Scan complete: ${newContactsAdded} new contacts, ${namesUpdated} names updated`);
 
-      // Check for messages from new contacts after a short delay
-      if (newContactsAdded > 0) {
-        setTimeout(() => {
-          console.log('🔄 Checking for messages from new contacts...');
-          updateAllContactsData();
-        }, 2000);
-      }
-    } else {
-      console.log('✅ Scan complete: All followed users already in contacts');
+    // Check for messages from new contacts after a short delay
+    if (newContactsAdded > 0) {
+      setTimeout(() => {
+        console.log('🔄 Checking for messages from new contacts...');
+        updateAllContactsData();
+      }, 2000);
     }
-
-  } catch (error) {
-    console.error('Failed to scan followed users:', error);
-    // Don't show error to user - this is a non-critical feature
+  } else {
+    console.log('✅ Scan complete: All followed users already in contacts');
   }
 }
 
@@ -608,7 +806,7 @@ async function updateContactUnreadCount(pubkey, skipRender = false) {
       contact.last_message = lastMessage.content.length > 30
           ? lastMessage.content.substring(0, 30) + '...'
           : lastMessage.content;
-      contact.last_message_time = lastMessage.timestamp;
+      contact.last_message_time = lastMessage.received_at ?? lastMessage.timestamp;
 
       // Count unread messages
       const unreadMessages = messages.filter(msg =>
@@ -801,6 +999,11 @@ function renderContacts() {
   // Sort contacts: by unread count (desc), then by last message time (desc)
   // No longer move active contact to top to prevent jumping
   const sortedContacts = filteredContacts.sort(([pubkeyA, contactA], [pubkeyB, contactB]) => {
+    // Saved messages always stays pinned at the top
+    if (contactA.is_saved_messages !== contactB.is_saved_messages) {
+      return contactA.is_saved_messages ? -1 : 1;
+    }
+
     // Sort by unread count (descending)
     if (contactB.unread_count !== contactA.unread_count) {
       return contactB.unread_count - contactA.unread_count;
@@ -841,10 +1044,19 @@ function renderContacts() {
           <button class="contact-edit-btn" title="Edit name">✏️</button>
         </div>
         <div class="contact-last-message">${displaySubtext}</div>
+        <div class="contact-status"></div>
       </div>
-      <button class="contact-delete-btn" title="Remove contact">×</button>
+      ${contact.is_saved_messages ? '' : '<button class="contact-delete-btn" title="Remove contact">×</button>'}
     `;
 
+    // Statuses come from other people's profiles, so never as HTML
+    const statusEl = contactEl.querySelector('.contact-status');
+    if (contact.status) {
+      statusEl.textContent = contact.status;
+    } else {
+      statusEl.remove();
+    }
+
     // Add click handler for selecting contact (but not on action buttons)
     contactEl.addEventListener('click', (e) => {
       if (!e.target.classList.contains('contact-delete-btn') &&
@@ -891,6 +1103,8 @@ async function selectContact(pubkey) {
 
   messageInput.disabled = false;
   sendBtn.disabled = false;
+  attachBtn.disabled = false;
+  attachmentExpirySelect.disabled = false;
 
   // Load conversation history
   await loadConversation(pubkey);
@@ -949,7 +1163,7 @@ async function loadConversation(pubkey) {
         contact.last_message = lastMessage.content.length > 30
             ? lastMessage.content.substring(0, 30) + '...'
             : lastMessage.content;
-        contact.last_message_time = lastMessage.timestamp;
+        contact.last_message_time = lastMessage.received_at ?? lastMessage.timestamp;
 
         // Since we're viewing this conversation, mark as read
         markContactAsRead(pubkey);
@@ -1029,6 +1243,45 @@ function renderMessages(messages) {
             </div>
         `;
 
+    if (message.timestamp_suspect) {
+      const suspectEl = document.createElement('span');
+      suspectEl.className = 'message-time-suspect';
+      suspectEl.textContent = '🕒';
+      suspectEl.title = 'The sender\'s timestamp doesn\'t match when this message arrived';
+      messageEl.querySelector('.message-meta').appendChild(suspectEl);
+    }
+
+    if (message.attachment?.preview) {
+      messageEl.querySelector('.message-content').appendChild(renderAttachmentPreview(message.attachment.preview));
+    }
+
+    if (message.attachment?.expires_at) {
+      const expiryEl = document.createElement('div');
+      expiryEl.className = 'attachment-expiry';
+      expiryEl.textContent = isAttachmentExpired(message.attachment)
+          ? 'File expired'
+          : `File expires ${new Date(message.attachment.expires_at * 1000).toLocaleString()}`;
+      messageEl.querySelector('.message-content').appendChild(expiryEl);
+    }
+
+    if (message.attachment && !isAttachmentExpired(message.attachment)) {
+      const saveBtn = document.createElement('button');
+      saveBtn.className = 'attachment-save-btn';
+      saveBtn.textContent = 'Save file';
+      saveBtn.addEventListener('click', async () => {
+        savingButtons.set(message.attachment.name, saveBtn);
+        try {
+          await invoke('save_attachment', { attachment: message.attachment });
+        } catch (error) {
+          alert('Failed to save file: ' + errorMessage(error));
+        } finally {
+          savingButtons.delete(message.attachment.name);
+          saveBtn.textContent = 'Save file';
+        }
+      });
+      messageEl.querySelector('.message-content').appendChild(saveBtn);
+    }
+
     messagesContainer.appendChild(messageEl);
   });
 
@@ -1103,7 +1356,35 @@ async function sendMessage() {
     }, 500);
 
     // Show error to user
-    alert('Failed to send message: ' + error);
+    alert('Failed to send message: ' + errorMessage(error));
+  }
+}
+
+function isAttachmentExpired(attachment) {
+  return attachment.expires_at != null && attachment.expires_at * 1000 <= Date.now();
+}
+
+// Seconds until sent files are deleted, or null to keep them
+function attachmentExpiresInSecs() {
+  return attachmentExpirySelect.value ? parseInt(attachmentExpirySelect.value, 10) : null;
+}
+
+// Send a file from the picker, or from a path dropped onto the window
+async function sendFile(path) {
+  if (!currentContact) return;
+
+  const expiresInSecs = attachmentExpiresInSecs();
+  try {
+    const result = path
+        ? await invoke('send_file', { recipientPubkey: currentContact, path, expiresInSecs })
+        : await invoke('pick_and_send_file', { recipientPubkey: currentContact, expiresInSecs });
+    if (result === null) return; // Picker cancelled
+
+    console.log('📎 File sent:', result);
+    await loadConversation(currentContact);
+  } catch (error) {
+    console.error('Failed to send file:', error);
+    alert('Failed to send file: ' + errorMessage(error));
   }
 }
 
@@ -1234,7 +1515,7 @@ function startActiveConversationPolling() {
           if (contact && messages.length > 0) {
             const lastMsg = messages[messages.length - 1];
             contact.last_message = lastMsg.content;
-            contact.last_message_time = lastMsg.timestamp;
+            contact.last_message_time = lastMsg.received_at ?? lastMsg.timestamp;
             saveContacts();
           }
         }
@@ -1254,6 +1535,16 @@ function stopActiveConversationPolling() {
 }
 
 // Utility functions
+
+// Backend commands reject with { code, message }; code is stable
+// (e.g. "not_signed_in", "bad_passphrase", "homeserver_unreachable")
+function errorMessage(error) {
+  if (error && typeof error === 'object' && 'message' in error) {
+    return error.message;
+  }
+  return String(error);
+}
+
 function showError(message) {
   loginError.textContent = message;
   loginError.style.display = 'block';
@@ -1271,11 +1562,125 @@ signInBtn.addEventListener('click', (e) => {
 });
 
 signOutBtn.addEventListener('click', signOut);
+unlockBtn.addEventListener('click', unlock);
+document.getElementById('panic-wipe-btn').addEventListener('click', panicWipe);
+document.getElementById('clear-cache-btn').addEventListener('click', clearAttachmentCache);
+unlockPinInput.addEventListener('keypress', (e) => {
+  if (e.key === 'Enter') unlock();
+});
 settingsBtn.addEventListener('click', showSettings);
 closeSettingsBtn.addEventListener('click', closeSettings);
 saveSettingsBtn.addEventListener('click', applySettings);
 addContactBtn.addEventListener('click', addContact);
 sendBtn.addEventListener('click', sendMessage);
+attachBtn.addEventListener('click', () => sendFile(null));
+
+// Files dropped onto the window go to the open conversation
+window.__TAURI__.event.listen('tauri://drag-drop', async (event) => {
+  for (const path of event.payload.paths || []) {
+    await sendFile(path);
+  }
+});
+
+// The background profile refresh found new follows or profile changes
+window.__TAURI__.event.listen('contacts-refreshed', (event) => {
+  if (!currentUser || !userSettings.pubkySyncEnabled) return;
+  mergeFollowedUsers(event.payload || []);
+});
+
+function formatDuration(ms) {
+  const seconds = Math.round(ms / 1000);
+  return `${Math.floor(seconds / 60)}:${String(seconds % 60).padStart(2, '0')}`;
+}
+
+// What the sender said is inside a file, shown before it's downloaded
+function renderAttachmentPreview(preview) {
+  const previewEl = document.createElement('div');
+  previewEl.className = `attachment-preview attachment-preview-${preview.kind}`;
+
+  if (preview.kind === 'text') {
+    const snippetEl = document.createElement('pre');
+    snippetEl.textContent = preview.snippet;
+    previewEl.appendChild(snippetEl);
+    const linesEl = document.createElement('span');
+    linesEl.textContent = `${preview.lines} lines`;
+    previewEl.appendChild(linesEl);
+  } else if (preview.kind === 'audio') {
+    const waveformEl = document.createElement('span');
+    waveformEl.className = 'attachment-waveform';
+    for (const peak of preview.waveform || []) {
+      const bar = document.createElement('span');
+      bar.style.height = `${Math.max(2, Math.round(peak / 255 * 100))}%`;
+      waveformEl.appendChild(bar);
+    }
+    previewEl.appendChild(waveformEl);
+    const durationEl = document.createElement('span');
+    durationEl.textContent = formatDuration(preview.duration_ms);
+    previewEl.appendChild(durationEl);
+  } else if (preview.kind === 'document') {
+    const parts = [];
+    if (preview.title) parts.push(preview.title);
+    if (preview.pages != null) parts.push(`${preview.pages} page${preview.pages === 1 ? '' : 's'}`);
+    previewEl.textContent = parts.join(' · ');
+  }
+  return previewEl;
+}
+
+// Save buttons with a download in progress, by attachment name
+const savingButtons = new Map();
+
+window.__TAURI__.event.listen('attachment-download-progress', (event) => {
+  const download = event.payload;
+  const button = savingButtons.get(download.name);
+  if (!button) return;
+  if (download.state === 'downloading' && download.total_bytes) {
+    button.textContent = `Downloading ${Math.floor(download.received_bytes * 100 / download.total_bytes)}%`;
+  } else if (download.state === 'downloading') {
+    button.textContent = 'Downloading…';
+  }
+});
+
+// A contact renamed themselves or swapped avatars; follow the new name
+// unless the user gave them their own
+window.__TAURI__.event.listen('contact-profile-changed', (event) => {
+  const change = event.payload;
+  const contact = contacts.get(change.pubky);
+  if (!contact) return;
+
+  console.warn(`🪪 Profile of ${change.pubky.substring(0, 8)} changed:`, change);
+  if (change.name_changed && change.name && contact.name === change.previous_name) {
+    contact.name = change.name;
+    saveContacts();
+    renderContacts();
+    if (currentContact === change.pubky) {
+      conversationTitle.textContent = contact.name;
+    }
+  }
+});
+
+// Many messages in one conversation failed to open at once: a key mismatch,
+// or someone flooding it with junk
+window.__TAURI__.event.listen('decryption-failures-spiked', (event) => {
+  const spike = event.payload;
+  const contact = contacts.get(spike.conversation);
+  const name = contact?.name || spike.conversation.substring(0, 8);
+  showError(`${spike.failures} messages from ${name} couldn't be decrypted and were set aside`);
+});
+
+// Pasting an image (no text) sends it as a file
+messageInput.addEventListener('paste', async (e) => {
+  const items = Array.from(e.clipboardData?.items || []);
+  if (!currentContact || !items.some(item => item.type.startsWith('image/'))) return;
+
+  e.preventDefault();
+  try {
+    await invoke('send_clipboard_image', { recipientPubkey: currentContact, expiresInSecs: attachmentExpiresInSecs() });
+    await loadConversation(currentContact);
+  } catch (error) {
+    console.error('Failed to send pasted image:', error);
+    alert('Failed to send image: ' + errorMessage(error));
+  }
+});
 
 messageInput.addEventListener('keydown', (e) => {
   if (e.key === 'Enter' && !e.shiftKey) {